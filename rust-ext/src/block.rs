@@ -0,0 +1,112 @@
+// Copyright (c) 2021-present, Gregory Szorc
+// All rights reserved.
+//
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+//! Bindings to the experimental block-level API.
+//!
+//! Blocks carry no frame header, checksum or content size. Each block is
+//! compressed independently and must be at most `BLOCKSIZE_MAX` bytes.
+
+use {
+    crate::{
+        compression_dict::ZstdCompressionDict,
+        constants::validate_level,
+        exceptions::ZstdError,
+        zstd_safe::{CCtx, DCtx},
+    },
+    pyo3::{buffer::PyBuffer, prelude::*, types::PyBytes},
+};
+
+#[pyclass(module = "zstandard.backend_rust")]
+struct ZstdBlockCompressor {
+    level: i32,
+    dict_data: Option<Py<ZstdCompressionDict>>,
+    cctx: CCtx<'static>,
+}
+
+#[pymethods]
+impl ZstdBlockCompressor {
+    #[new]
+    #[args(level = "3", dict_data = "None")]
+    fn new(level: i32, dict_data: Option<Py<ZstdCompressionDict>>) -> PyResult<Self> {
+        validate_level(level)?;
+
+        Ok(Self {
+            level,
+            dict_data,
+            cctx: CCtx::new().map_err(ZstdError::new_err)?,
+        })
+    }
+
+    /// Compress `data` into a single block.
+    ///
+    /// Returns empty bytes if the data isn't compressible. Callers must then
+    /// store the data uncompressed.
+    fn compress<'p>(&self, py: Python<'p>, data: PyBuffer<u8>) -> PyResult<&'p PyBytes> {
+        let source = unsafe {
+            std::slice::from_raw_parts::<u8>(data.buf_ptr() as *const _, data.len_bytes())
+        };
+
+        let dict = self.dict_data.as_ref().map(|dict| dict.borrow(py));
+        let dict_slice = dict.as_ref().map(|dict| dict.as_slice()).unwrap_or(&[]);
+
+        let block = py
+            .allow_threads(|| self.cctx.compress_block(self.level, dict_slice, source))
+            .map_err(|msg| ZstdError::new_err(format!("cannot compress block: {}", msg)))?;
+
+        Ok(PyBytes::new(py, &block))
+    }
+}
+
+#[pyclass(module = "zstandard.backend_rust")]
+struct ZstdBlockDecompressor {
+    dict_data: Option<Py<ZstdCompressionDict>>,
+    dctx: DCtx<'static>,
+}
+
+#[pymethods]
+impl ZstdBlockDecompressor {
+    #[new]
+    #[args(dict_data = "None")]
+    fn new(dict_data: Option<Py<ZstdCompressionDict>>) -> PyResult<Self> {
+        Ok(Self {
+            dict_data,
+            dctx: DCtx::new().map_err(ZstdError::new_err)?,
+        })
+    }
+
+    /// Decompress a single block produced by `ZstdBlockCompressor`.
+    #[args(data, max_output_size = "zstd_sys::ZSTD_BLOCKSIZE_MAX as usize")]
+    fn decompress<'p>(
+        &self,
+        py: Python<'p>,
+        data: PyBuffer<u8>,
+        max_output_size: usize,
+    ) -> PyResult<&'p PyBytes> {
+        let source = unsafe {
+            std::slice::from_raw_parts::<u8>(data.buf_ptr() as *const _, data.len_bytes())
+        };
+
+        let dict = self.dict_data.as_ref().map(|dict| dict.borrow(py));
+        let dict_slice = dict.as_ref().map(|dict| dict.as_slice()).unwrap_or(&[]);
+
+        let block = py
+            .allow_threads(|| {
+                self.dctx
+                    .decompress_block(dict_slice, source, max_output_size)
+            })
+            .map_err(|msg| ZstdError::new_err(format!("cannot decompress block: {}", msg)))?;
+
+        Ok(PyBytes::new(py, &block))
+    }
+}
+
+pub(crate) fn init_module(module: &PyModule) -> PyResult<()> {
+    module.add("BLOCKSIZE_MAX", zstd_sys::ZSTD_BLOCKSIZE_MAX)?;
+    module.add_class::<ZstdBlockCompressor>()?;
+    module.add_class::<ZstdBlockDecompressor>()?;
+
+    Ok(())
+}
@@ -5,16 +5,16 @@
 // of the BSD license. See the LICENSE file for details.
 
 use {
-    crate::exceptions::ZstdError,
     pyo3::{
         buffer::PyBuffer,
-        class::{PyBufferProtocol, PySequenceProtocol},
+        class::{PyBufferProtocol, PyObjectProtocol, PySequenceProtocol},
         exceptions::{PyIndexError, PyTypeError, PyValueError},
         ffi::Py_buffer,
         prelude::*,
         types::{PyBytes, PyTuple},
-        AsPyPointer,
+        AsPyPointer, PyNativeType,
     },
+    std::sync::Arc,
 };
 
 #[repr(C)]
@@ -24,12 +24,203 @@ pub(crate) struct BufferSegment {
     pub length: u64,
 }
 
+/// A C-contiguous buffer viewed as raw bytes, whatever its item format.
+///
+/// `PyBuffer<u8>` rejects buffers not made of unsigned bytes, such as numpy
+/// arrays of floats or multi-byte integers. Compression only needs the bytes.
+pub(crate) struct ByteBuffer(std::pin::Pin<Box<Py_buffer>>);
+
+impl ByteBuffer {
+    pub fn get(obj: &PyAny) -> PyResult<Self> {
+        Self::get_with_flags(obj, pyo3::ffi::PyBUF_CONTIG_RO)
+    }
+
+    /// Obtain a buffer that can be written to.
+    ///
+    /// The exporter raises if it can't provide one.
+    pub fn get_writable(obj: &PyAny) -> PyResult<Self> {
+        Self::get_with_flags(
+            obj,
+            pyo3::ffi::PyBUF_WRITABLE | pyo3::ffi::PyBUF_C_CONTIGUOUS,
+        )
+    }
+
+    fn get_with_flags(obj: &PyAny, flags: libc::c_int) -> PyResult<Self> {
+        let mut buffer = Box::pin(Py_buffer::new());
+
+        if unsafe { pyo3::ffi::PyObject_GetBuffer(obj.as_ptr(), &mut *buffer, flags) } == -1 {
+            return Err(PyErr::fetch(obj.py()));
+        }
+
+        Ok(Self(buffer))
+    }
+
+    /// The object the buffer was obtained from.
+    pub fn object<'p>(&self, py: Python<'p>) -> &'p PyAny {
+        unsafe { py.from_borrowed_ptr(self.0.obj) }
+    }
+
+    pub fn readonly(&self) -> bool {
+        self.0.readonly != 0
+    }
+
+    /// Size in bytes of each item in the buffer.
+    pub fn item_size(&self) -> usize {
+        self.0.itemsize as usize
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.0.len as usize
+    }
+
+    /// Pointer to the start of the buffer for writing.
+    ///
+    /// Callers must obtain the buffer with `get_writable()` or check
+    /// `readonly()` first.
+    pub fn as_mut_ptr(&self) -> *mut u8 {
+        self.0.buf as *mut u8
+    }
+
+    /// The bytes of the buffer.
+    ///
+    /// The memory may be mutated by other Python code, so this should only be
+    /// used while the GIL is held or the object is otherwise not modified.
+    pub fn as_slice(&self) -> &[u8] {
+        if self.0.buf.is_null() {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(self.0.buf as *const u8, self.0.len as usize) }
+        }
+    }
+}
+
+impl<'source> FromPyObject<'source> for ByteBuffer {
+    fn extract(obj: &'source PyAny) -> PyResult<Self> {
+        Self::get(obj)
+    }
+}
+
+impl Drop for ByteBuffer {
+    fn drop(&mut self) {
+        Python::with_gil(|_| unsafe { pyo3::ffi::PyBuffer_Release(&mut *self.0) });
+    }
+}
+
+unsafe impl Send for ByteBuffer {}
+
+/// A `bytes` object filled in place and resized as output is produced.
+///
+/// Avoids building output in a `Vec<u8>` and copying it into a `bytes`.
+/// The object is not shared until `finish()`, so resizing it is allowed.
+///
+/// The object is kept as a raw pointer because `_PyBytes_Resize()` may
+/// replace it. Methods that resize or release it take a `Python` token. As
+/// the builder is `Send` and could be dropped anywhere, dropping acquires the
+/// GIL.
+pub(crate) struct BytesBuilder {
+    obj: *mut pyo3::ffi::PyObject,
+    len: usize,
+    capacity: usize,
+}
+
+impl BytesBuilder {
+    pub fn with_capacity(py: Python, capacity: usize) -> PyResult<Self> {
+        // Size 0 returns a shared singleton, which cannot be resized.
+        let capacity = capacity.max(1);
+
+        if capacity > isize::MAX as usize {
+            return Err(pyo3::exceptions::PyMemoryError::new_err(()));
+        }
+
+        let obj = unsafe { pyo3::ffi::PyBytes_FromStringAndSize(std::ptr::null(), capacity as _) };
+        if obj.is_null() {
+            return Err(PyErr::fetch(py));
+        }
+
+        Ok(Self {
+            obj,
+            len: 0,
+            capacity,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Output buffer covering the unused capacity.
+    pub fn out_buffer(&mut self) -> zstd_sys::ZSTD_outBuffer {
+        zstd_sys::ZSTD_outBuffer {
+            dst: unsafe { pyo3::ffi::PyBytes_AsString(self.obj) } as *mut _,
+            size: self.capacity,
+            pos: self.len,
+        }
+    }
+
+    /// Record how much of an `out_buffer()` has been filled.
+    pub fn set_len(&mut self, len: usize) {
+        assert!(len <= self.capacity);
+        self.len = len;
+    }
+
+    fn resize(&mut self, py: Python, size: usize) -> PyResult<()> {
+        if size > isize::MAX as usize {
+            return Err(pyo3::exceptions::PyMemoryError::new_err(()));
+        }
+
+        // On failure the object is released and the pointer cleared.
+        if unsafe { pyo3::ffi::_PyBytes_Resize(&mut self.obj, size as _) } != 0 {
+            return Err(PyErr::fetch(py));
+        }
+
+        self.capacity = size;
+
+        Ok(())
+    }
+
+    pub fn reserve_exact(&mut self, py: Python, additional: usize) -> PyResult<()> {
+        let size = self
+            .capacity
+            .checked_add(additional)
+            .ok_or_else(|| pyo3::exceptions::PyMemoryError::new_err(()))?;
+
+        self.resize(py, size)
+    }
+
+    /// Truncate to the filled length and hand over the `bytes`.
+    pub fn finish<'p>(mut self, py: Python<'p>) -> PyResult<&'p PyBytes> {
+        if self.len != self.capacity {
+            self.resize(py, self.len)?;
+        }
+
+        let obj = std::mem::replace(&mut self.obj, std::ptr::null_mut());
+
+        Ok(unsafe { py.from_owned_ptr(obj) })
+    }
+}
+
+impl Drop for BytesBuilder {
+    fn drop(&mut self) {
+        if !self.obj.is_null() {
+            Python::with_gil(|_| unsafe { pyo3::ffi::Py_DECREF(self.obj) });
+        }
+    }
+}
+
+// The object is only accessed through the builder, with the GIL held or
+// acquired.
+unsafe impl Send for BytesBuilder {}
+
 #[pyclass(module = "zstandard.backend_rust", name = "BufferSegment")]
 pub struct ZstdBufferSegment {
     /// The object backing storage. For reference counting.
     _parent: PyObject,
-    /// PyBuffer into parent object.
-    buffer: PyBuffer<u8>,
+    /// PyBuffer into parent object, shared with the `BufferWithSegments`.
+    buffer: Arc<PyBuffer<u8>>,
     /// Offset of segment within data.
     offset: usize,
     /// Length of segment within data.
@@ -44,6 +235,13 @@ impl ZstdBufferSegment {
     }
 }
 
+#[pyproto]
+impl PyObjectProtocol for ZstdBufferSegment {
+    fn __repr__(&self) -> String {
+        format!("BufferSegment(offset={}, size={})", self.offset, self.len)
+    }
+}
+
 #[pymethods]
 impl ZstdBufferSegment {
     #[getter]
@@ -125,7 +323,8 @@ impl PyBufferProtocol for ZstdBufferSegments {
 #[pyclass(module = "zstandard.backend_rust", name = "BufferWithSegments")]
 pub struct ZstdBufferWithSegments {
     source: PyObject,
-    pub(crate) buffer: PyBuffer<u8>,
+    /// Buffer into `source`, obtained once and shared with segments.
+    pub(crate) buffer: Arc<PyBuffer<u8>>,
     pub(crate) segments: Vec<BufferSegment>,
 }
 
@@ -136,6 +335,31 @@ impl ZstdBufferWithSegments {
         }
     }
 
+    /// Obtain the segment at `key`.
+    fn segment(&self, py: Python, key: isize) -> PyResult<ZstdBufferSegment> {
+        if key < 0 {
+            return Err(PyIndexError::new_err("offset must be non-negative"));
+        }
+
+        let key = key as usize;
+
+        if key >= self.segments.len() {
+            return Err(PyIndexError::new_err(format!(
+                "offset must be less than {}",
+                self.segments.len()
+            )));
+        }
+
+        let segment = &self.segments[key];
+
+        Ok(ZstdBufferSegment {
+            _parent: self.source.clone_ref(py),
+            buffer: self.buffer.clone(),
+            offset: segment.offset as _,
+            len: segment.length as _,
+        })
+    }
+
     pub fn get_segment_slice<'p>(&self, _py: Python<'p>, i: usize) -> &'p [u8] {
         let segment = &self.segments[i];
 
@@ -148,12 +372,48 @@ impl ZstdBufferWithSegments {
     }
 }
 
+#[pyproto]
+impl PyObjectProtocol for ZstdBufferWithSegments {
+    fn __repr__(&self) -> String {
+        format!(
+            "BufferWithSegments(size={}, segments={})",
+            self.buffer.len_bytes(),
+            self.segments.len()
+        )
+    }
+}
+
+impl ZstdBufferWithSegments {
+    /// Construct an instance over `data` from already parsed segments.
+    pub(crate) fn from_segments(
+        py: Python,
+        data: &PyAny,
+        segments: Vec<BufferSegment>,
+    ) -> PyResult<Self> {
+        let data_buffer = PyBuffer::get(data)?;
+
+        // Validate segments data, as blindly trusting it could lead to
+        // arbitrary memory access.
+        for segment in &segments {
+            if segment.offset + segment.length > data_buffer.len_bytes() as _ {
+                return Err(PyValueError::new_err(
+                    "offset within segments array references memory outside buffer",
+                ));
+            }
+        }
+
+        Ok(Self {
+            source: data.into_py(py),
+            buffer: Arc::new(data_buffer),
+            segments,
+        })
+    }
+}
+
 #[pymethods]
 impl ZstdBufferWithSegments {
     #[new]
     pub fn new(py: Python, data: &PyAny, segments: PyBuffer<u8>) -> PyResult<Self> {
-        let data_buffer = PyBuffer::get(data)?;
-
         if segments.len_bytes() % std::mem::size_of::<BufferSegment>() != 0 {
             return Err(PyValueError::new_err(format!(
                 "segments array size is not a multiple of {}",
@@ -170,23 +430,7 @@ impl ZstdBufferWithSegments {
 
         // Make a copy of the segments data. It is cheap to do so and is a
         // guard against caller changing offsets, which has security implications.
-        let segments = segments_slice.to_vec();
-
-        // Validate segments data, as blindly trusting it could lead to
-        // arbitrary memory access.
-        for segment in &segments {
-            if segment.offset + segment.length > data_buffer.len_bytes() as _ {
-                return Err(PyValueError::new_err(
-                    "offset within segments array references memory outside buffer",
-                ));
-            }
-        }
-
-        Ok(Self {
-            source: data.into_py(py),
-            buffer: data_buffer,
-            segments,
-        })
+        Self::from_segments(py, data, segments_slice.to_vec())
     }
 
     #[getter]
@@ -213,29 +457,9 @@ impl PySequenceProtocol for ZstdBufferWithSegments {
     }
 
     fn __getitem__(&self, key: isize) -> PyResult<ZstdBufferSegment> {
-        let py = unsafe { Python::assume_gil_acquired() };
-
-        if key < 0 {
-            return Err(PyIndexError::new_err("offset must be non-negative"));
-        }
-
-        let key = key as usize;
-
-        if key >= self.segments.len() {
-            return Err(PyIndexError::new_err(format!(
-                "offset must be less than {}",
-                self.segments.len()
-            )));
-        }
-
-        let segment = &self.segments[key];
-
-        Ok(ZstdBufferSegment {
-            _parent: self.source.clone_ref(py),
-            buffer: PyBuffer::get(self.source.extract(py)?)?,
-            offset: segment.offset as _,
-            len: segment.length as _,
-        })
+        // Protocol methods don't receive a token. The GIL is held, so this
+        // is cheap.
+        Python::with_gil(|py| self.segment(py, key))
     }
 }
 
@@ -263,6 +487,128 @@ impl PyBufferProtocol for ZstdBufferWithSegments {
     fn bf_releasebuffer(slf: PyRefMut<Self>, view: *mut Py_buffer) {}
 }
 
+/// Assembles a `BufferWithSegments` from chunks appended one at a time.
+///
+/// Chunks are copied into a single allocation that grows as needed, becoming
+/// the backing storage of the finished instance without a further copy.
+#[pyclass(module = "zstandard.backend_rust", name = "BufferWithSegmentsBuilder")]
+pub struct ZstdBufferWithSegmentsBuilder {
+    /// `None` once `finish()` was called.
+    data: Option<BytesBuilder>,
+    segments: Vec<BufferSegment>,
+}
+
+impl ZstdBufferWithSegmentsBuilder {
+    pub fn with_capacity(py: Python, capacity: usize) -> PyResult<Self> {
+        Ok(Self {
+            data: Some(BytesBuilder::with_capacity(py, capacity)?),
+            segments: vec![],
+        })
+    }
+
+    fn data(&mut self) -> PyResult<&mut BytesBuilder> {
+        self.data
+            .as_mut()
+            .ok_or_else(|| PyValueError::new_err("builder has already been finished"))
+    }
+
+    /// Append `chunk` as a new segment.
+    pub fn push(&mut self, py: Python, chunk: &[u8]) -> PyResult<()> {
+        let data = self.data()?;
+        let offset = data.len();
+        let end = offset + chunk.len();
+
+        // Grow geometrically so appending many small chunks stays cheap.
+        if end > data.capacity() {
+            let capacity = std::cmp::max(end, data.capacity() * 2);
+            data.reserve_exact(py, capacity - data.capacity())?;
+        }
+
+        let out_buffer = data.out_buffer();
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                chunk.as_ptr(),
+                (out_buffer.dst as *mut u8).add(offset),
+                chunk.len(),
+            );
+        }
+        data.set_len(end);
+
+        self.segments.push(BufferSegment {
+            offset: offset as _,
+            length: chunk.len() as _,
+        });
+
+        Ok(())
+    }
+
+    /// Produce a `BufferWithSegments` holding the appended chunks.
+    pub fn build(&mut self, py: Python) -> PyResult<ZstdBufferWithSegments> {
+        self.data()?;
+        let data = self.data.take().unwrap();
+
+        ZstdBufferWithSegments::from_segments(
+            py,
+            data.finish(py)?,
+            std::mem::take(&mut self.segments),
+        )
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for ZstdBufferWithSegmentsBuilder {
+    fn __repr__(&self) -> String {
+        format!(
+            "BufferWithSegmentsBuilder(size={}, segments={})",
+            self.data.as_ref().map_or(0, |data| data.len()),
+            self.segments.len()
+        )
+    }
+}
+
+#[pymethods]
+impl ZstdBufferWithSegmentsBuilder {
+    #[new]
+    #[args(capacity = "0")]
+    fn new(py: Python, capacity: usize) -> PyResult<Self> {
+        Self::with_capacity(py, capacity)
+    }
+
+    /// Total size of the appended chunks.
+    #[getter]
+    fn size(&self) -> usize {
+        self.data.as_ref().map_or(0, |data| data.len())
+    }
+
+    /// Append the bytes of `data` as a new segment.
+    fn append(&mut self, py: Python, data: ByteBuffer) -> PyResult<()> {
+        self.push(py, data.as_slice())
+    }
+
+    /// Append each item of `iterable` as a new segment.
+    fn extend(&mut self, py: Python, iterable: &PyAny) -> PyResult<()> {
+        for item in iterable.iter()? {
+            self.push(py, ByteBuffer::get(item?)?.as_slice())?;
+        }
+
+        Ok(())
+    }
+
+    /// Obtain a `BufferWithSegments` with a segment for each appended chunk.
+    ///
+    /// The builder can't be used afterwards.
+    fn finish(&mut self, py: Python) -> PyResult<ZstdBufferWithSegments> {
+        self.build(py)
+    }
+}
+
+#[pyproto]
+impl PySequenceProtocol for ZstdBufferWithSegmentsBuilder {
+    fn __len__(&self) -> usize {
+        self.segments.len()
+    }
+}
+
 #[pyclass(
     module = "zstandard.backend_rust",
     name = "BufferWithSegmentsCollection"
@@ -273,6 +619,17 @@ pub struct ZstdBufferWithSegmentsCollection {
     first_elements: Vec<usize>,
 }
 
+#[pyproto]
+impl PyObjectProtocol for ZstdBufferWithSegmentsCollection {
+    fn __repr__(&self) -> String {
+        format!(
+            "BufferWithSegmentsCollection(buffers={}, segments={})",
+            self.buffers.len(),
+            self.first_elements.last().copied().unwrap_or(0)
+        )
+    }
+}
+
 #[pymethods]
 impl ZstdBufferWithSegmentsCollection {
     #[new]
@@ -325,44 +682,59 @@ impl ZstdBufferWithSegmentsCollection {
     }
 }
 
-#[pyproto]
-impl PySequenceProtocol for ZstdBufferWithSegmentsCollection {
-    fn __len__(&self) -> usize {
-        self.first_elements.last().unwrap().clone()
-    }
+impl ZstdBufferWithSegmentsCollection {
+    /// Create an instance holding just `buffer`.
+    ///
+    /// Unlike the Python constructor, `buffer` may be empty.
+    pub fn from_buffer(py: Python, buffer: ZstdBufferWithSegments) -> PyResult<Self> {
+        let count = buffer.segments.len();
 
-    fn __getitem__(&self, key: isize) -> PyResult<ZstdBufferSegment> {
-        let py = unsafe { Python::assume_gil_acquired() };
+        Ok(Self {
+            buffers: vec![Py::new(py, buffer)?.into_py(py)],
+            first_elements: vec![count],
+        })
+    }
 
+    /// Obtain the segment at `key` across all buffers.
+    fn segment(&self, py: Python, key: isize) -> PyResult<ZstdBufferSegment> {
         if key < 0 {
             return Err(PyIndexError::new_err("offset must be non-negative"));
         }
 
         let key = key as usize;
+        let len = self.__len__();
 
-        if key >= self.__len__() {
+        if key >= len {
             return Err(PyIndexError::new_err(format!(
                 "offset must be less than {}",
-                self.__len__()
+                len
             )));
         }
 
-        let mut offset = 0;
-        for (buffer_index, segment) in self.buffers.iter().enumerate() {
-            if key < self.first_elements[buffer_index] {
-                if buffer_index > 0 {
-                    offset = self.first_elements[buffer_index - 1];
-                }
+        // first_elements holds the cumulative segment count through each
+        // buffer, so the first entry greater than key is the owning buffer.
+        let buffer_index = self.first_elements.partition_point(|&count| count <= key);
+        let offset = if buffer_index > 0 {
+            self.first_elements[buffer_index - 1]
+        } else {
+            0
+        };
 
-                let item: &PyCell<ZstdBufferWithSegments> = segment.extract(py)?;
+        let item: &PyCell<ZstdBufferWithSegments> = self.buffers[buffer_index].extract(py)?;
+        let segment = item.borrow().segment(py, (key - offset) as isize);
 
-                return item.borrow().__getitem__((key - offset) as isize);
-            }
-        }
+        segment
+    }
+}
 
-        Err(ZstdError::new_err(
-            "error resolving segment; this should not happen",
-        ))
+#[pyproto]
+impl PySequenceProtocol for ZstdBufferWithSegmentsCollection {
+    fn __len__(&self) -> usize {
+        self.first_elements.last().unwrap().clone()
+    }
+
+    fn __getitem__(&self, key: isize) -> PyResult<ZstdBufferSegment> {
+        Python::with_gil(|py| self.segment(py, key))
     }
 }
 
@@ -370,6 +742,7 @@ pub(crate) fn init_module(module: &PyModule) -> PyResult<()> {
     module.add_class::<ZstdBufferSegment>()?;
     module.add_class::<ZstdBufferSegments>()?;
     module.add_class::<ZstdBufferWithSegments>()?;
+    module.add_class::<ZstdBufferWithSegmentsBuilder>()?;
     module.add_class::<ZstdBufferWithSegmentsCollection>()?;
 
     Ok(())
@@ -6,31 +6,93 @@
 
 use {
     crate::{
-        exceptions::ZstdError,
-        stream::{make_in_buffer_source, InBufferSource},
+        constants::skippable_header,
+        exceptions::{warn_resource, ZstdError},
+        stream::{make_in_buffer_source, ChunkOutput, InBufferSource},
         zstd_safe::CCtx,
     },
-    pyo3::{prelude::*, types::PyBytes, PyIterProtocol},
-    std::sync::Arc,
+    pyo3::{class::PyObjectProtocol, prelude::*, types::PyBytes, PyIterProtocol},
+    std::{
+        convert::TryInto,
+        sync::{Arc, Mutex},
+    },
 };
 
+/// Records the position and sizes of the frames a chunker emits.
+#[derive(Default)]
+struct FrameIndex {
+    /// Number of bytes emitted.
+    offset: u64,
+    /// Offset of the current frame.
+    frame_start: u64,
+    /// Uncompressed bytes fed into the current frame.
+    frame_input: u64,
+    /// `(offset, compressed size, content size)` of each ended frame.
+    entries: Vec<(u64, u64, u64)>,
+}
+
+impl FrameIndex {
+    /// Record the end of the current frame.
+    ///
+    /// `pending` is the number of bytes of the frame not emitted yet.
+    fn end_frame(&mut self, pending: usize) {
+        let end = self.offset + pending as u64;
+
+        self.entries
+            .push((self.frame_start, end - self.frame_start, self.frame_input));
+        self.frame_start = end;
+        self.frame_input = 0;
+    }
+}
+
 #[pyclass(module = "zstandard.backend_rust")]
 pub struct ZstdCompressionChunker {
     cctx: Arc<CCtx<'static>>,
     chunk_size: usize,
     finished: bool,
+    entered: bool,
     iterator: Option<Py<ZstdCompressionChunkerIterator>>,
     partial_buffer: Option<Vec<u8>>,
+    output: ChunkOutput,
+    /// Whether input has been fed into the current frame.
+    frame_open: bool,
+    /// Whether the current frame has consumed input or emitted output.
+    ///
+    /// zstd's frame progression still describes the previous frame until the
+    /// next one starts, so this is tracked separately.
+    frame_started: bool,
+    /// Frames emitted so far, if being recorded.
+    frame_index: Option<Arc<Mutex<FrameIndex>>>,
+    /// Output of the implicit `finish()` performed when exiting a context
+    /// manager.
+    #[pyo3(get)]
+    final_chunk: Option<PyObject>,
 }
 
 impl ZstdCompressionChunker {
-    pub fn new(cctx: Arc<CCtx<'static>>, chunk_size: usize) -> PyResult<Self> {
+    pub fn new(
+        py: Python,
+        cctx: Arc<CCtx<'static>>,
+        chunk_size: usize,
+        as_memoryview: bool,
+        frame_index: bool,
+    ) -> PyResult<Self> {
         Ok(Self {
             cctx,
             chunk_size,
             finished: false,
+            entered: false,
             iterator: None,
             partial_buffer: None,
+            output: ChunkOutput::new(py, as_memoryview, chunk_size)?,
+            frame_open: false,
+            frame_started: false,
+            frame_index: if frame_index {
+                Some(Arc::new(Mutex::new(FrameIndex::default())))
+            } else {
+                None
+            },
+            final_chunk: None,
         })
     }
 }
@@ -43,6 +105,10 @@ impl ZstdCompressionChunker {
                     self.finished = true;
                 }
 
+                if it.borrow(py).frame_started {
+                    self.frame_started = true;
+                }
+
                 if !it.borrow(py).dest_buffer.is_empty() {
                     // TODO can we avoid the memory copy?
                     // Vec.clone() won't preserve the capacity of the source.
@@ -68,8 +134,69 @@ impl ZstdCompressionChunker {
     }
 }
 
+impl Drop for ZstdCompressionChunker {
+    fn drop(&mut self) {
+        Python::with_gil(|py| {
+            self.ensure_state(py);
+
+            if self.frame_open && !self.finished {
+                warn_resource(
+                    py,
+                    "ZstdCompressionChunker destroyed with an unfinished frame; \
+                     call finish() to end the frame",
+                );
+            }
+        });
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for ZstdCompressionChunker {
+    fn __repr__(&self) -> String {
+        format!(
+            "ZstdCompressionChunker(chunk_size={}, finished={})",
+            self.chunk_size,
+            if self.finished { "True" } else { "False" },
+        )
+    }
+}
+
 #[pymethods]
 impl ZstdCompressionChunker {
+    fn __enter__<'p>(mut slf: PyRefMut<'p, Self>, _py: Python<'p>) -> PyResult<PyRefMut<'p, Self>> {
+        if slf.entered {
+            Err(ZstdError::new_err("cannot __enter__ multiple times"))
+        } else {
+            slf.entered = true;
+            Ok(slf)
+        }
+    }
+
+    fn __exit__<'p>(
+        mut slf: PyRefMut<'p, Self>,
+        py: Python<'p>,
+        exc_type: &PyAny,
+        _exc_value: &PyAny,
+        _exc_tb: &PyAny,
+    ) -> PyResult<bool> {
+        slf.entered = false;
+        slf.ensure_state(py);
+
+        // Don't attempt to end the frame if the block raised or the caller
+        // already finished the stream.
+        if exc_type.is_none() && !slf.finished {
+            let it = slf.finish(py)?;
+
+            let empty = PyBytes::new(py, &[]);
+            let chunk = empty.call_method1("join", (it,))?;
+
+            slf.ensure_state(py);
+            slf.final_chunk = Some(chunk.into_py(py));
+        }
+
+        Ok(false)
+    }
+
     fn compress(
         &mut self,
         py: Python,
@@ -84,6 +211,7 @@ impl ZstdCompressionChunker {
         }
 
         let source = make_in_buffer_source(py, data, zstd_safe::cstream_in_size())?;
+        self.frame_open = true;
 
         let it = Py::new(
             py,
@@ -92,7 +220,12 @@ impl ZstdCompressionChunker {
                 source,
                 mode: IteratorMode::Normal,
                 dest_buffer: self.get_dest_buffer(),
+                output: self.output.clone_ref(py),
                 finished: false,
+                metadata: None,
+                empty_frame: false,
+                frame_started: false,
+                frame_index: self.frame_index.clone(),
             },
         )?;
 
@@ -101,7 +234,18 @@ impl ZstdCompressionChunker {
         Ok(it)
     }
 
-    fn flush<'p>(&mut self, py: Python<'p>) -> PyResult<Py<ZstdCompressionChunkerIterator>> {
+    /// Flush buffered input as a block.
+    ///
+    /// With `end_frame=True`, the current frame is ended instead, so output
+    /// emitted so far is a sequence of complete frames. The chunker remains
+    /// usable afterwards and later input starts a new frame. Nothing is
+    /// emitted if no frame has started.
+    #[args(end_frame = "false")]
+    fn flush<'p>(
+        &mut self,
+        py: Python<'p>,
+        end_frame: bool,
+    ) -> PyResult<Py<ZstdCompressionChunkerIterator>> {
         self.ensure_state(py);
 
         if self.finished {
@@ -116,6 +260,13 @@ impl ZstdCompressionChunker {
             ));
         }
 
+        // Ending a frame that wasn't started would emit an empty frame.
+        let mode = match (end_frame, self.frame_started) {
+            (true, true) => IteratorMode::EndFrame,
+            (true, false) => IteratorMode::Normal,
+            (false, _) => IteratorMode::Flush,
+        };
+
         let source =
             make_in_buffer_source(py, PyBytes::new(py, &[]), zstd_safe::cstream_in_size())?;
 
@@ -124,12 +275,21 @@ impl ZstdCompressionChunker {
             ZstdCompressionChunkerIterator {
                 cctx: self.cctx.clone(),
                 source,
-                mode: IteratorMode::Flush,
+                mode,
                 dest_buffer: self.get_dest_buffer(),
+                output: self.output.clone_ref(py),
                 finished: false,
+                metadata: None,
+                empty_frame: false,
+                frame_started: false,
+                frame_index: self.frame_index.clone(),
             },
         )?;
 
+        if end_frame {
+            self.frame_open = false;
+            self.frame_started = false;
+        }
         self.iterator = Some(it.clone());
 
         Ok(it)
@@ -160,10 +320,141 @@ impl ZstdCompressionChunker {
                 source,
                 mode: IteratorMode::Finish,
                 dest_buffer: self.get_dest_buffer(),
+                output: self.output.clone_ref(py),
+                finished: false,
+                metadata: None,
+                empty_frame: false,
+                frame_started: false,
+                frame_index: self.frame_index.clone(),
+            },
+        )?;
+
+        self.iterator = Some(it.clone());
+
+        Ok(it)
+    }
+
+    /// Emit `data` as a skippable frame.
+    ///
+    /// Skippable frames can't appear inside a zstd frame. So the current
+    /// frame, if any, is ended first and later input starts a new frame.
+    fn add_metadata<'p>(
+        &mut self,
+        py: Python<'p>,
+        data: &[u8],
+    ) -> PyResult<Py<ZstdCompressionChunkerIterator>> {
+        self.ensure_state(py);
+
+        if self.finished {
+            return Err(ZstdError::new_err(
+                "cannot call add_metadata() after compression finished",
+            ));
+        }
+
+        if self.iterator.is_some() {
+            return Err(ZstdError::new_err(
+                "cannot call add_metadata() before consuming output from previous operation",
+            ));
+        }
+
+        let size: u32 = data
+            .len()
+            .try_into()
+            .map_err(|_| ZstdError::new_err("metadata too large for a skippable frame"))?;
+
+        let mut frame = Vec::with_capacity(8 + data.len());
+        frame.extend_from_slice(&skippable_header(0, size));
+        frame.extend_from_slice(data);
+
+        let source =
+            make_in_buffer_source(py, PyBytes::new(py, &[]), zstd_safe::cstream_in_size())?;
+
+        let it = Py::new(
+            py,
+            ZstdCompressionChunkerIterator {
+                cctx: self.cctx.clone(),
+                source,
+                mode: if self.frame_started {
+                    IteratorMode::EndFrame
+                } else {
+                    IteratorMode::Normal
+                },
+                dest_buffer: self.get_dest_buffer(),
+                output: self.output.clone_ref(py),
+                finished: false,
+                metadata: Some(frame),
+                empty_frame: false,
+                frame_started: false,
+                frame_index: self.frame_index.clone(),
+            },
+        )?;
+
+        self.frame_open = false;
+        self.frame_started = false;
+        self.iterator = Some(it.clone());
+
+        Ok(it)
+    }
+
+    /// `(offset, compressed size, content size)` of each frame ended so far.
+    ///
+    /// Offsets are relative to the first byte emitted. Skippable frames from
+    /// `add_metadata()` aren't included. `None` unless `frame_index=True` was
+    /// passed to `chunker()`.
+    #[getter]
+    fn frame_index(&self) -> Option<Vec<(u64, u64, u64)>> {
+        self.frame_index
+            .as_ref()
+            .map(|index| index.lock().unwrap().entries.clone())
+    }
+
+    /// Emit an empty frame.
+    ///
+    /// The current frame, if any, is ended first. The chunker remains usable
+    /// afterwards and later input starts a new frame.
+    fn write_empty_frame<'p>(
+        &mut self,
+        py: Python<'p>,
+    ) -> PyResult<Py<ZstdCompressionChunkerIterator>> {
+        self.ensure_state(py);
+
+        if self.finished {
+            return Err(ZstdError::new_err(
+                "cannot call write_empty_frame() after compression finished",
+            ));
+        }
+
+        if self.iterator.is_some() {
+            return Err(ZstdError::new_err(
+                "cannot call write_empty_frame() before consuming output from previous operation",
+            ));
+        }
+
+        // A frame fed only empty input hasn't started and ending it emits
+        // the empty frame itself.
+        let started = self.frame_started;
+
+        let source =
+            make_in_buffer_source(py, PyBytes::new(py, &[]), zstd_safe::cstream_in_size())?;
+
+        let it = Py::new(
+            py,
+            ZstdCompressionChunkerIterator {
+                cctx: self.cctx.clone(),
+                source,
+                mode: IteratorMode::EndFrame,
+                dest_buffer: self.get_dest_buffer(),
+                output: self.output.clone_ref(py),
                 finished: false,
+                metadata: None,
+                empty_frame: started,
+                frame_started: false,
+                frame_index: self.frame_index.clone(),
             },
         )?;
 
+        self.frame_open = false;
+        self.frame_started = false;
         self.iterator = Some(it.clone());
 
         Ok(it)
@@ -175,6 +466,8 @@ enum IteratorMode {
     Normal,
     Flush,
     Finish,
+    /// Like `Finish` but the chunker remains usable afterwards.
+    EndFrame,
 }
 
 #[pyclass(module = "zstandard.backend_rust")]
@@ -183,7 +476,55 @@ struct ZstdCompressionChunkerIterator {
     source: Box<dyn InBufferSource + Send>,
     mode: IteratorMode,
     dest_buffer: Vec<u8>,
+    output: ChunkOutput,
     finished: bool,
+    /// Skippable frame to emit once all other output is emitted.
+    metadata: Option<Vec<u8>>,
+    /// Whether an empty frame must follow the end of the current frame.
+    empty_frame: bool,
+    /// Whether this iteration consumed input or emitted part of a frame.
+    frame_started: bool,
+    frame_index: Option<Arc<Mutex<FrameIndex>>>,
+}
+
+impl ZstdCompressionChunkerIterator {
+    /// Mark iteration as finished, returning any pending metadata chunk.
+    fn finish_iteration(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        self.finished = true;
+
+        if let Some(frame) = self.metadata.take() {
+            // Skippable frames sit between indexed frames.
+            if let Some(index) = &self.frame_index {
+                let mut index = index.lock().unwrap();
+                index.offset += frame.len() as u64;
+                index.frame_start = index.offset;
+            }
+
+            Ok(Some(self.output.emit(py, &frame)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Emit the contents of `dest_buffer` as a chunk.
+    fn emit_dest_buffer(&mut self, py: Python) -> PyResult<PyObject> {
+        if let Some(index) = &self.frame_index {
+            index.lock().unwrap().offset += self.dest_buffer.len() as u64;
+        }
+
+        let chunk = self.output.emit(py, &self.dest_buffer)?;
+        self.dest_buffer.clear();
+
+        Ok(chunk)
+    }
+
+    /// Record that the current frame ended with its remaining output in
+    /// `dest_buffer`.
+    fn record_frame_end(&self) {
+        if let Some(index) = &self.frame_index {
+            index.lock().unwrap().end_frame(self.dest_buffer.len());
+        }
+    }
 }
 
 #[pyproto]
@@ -193,12 +534,12 @@ impl PyIterProtocol for ZstdCompressionChunkerIterator {
     }
 
     fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let py = unsafe { Python::assume_gil_acquired() };
+
         if slf.finished {
-            return Ok(None);
+            return slf.finish_iteration(py);
         }
 
-        let py = unsafe { Python::assume_gil_acquired() };
-
         // Consume any data left in the input.
         while let Some(mut in_buffer) = slf.source.input_buffer(py)? {
             let old_pos = in_buffer.pos;
@@ -214,12 +555,17 @@ impl PyIterProtocol for ZstdCompressionChunkerIterator {
 
             slf.source.record_bytes_read(in_buffer.pos - old_pos);
 
+            if in_buffer.pos > old_pos {
+                slf.frame_started = true;
+            }
+
+            if let Some(index) = &slf.frame_index {
+                index.lock().unwrap().frame_input += (in_buffer.pos - old_pos) as u64;
+            }
+
             // If we produced a full output chunk, emit it.
             if slf.dest_buffer.len() == slf.dest_buffer.capacity() {
-                let chunk = PyBytes::new(py, &slf.dest_buffer);
-                slf.dest_buffer.clear();
-
-                return Ok(Some(chunk.into_py(py)));
+                return Ok(Some(slf.emit_dest_buffer(py)?));
             }
 
             // Else continue to compress available input data.
@@ -232,11 +578,12 @@ impl PyIterProtocol for ZstdCompressionChunkerIterator {
 
         let flush_mode = match slf.mode {
             IteratorMode::Normal => {
-                slf.finished = true;
-                return Ok(None);
+                return slf.finish_iteration(py);
             }
             IteratorMode::Flush => zstd_sys::ZSTD_EndDirective::ZSTD_e_flush,
-            IteratorMode::Finish => zstd_sys::ZSTD_EndDirective::ZSTD_e_end,
+            IteratorMode::Finish | IteratorMode::EndFrame => {
+                zstd_sys::ZSTD_EndDirective::ZSTD_e_end
+            }
         };
 
         let mut in_buffer = zstd_sys::ZSTD_inBuffer {
@@ -245,11 +592,38 @@ impl PyIterProtocol for ZstdCompressionChunkerIterator {
             pos: 0,
         };
 
-        let zresult = slf
-            .cctx
-            .clone()
-            .compress_into_vec(&mut slf.dest_buffer, &mut in_buffer, flush_mode)
-            .map_err(|msg| ZstdError::new_err(format!("zstd compress error: {}", msg)))?;
+        let zresult = loop {
+            let zresult = slf
+                .cctx
+                .clone()
+                .compress_into_vec(&mut slf.dest_buffer, &mut in_buffer, flush_mode)
+                .map_err(|msg| ZstdError::new_err(format!("zstd compress error: {}", msg)))?;
+
+            // Flushing emits the frame header if nothing else.
+            if flush_mode == zstd_sys::ZSTD_EndDirective::ZSTD_e_flush
+                && !slf.dest_buffer.is_empty()
+            {
+                slf.frame_started = true;
+            }
+
+            if zresult == 0 && flush_mode == zstd_sys::ZSTD_EndDirective::ZSTD_e_end {
+                slf.record_frame_end();
+            }
+
+            // Ending the frame again from the initial state emits an empty
+            // frame.
+            if zresult == 0 && slf.empty_frame {
+                slf.empty_frame = false;
+
+                if slf.dest_buffer.len() == slf.dest_buffer.capacity() {
+                    return Ok(Some(slf.emit_dest_buffer(py)?));
+                }
+
+                continue;
+            }
+
+            break zresult;
+        };
 
         // When flushing or finishing, we always emit data in the output
         // buffer. But the operation could fill the output buffer and not be
@@ -258,8 +632,7 @@ impl PyIterProtocol for ZstdCompressionChunkerIterator {
         // If we didn't emit anything to the output buffer, we must be finished.
         // Update state and stop iteration.
         if slf.dest_buffer.is_empty() {
-            slf.finished = true;
-            return Ok(None);
+            return slf.finish_iteration(py);
         }
 
         // If the flush or finish didn't fill the output buffer, we must
@@ -269,9 +642,6 @@ impl PyIterProtocol for ZstdCompressionChunkerIterator {
             slf.finished = true;
         }
 
-        let chunk = PyBytes::new(py, &slf.dest_buffer);
-        slf.dest_buffer.clear();
-
-        Ok(Some(chunk.into_py(py)))
+        Ok(Some(slf.emit_dest_buffer(py)?))
     }
 }
@@ -6,24 +6,109 @@
 
 use {
     crate::{
+        buffers::{ByteBuffer, ZstdBufferWithSegments, ZstdBufferWithSegmentsCollection},
         compression_parameters::{get_cctx_parameter, int_to_strategy, ZstdCompressionParameters},
-        zstd_safe::{train_dictionary_fastcover, CCtx, CDict, DCtx, DDict},
+        options::apply_kwargs,
+        zstd_safe::{
+            train_dictionary_cover, train_dictionary_fastcover, CCtx, CDict, DCtx, DDict,
+            DictContent,
+        },
         ZstdError,
     },
     pyo3::{
         buffer::PyBuffer,
-        exceptions::PyValueError,
+        class::PyObjectProtocol,
+        exceptions::{PyTypeError, PyValueError},
         prelude::*,
-        types::{PyBytes, PyList},
+        types::{IntoPyDict, PyBytes, PyDict, PyList, PySlice, PyType},
         wrap_pyfunction,
     },
+    std::sync::{Arc, OnceLock},
 };
 
+/// Size of the length prefix written ahead of shared dictionary data.
+const SHARED_HEADER_SIZE: usize = 8;
+
+/// Backing storage for dictionary data.
+enum DictData {
+    /// Data owned by us.
+    Owned(Vec<u8>),
+
+    /// Data living in a `multiprocessing.shared_memory.SharedMemory` segment.
+    ///
+    /// The segment and an exported buffer to it are held for as long as we
+    /// exist, so the mapping can't go away underneath us. The buffer is
+    /// declared first so it is released before the segment is closed.
+    Shared {
+        buffer: PyBuffer<u8>,
+        len: usize,
+        _shm: PyObject,
+    },
+}
+
+impl std::ops::Deref for DictData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Owned(data) => data,
+            Self::Shared { buffer, len, .. } => unsafe {
+                std::slice::from_raw_parts(
+                    (buffer.buf_ptr() as *const u8).add(SHARED_HEADER_SIZE),
+                    *len,
+                )
+            },
+        }
+    }
+}
+
+impl AsRef<[u8]> for DictData {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+fn dict_content_type(dict_type: Option<u32>) -> PyResult<zstd_sys::ZSTD_dictContentType_e> {
+    if dict_type == Some(zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_auto as u32) {
+        Ok(zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_auto)
+    } else if dict_type == Some(zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_fullDict as u32) {
+        Ok(zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_fullDict)
+    } else if dict_type == Some(zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_rawContent as u32) {
+        Ok(zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_rawContent)
+    } else if let Some(value) = dict_type {
+        Err(PyValueError::new_err(format!(
+            "invalid dictionary load mode: {}; must use DICT_TYPE_* constants",
+            value
+        )))
+    } else {
+        Ok(zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_auto)
+    }
+}
+
+fn dict_load_method(load_method: Option<u32>) -> PyResult<zstd_sys::ZSTD_dictLoadMethod_e> {
+    match load_method {
+        None => Ok(zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byRef),
+        Some(value) if value == zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byRef as u32 => {
+            Ok(zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byRef)
+        }
+        Some(value) if value == zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byCopy as u32 => {
+            Ok(zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byCopy)
+        }
+        Some(value) => Err(PyValueError::new_err(format!(
+            "invalid dictionary load method: {}; must use DICT_LOAD_* constants",
+            value
+        ))),
+    }
+}
+
 #[pyclass(module = "zstandard.backend_rust")]
 pub struct ZstdCompressionDict {
     /// Internal format of dictionary data.
     content_type: zstd_sys::ZSTD_dictContentType_e,
 
+    /// Whether zstd references dictionary data in place or copies it.
+    load_method: zstd_sys::ZSTD_dictLoadMethod_e,
+
     /// Segment size.
     #[pyo3(get)]
     k: u32,
@@ -34,82 +119,204 @@ pub struct ZstdCompressionDict {
 
     /// Raw dictionary data.
     ///
-    /// Owned by us.
-    data: Vec<u8>,
+    /// Owned by us or referenced in shared memory. With `ZSTD_dlm_byRef`,
+    /// shared with `cdict`, `ddict` and compression contexts, which reference
+    /// it in place.
+    data: Arc<DictData>,
 
     /// Precomputed compression dictionary.
-    cdict: Option<CDict<'static>>,
+    ///
+    /// Shared with compression contexts referencing it, so it outlives being
+    /// replaced by `precompute_compress()`.
+    cdict: Option<Arc<CDict>>,
 
     /// Precomputed decompression dictionary.
-    ddict: Option<DDict<'static>>,
+    ///
+    /// Created on first use. Only needs a shared borrow so decompressors on
+    /// different threads can use the dictionary at the same time.
+    ddict: OnceLock<Arc<DDict>>,
 }
 
 impl ZstdCompressionDict {
+    /// Dictionary data for building prepared dictionaries.
+    fn content(&self) -> DictContent {
+        self.data.clone()
+    }
+
+    /// Raw dictionary data.
+    #[cfg(feature = "block-api")]
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+
     pub(crate) fn load_into_cctx(&self, cctx: &CCtx) -> PyResult<()> {
         if let Some(cdict) = &self.cdict {
             cctx.load_computed_dict(cdict)
         } else {
-            cctx.load_dict_data(&self.data, self.content_type)
+            cctx.load_dict_data(self.content(), self.content_type, self.load_method)
         }
         .map_err(|msg| {
             ZstdError::new_err(format!("could not load compression dictionary: {}", msg))
         })
     }
 
-    /// Ensure the DDict is populated.
-    pub(crate) fn ensure_ddict(&mut self) -> PyResult<()> {
-        if self.ddict.is_some() {
-            return Ok(());
+    /// Obtain the DDict, creating it if needed.
+    ///
+    /// Threads racing to create it each build one and all but the first
+    /// are discarded.
+    pub(crate) fn ensure_ddict(&self) -> PyResult<&Arc<DDict>> {
+        if let Some(ddict) = self.ddict.get() {
+            return Ok(ddict);
         }
 
-        self.ddict = Some(
-            DDict::from_data(&self.data, self.content_type)
-                .map_err(|msg| ZstdError::new_err(msg))?,
-        );
+        let ddict = DDict::from_data(self.content(), self.content_type, self.load_method)
+            .map_err(|msg| ZstdError::new_err(msg))?;
+        let _ = self.ddict.set(Arc::new(ddict));
 
-        Ok(())
+        Ok(self.ddict.get().unwrap())
     }
 
-    pub(crate) fn load_into_dctx(&mut self, dctx: &DCtx) -> PyResult<()> {
-        self.ensure_ddict()?;
+    /// Pointer to the prepared decompression dictionary, creating it if needed.
+    pub(crate) fn prepared_ddict(&self) -> PyResult<*const zstd_sys::ZSTD_DDict> {
+        Ok(self.ensure_ddict()?.as_ptr())
+    }
 
-        dctx.load_prepared_dict(self.ddict.as_ref().unwrap())
+    pub(crate) fn load_into_dctx(&self, dctx: &DCtx) -> PyResult<()> {
+        dctx.load_prepared_dict(self.ensure_ddict()?)
             .map_err(|msg| {
                 ZstdError::new_err(format!("unable to reference prepared dictionary: {}", msg))
             })
     }
 }
 
+#[pyproto]
+impl PyObjectProtocol for ZstdCompressionDict {
+    fn __repr__(&self) -> String {
+        format!(
+            "ZstdCompressionDict(dict_id={}, size={})",
+            self.dict_id(),
+            self.data.len()
+        )
+    }
+}
+
 #[pymethods]
 impl ZstdCompressionDict {
     #[new]
-    #[args(data, dict_type = "None")]
-    fn new(py: Python, buffer: PyBuffer<u8>, dict_type: Option<u32>) -> PyResult<Self> {
-        let dict_type = if dict_type == Some(zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_auto as u32)
-        {
-            Ok(zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_auto)
-        } else if dict_type == Some(zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_fullDict as u32) {
-            Ok(zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_fullDict)
-        } else if dict_type == Some(zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_rawContent as u32) {
-            Ok(zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_rawContent)
-        } else if let Some(value) = dict_type {
-            Err(PyValueError::new_err(format!(
-                "invalid dictionary load mode: {}; must use DICT_TYPE_* constants",
-                value
-            )))
-        } else {
-            Ok(zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_auto)
-        }?;
+    #[args(data, dict_type = "None", load_method = "None")]
+    fn new(
+        py: Python,
+        buffer: PyBuffer<u8>,
+        dict_type: Option<u32>,
+        load_method: Option<u32>,
+    ) -> PyResult<Self> {
+        let dict_type = dict_content_type(dict_type)?;
+        let load_method = dict_load_method(load_method)?;
 
         let dict_data = buffer.to_vec(py)?;
 
         Ok(ZstdCompressionDict {
             content_type: dict_type,
+            load_method,
             k: 0,
             d: 0,
-            data: dict_data,
+            data: Arc::new(DictData::Owned(dict_data)),
             cdict: None,
-            ddict: None,
+            ddict: OnceLock::new(),
+        })
+    }
+
+    /// Copy dictionary data into a new shared memory segment.
+    ///
+    /// Returns the `multiprocessing.shared_memory.SharedMemory` instance. Its
+    /// `name` can be passed to `from_shared_memory()` in other processes. The
+    /// caller is responsible for calling `close()` and `unlink()` on it.
+    fn share(&self, py: Python) -> PyResult<PyObject> {
+        let shm = py
+            .import("multiprocessing.shared_memory")?
+            .getattr("SharedMemory")?
+            .call(
+                (),
+                Some(
+                    [
+                        ("create", true.into_py(py)),
+                        ("size", (SHARED_HEADER_SIZE + self.data.len()).into_py(py)),
+                    ]
+                    .into_py_dict(py),
+                ),
+            )?;
+
+        let mut content = Vec::with_capacity(SHARED_HEADER_SIZE + self.data.len());
+        content.extend_from_slice(&(self.data.len() as u64).to_le_bytes());
+        content.extend_from_slice(&self.data);
+
+        let buf = shm.getattr("buf")?;
+        buf.set_item(
+            PySlice::new(py, 0, content.len() as isize, 1),
+            PyBytes::new(py, &content),
+        )?;
+        // Release our export so the segment can be closed by the caller.
+        buf.call_method0("release")?;
+
+        Ok(shm.into_py(py))
+    }
+
+    /// Attach to dictionary data placed in shared memory by `share()`.
+    ///
+    /// Dictionary data is referenced in place rather than copied. The segment
+    /// stays mapped for the lifetime of the returned instance.
+    #[classmethod]
+    #[args(name, dict_type = "None", load_method = "None")]
+    fn from_shared_memory(
+        _cls: &PyType,
+        py: Python,
+        name: &str,
+        dict_type: Option<u32>,
+        load_method: Option<u32>,
+    ) -> PyResult<Self> {
+        let dict_type = dict_content_type(dict_type)?;
+        let load_method = dict_load_method(load_method)?;
+
+        let shm = py
+            .import("multiprocessing.shared_memory")?
+            .getattr("SharedMemory")?
+            .call((), Some([("name", name.into_py(py))].into_py_dict(py)))?;
+
+        let buffer = PyBuffer::<u8>::get(shm.getattr("buf")?)?;
+        let size = buffer.len_bytes();
+
+        if size < SHARED_HEADER_SIZE {
+            return Err(ZstdError::new_err(format!(
+                "shared memory segment {} is too small to hold a dictionary",
+                name
+            )));
+        }
+
+        let mut header = [0u8; SHARED_HEADER_SIZE];
+        header.copy_from_slice(unsafe {
+            std::slice::from_raw_parts(buffer.buf_ptr() as *const u8, SHARED_HEADER_SIZE)
+        });
+        let len = u64::from_le_bytes(header) as usize;
+
+        if len > size - SHARED_HEADER_SIZE {
+            return Err(ZstdError::new_err(format!(
+                "shared memory segment {} does not contain dictionary data",
+                name
+            )));
+        }
+
+        Ok(ZstdCompressionDict {
+            content_type: dict_type,
+            load_method,
+            k: 0,
+            d: 0,
+            data: Arc::new(DictData::Shared {
+                buffer,
+                len,
+                _shm: shm.into_py(py),
+            }),
+            cdict: None,
+            ddict: OnceLock::new(),
         })
     }
 
@@ -121,10 +328,60 @@ impl ZstdCompressionDict {
         Ok(PyBytes::new(py, &self.data))
     }
 
-    fn dict_id(&self) -> u32 {
+    pub(crate) fn dict_id(&self) -> u32 {
         zstd_safe::get_dict_id(&self.data).unwrap_or(0)
     }
 
+    /// Describe the structure of the dictionary data.
+    ///
+    /// `entropy_tables_valid` and `header_size` are `None` for data without
+    /// a zstd dictionary header, which can only be loaded as raw content.
+    fn info<'p>(&self, py: Python<'p>) -> PyResult<&'p PyDict> {
+        let has_header = self.data.len() >= 8
+            && self.data[0..4] == zstd_sys::ZSTD_MAGIC_DICTIONARY.to_le_bytes();
+
+        let (entropy_tables_valid, header_size) = if has_header {
+            match crate::zstd_safe::dict_header_size(&self.data) {
+                Ok(size) => (Some(true), Some(size)),
+                Err(_) => (Some(false), None),
+            }
+        } else {
+            (None, None)
+        };
+
+        let content_size = if has_header {
+            header_size.map(|size| self.data.len() - size)
+        } else {
+            Some(self.data.len())
+        };
+
+        let dict_type = match self.content_type {
+            zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_auto => "auto",
+            zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_rawContent => "rawcontent",
+            zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_fullDict => "fulldict",
+        };
+
+        let info = PyDict::new(py);
+        info.set_item("size", self.data.len())?;
+        info.set_item("dict_type", dict_type)?;
+        info.set_item(
+            "load_method",
+            match self.load_method {
+                zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byRef => "byref",
+                zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byCopy => "bycopy",
+            },
+        )?;
+        info.set_item("has_header", has_header)?;
+        info.set_item("dict_id", self.dict_id())?;
+        info.set_item("entropy_tables_valid", entropy_tables_valid)?;
+        info.set_item("header_size", header_size)?;
+        info.set_item("content_size", content_size)?;
+        info.set_item("k", self.k)?;
+        info.set_item("d", self.d)?;
+
+        Ok(info)
+    }
+
     #[args(level = "None", compression_params = "None")]
     fn precompute_compress(
         &mut self,
@@ -175,32 +432,75 @@ impl ZstdCompressionDict {
             ));
         };
 
-        self.cdict = Some(
-            CDict::from_data(&self.data, self.content_type, params)
+        self.cdict = Some(Arc::new(
+            CDict::from_data(self.content(), self.content_type, self.load_method, params)
                 .map_err(|msg| ZstdError::new_err(msg))?,
-        );
+        ));
 
         Ok(())
     }
 }
 
-#[pyfunction(
-    dict_size,
-    samples,
-    k = "0",
-    d = "0",
-    f = "0",
-    split_point = "0.0",
-    accel = "0",
-    notifications = "0",
-    dict_id = "0",
-    level = "0",
-    steps = "0",
-    threads = "0"
-)]
-fn train_dictionary(
-    dict_size: usize,
-    samples: &PyList,
+/// splitmix64 generator driving reproducible sample selection.
+struct SampleRng(u64);
+
+impl SampleRng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `0..n`.
+    fn below(&mut self, n: u64) -> u64 {
+        ((self.next() as u128 * n as u128) >> 64) as u64
+    }
+}
+
+/// Choose `count` items uniformly at random (Algorithm R).
+///
+/// Items keep the relative order they had in `items`.
+fn reservoir_sample<T>(
+    items: impl Iterator<Item = PyResult<T>>,
+    count: usize,
+    seed: u64,
+) -> PyResult<Vec<T>> {
+    let mut rng = SampleRng(seed);
+    let mut reservoir: Vec<(u64, T)> = Vec::with_capacity(count);
+
+    for (i, item) in items.enumerate() {
+        let item = item?;
+        let i = i as u64;
+
+        if reservoir.len() < count {
+            reservoir.push((i, item));
+        } else {
+            let j = rng.below(i + 1) as usize;
+            if j < count {
+                reservoir[j] = (i, item);
+            }
+        }
+    }
+
+    reservoir.sort_by_key(|(i, _)| *i);
+
+    Ok(reservoir.into_iter().map(|(_, item)| item).collect())
+}
+
+/// Largest total size of samples COVER and fastCOVER accept.
+const MAX_SAMPLES_SIZE: usize = if cfg!(target_pointer_width = "64") {
+    u32::MAX as usize - 1
+} else {
+    (1 << 30) - 1
+};
+
+/// Keyword-only options to `train_dictionary()`.
+///
+/// Besides the COVER and fastCOVER tuning parameters, these control which
+/// samples are trained on.
+struct TrainingOptions {
     k: u32,
     d: u32,
     f: u32,
@@ -211,7 +511,157 @@ fn train_dictionary(
     level: i32,
     steps: u32,
     threads: i32,
+    sample_count: Option<usize>,
+    seed: u64,
+    algorithm: String,
+    sample_sizes: Option<Vec<usize>>,
+    shrink_dict: bool,
+    shrink_dict_max_regression: Option<u32>,
+    max_samples_size: usize,
+}
+
+impl Default for TrainingOptions {
+    fn default() -> Self {
+        Self {
+            k: 0,
+            d: 0,
+            f: 0,
+            split_point: 0.0,
+            accel: 0,
+            notifications: 0,
+            dict_id: 0,
+            level: 0,
+            steps: 0,
+            threads: 0,
+            sample_count: None,
+            seed: 0,
+            algorithm: "fastcover".to_string(),
+            sample_sizes: None,
+            shrink_dict: false,
+            shrink_dict_max_regression: None,
+            max_samples_size: MAX_SAMPLES_SIZE,
+        }
+    }
+}
+
+impl TrainingOptions {
+    fn from_kwargs(kwargs: Option<&PyDict>) -> PyResult<Self> {
+        let mut options = Self::default();
+
+        apply_kwargs("train_dictionary()", kwargs, |key, value| {
+            match key {
+                "k" => options.k = value.extract()?,
+                "d" => options.d = value.extract()?,
+                "f" => options.f = value.extract()?,
+                "split_point" => options.split_point = value.extract()?,
+                "accel" => options.accel = value.extract()?,
+                "notifications" => options.notifications = value.extract()?,
+                "dict_id" => options.dict_id = value.extract()?,
+                "level" => options.level = value.extract()?,
+                "steps" => options.steps = value.extract()?,
+                "threads" => options.threads = value.extract()?,
+                "sample_count" => options.sample_count = value.extract()?,
+                "seed" => options.seed = value.extract()?,
+                "algorithm" => options.algorithm = value.extract()?,
+                "sample_sizes" => options.sample_sizes = value.extract()?,
+                "shrink_dict" => options.shrink_dict = value.extract()?,
+                "shrink_dict_max_regression" => {
+                    options.shrink_dict_max_regression = value.extract()?
+                }
+                "max_samples_size" => options.max_samples_size = value.extract()?,
+                _ => return Ok(false),
+            }
+
+            Ok(true)
+        })?;
+
+        Ok(options)
+    }
+}
+
+/// Choose samples totaling at most `max_size` bytes uniformly at random.
+///
+/// Samples are considered in a random order and kept while they fit. Kept
+/// samples stay in their original order.
+fn bounded_sample(samples: Vec<&[u8]>, max_size: usize, seed: u64) -> Vec<&[u8]> {
+    let mut rng = SampleRng(seed);
+    let mut order: Vec<usize> = (0..samples.len()).collect();
+
+    for i in (1..order.len()).rev() {
+        order.swap(i, rng.below(i as u64 + 1) as usize);
+    }
+
+    let mut remaining = max_size;
+    let mut chosen = order
+        .into_iter()
+        .filter(|i| {
+            let fits = samples[*i].len() <= remaining;
+            if fits {
+                remaining -= samples[*i].len();
+            }
+            fits
+        })
+        .collect::<Vec<_>>();
+    chosen.sort_unstable();
+
+    chosen.into_iter().map(|i| samples[i]).collect()
+}
+
+#[pyfunction(dict_size, samples, kwargs = "**")]
+fn train_dictionary(
+    py: Python,
+    dict_size: usize,
+    samples: &PyAny,
+    kwargs: Option<&PyDict>,
 ) -> PyResult<ZstdCompressionDict> {
+    let TrainingOptions {
+        k,
+        d,
+        f,
+        split_point,
+        accel,
+        notifications,
+        dict_id,
+        level,
+        steps,
+        threads,
+        sample_count,
+        seed,
+        algorithm,
+        sample_sizes,
+        shrink_dict,
+        shrink_dict_max_regression,
+        max_samples_size,
+    } = TrainingOptions::from_kwargs(kwargs)?;
+
+    if sample_count == Some(0) {
+        return Err(PyValueError::new_err("sample_count must be positive"));
+    }
+
+    if shrink_dict_max_regression.is_some() && !shrink_dict {
+        return Err(PyValueError::new_err(
+            "shrink_dict_max_regression requires shrink_dict",
+        ));
+    }
+
+    let cover = match algorithm.as_str() {
+        "fastcover" => false,
+        "cover" => {
+            if f != 0 || accel != 0 {
+                return Err(PyValueError::new_err(
+                    "f and accel require algorithm=\"fastcover\"",
+                ));
+            }
+            true
+        }
+        _ => {
+            return Err(PyValueError::new_err(format!(
+                "algorithm must be \"cover\" or \"fastcover\"; got {}",
+                algorithm
+            )))
+        }
+    };
+
     let threads = if threads < 0 {
         num_cpus::get() as u32
     } else {
@@ -237,8 +687,8 @@ fn train_dictionary(
         nbThreads: threads,
         splitPoint: split_point,
         accel,
-        shrinkDict: 0,
-        shrinkDictMaxRegression: 0,
+        shrinkDict: shrink_dict as u32,
+        shrinkDictMaxRegression: shrink_dict_max_regression.unwrap_or(0),
         zParams: zstd_sys::ZDICT_params_t {
             compressionLevel: level,
             notificationLevel: notifications,
@@ -246,40 +696,139 @@ fn train_dictionary(
         },
     };
 
-    let mut samples_len = 0;
+    // Collect references to every sample so we can size the contiguous
+    // buffer ZDICT wants before copying into it. Segments from
+    // BufferWithSegments types are resolved natively, without
+    // materializing a Python object per sample.
+    let mut sample_slices: Vec<&[u8]> = vec![];
+    // Samples chosen from an arbitrary iterable.
+    let mut sampled: Vec<Py<PyBytes>> = vec![];
+    // Buffer split into samples by `sample_sizes`.
+    let samples_data;
+
+    if let Some(sizes) = sample_sizes {
+        samples_data = ByteBuffer::get(samples)?;
+        let data = samples_data.as_slice();
+
+        if sizes.iter().sum::<usize>() != data.len() {
+            return Err(PyValueError::new_err(format!(
+                "sample_sizes must add up to the size of samples ({} bytes)",
+                data.len()
+            )));
+        }
 
-    // Figure out total size of input samples. A side-effect is all elements are
-    // validated to be PyBytes.
-    for sample in samples.iter() {
-        let bytes = sample
-            .cast_as::<PyBytes>()
-            .or_else(|_| Err(PyValueError::new_err("samples must be bytes")))?;
+        let mut offset = 0;
+        for size in sizes {
+            sample_slices.push(&data[offset..offset + size]);
+            offset += size;
+        }
+    } else if let Ok(buffer) = samples.extract::<&PyCell<ZstdBufferWithSegments>>() {
+        let borrow = buffer.borrow();
+
+        for i in 0..borrow.segments.len() {
+            sample_slices.push(borrow.get_segment_slice(py, i));
+        }
+    } else if let Ok(collection) = samples.extract::<&PyCell<ZstdBufferWithSegmentsCollection>>() {
+        for buffer_obj in &collection.borrow().buffers {
+            let buffer = buffer_obj.extract::<&PyCell<ZstdBufferWithSegments>>(py)?;
+            let borrow = buffer.borrow();
 
-        samples_len += bytes.as_bytes().len();
+            for i in 0..borrow.segments.len() {
+                sample_slices.push(borrow.get_segment_slice(py, i));
+            }
+        }
+    } else if let Ok(list) = samples.extract::<&PyList>() {
+        // A side-effect is all elements are validated to be PyBytes.
+        for sample in list.iter() {
+            let bytes = sample
+                .cast_as::<PyBytes>()
+                .map_err(|_| PyValueError::new_err("samples must be bytes"))?;
+
+            sample_slices.push(bytes.as_bytes());
+        }
+    } else if let (Some(count), Ok(mut iter)) = (sample_count, samples.iter()) {
+        // The corpus may be far larger than memory, so only retain chosen
+        // samples. Each item is fetched in its own pool so discarded ones are
+        // freed as iteration proceeds.
+        let items = std::iter::from_fn(|| {
+            let pool = unsafe { py.new_pool() };
+            let py = pool.python();
+
+            iter.next().map(|sample| {
+                let sample = sample?
+                    .cast_as::<PyBytes>()
+                    .map_err(|_| PyValueError::new_err("samples must be bytes"))?;
+
+                Ok(sample.into_py(py))
+            })
+        });
+
+        sampled = reservoir_sample(items, count, seed)?;
+        sample_slices.extend(sampled.iter().map(|sample| sample.as_ref(py).as_bytes()));
+    } else {
+        return Err(PyTypeError::new_err(
+            "samples must be a list of bytes, BufferWithSegments, or BufferWithSegmentsCollection",
+        ));
     }
 
-    let mut samples_buffer: Vec<u8> = Vec::with_capacity(samples_len);
-    let mut sample_sizes: Vec<libc::size_t> = Vec::with_capacity(samples.len());
+    if let Some(count) = sample_count {
+        if sampled.is_empty() {
+            sample_slices = reservoir_sample(sample_slices.into_iter().map(Ok), count, seed)?;
+        }
+    }
 
-    for sample in samples.iter() {
-        let bytes: &PyBytes = sample.downcast()?;
-        let data = bytes.as_bytes();
-        sample_sizes.push(data.len());
-        samples_buffer.extend_from_slice(data);
+    if sample_slices.iter().map(|data| data.len()).sum::<usize>() > max_samples_size {
+        sample_slices = bounded_sample(sample_slices, max_samples_size, seed);
     }
 
+    let sample_sizes: Vec<libc::size_t> = sample_slices.iter().map(|data| data.len()).collect();
+
+    // Samples already laid out back to back in memory, as is typical for
+    // BufferWithSegments and `sample_sizes`, are trained on in place.
+    let contiguous = sample_slices
+        .windows(2)
+        .all(|pair| pair[0].as_ptr_range().end == pair[1].as_ptr());
+
+    let samples_copy: Vec<u8>;
+    let samples_buffer: &[u8] = if contiguous && !sample_slices.is_empty() {
+        unsafe { std::slice::from_raw_parts(sample_slices[0].as_ptr(), sample_sizes.iter().sum()) }
+    } else {
+        // ZDICT needs one buffer. `max_samples_size` bounds the copy, as
+        // samples were already chosen to fit it above.
+        samples_copy = sample_slices.concat();
+        &samples_copy
+    };
+
     let mut dict_data: Vec<u8> = Vec::with_capacity(dict_size);
 
-    train_dictionary_fastcover(&mut dict_data, &samples_buffer, &sample_sizes, &params)
-        .map_err(|msg| ZstdError::new_err(format!("cannot train dict: {}", msg)))?;
+    let (k, d) = if cover {
+        let mut params = zstd_sys::ZDICT_cover_params_t {
+            k: params.k,
+            d: params.d,
+            steps: params.steps,
+            nbThreads: params.nbThreads,
+            splitPoint: params.splitPoint,
+            shrinkDict: params.shrinkDict,
+            shrinkDictMaxRegression: params.shrinkDictMaxRegression,
+            zParams: params.zParams,
+        };
+
+        train_dictionary_cover(&mut dict_data, samples_buffer, &sample_sizes, &mut params)
+            .map(|_| (params.k, params.d))
+    } else {
+        train_dictionary_fastcover(&mut dict_data, samples_buffer, &sample_sizes, &params)
+            .map(|_| (params.k, params.d))
+    }
+    .map_err(|msg| ZstdError::new_err(format!("cannot train dict: {}", msg)))?;
 
     Ok(ZstdCompressionDict {
         content_type: zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_fullDict,
-        k: params.k,
-        d: params.d,
-        data: dict_data,
+        load_method: zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byRef,
+        k,
+        d,
+        data: Arc::new(DictData::Owned(dict_data)),
         cdict: None,
-        ddict: None,
+        ddict: OnceLock::new(),
     })
 }
 
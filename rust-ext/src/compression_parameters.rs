@@ -5,18 +5,37 @@
 // of the BSD license. See the LICENSE file for details.
 
 use {
-    crate::ZstdError,
+    crate::{constants::validate_level, frame_parameters::parse_frame_header, ZstdError},
     libc::c_int,
     pyo3::{
+        basic::CompareOp,
+        buffer::PyBuffer,
+        class::PyObjectProtocol,
         exceptions::{PyMemoryError, PyTypeError, PyValueError},
         prelude::*,
         types::{PyDict, PyTuple, PyType},
+        PyNativeType,
+    },
+    std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+        marker::PhantomData,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
     },
-    std::marker::PhantomData,
 };
 
 /// Safe wrapper for ZSTD_CCtx_params instances.
-pub struct CCtxParams<'a>(*mut zstd_sys::ZSTD_CCtx_params, PhantomData<&'a ()>);
+///
+/// The second field records `enable_ldm="auto"`, which zstd has no parameter
+/// for. It is applied by `CCtx` when a frame's source size is known.
+pub struct CCtxParams<'a>(
+    *mut zstd_sys::ZSTD_CCtx_params,
+    AtomicBool,
+    PhantomData<&'a ()>,
+);
 
 impl<'a> Drop for CCtxParams<'a> {
     fn drop(&mut self) {
@@ -33,6 +52,46 @@ impl<'a> CCtxParams<'a> {
     pub(crate) unsafe fn get_raw_ptr(&self) -> *mut zstd_sys::ZSTD_CCtx_params {
         self.0
     }
+
+    /// Whether long distance matching is chosen per frame.
+    pub(crate) fn auto_ldm(&self) -> bool {
+        self.1.load(Ordering::Relaxed)
+    }
+}
+
+/// Constructor arguments that fully determine a compressor's `CCtxParams`.
+///
+/// Fields are (level, write_content_size, write_checksum, write_dict_id, threads,
+/// format).
+pub(crate) type CCtxParamsKey = (i32, bool, bool, bool, i32, u32);
+
+/// Maximum number of parameter sets retained by `cached_cctx_params()`.
+const CCTX_PARAMS_CACHE_SIZE: usize = 32;
+
+static CCTX_PARAMS_CACHE: Mutex<Vec<(CCtxParamsKey, Arc<CCtxParams<'static>>)>> =
+    Mutex::new(Vec::new());
+
+/// Obtain a shared `CCtxParams` for `key`, building it on a cache miss.
+///
+/// Cached instances are never mutated. Callers must treat them as read-only.
+pub(crate) fn cached_cctx_params(
+    key: CCtxParamsKey,
+    build: impl FnOnce() -> PyResult<CCtxParams<'static>>,
+) -> PyResult<Arc<CCtxParams<'static>>> {
+    let mut cache = CCTX_PARAMS_CACHE.lock().unwrap();
+
+    if let Some((_, params)) = cache.iter().find(|(k, _)| *k == key) {
+        return Ok(params.clone());
+    }
+
+    let params = Arc::new(build()?);
+
+    if cache.len() >= CCTX_PARAMS_CACHE_SIZE {
+        cache.remove(0);
+    }
+    cache.push((key, params.clone()));
+
+    Ok(params)
 }
 
 impl<'a> CCtxParams<'a> {
@@ -41,7 +100,7 @@ impl<'a> CCtxParams<'a> {
         if params.is_null() {
             return Err(PyMemoryError::new_err("unable to create ZSTD_CCtx_params"));
         }
-        Ok(CCtxParams(params, PhantomData))
+        Ok(CCtxParams(params, AtomicBool::new(false), PhantomData))
     }
 
     pub fn set_parameter(&self, param: zstd_sys::ZSTD_cParameter, value: i32) -> PyResult<()> {
@@ -71,91 +130,228 @@ impl<'a> CCtxParams<'a> {
         py: Python,
         params: &Py<ZstdCompressionParameters>,
     ) -> PyResult<()> {
-        self.apply_compression_parameter(py, &params, zstd_sys::ZSTD_cParameter::ZSTD_c_nbWorkers)?;
+        self.apply_compression_parameter(py, params, zstd_sys::ZSTD_cParameter::ZSTD_c_nbWorkers)?;
         // ZSTD_c_format.
         self.apply_compression_parameter(
             py,
-            &params,
+            params,
             zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam2,
         )?;
         self.apply_compression_parameter(
             py,
-            &params,
+            params,
             zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel,
         )?;
-        self.apply_compression_parameter(py, &params, zstd_sys::ZSTD_cParameter::ZSTD_c_windowLog)?;
-        self.apply_compression_parameter(py, &params, zstd_sys::ZSTD_cParameter::ZSTD_c_hashLog)?;
-        self.apply_compression_parameter(py, &params, zstd_sys::ZSTD_cParameter::ZSTD_c_chainLog)?;
-        self.apply_compression_parameter(py, &params, zstd_sys::ZSTD_cParameter::ZSTD_c_searchLog)?;
-        self.apply_compression_parameter(py, &params, zstd_sys::ZSTD_cParameter::ZSTD_c_minMatch)?;
+        self.apply_compression_parameter(py, params, zstd_sys::ZSTD_cParameter::ZSTD_c_windowLog)?;
+        self.apply_compression_parameter(py, params, zstd_sys::ZSTD_cParameter::ZSTD_c_hashLog)?;
+        self.apply_compression_parameter(py, params, zstd_sys::ZSTD_cParameter::ZSTD_c_chainLog)?;
+        self.apply_compression_parameter(py, params, zstd_sys::ZSTD_cParameter::ZSTD_c_searchLog)?;
+        self.apply_compression_parameter(py, params, zstd_sys::ZSTD_cParameter::ZSTD_c_minMatch)?;
         self.apply_compression_parameter(
             py,
-            &params,
+            params,
             zstd_sys::ZSTD_cParameter::ZSTD_c_targetLength,
         )?;
-        self.apply_compression_parameter(py, &params, zstd_sys::ZSTD_cParameter::ZSTD_c_strategy)?;
+        self.apply_compression_parameter(py, params, zstd_sys::ZSTD_cParameter::ZSTD_c_strategy)?;
         self.apply_compression_parameter(
             py,
-            &params,
+            params,
             zstd_sys::ZSTD_cParameter::ZSTD_c_contentSizeFlag,
         )?;
         self.apply_compression_parameter(
             py,
-            &params,
+            params,
             zstd_sys::ZSTD_cParameter::ZSTD_c_checksumFlag,
         )?;
+        self.apply_compression_parameter(py, params, zstd_sys::ZSTD_cParameter::ZSTD_c_dictIDFlag)?;
+        self.apply_compression_parameter(py, params, zstd_sys::ZSTD_cParameter::ZSTD_c_jobSize)?;
+        self.apply_compression_parameter(py, params, zstd_sys::ZSTD_cParameter::ZSTD_c_overlapLog)?;
+        // ZSTD_c_forceMaxWindow
         self.apply_compression_parameter(
             py,
-            &params,
-            zstd_sys::ZSTD_cParameter::ZSTD_c_dictIDFlag,
+            params,
+            zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam3,
         )?;
-        self.apply_compression_parameter(py, &params, zstd_sys::ZSTD_cParameter::ZSTD_c_jobSize)?;
+        // ZSTD_c_deterministicRefPrefix
         self.apply_compression_parameter(
             py,
-            &params,
-            zstd_sys::ZSTD_cParameter::ZSTD_c_overlapLog,
+            params,
+            zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam15,
         )?;
-        // ZSTD_c_forceMaxWindow
+        // ZSTD_c_targetCBlockSize
         self.apply_compression_parameter(
             py,
-            &params,
-            zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam3,
+            params,
+            zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam6,
         )?;
+        // ZSTD_c_enableDedicatedDictSearch
         self.apply_compression_parameter(
             py,
-            &params,
-            zstd_sys::ZSTD_cParameter::ZSTD_c_overlapLog,
+            params,
+            zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam8,
         )?;
+        // ZSTD_c_useRowMatchFinder
         self.apply_compression_parameter(
             py,
-            &params,
-            zstd_sys::ZSTD_cParameter::ZSTD_c_enableLongDistanceMatching,
+            params,
+            zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam14,
+        )?;
+        // ZSTD_c_stableInBuffer
+        self.apply_compression_parameter(
+            py,
+            params,
+            zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam9,
         )?;
+        // ZSTD_c_stableOutBuffer
         self.apply_compression_parameter(
             py,
-            &params,
-            zstd_sys::ZSTD_cParameter::ZSTD_c_ldmHashLog,
+            params,
+            zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam10,
         )?;
+        self.apply_compression_parameter(py, params, zstd_sys::ZSTD_cParameter::ZSTD_c_overlapLog)?;
         self.apply_compression_parameter(
             py,
-            &params,
+            params,
+            zstd_sys::ZSTD_cParameter::ZSTD_c_enableLongDistanceMatching,
+        )?;
+        self.apply_compression_parameter(py, params, zstd_sys::ZSTD_cParameter::ZSTD_c_ldmHashLog)?;
+        self.apply_compression_parameter(
+            py,
+            params,
             zstd_sys::ZSTD_cParameter::ZSTD_c_ldmMinMatch,
         )?;
         self.apply_compression_parameter(
             py,
-            &params,
+            params,
             zstd_sys::ZSTD_cParameter::ZSTD_c_ldmBucketSizeLog,
         )?;
         self.apply_compression_parameter(
             py,
-            &params,
+            params,
             zstd_sys::ZSTD_cParameter::ZSTD_c_ldmHashRateLog,
         )?;
 
+        self.1.store(params.borrow(py).auto_ldm, Ordering::Relaxed);
+
+        Ok(())
+    }
+}
+
+/// Check a parameter value against the range zstd accepts for it.
+///
+/// 0 selects the default for every parameter and is always accepted.
+fn check_bounds(name: &str, param: zstd_sys::ZSTD_cParameter, value: i32) -> Result<(), String> {
+    if value == 0 {
+        return Ok(());
+    }
+
+    let bounds = unsafe { zstd_sys::ZSTD_cParam_getBounds(param) };
+
+    if unsafe { zstd_sys::ZSTD_isError(bounds.error) } == 0
+        && (value < bounds.lowerBound || value > bounds.upperBound)
+    {
+        Err(format!(
+            "{} must be between {} and {}; got {}",
+            name, bounds.lowerBound, bounds.upperBound, value
+        ))
+    } else {
         Ok(())
     }
 }
 
+/// Names of compression parameters as accepted by `ZstdCompressionParameters`.
+///
+/// Order matches the order parameters are applied in, with `threads` first
+/// since setting `ZSTD_c_nbWorkers` resets other parameters.
+pub(crate) const PARAMETER_NAMES: &[(&str, zstd_sys::ZSTD_cParameter)] = &[
+    ("threads", zstd_sys::ZSTD_cParameter::ZSTD_c_nbWorkers),
+    (
+        "format",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam2,
+    ),
+    (
+        "compression_level",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel,
+    ),
+    ("window_log", zstd_sys::ZSTD_cParameter::ZSTD_c_windowLog),
+    ("hash_log", zstd_sys::ZSTD_cParameter::ZSTD_c_hashLog),
+    ("chain_log", zstd_sys::ZSTD_cParameter::ZSTD_c_chainLog),
+    ("search_log", zstd_sys::ZSTD_cParameter::ZSTD_c_searchLog),
+    ("min_match", zstd_sys::ZSTD_cParameter::ZSTD_c_minMatch),
+    (
+        "target_length",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_targetLength,
+    ),
+    ("strategy", zstd_sys::ZSTD_cParameter::ZSTD_c_strategy),
+    (
+        "write_content_size",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_contentSizeFlag,
+    ),
+    (
+        "write_checksum",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_checksumFlag,
+    ),
+    (
+        "write_dict_id",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_dictIDFlag,
+    ),
+    ("job_size", zstd_sys::ZSTD_cParameter::ZSTD_c_jobSize),
+    ("overlap_log", zstd_sys::ZSTD_cParameter::ZSTD_c_overlapLog),
+    (
+        "force_max_window",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam3,
+    ),
+    (
+        "deterministic_ref_prefix",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam15,
+    ),
+    (
+        "target_cblock_size",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam6,
+    ),
+    (
+        "enable_dedicated_dict_search",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam8,
+    ),
+    (
+        "use_row_match_finder",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam14,
+    ),
+    (
+        "stable_in_buffer",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam9,
+    ),
+    (
+        "stable_out_buffer",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam10,
+    ),
+    (
+        "enable_ldm",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_enableLongDistanceMatching,
+    ),
+    ("ldm_hash_log", zstd_sys::ZSTD_cParameter::ZSTD_c_ldmHashLog),
+    (
+        "ldm_min_match",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_ldmMinMatch,
+    ),
+    (
+        "ldm_bucket_size_log",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_ldmBucketSizeLog,
+    ),
+    (
+        "ldm_hash_rate_log",
+        zstd_sys::ZSTD_cParameter::ZSTD_c_ldmHashRateLog,
+    ),
+];
+
+/// Keyword arguments that only apply with long distance matching enabled.
+const LDM_PARAMETER_NAMES: &[&str] = &[
+    "ldm_hash_log",
+    "ldm_min_match",
+    "ldm_bucket_size_log",
+    "ldm_hash_rate_log",
+];
+
 /// Resolve the value of a compression context parameter.
 pub(crate) fn get_cctx_parameter(
     params: *mut zstd_sys::ZSTD_CCtx_params,
@@ -176,6 +372,139 @@ pub(crate) fn get_cctx_parameter(
     }
 }
 
+/// Smallest job size accepted by zstd's multi-threaded compressor.
+const MIN_JOB_SIZE: i32 = 512 * 1024;
+
+/// Threading configuration of a compressor and its estimated native memory use.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct MemoryPlan {
+    pub threads: i32,
+    pub job_size: i32,
+    pub estimated_size: usize,
+}
+
+impl<'a> CCtxParams<'a> {
+    /// Create a copy of these parameters with some values replaced.
+    pub(crate) fn try_clone_with(
+        &self,
+        overrides: &[(zstd_sys::ZSTD_cParameter, i32)],
+    ) -> PyResult<CCtxParams<'static>> {
+        let params = CCtxParams::create()?;
+
+        for (_, param) in PARAMETER_NAMES {
+            let value = match overrides.iter().find(|(p, _)| *p as i32 == *param as i32) {
+                Some((_, value)) => *value,
+                None => get_cctx_parameter(self.0, *param)?,
+            };
+
+            params.set_parameter(*param, value)?;
+        }
+
+        params.1.store(self.auto_ldm(), Ordering::Relaxed);
+
+        Ok(params)
+    }
+
+    /// The job size zstd picks when `ZSTD_c_jobSize` is 0.
+    fn default_job_size(&self) -> PyResult<i32> {
+        let level = get_cctx_parameter(self.0, zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel)?;
+        let defaults = unsafe { zstd_sys::ZSTD_getCParams(level, 0, 0) };
+
+        let window_log =
+            match get_cctx_parameter(self.0, zstd_sys::ZSTD_cParameter::ZSTD_c_windowLog)? {
+                0 => defaults.windowLog as i32,
+                value => value,
+            };
+        let chain_log =
+            match get_cctx_parameter(self.0, zstd_sys::ZSTD_cParameter::ZSTD_c_chainLog)? {
+                0 => defaults.chainLog as i32,
+                value => value,
+            };
+
+        let job_log = if get_cctx_parameter(
+            self.0,
+            zstd_sys::ZSTD_cParameter::ZSTD_c_enableLongDistanceMatching,
+        )? == 1
+        {
+            std::cmp::max(21, chain_log + 4)
+        } else {
+            std::cmp::max(20, window_log + 2)
+        };
+
+        Ok(1 << std::cmp::min(job_log, 30))
+    }
+
+    /// Estimate native memory used when compressing with these parameters.
+    ///
+    /// Multi-threaded use is approximated as one compression context plus
+    /// input and output buffers per worker, plus the shared input buffer.
+    fn estimate_memory(&self, threads: i32, job_size: i32) -> PyResult<MemoryPlan> {
+        let single = self.try_clone_with(&[(zstd_sys::ZSTD_cParameter::ZSTD_c_nbWorkers, 0)])?;
+
+        let estimated_size = if threads == 0 {
+            unsafe { zstd_sys::ZSTD_estimateCStreamSize_usingCCtxParams(single.0) }
+        } else {
+            let cctx_size = unsafe { zstd_sys::ZSTD_estimateCCtxSize_usingCCtxParams(single.0) };
+            let job_size = job_size as usize;
+            let job_bound = unsafe { zstd_sys::ZSTD_compressBound(job_size) };
+
+            threads as usize * (cctx_size + job_size + job_bound) + job_size
+        };
+
+        if unsafe { zstd_sys::ZSTD_isError(estimated_size) } != 0 {
+            return Err(ZstdError::new_err(format!(
+                "unable to estimate memory usage: {}",
+                zstd_safe::get_error_name(estimated_size)
+            )));
+        }
+
+        Ok(MemoryPlan {
+            threads,
+            job_size: if threads == 0 { 0 } else { job_size },
+            estimated_size,
+        })
+    }
+
+    /// Find a threading configuration whose estimated memory fits in `limit`.
+    ///
+    /// Returns the requested configuration and the one to use. Workers are
+    /// removed first, then the job size is halved, then multi-threading is
+    /// disabled.
+    pub(crate) fn fit_memory(&self, limit: usize) -> PyResult<(MemoryPlan, MemoryPlan)> {
+        let threads = get_cctx_parameter(self.0, zstd_sys::ZSTD_cParameter::ZSTD_c_nbWorkers)?;
+        let job_size = match get_cctx_parameter(self.0, zstd_sys::ZSTD_cParameter::ZSTD_c_jobSize)?
+        {
+            0 => self.default_job_size()?,
+            value => std::cmp::max(value, MIN_JOB_SIZE),
+        };
+
+        let requested = self.estimate_memory(threads, job_size)?;
+        let mut plan = requested;
+
+        while plan.estimated_size > limit && plan.threads > 1 {
+            plan = self.estimate_memory(plan.threads - 1, plan.job_size)?;
+        }
+
+        while plan.estimated_size > limit && plan.threads > 0 && plan.job_size > MIN_JOB_SIZE {
+            plan =
+                self.estimate_memory(plan.threads, std::cmp::max(plan.job_size / 2, MIN_JOB_SIZE))?;
+        }
+
+        if plan.estimated_size > limit && plan.threads > 0 {
+            plan = self.estimate_memory(0, 0)?;
+        }
+
+        if plan.estimated_size > limit {
+            return Err(ZstdError::new_err(format!(
+                "max_native_memory of {} bytes is too small; compression requires at least {} bytes",
+                limit, plan.estimated_size
+            )));
+        }
+
+        Ok((requested, plan))
+    }
+}
+
 // Surely there is a better way...
 pub(crate) fn int_to_strategy(value: u32) -> Result<zstd_sys::ZSTD_strategy, PyErr> {
     if zstd_sys::ZSTD_strategy::ZSTD_fast as u32 == value {
@@ -204,6 +533,10 @@ pub(crate) fn int_to_strategy(value: u32) -> Result<zstd_sys::ZSTD_strategy, PyE
 #[pyclass(module = "zstandard.backend_rust")]
 pub struct ZstdCompressionParameters {
     pub(crate) params: *mut zstd_sys::ZSTD_CCtx_params,
+    /// Whether `enable_ldm="auto"` was requested.
+    pub(crate) auto_ldm: bool,
+    /// Whether `enable_ldm="disable"` was requested.
+    ldm_disabled: bool,
 }
 
 impl Drop for ZstdCompressionParameters {
@@ -234,7 +567,46 @@ impl ZstdCompressionParameters {
         Ok(value)
     }
 
+    /// Values of every parameter in `PARAMETER_NAMES` order.
+    ///
+    /// `enable_ldm="auto"` and `enable_ldm="disable"` are reported separately
+    /// by `auto_ldm` and `ldm_disabled`.
+    fn values(&self) -> PyResult<Vec<c_int>> {
+        PARAMETER_NAMES
+            .iter()
+            .map(|(_, param)| self.get_parameter(*param))
+            .collect()
+    }
+
+    /// Keyword arguments that recreate these parameters.
+    ///
+    /// With `all`, parameters using zstd's default (0) are included.
+    fn kwargs<'p>(&self, py: Python<'p>, all: bool) -> PyResult<&'p PyDict> {
+        let kwargs = PyDict::new(py);
+
+        for ((name, _), value) in PARAMETER_NAMES.iter().zip(self.values()?) {
+            if *name == "enable_ldm" && self.auto_ldm {
+                kwargs.set_item(*name, "auto")?;
+            } else if *name == "enable_ldm" && self.ldm_disabled {
+                kwargs.set_item(*name, "disable")?;
+            } else if all || value != 0 {
+                kwargs.set_item(*name, value)?;
+            }
+        }
+
+        Ok(kwargs)
+    }
+
+    /// Set a parameter after checking it against the range zstd accepts.
+    ///
+    /// Compression levels are checked like `ZstdCompressor(level=...)` does.
     fn set_parameter(&self, param: zstd_sys::ZSTD_cParameter, value: i32) -> PyResult<()> {
+        if param == zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel {
+            validate_level(value)?;
+        } else if let Some((name, _)) = PARAMETER_NAMES.iter().find(|(_, p)| *p == param) {
+            check_bounds(name, param, value).map_err(PyValueError::new_err)?;
+        }
+
         let zresult = unsafe { zstd_sys::ZSTD_CCtxParams_setParameter(self.params, param, value) };
 
         if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
@@ -268,6 +640,12 @@ impl ZstdCompressionParameters {
         let mut job_size = 0;
         let mut overlap_log = -1;
         let mut force_max_window = 0;
+        let mut deterministic_ref_prefix = 0;
+        let mut target_cblock_size = 0;
+        let mut enable_dedicated_dict_search = 0;
+        let mut use_row_match_finder = 0;
+        let mut stable_in_buffer = 0;
+        let mut stable_out_buffer = 0;
         let mut enable_ldm = 0;
         let mut ldm_hash_log = 0;
         let mut ldm_min_match = 0;
@@ -294,6 +672,14 @@ impl ZstdCompressionParameters {
                 "job_size" => job_size = value.extract::<_>()?,
                 "overlap_log" => overlap_log = value.extract::<_>()?,
                 "force_max_window" => force_max_window = value.extract::<_>()?,
+                "deterministic_ref_prefix" => deterministic_ref_prefix = value.extract::<_>()?,
+                "target_cblock_size" => target_cblock_size = value.extract::<_>()?,
+                "enable_dedicated_dict_search" => {
+                    enable_dedicated_dict_search = value.extract::<_>()?
+                }
+                "use_row_match_finder" => use_row_match_finder = value.extract::<_>()?,
+                "stable_in_buffer" => stable_in_buffer = value.extract::<_>()?,
+                "stable_out_buffer" => stable_out_buffer = value.extract::<_>()?,
                 "enable_ldm" => enable_ldm = value.extract::<_>()?,
                 "ldm_hash_log" => ldm_hash_log = value.extract::<_>()?,
                 "ldm_min_match" => ldm_min_match = value.extract::<_>()?,
@@ -357,6 +743,30 @@ impl ZstdCompressionParameters {
             zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam3,
             force_max_window,
         )?;
+        self.set_parameter(
+            zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam15,
+            deterministic_ref_prefix,
+        )?;
+        self.set_parameter(
+            zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam6,
+            target_cblock_size,
+        )?;
+        self.set_parameter(
+            zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam8,
+            enable_dedicated_dict_search,
+        )?;
+        self.set_parameter(
+            zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam14,
+            use_row_match_finder,
+        )?;
+        self.set_parameter(
+            zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam9,
+            stable_in_buffer,
+        )?;
+        self.set_parameter(
+            zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam10,
+            stable_out_buffer,
+        )?;
         self.set_parameter(
             zstd_sys::ZSTD_cParameter::ZSTD_c_enableLongDistanceMatching,
             enable_ldm,
@@ -379,10 +789,154 @@ impl ZstdCompressionParameters {
 
         Ok(())
     }
+
+    /// Check long distance matching parameters against the window they
+    /// will be used with.
+    ///
+    /// zstd accepts these combinations when parameters are set and only
+    /// fails, if at all, once a frame is started. Without an explicit
+    /// `window_log`, zstd uses a 128 MB window for long distance matching.
+    fn check_ldm_window(&self) -> PyResult<()> {
+        let enabled = self.auto_ldm
+            || self.get_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_enableLongDistanceMatching)?
+                != 0;
+
+        if !enabled {
+            return Ok(());
+        }
+
+        let window_log = match self.window_log()? {
+            0 => zstd_sys::ZSTD_WINDOWLOG_LIMIT_DEFAULT as c_int,
+            value => value,
+        };
+
+        for (name, value) in &[
+            ("ldm_hash_log", self.ldm_hash_log()?),
+            ("ldm_hash_rate_log", self.ldm_hash_rate_log()?),
+        ] {
+            if *value > window_log {
+                return Err(PyValueError::new_err(format!(
+                    "{} ({}) must not exceed window_log ({})",
+                    name, value, window_log
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for ZstdCompressionParameters {
+    /// Lists parameters that aren't 0, i.e. that don't use zstd's default.
+    fn __repr__(&self) -> PyResult<String> {
+        let mut fields = vec![];
+
+        for (name, param) in PARAMETER_NAMES {
+            if *name == "enable_ldm" && self.auto_ldm {
+                fields.push("enable_ldm='auto'".to_string());
+                continue;
+            }
+            if *name == "enable_ldm" && self.ldm_disabled {
+                fields.push("enable_ldm='disable'".to_string());
+                continue;
+            }
+
+            let value = self.get_parameter(*param)?;
+            if value != 0 {
+                fields.push(format!("{}={}", name, value));
+            }
+        }
+
+        Ok(format!("ZstdCompressionParameters({})", fields.join(", ")))
+    }
+
+    fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyResult<PyObject> {
+        let py = other.py();
+
+        let other = match other.extract::<PyRef<ZstdCompressionParameters>>() {
+            Ok(other) => other,
+            Err(_) => return Ok(py.NotImplemented()),
+        };
+
+        let equal = self.auto_ldm == other.auto_ldm
+            && self.ldm_disabled == other.ldm_disabled
+            && self.values()? == other.values()?;
+
+        match op {
+            CompareOp::Eq => Ok(equal.into_py(py)),
+            CompareOp::Ne => Ok((!equal).into_py(py)),
+            _ => Ok(py.NotImplemented()),
+        }
+    }
+
+    fn __hash__(&self) -> PyResult<isize> {
+        let mut hasher = DefaultHasher::new();
+        self.auto_ldm.hash(&mut hasher);
+        self.ldm_disabled.hash(&mut hasher);
+        self.values()?.hash(&mut hasher);
+
+        Ok(hasher.finish() as isize)
+    }
 }
 
 #[pymethods]
 impl ZstdCompressionParameters {
+    /// Derive parameters matching the header of an existing frame.
+    ///
+    /// The window is the largest power of 2 not exceeding the frame's, so
+    /// output decompresses under the same window limits. The checksum,
+    /// content size and dictionary ID flags mirror the frame. Keyword
+    /// arguments override derived values.
+    #[classmethod]
+    #[args(data, kwargs = "**")]
+    fn from_frame(
+        _cls: &PyType,
+        py: Python,
+        data: PyBuffer<u8>,
+        kwargs: Option<&PyDict>,
+    ) -> PyResult<Self> {
+        let header = parse_frame_header(&data)?;
+
+        if header.frameType == zstd_sys::ZSTD_frameType_e::ZSTD_skippableFrame {
+            return Err(ZstdError::new_err(
+                "cannot derive parameters from skippable frame",
+            ));
+        }
+
+        let window_log = 63 - header.windowSize.max(1).leading_zeros() as i32;
+        let window_log = window_log.max(zstd_sys::ZSTD_WINDOWLOG_MIN as i32);
+
+        let params = PyDict::new(py);
+        params.set_item("window_log", window_log)?;
+        params.set_item("write_checksum", header.checksumFlag != 0)?;
+        params.set_item(
+            "write_content_size",
+            header.frameContentSize != zstd_sys::ZSTD_CONTENTSIZE_UNKNOWN as _,
+        )?;
+        params.set_item("write_dict_id", header.dictID != 0)?;
+
+        if let Some(kwargs) = kwargs {
+            for (key, value) in kwargs.iter() {
+                params.set_item(key, value)?;
+            }
+        }
+
+        Self::new(py, PyTuple::empty(py), Some(params))
+    }
+
+    /// Obtain a builder to assemble parameters with chained setters.
+    #[classmethod]
+    fn builder(_cls: &PyType, py: Python) -> ZstdCompressionParametersBuilder {
+        ZstdCompressionParametersBuilder {
+            values: PyDict::new(py).into(),
+        }
+    }
+
+    /// Parameters for compression `level`.
+    ///
+    /// Negative levels down to `MIN_COMPRESSION_LEVEL` select the fast
+    /// strategy, using the negated level as `target_length`.
     #[classmethod]
     #[args(args = "*", kwargs = "**")]
     fn from_level(
@@ -404,7 +958,7 @@ impl ZstdCompressionParameters {
             PyDict::new(py)
         };
 
-        let level = args.get_item(0).extract::<i32>()?;
+        let level = validate_level(args.get_item(0).extract::<i32>()?)?;
 
         let source_size = if let Some(value) = kwargs.get_item("source_size") {
             kwargs.del_item("source_size")?;
@@ -423,7 +977,13 @@ impl ZstdCompressionParameters {
         let compression_params =
             unsafe { zstd_sys::ZSTD_getCParams(level, source_size, dict_size) };
 
-        if !kwargs.contains("window_log")? {
+        // The window is picked per frame with enable_ldm="auto".
+        let auto_ldm = kwargs
+            .get_item("enable_ldm")
+            .and_then(|v| v.extract::<&str>().ok())
+            == Some("auto");
+
+        if !kwargs.contains("window_log")? && !auto_ldm {
             kwargs.set_item("window_log", compression_params.windowLog)?;
         }
         if !kwargs.contains("chain_log")? {
@@ -450,21 +1010,65 @@ impl ZstdCompressionParameters {
 
     #[new]
     #[args(_args = "*", kwargs = "**")]
-    fn new(py: Python, _args: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<Self> {
+    pub(crate) fn new(py: Python, _args: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<Self> {
         let params = unsafe { zstd_sys::ZSTD_createCCtxParams() };
         if params.is_null() {
             return Err(PyMemoryError::new_err("unable to create ZSTD_CCtx_params"));
         }
 
-        let instance = ZstdCompressionParameters { params };
-
         let kwargs = if let Some(v) = kwargs {
             v.copy()?
         } else {
             PyDict::new(py)
         };
 
+        let ldm_mode = kwargs
+            .get_item("enable_ldm")
+            .and_then(|v| v.extract::<&str>().ok());
+
+        let auto_ldm = match ldm_mode {
+            Some("auto") => {
+                if let Some(window_log) = kwargs.get_item("window_log") {
+                    if window_log.extract::<i32>().ok() != Some(0) {
+                        return Err(PyValueError::new_err(
+                            "cannot specify window_log with enable_ldm=\"auto\"",
+                        ));
+                    }
+                }
+
+                kwargs.set_item("enable_ldm", 0)?;
+                true
+            }
+            _ => false,
+        };
+
+        let ldm_disabled = match ldm_mode {
+            Some("disable") => {
+                for name in LDM_PARAMETER_NAMES {
+                    if let Some(value) = kwargs.get_item(*name) {
+                        if value.extract::<i32>().ok() != Some(0) {
+                            return Err(PyValueError::new_err(format!(
+                                "cannot specify {} with enable_ldm=\"disable\"",
+                                name
+                            )));
+                        }
+                    }
+                }
+
+                kwargs.set_item("enable_ldm", 0)?;
+                true
+            }
+            _ => false,
+        };
+
+        let instance = ZstdCompressionParameters {
+            params,
+            auto_ldm,
+            ldm_disabled,
+        };
+
         instance.set_parameters(&kwargs)?;
+        instance.check_ldm_window()?;
 
         Ok(instance)
     }
@@ -474,6 +1078,10 @@ impl ZstdCompressionParameters {
         self.get_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam2)
     }
 
+    /// The compression level in effect.
+    ///
+    /// 0 resolves to the default level and out of range levels to the
+    /// nearest supported level.
     #[getter]
     fn compression_level(&self) -> PyResult<c_int> {
         self.get_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel)
@@ -540,8 +1148,47 @@ impl ZstdCompressionParameters {
     }
 
     #[getter]
-    fn enable_ldm(&self) -> PyResult<c_int> {
-        self.get_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_enableLongDistanceMatching)
+    fn deterministic_ref_prefix(&self) -> PyResult<c_int> {
+        self.get_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam15)
+    }
+
+    #[getter]
+    fn target_cblock_size(&self) -> PyResult<c_int> {
+        self.get_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam6)
+    }
+
+    #[getter]
+    fn enable_dedicated_dict_search(&self) -> PyResult<c_int> {
+        self.get_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam8)
+    }
+
+    #[getter]
+    fn use_row_match_finder(&self) -> PyResult<c_int> {
+        self.get_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam14)
+    }
+
+    #[getter]
+    fn stable_in_buffer(&self) -> PyResult<c_int> {
+        self.get_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam9)
+    }
+
+    #[getter]
+    fn stable_out_buffer(&self) -> PyResult<c_int> {
+        self.get_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam10)
+    }
+
+    #[getter]
+    fn enable_ldm(&self, py: Python) -> PyResult<PyObject> {
+        if self.auto_ldm {
+            return Ok("auto".into_py(py));
+        }
+        if self.ldm_disabled {
+            return Ok("disable".into_py(py));
+        }
+
+        Ok(self
+            .get_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_enableLongDistanceMatching)?
+            .into_py(py))
     }
 
     #[getter]
@@ -579,10 +1226,298 @@ impl ZstdCompressionParameters {
 
         Ok(size)
     }
+
+    /// Every parameter keyed by its keyword argument name.
+    fn as_dict<'p>(&self, py: Python<'p>) -> PyResult<&'p PyDict> {
+        self.kwargs(py, true)
+    }
+
+    /// Copy of these parameters with keyword arguments overriding values.
+    #[args(overrides = "**")]
+    fn replace(&self, py: Python, overrides: Option<&PyDict>) -> PyResult<Self> {
+        let kwargs = self.kwargs(py, false)?;
+
+        if let Some(overrides) = overrides {
+            for (key, value) in overrides.iter() {
+                kwargs.set_item(key, value)?;
+            }
+        }
+
+        Self::new(py, PyTuple::empty(py), Some(kwargs))
+    }
+}
+
+/// Accumulates compression parameters for `ZstdCompressionParameters`.
+///
+/// Obtained from `ZstdCompressionParameters.builder()`. Each setter returns
+/// the builder so calls can be chained. Parameters are validated together
+/// by `build()`.
+#[pyclass(module = "zstandard.backend_rust")]
+pub struct ZstdCompressionParametersBuilder {
+    values: Py<PyDict>,
+}
+
+impl ZstdCompressionParametersBuilder {
+    fn set<'p>(slf: PyRefMut<'p, Self>, name: &str, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        slf.values.as_ref(slf.py()).set_item(name, value)?;
+
+        Ok(slf)
+    }
+
+    /// Describe every problem with the accumulated parameters.
+    fn violations(&self, py: Python) -> PyResult<Vec<String>> {
+        let values = self.values.as_ref(py);
+        let mut violations = vec![];
+        let mut ints = std::collections::HashMap::new();
+
+        for (name, param) in PARAMETER_NAMES {
+            let value = match values.get_item(*name) {
+                Some(value) => value,
+                None => continue,
+            };
+
+            if *name == "enable_ldm"
+                && matches!(value.extract::<&str>(), Ok("auto") | Ok("disable"))
+            {
+                continue;
+            }
+
+            let value = match value.extract::<i32>() {
+                Ok(value) => value,
+                Err(_) => {
+                    violations.push(format!("{} must be an integer", name));
+                    continue;
+                }
+            };
+
+            if *name == "compression_level" {
+                if let Err(err) = validate_level(value) {
+                    violations.push(err.pvalue(py).to_string());
+                }
+            } else if let Err(violation) = check_bounds(name, *param, value) {
+                violations.push(violation);
+            }
+
+            ints.insert(*name, value);
+        }
+
+        let get = |name: &str| ints.get(name).copied().unwrap_or(0);
+        let auto_ldm = values
+            .get_item("enable_ldm")
+            .and_then(|v| v.extract::<&str>().ok())
+            == Some("auto");
+
+        if auto_ldm && get("window_log") != 0 {
+            violations.push("window_log cannot be combined with enable_ldm=\"auto\"".to_string());
+        }
+
+        if get("enable_ldm") == 0 && !auto_ldm {
+            for name in LDM_PARAMETER_NAMES {
+                if get(name) != 0 {
+                    violations.push(format!("{} requires enable_ldm", name));
+                }
+            }
+        }
+
+        if get("ldm_hash_log") != 0
+            && get("window_log") != 0
+            && get("ldm_hash_log") > get("window_log")
+        {
+            violations.push(format!(
+                "ldm_hash_log ({}) must not exceed window_log ({})",
+                get("ldm_hash_log"),
+                get("window_log")
+            ));
+        }
+
+        if get("ldm_bucket_size_log") != 0
+            && get("ldm_hash_log") != 0
+            && get("ldm_bucket_size_log") > get("ldm_hash_log")
+        {
+            violations.push(format!(
+                "ldm_bucket_size_log ({}) must not exceed ldm_hash_log ({})",
+                get("ldm_bucket_size_log"),
+                get("ldm_hash_log")
+            ));
+        }
+
+        if get("threads") == 0 {
+            for name in &["job_size", "overlap_log"] {
+                if get(name) != 0 {
+                    violations.push(format!("{} requires threads", name));
+                }
+            }
+        }
+
+        Ok(violations)
+    }
+}
+
+#[pymethods]
+impl ZstdCompressionParametersBuilder {
+    /// Validate the parameters and construct `ZstdCompressionParameters`.
+    ///
+    /// Raises `ValueError` listing every violation if any are found.
+    fn build(&self, py: Python) -> PyResult<ZstdCompressionParameters> {
+        let violations = self.violations(py)?;
+
+        if !violations.is_empty() {
+            return Err(PyValueError::new_err(format!(
+                "invalid compression parameters: {}",
+                violations.join("; ")
+            )));
+        }
+
+        let kwargs = self.values.as_ref(py).copy()?;
+
+        ZstdCompressionParameters::new(py, PyTuple::empty(py), Some(kwargs))
+    }
+
+    fn threads<'p>(slf: PyRefMut<'p, Self>, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "threads", value)
+    }
+
+    fn format<'p>(slf: PyRefMut<'p, Self>, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "format", value)
+    }
+
+    fn compression_level<'p>(
+        slf: PyRefMut<'p, Self>,
+        value: &PyAny,
+    ) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "compression_level", value)
+    }
+
+    fn window_log<'p>(slf: PyRefMut<'p, Self>, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "window_log", value)
+    }
+
+    fn hash_log<'p>(slf: PyRefMut<'p, Self>, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "hash_log", value)
+    }
+
+    fn chain_log<'p>(slf: PyRefMut<'p, Self>, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "chain_log", value)
+    }
+
+    fn search_log<'p>(slf: PyRefMut<'p, Self>, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "search_log", value)
+    }
+
+    fn min_match<'p>(slf: PyRefMut<'p, Self>, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "min_match", value)
+    }
+
+    fn target_length<'p>(slf: PyRefMut<'p, Self>, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "target_length", value)
+    }
+
+    fn strategy<'p>(slf: PyRefMut<'p, Self>, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "strategy", value)
+    }
+
+    fn write_content_size<'p>(
+        slf: PyRefMut<'p, Self>,
+        value: &PyAny,
+    ) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "write_content_size", value)
+    }
+
+    fn write_checksum<'p>(slf: PyRefMut<'p, Self>, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "write_checksum", value)
+    }
+
+    fn write_dict_id<'p>(slf: PyRefMut<'p, Self>, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "write_dict_id", value)
+    }
+
+    fn job_size<'p>(slf: PyRefMut<'p, Self>, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "job_size", value)
+    }
+
+    fn overlap_log<'p>(slf: PyRefMut<'p, Self>, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "overlap_log", value)
+    }
+
+    fn force_max_window<'p>(
+        slf: PyRefMut<'p, Self>,
+        value: &PyAny,
+    ) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "force_max_window", value)
+    }
+
+    fn deterministic_ref_prefix<'p>(
+        slf: PyRefMut<'p, Self>,
+        value: &PyAny,
+    ) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "deterministic_ref_prefix", value)
+    }
+
+    fn target_cblock_size<'p>(
+        slf: PyRefMut<'p, Self>,
+        value: &PyAny,
+    ) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "target_cblock_size", value)
+    }
+
+    fn enable_dedicated_dict_search<'p>(
+        slf: PyRefMut<'p, Self>,
+        value: &PyAny,
+    ) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "enable_dedicated_dict_search", value)
+    }
+
+    fn use_row_match_finder<'p>(
+        slf: PyRefMut<'p, Self>,
+        value: &PyAny,
+    ) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "use_row_match_finder", value)
+    }
+
+    fn stable_in_buffer<'p>(
+        slf: PyRefMut<'p, Self>,
+        value: &PyAny,
+    ) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "stable_in_buffer", value)
+    }
+
+    fn stable_out_buffer<'p>(
+        slf: PyRefMut<'p, Self>,
+        value: &PyAny,
+    ) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "stable_out_buffer", value)
+    }
+
+    fn enable_ldm<'p>(slf: PyRefMut<'p, Self>, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "enable_ldm", value)
+    }
+
+    fn ldm_hash_log<'p>(slf: PyRefMut<'p, Self>, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "ldm_hash_log", value)
+    }
+
+    fn ldm_min_match<'p>(slf: PyRefMut<'p, Self>, value: &PyAny) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "ldm_min_match", value)
+    }
+
+    fn ldm_bucket_size_log<'p>(
+        slf: PyRefMut<'p, Self>,
+        value: &PyAny,
+    ) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "ldm_bucket_size_log", value)
+    }
+
+    fn ldm_hash_rate_log<'p>(
+        slf: PyRefMut<'p, Self>,
+        value: &PyAny,
+    ) -> PyResult<PyRefMut<'p, Self>> {
+        Self::set(slf, "ldm_hash_rate_log", value)
+    }
 }
 
 pub(crate) fn init_module(module: &PyModule) -> PyResult<()> {
     module.add_class::<ZstdCompressionParameters>()?;
+    module.add_class::<ZstdCompressionParametersBuilder>()?;
 
     Ok(())
 }
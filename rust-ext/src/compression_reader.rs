@@ -6,12 +6,14 @@
 
 use {
     crate::{
-        exceptions::ZstdError,
+        buffers::BytesBuilder,
+        exceptions::{warn_resource, CommonError, ZstdError},
         stream::{make_in_buffer_source, InBufferSource},
         zstd_safe::CCtx,
     },
     pyo3::{
         buffer::PyBuffer,
+        class::PyObjectProtocol,
         exceptions::{PyOSError, PyValueError},
         prelude::*,
         types::{PyBytes, PyList},
@@ -93,20 +95,67 @@ impl ZstdCompressionReader {
         }
     }
 
-    fn compress_into_vec(&mut self, py: Python, dest_buffer: &mut Vec<u8>) -> PyResult<bool> {
-        let mut out_buffer = zstd_sys::ZSTD_outBuffer {
-            dst: dest_buffer.as_mut_ptr() as *mut _,
-            size: dest_buffer.capacity(),
-            pos: dest_buffer.len(),
+    /// Flush the end of the frame into `out_buffer`.
+    fn end_stream(&mut self, out_buffer: &mut zstd_sys::ZSTD_outBuffer) -> PyResult<()> {
+        let old_pos = out_buffer.pos;
+
+        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+            src: std::ptr::null_mut(),
+            size: 0,
+            pos: 0,
         };
 
-        let res = self.compress_into_buffer(py, &mut out_buffer)?;
+        let zresult = self
+            .cctx
+            .compress_buffers(
+                out_buffer,
+                &mut in_buffer,
+                zstd_sys::ZSTD_EndDirective::ZSTD_e_end,
+            )
+            .map_err(|msg| {
+                ZstdError::new_err(format!("error ending compression stream: {}", msg))
+            })?;
+
+        self.bytes_compressed += out_buffer.pos - old_pos;
 
-        unsafe {
-            dest_buffer.set_len(out_buffer.pos);
+        if zresult == 0 {
+            self.finished_output = true;
         }
 
-        Ok(res)
+        Ok(())
+    }
+}
+
+impl Drop for ZstdCompressionReader {
+    fn drop(&mut self) {
+        if self.bytes_compressed > 0 && !self.finished_output && !self.closed {
+            Python::with_gil(|py| {
+                warn_resource(
+                    py,
+                    &format!(
+                        "ZstdCompressionReader destroyed before the end of the frame \
+                         ({} bytes of compressed output read)",
+                        self.bytes_compressed
+                    ),
+                )
+            });
+        }
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for ZstdCompressionReader {
+    fn __repr__(&self) -> String {
+        format!(
+            "ZstdCompressionReader(bytes_compressed={}, closed={}, finished={})",
+            self.bytes_compressed,
+            if self.closed { "True" } else { "False" },
+            if self.finished_output {
+                "True"
+            } else {
+                "False"
+            },
+        )
     }
 }
 
@@ -116,7 +165,7 @@ impl ZstdCompressionReader {
         if slf.entered {
             Err(PyValueError::new_err("cannot __enter__ multiple times"))
         } else if slf.closed {
-            Err(PyValueError::new_err("stream is closed"))
+            Err(CommonError::StreamClosed.into())
         } else {
             slf.entered = true;
             Ok(slf)
@@ -226,7 +275,7 @@ impl ZstdCompressionReader {
     #[args(size = "-1")]
     fn read<'p>(&mut self, py: Python<'p>, size: isize) -> PyResult<&'p PyAny> {
         if self.closed {
-            return Err(PyValueError::new_err("stream is closed"));
+            return Err(CommonError::StreamClosed.into());
         }
 
         if size < -1 {
@@ -243,51 +292,29 @@ impl ZstdCompressionReader {
             return Ok(PyBytes::new(py, &[]));
         }
 
-        let mut dest_buffer: Vec<u8> = Vec::with_capacity(size as _);
+        let mut dest = BytesBuilder::with_capacity(py, size as _)?;
+        let mut out_buffer = dest.out_buffer();
 
         while !self.source.finished() {
             // If the output buffer is full, return its content.
-            if self.compress_into_vec(py, &mut dest_buffer)? {
-                // TODO avoid buffer copy.
-                return Ok(PyBytes::new(py, &dest_buffer));
+            if self.compress_into_buffer(py, &mut out_buffer)? {
+                dest.set_len(out_buffer.pos);
+                return Ok(dest.finish(py)?);
             }
             // Else continue to read new input into the compressor.
         }
 
         // EOF.
-        let old_pos = dest_buffer.len();
-
-        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
-            src: std::ptr::null_mut(),
-            size: 0,
-            pos: 0,
-        };
+        self.end_stream(&mut out_buffer)?;
 
-        let zresult = self
-            .cctx
-            .compress_into_vec(
-                &mut dest_buffer,
-                &mut in_buffer,
-                zstd_sys::ZSTD_EndDirective::ZSTD_e_end,
-            )
-            .map_err(|msg| {
-                ZstdError::new_err(format!("error ending compression stream: {}", msg))
-            })?;
-
-        self.bytes_compressed += dest_buffer.len() - old_pos;
-
-        if zresult == 0 {
-            self.finished_output = true;
-        }
-
-        // TODO avoid buffer copy.
-        Ok(PyBytes::new(py, &dest_buffer))
+        dest.set_len(out_buffer.pos);
+        Ok(dest.finish(py)?)
     }
 
     #[args(size = "-1")]
     fn read1<'p>(&mut self, py: Python<'p>, size: isize) -> PyResult<&'p PyAny> {
         if self.closed {
-            return Err(PyValueError::new_err("stream is closed"));
+            return Err(CommonError::StreamClosed.into());
         }
 
         if size < -1 {
@@ -307,7 +334,8 @@ impl ZstdCompressionReader {
             size as _
         };
 
-        let mut dest_buffer: Vec<u8> = Vec::with_capacity(size);
+        let mut dest = BytesBuilder::with_capacity(py, size)?;
+        let mut out_buffer = dest.out_buffer();
 
         // read1() dictates that we can perform at most 1 call to the
         // underlying stream to get input. However, we can't satisfy this
@@ -318,48 +346,23 @@ impl ZstdCompressionReader {
         // have any output.
 
         // Read data until we exhaust input or have output data.
-        while !self.source.finished() && dest_buffer.is_empty() {
-            self.compress_into_vec(py, &mut dest_buffer)?;
+        while !self.source.finished() && out_buffer.pos == 0 {
+            self.compress_into_buffer(py, &mut out_buffer)?;
         }
 
         // We return immediately if:
         // a) output buffer is full
         // b) output buffer has data and input isn't exhausted.
-        if dest_buffer.len() == dest_buffer.capacity()
-            || (!dest_buffer.is_empty() && !self.source.finished())
-        {
-            // TODO avoid buffer copy.
-            return Ok(PyBytes::new(py, &dest_buffer));
+        if out_buffer.pos == out_buffer.size || (out_buffer.pos > 0 && !self.source.finished()) {
+            dest.set_len(out_buffer.pos);
+            return Ok(dest.finish(py)?);
         }
 
         // Input must be exhausted. Finish the compression stream.
-        let old_pos = dest_buffer.len();
-
-        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
-            src: std::ptr::null_mut(),
-            size: 0,
-            pos: 0,
-        };
-
-        let zresult = self
-            .cctx
-            .compress_into_vec(
-                &mut dest_buffer,
-                &mut in_buffer,
-                zstd_sys::ZSTD_EndDirective::ZSTD_e_end,
-            )
-            .map_err(|msg| {
-                ZstdError::new_err(format!("error ending compression stream: {}", msg))
-            })?;
+        self.end_stream(&mut out_buffer)?;
 
-        self.bytes_compressed += dest_buffer.len() - old_pos;
-
-        if zresult == 0 {
-            self.finished_output = true;
-        }
-
-        // TODO avoid buffer copy
-        Ok(PyBytes::new(py, &dest_buffer))
+        dest.set_len(out_buffer.pos);
+        Ok(dest.finish(py)?)
     }
 
     fn readinto(&mut self, py: Python, buffer: PyBuffer<u8>) -> PyResult<usize> {
@@ -368,7 +371,7 @@ impl ZstdCompressionReader {
         }
 
         if self.closed {
-            return Err(PyValueError::new_err("stream is closed"));
+            return Err(CommonError::StreamClosed.into());
         }
 
         if self.finished_output {
@@ -388,30 +391,7 @@ impl ZstdCompressionReader {
         }
 
         // EOF.
-        let old_pos = out_buffer.pos;
-
-        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
-            src: std::ptr::null_mut(),
-            size: 0,
-            pos: 0,
-        };
-
-        let zresult = self
-            .cctx
-            .compress_buffers(
-                &mut out_buffer,
-                &mut in_buffer,
-                zstd_sys::ZSTD_EndDirective::ZSTD_e_end,
-            )
-            .map_err(|msg| {
-                ZstdError::new_err(format!("error ending compression stream: {}", msg))
-            })?;
-
-        self.bytes_compressed += out_buffer.pos - old_pos;
-
-        if zresult == 0 {
-            self.finished_output = true;
-        }
+        self.end_stream(&mut out_buffer)?;
 
         Ok(out_buffer.pos)
     }
@@ -422,7 +402,7 @@ impl ZstdCompressionReader {
         }
 
         if self.closed {
-            return Err(PyValueError::new_err("stream is closed"));
+            return Err(CommonError::StreamClosed.into());
         }
 
         if self.finished_output {
@@ -446,30 +426,7 @@ impl ZstdCompressionReader {
         }
 
         // EOF.
-        let old_pos = out_buffer.pos;
-
-        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
-            src: std::ptr::null_mut(),
-            size: 0,
-            pos: 0,
-        };
-
-        let zresult = self
-            .cctx
-            .compress_buffers(
-                &mut out_buffer,
-                &mut in_buffer,
-                zstd_sys::ZSTD_EndDirective::ZSTD_e_end,
-            )
-            .map_err(|msg| {
-                ZstdError::new_err(format!("error ending compression stream: {}", msg))
-            })?;
-
-        self.bytes_compressed += out_buffer.pos - old_pos;
-
-        if zresult == 0 {
-            self.finished_output = true;
-        }
+        self.end_stream(&mut out_buffer)?;
 
         Ok(out_buffer.pos)
     }
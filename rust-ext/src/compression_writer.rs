@@ -5,18 +5,108 @@
 // of the BSD license. See the LICENSE file for details.
 
 use {
-    crate::{exceptions::ZstdError, zstd_safe::CCtx},
+    crate::{
+        buffers::ByteBuffer,
+        compression_dict::ZstdCompressionDict,
+        compressor::{frame_progression, padding_frame, DEADLINE_FALLBACK_LEVEL},
+        exceptions::{warn_resource, CommonError, ZstdError},
+        stream::CopyDeadline,
+        zstd_safe::CCtx,
+    },
     pyo3::{
+        basic::CompareOp,
         buffer::PyBuffer,
-        exceptions::{PyNotImplementedError, PyOSError, PyValueError},
+        class::{PyNumberProtocol, PyObjectProtocol},
+        exceptions::{PyNotImplementedError, PyValueError},
         prelude::*,
         types::PyBytes,
+        PyNativeType,
     },
     std::sync::Arc,
 };
 
-const FLUSH_BLOCK: usize = 0;
-const FLUSH_FRAME: usize = 1;
+const FLUSH_BLOCK: i64 = 0;
+const FLUSH_FRAME: i64 = 1;
+
+/// Dictionary overriding the compressor's for a single frame.
+enum FrameDict {
+    Dict(Py<ZstdCompressionDict>),
+    /// Kept alive because zstd references the data until the frame ends.
+    Prefix(PyBuffer<u8>),
+}
+
+/// Result of `ZstdCompressionWriter.flush()`.
+///
+/// Behaves as the number of bytes written for compatibility with the integer
+/// returned by other backends.
+#[pyclass(module = "zstandard.backend_rust")]
+pub struct FlushResult {
+    /// Compressed bytes written to the underlying writer.
+    #[pyo3(get)]
+    bytes_written: usize,
+    /// Whether the frame was ended. If not, the frame epilogue (final block
+    /// and checksum) is still to be written.
+    #[pyo3(get)]
+    frame_ended: bool,
+    /// Whether `flush()` was called on the underlying writer.
+    #[pyo3(get)]
+    writer_flushed: bool,
+    /// Digest of the uncompressed input if the frame was ended and the writer
+    /// has a hasher.
+    #[pyo3(get)]
+    digest: Option<PyObject>,
+}
+
+#[pyproto]
+impl PyObjectProtocol for FlushResult {
+    fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyResult<PyObject> {
+        let py = other.py();
+
+        let other = match other.extract::<PyRef<FlushResult>>() {
+            Ok(other) => other.bytes_written,
+            Err(_) => match other.extract::<usize>() {
+                Ok(other) => other,
+                Err(_) => return Ok(py.NotImplemented()),
+            },
+        };
+
+        let value = self.bytes_written;
+
+        Ok(match op {
+            CompareOp::Lt => value < other,
+            CompareOp::Le => value <= other,
+            CompareOp::Eq => value == other,
+            CompareOp::Ne => value != other,
+            CompareOp::Gt => value > other,
+            CompareOp::Ge => value >= other,
+        }
+        .into_py(py))
+    }
+
+    fn __hash__(&self) -> isize {
+        self.bytes_written as isize
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "FlushResult(bytes_written={}, frame_ended={}, writer_flushed={})",
+            self.bytes_written,
+            if self.frame_ended { "True" } else { "False" },
+            if self.writer_flushed { "True" } else { "False" },
+        )
+    }
+}
+
+#[pyproto]
+impl PyNumberProtocol for FlushResult {
+    fn __int__(&self) -> usize {
+        self.bytes_written
+    }
+
+    fn __index__(&self) -> usize {
+        self.bytes_written
+    }
+}
 
 #[pyclass(module = "zstandard.backend_rust")]
 pub struct ZstdCompressionWriter {
@@ -28,7 +118,37 @@ pub struct ZstdCompressionWriter {
     closing: bool,
     closed: bool,
     bytes_compressed: usize,
+    bytes_written: usize,
+    /// Whether input has been written since the last frame ended.
+    frame_open: bool,
     dest_buffer: Vec<u8>,
+    /// Time limit for each `write()` and the compression level to restore
+    /// after a frame that fell back to a lower level.
+    deadline: Option<(u64, i32)>,
+    /// Whether the current frame uses the deadline fallback level.
+    fallback: bool,
+    /// Record size each frame is padded to a multiple of.
+    pad_to: Option<usize>,
+    /// Value of `bytes_compressed` when the current frame started.
+    frame_start: usize,
+    /// Receives uncompressed input via `update()`.
+    hasher: Option<PyObject>,
+    /// Digest of the uncompressed input as of the last frame end.
+    digest: Option<PyObject>,
+    /// The compressor's dictionary, restored after a frame with an override.
+    dict: Option<Py<ZstdCompressionDict>>,
+    /// Dictionary or prefix used by the current frame instead of `dict`.
+    frame_dict: Option<FrameDict>,
+    /// Offset of the frame header descriptor within frames, if content sizes
+    /// missing from frame headers are patched in when frames end.
+    patch_content_size: Option<usize>,
+    /// Writer position of the content size field to patch.
+    content_size_pos: Option<u64>,
+    /// Uncompressed bytes consumed by the current frame.
+    frame_input: u64,
+    /// `(offset, compressed size, content size)` of each frame written, if
+    /// being recorded.
+    frame_index: Option<Vec<(u64, u64, u64)>>,
 }
 
 impl ZstdCompressionWriter {
@@ -53,16 +173,295 @@ impl ZstdCompressionWriter {
             closing: false,
             closed: false,
             bytes_compressed: 0,
+            bytes_written: 0,
+            frame_open: false,
             dest_buffer: Vec::with_capacity(write_size),
+            deadline: None,
+            fallback: false,
+            pad_to: None,
+            frame_start: 0,
+            hasher: None,
+            digest: None,
+            dict: None,
+            frame_dict: None,
+            patch_content_size: None,
+            content_size_pos: None,
+            frame_input: 0,
+            frame_index: None,
         })
     }
+
+    /// Bound the time spent compressing in each `write()`.
+    ///
+    /// When exceeded, the frame is ended and the rest of the write starts a new
+    /// frame at a lower level. `level` is restored once that frame ends.
+    pub fn with_deadline(mut self, deadline_ms: u64, level: i32) -> Self {
+        self.deadline = Some((deadline_ms, level));
+        self
+    }
+
+    /// Pad each frame with a skippable frame to a multiple of `pad_to` bytes.
+    pub fn with_padding(mut self, pad_to: usize) -> Self {
+        self.pad_to = Some(pad_to);
+        self
+    }
+
+    /// Feed uncompressed input to `hasher`, capturing its digest at frame end.
+    ///
+    /// The hasher sees all input written, so the digest covers every frame
+    /// produced so far.
+    pub fn with_hasher(mut self, hasher: PyObject) -> Self {
+        self.hasher = Some(hasher);
+        self
+    }
+
+    /// Record the compressor's dictionary so it can be restored after frames
+    /// using a different one.
+    pub fn with_dict(mut self, dict: Py<ZstdCompressionDict>) -> Self {
+        self.dict = Some(dict);
+        self
+    }
+
+    /// Reserve space for the content size in headers of frames with an
+    /// unknown size and write the size once each frame ends.
+    ///
+    /// `magicless` says whether frames lack the magic number. The writer must
+    /// be seekable.
+    pub fn with_content_size_patching(mut self, magicless: bool) -> Self {
+        self.patch_content_size = Some(if magicless { 0 } else { 4 });
+        self
+    }
+
+    /// Record the position and sizes of each frame as it ends.
+    pub fn with_frame_index(mut self) -> Self {
+        self.frame_index = Some(vec![]);
+        self
+    }
+
+    /// Write compressed output in `dest_buffer` to the writer.
+    ///
+    /// Returns the number of bytes written.
+    fn write_dest_buffer(&mut self, py: Python) -> PyResult<usize> {
+        if self.dest_buffer.is_empty() {
+            return Ok(0);
+        }
+
+        if let Some(fhd_offset) = self.patch_content_size {
+            if self.bytes_compressed == self.frame_start {
+                if let Some(offset) = reserve_content_size(&mut self.dest_buffer, fhd_offset)? {
+                    let pos = self.writer.call_method0(py, "tell")?.extract::<u64>(py)?;
+                    self.content_size_pos = Some(pos + offset as u64);
+                }
+            }
+        }
+
+        // TODO avoid buffer copy.
+        let chunk = PyBytes::new(py, &self.dest_buffer);
+        self.writer.call_method1(py, "write", (chunk,))?;
+
+        let size = self.dest_buffer.len();
+        self.bytes_compressed += size;
+        self.dest_buffer.clear();
+
+        Ok(size)
+    }
+
+    /// Write the content size of the frame that just ended into its header.
+    fn patch_content_size(&mut self, py: Python) -> PyResult<()> {
+        if let Some(pos) = self.content_size_pos.take() {
+            let end = self.writer.call_method0(py, "tell")?;
+            self.writer.call_method1(py, "seek", (pos,))?;
+            let size = PyBytes::new(py, &self.frame_input.to_le_bytes());
+            self.writer.call_method1(py, "write", (size,))?;
+            self.writer.call_method1(py, "seek", (end,))?;
+        }
+
+        Ok(())
+    }
+
+    /// Have the next frame use `frame_dict` instead of the compressor's
+    /// dictionary, or restore the compressor's dictionary for `None`.
+    fn set_frame_dict(&mut self, py: Python, frame_dict: Option<FrameDict>) -> PyResult<()> {
+        match (&frame_dict, &self.dict) {
+            (Some(FrameDict::Dict(dict)), _) | (None, Some(dict)) => {
+                dict.borrow(py).load_into_cctx(&self.cctx)?
+            }
+            (Some(FrameDict::Prefix(prefix)), _) => {
+                let data = unsafe {
+                    std::slice::from_raw_parts(prefix.buf_ptr() as *const u8, prefix.len_bytes())
+                };
+                self.cctx.ref_prefix(data).map_err(|msg| {
+                    ZstdError::new_err(format!("could not reference prefix: {}", msg))
+                })?
+            }
+            (None, None) => self.cctx.clear_dict().map_err(|msg| {
+                ZstdError::new_err(format!("could not clear compression dictionary: {}", msg))
+            })?,
+        }
+
+        self.frame_dict = frame_dict;
+
+        Ok(())
+    }
+
+    /// Flush buffered data to the writer, ending the frame for `ZSTD_e_end`.
+    ///
+    /// Returns the number of bytes written and whether the writer was flushed.
+    fn flush_mode(
+        &mut self,
+        py: Python,
+        flush: zstd_sys::ZSTD_EndDirective,
+    ) -> PyResult<(usize, bool)> {
+        if self.closed {
+            return Err(CommonError::StreamClosed.into());
+        }
+
+        let mut total_write = 0;
+
+        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+            src: std::ptr::null_mut(),
+            size: 0,
+            pos: 0,
+        };
+
+        loop {
+            let zresult = self
+                .cctx
+                .compress_into_vec(&mut self.dest_buffer, &mut in_buffer, flush)
+                .map_err(|msg| ZstdError::new_err(format!("zstd compress error: {}", msg)))?;
+
+            total_write += self.write_dest_buffer(py)?;
+
+            if zresult == 0 {
+                break;
+            }
+        }
+
+        if flush == zstd_sys::ZSTD_EndDirective::ZSTD_e_end {
+            self.frame_open = false;
+
+            if let Some(index) = &mut self.frame_index {
+                let size = self.bytes_compressed - self.frame_start;
+
+                if size > 0 {
+                    index.push((self.frame_start as u64, size as u64, self.frame_input));
+                }
+            }
+
+            self.patch_content_size(py)?;
+            self.frame_input = 0;
+
+            if let Some(pad_to) = self.pad_to {
+                let padding = padding_frame(self.bytes_compressed - self.frame_start, pad_to);
+
+                if !padding.is_empty() {
+                    let chunk = PyBytes::new(py, &padding);
+                    self.writer.call_method1(py, "write", (chunk,))?;
+
+                    total_write += padding.len();
+                    self.bytes_compressed += padding.len();
+                }
+            }
+
+            self.frame_start = self.bytes_compressed;
+
+            if let Some(hasher) = &self.hasher {
+                self.digest = Some(hasher.call_method0(py, "digest")?);
+            }
+
+            if self.frame_dict.is_some() {
+                self.set_frame_dict(py, None)?;
+            }
+
+            if self.fallback {
+                self.fallback = false;
+
+                if let Some((_, level)) = self.deadline {
+                    self.cctx
+                        .set_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel, level)
+                        .map_err(|msg| {
+                            ZstdError::new_err(format!("unable to set compression level: {}", msg))
+                        })?;
+                }
+            }
+        }
+
+        let mut writer_flushed = false;
+        if let Ok(flush) = self.writer.getattr(py, "flush") {
+            if !self.closing {
+                flush.call0(py)?;
+                writer_flushed = true;
+            }
+        }
+
+        Ok((total_write, writer_flushed))
+    }
+}
+
+/// Widen the content size field of the frame header at the start of `chunk`
+/// to 8 bytes if the header lacks a content size.
+///
+/// `fhd_offset` is the offset of the frame header descriptor. Returns the
+/// offset of the reserved field, which is zeroed.
+fn reserve_content_size(chunk: &mut Vec<u8>, fhd_offset: usize) -> PyResult<Option<usize>> {
+    let fhd = *chunk
+        .get(fhd_offset)
+        .ok_or_else(|| ZstdError::new_err("frame header split across writes"))?;
+
+    // Single segment frames always have a content size field.
+    if fhd & 0xe0 != 0 {
+        return Ok(None);
+    }
+
+    let dict_id_size = [0, 1, 2, 4][(fhd & 0x03) as usize];
+    // Frame header descriptor and window descriptor.
+    let offset = fhd_offset + 2 + dict_id_size;
+
+    if chunk.len() < offset {
+        return Err(ZstdError::new_err("frame header split across writes"));
+    }
+
+    chunk[fhd_offset] |= 0xc0;
+    chunk.splice(offset..offset, [0u8; 8].iter().cloned());
+
+    Ok(Some(offset))
+}
+
+impl Drop for ZstdCompressionWriter {
+    fn drop(&mut self) {
+        if self.frame_open {
+            Python::with_gil(|py| {
+                warn_resource(
+                    py,
+                    &format!(
+                        "ZstdCompressionWriter destroyed with an unfinished frame \
+                         ({} bytes written, {} bytes of compressed output); \
+                         call close() or flush(FLUSH_FRAME) to end the frame",
+                        self.bytes_written, self.bytes_compressed
+                    ),
+                )
+            });
+        }
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for ZstdCompressionWriter {
+    fn __repr__(&self) -> String {
+        format!(
+            "ZstdCompressionWriter(bytes_compressed={}, bytes_written={}, closed={})",
+            self.bytes_compressed,
+            self.bytes_written,
+            if self.closed { "True" } else { "False" },
+        )
+    }
 }
 
 #[pymethods]
 impl ZstdCompressionWriter {
     fn __enter__<'p>(mut slf: PyRefMut<'p, Self>, _py: Python<'p>) -> PyResult<PyRefMut<'p, Self>> {
         if slf.closed {
-            Err(PyValueError::new_err("stream is closed"))
+            Err(CommonError::StreamClosed.into())
         } else if slf.entered {
             Err(ZstdError::new_err("cannot __enter__ multiple times"))
         } else {
@@ -94,9 +493,7 @@ impl ZstdCompressionWriter {
         if let Ok(fileno) = self.writer.getattr(py, "fileno") {
             fileno.call0(py)
         } else {
-            Err(PyOSError::new_err(
-                "filenot not available on underlying writer",
-            ))
+            Err(CommonError::NoFileno.into())
         }
     }
 
@@ -106,7 +503,7 @@ impl ZstdCompressionWriter {
         }
 
         self.closing = true;
-        let res = self.flush(py, FLUSH_FRAME);
+        let res = self.flush_mode(py, zstd_sys::ZSTD_EndDirective::ZSTD_e_end);
         self.closing = false;
         self.closed = true;
 
@@ -207,39 +604,71 @@ impl ZstdCompressionWriter {
         Err(PyErr::from_instance(exc))
     }
 
-    fn write(&mut self, py: Python, buffer: PyBuffer<u8>) -> PyResult<usize> {
+    fn write(&mut self, py: Python, buffer: ByteBuffer) -> PyResult<usize> {
         if self.closed {
-            return Err(PyValueError::new_err("stream is closed"));
+            return Err(CommonError::StreamClosed.into());
+        }
+
+        if let Some(hasher) = &self.hasher {
+            hasher.call_method1(py, "update", (buffer.object(py),))?;
         }
 
         let mut total_write = 0;
 
+        let source = buffer.as_slice();
         let mut in_buffer = zstd_sys::ZSTD_inBuffer {
-            src: buffer.buf_ptr(),
-            size: buffer.len_bytes(),
+            src: source.as_ptr() as *const _,
+            size: 0,
             pos: 0,
         };
 
-        while in_buffer.pos < in_buffer.size {
-            self.cctx
-                .compress_into_vec(
-                    &mut self.dest_buffer,
-                    &mut in_buffer,
-                    zstd_sys::ZSTD_EndDirective::ZSTD_e_continue,
-                )
-                .map_err(|msg| ZstdError::new_err(format!("zstd compress error: {}", msg)))?;
+        // With a deadline, input is fed in slices so it can be checked.
+        let deadline = CopyDeadline::new(self.deadline.map(|(ms, _)| ms));
+        let slice_size = if self.deadline.is_some() {
+            zstd_safe::cstream_in_size()
+        } else {
+            source.len()
+        };
 
-            if !self.dest_buffer.is_empty() {
-                // TODO avoid buffer copy.
-                let chunk = PyBytes::new(py, &self.dest_buffer);
-                self.writer.call_method1(py, "write", (chunk,))?;
+        while in_buffer.pos < source.len() {
+            if !self.fallback && in_buffer.pos > 0 && deadline.expired() {
+                total_write += self
+                    .flush_mode(py, zstd_sys::ZSTD_EndDirective::ZSTD_e_end)?
+                    .0;
+
+                self.cctx
+                    .set_parameter(
+                        zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel,
+                        DEADLINE_FALLBACK_LEVEL,
+                    )
+                    .map_err(|msg| {
+                        ZstdError::new_err(format!("unable to set compression level: {}", msg))
+                    })?;
+                self.fallback = true;
+            }
+
+            in_buffer.size = std::cmp::min(in_buffer.pos + slice_size, source.len());
+
+            while in_buffer.pos < in_buffer.size {
+                let start = in_buffer.pos;
 
-                total_write += self.dest_buffer.len();
-                self.bytes_compressed += self.dest_buffer.len();
-                self.dest_buffer.clear();
+                self.cctx
+                    .compress_into_vec(
+                        &mut self.dest_buffer,
+                        &mut in_buffer,
+                        zstd_sys::ZSTD_EndDirective::ZSTD_e_continue,
+                    )
+                    .map_err(|msg| ZstdError::new_err(format!("zstd compress error: {}", msg)))?;
+
+                self.frame_input += (in_buffer.pos - start) as u64;
+                total_write += self.write_dest_buffer(py)?;
             }
+
+            self.frame_open = true;
         }
 
+        self.bytes_written += in_buffer.pos;
+
         if self.write_return_read {
             Ok(in_buffer.pos)
         } else {
@@ -247,60 +676,110 @@ impl ZstdCompressionWriter {
         }
     }
 
-    #[args(flush_mode = "FLUSH_BLOCK")]
-    fn flush(&mut self, py: Python, flush_mode: usize) -> PyResult<usize> {
-        let flush = match flush_mode {
-            FLUSH_BLOCK => Ok(zstd_sys::ZSTD_EndDirective::ZSTD_e_flush),
-            FLUSH_FRAME => Ok(zstd_sys::ZSTD_EndDirective::ZSTD_e_end),
-            _ => Err(PyValueError::new_err(format!(
-                "unknown flush_mode: {}",
-                flush_mode
-            ))),
-        }?;
-
+    /// Write each buffer in `buffers` sequentially.
+    ///
+    /// Returns the sum of what `write()` returns for each buffer.
+    fn write_vectored(&mut self, py: Python, buffers: &PyAny) -> PyResult<usize> {
         if self.closed {
-            return Err(PyValueError::new_err("stream is closed"));
+            return Err(CommonError::StreamClosed.into());
         }
 
-        let mut total_write = 0;
+        let mut total = 0;
 
-        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
-            src: std::ptr::null_mut(),
-            size: 0,
-            pos: 0,
-        };
+        for buffer in buffers.iter()? {
+            total += self.write(py, ByteBuffer::get(buffer?)?)?;
+        }
 
-        loop {
-            let zresult = self
-                .cctx
-                .compress_into_vec(&mut self.dest_buffer, &mut in_buffer, flush)
-                .map_err(|msg| ZstdError::new_err(format!("zstd compress error: {}", msg)))?;
+        Ok(total)
+    }
 
-            if !self.dest_buffer.is_empty() {
-                // TODO avoid buffer copy.
-                let chunk = PyBytes::new(py, &self.dest_buffer);
-                self.writer.call_method1(py, "write", (chunk,))?;
+    /// Flush data to the writer.
+    ///
+    /// With `FLUSH_FRAME`, `dict_data` or `prefix` makes the next frame use
+    /// that dictionary or raw content prefix instead of the compressor's
+    /// dictionary. Later frames revert to the compressor's dictionary. If no
+    /// input was written since the last frame ended, no frame is written and
+    /// the override applies to the frame about to start.
+    #[args(flush_mode = "FLUSH_BLOCK", "*", dict_data = "None", prefix = "None")]
+    fn flush(
+        &mut self,
+        py: Python,
+        flush_mode: i64,
+        dict_data: Option<Py<ZstdCompressionDict>>,
+        prefix: Option<PyBuffer<u8>>,
+    ) -> PyResult<FlushResult> {
+        let flush = match flush_mode {
+            FLUSH_BLOCK => Ok(zstd_sys::ZSTD_EndDirective::ZSTD_e_flush),
+            FLUSH_FRAME => Ok(zstd_sys::ZSTD_EndDirective::ZSTD_e_end),
+            _ => Err(CommonError::UnknownFlushMode(flush_mode)),
+        }?;
 
-                total_write += self.dest_buffer.len();
-                self.bytes_compressed += self.dest_buffer.len();
-                self.dest_buffer.clear();
+        let frame_dict = match (dict_data, prefix) {
+            (Some(_), Some(_)) => {
+                return Err(PyValueError::new_err(
+                    "cannot specify both dict_data and prefix",
+                ))
             }
+            (Some(dict), None) => Some(FrameDict::Dict(dict)),
+            (None, Some(prefix)) => Some(FrameDict::Prefix(prefix)),
+            (None, None) => None,
+        };
 
-            if zresult == 0 {
-                break;
-            }
+        if frame_dict.is_some() && flush != zstd_sys::ZSTD_EndDirective::ZSTD_e_end {
+            return Err(PyValueError::new_err(
+                "dict_data and prefix require flush_mode=FLUSH_FRAME",
+            ));
         }
 
-        if let Ok(flush) = self.writer.getattr(py, "flush") {
-            if !self.closing {
-                flush.call0(py)?;
+        let (bytes_written, writer_flushed) = if frame_dict.is_some() && !self.frame_open {
+            if self.closed {
+                return Err(CommonError::StreamClosed.into());
             }
+
+            (0, false)
+        } else {
+            self.flush_mode(py, flush)?
+        };
+        let frame_ended = flush == zstd_sys::ZSTD_EndDirective::ZSTD_e_end;
+
+        if frame_dict.is_some() {
+            self.set_frame_dict(py, frame_dict)?;
         }
 
-        Ok(total_write)
+        Ok(FlushResult {
+            bytes_written,
+            frame_ended,
+            writer_flushed,
+            digest: if frame_ended {
+                self.digest.as_ref().map(|digest| digest.clone_ref(py))
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Digest of the uncompressed input as of the last frame end.
+    #[getter]
+    fn digest(&self, py: Python) -> Option<PyObject> {
+        self.digest.as_ref().map(|digest| digest.clone_ref(py))
     }
 
     fn tell(&self) -> usize {
         self.bytes_compressed
     }
+
+    /// `(offset, compressed size, content size)` of each frame written so far.
+    ///
+    /// Offsets are relative to the first byte this writer wrote. Padding
+    /// frames from `pad_to` aren't included. `None` unless
+    /// `frame_index=True` was passed to `stream_writer()`.
+    #[getter]
+    fn frame_index(&self) -> Option<Vec<(u64, u64, u64)>> {
+        self.frame_index.clone()
+    }
+
+    #[args(detailed = "false")]
+    fn frame_progression(&self, py: Python, detailed: bool) -> PyResult<PyObject> {
+        frame_progression(py, &self.cctx, detailed)
+    }
 }
@@ -6,11 +6,13 @@
 
 use {
     crate::{
+        buffers::ByteBuffer,
         constants::{COMPRESSOBJ_FLUSH_BLOCK, COMPRESSOBJ_FLUSH_FINISH},
+        exceptions::CommonError,
         zstd_safe::CCtx,
         ZstdError,
     },
-    pyo3::{buffer::PyBuffer, exceptions::PyValueError, prelude::*, types::PyBytes},
+    pyo3::{class::PyObjectProtocol, prelude::*, types::PyBytes},
     std::sync::Arc,
 };
 
@@ -18,51 +20,127 @@ use {
 pub struct ZstdCompressionObj {
     cctx: Arc<CCtx<'static>>,
     finished: bool,
+    /// Receives uncompressed input via `update()`.
+    hasher: Option<PyObject>,
+    /// Digest of the uncompressed input, once the frame has ended.
+    #[pyo3(get)]
+    digest: Option<PyObject>,
+    /// Total uncompressed bytes passed to `compress()`.
+    #[pyo3(get)]
+    bytes_ingested: usize,
+    /// Total compressed bytes returned by `compress()` and `flush()`.
+    #[pyo3(get)]
+    bytes_produced: usize,
+    /// Receives output of each compression call before it is collected.
+    dest_buffer: Vec<u8>,
 }
 
 impl ZstdCompressionObj {
-    pub fn new(cctx: Arc<CCtx<'static>>) -> PyResult<Self> {
+    pub fn new(cctx: Arc<CCtx<'static>>, hasher: Option<PyObject>) -> PyResult<Self> {
         Ok(ZstdCompressionObj {
             cctx,
             finished: false,
+            hasher,
+            digest: None,
+            bytes_ingested: 0,
+            bytes_produced: 0,
+            dest_buffer: vec![0; zstd_safe::cstream_out_size()],
         })
     }
 }
 
-#[pymethods]
 impl ZstdCompressionObj {
-    fn compress<'p>(&self, py: Python<'p>, buffer: PyBuffer<u8>) -> PyResult<&'p PyBytes> {
-        if self.finished {
-            return Err(ZstdError::new_err(
-                "cannot call compress() after compressor finished",
-            ));
-        }
-
-        let mut source = unsafe {
-            std::slice::from_raw_parts::<u8>(buffer.buf_ptr() as *const _, buffer.len_bytes())
-        };
-
-        // TODO consider collecting chunks and joining
-        // TODO try to use zero copy into return value.
-        let mut compressed = Vec::new();
-        let write_size = zstd_safe::cstream_out_size();
+    /// Feed `source` into the current frame, appending output to `compressed`.
+    fn compress_into(
+        &mut self,
+        py: Python,
+        mut source: &[u8],
+        compressed: &mut Vec<u8>,
+    ) -> PyResult<()> {
+        let old_len = compressed.len();
+        self.bytes_ingested += source.len();
 
         let cctx = &self.cctx;
+        let dest_buffer = &mut self.dest_buffer;
+
         while !source.is_empty() {
             let result = py
                 .allow_threads(|| {
-                    cctx.compress_chunk(
+                    cctx.compress_chunk_into(
                         source,
                         zstd_sys::ZSTD_EndDirective::ZSTD_e_continue,
-                        write_size,
+                        dest_buffer,
                     )
                 })
                 .or_else(|msg| Err(ZstdError::new_err(format!("zstd compress error: {}", msg))))?;
 
-            compressed.extend(result.0);
+            compressed.extend_from_slice(&dest_buffer[0..result.0]);
             source = result.1;
         }
 
+        self.bytes_produced += compressed.len() - old_len;
+
+        Ok(())
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for ZstdCompressionObj {
+    fn __repr__(&self) -> String {
+        format!(
+            "ZstdCompressionObj(bytes_ingested={}, bytes_produced={}, finished={})",
+            self.bytes_ingested,
+            self.bytes_produced,
+            if self.finished { "True" } else { "False" },
+        )
+    }
+}
+
+#[pymethods]
+impl ZstdCompressionObj {
+    fn compress<'p>(&mut self, py: Python<'p>, buffer: ByteBuffer) -> PyResult<&'p PyBytes> {
+        if self.finished {
+            return Err(ZstdError::new_err(
+                "cannot call compress() after compressor finished",
+            ));
+        }
+
+        // TODO consider collecting chunks and joining
+        // TODO try to use zero copy into return value.
+        if let Some(hasher) = &self.hasher {
+            hasher.call_method1(py, "update", (buffer.object(py),))?;
+        }
+
+        let mut compressed = Vec::new();
+        self.compress_into(py, buffer.as_slice(), &mut compressed)?;
+
+        Ok(PyBytes::new(py, &compressed))
+    }
+
+    /// Compress each buffer in `buffers` sequentially into the current frame.
+    ///
+    /// Equivalent to `compress(b"".join(buffers))` without the concatenation.
+    fn compress_vectored<'p>(&mut self, py: Python<'p>, buffers: &PyAny) -> PyResult<&'p PyBytes> {
+        if self.finished {
+            return Err(ZstdError::new_err(
+                "cannot call compress_vectored() after compressor finished",
+            ));
+        }
+
+        let mut compressed = Vec::new();
+
+        for buffer in buffers.iter()? {
+            let buffer = buffer?;
+
+            if let Some(hasher) = &self.hasher {
+                hasher.call_method1(py, "update", (buffer,))?;
+            }
+
+            let buffer = ByteBuffer::get(buffer)?;
+
+            self.compress_into(py, buffer.as_slice(), &mut compressed)?;
+        }
+
         Ok(PyBytes::new(py, &compressed))
     }
 
@@ -71,7 +149,7 @@ impl ZstdCompressionObj {
             match flush_mode {
                 COMPRESSOBJ_FLUSH_FINISH => Ok(zstd_sys::ZSTD_EndDirective::ZSTD_e_end),
                 COMPRESSOBJ_FLUSH_BLOCK => Ok(zstd_sys::ZSTD_EndDirective::ZSTD_e_flush),
-                _ => Err(PyValueError::new_err("flush mode not recognized")),
+                _ => Err(CommonError::UnrecognizedFlushMode),
             }
         } else {
             Ok(zstd_sys::ZSTD_EndDirective::ZSTD_e_end)
@@ -85,15 +163,15 @@ impl ZstdCompressionObj {
             self.finished = true;
         }
 
-        let write_size = zstd_safe::cstream_out_size();
         let cctx = &self.cctx;
+        let dest_buffer = &mut self.dest_buffer;
 
         // TODO avoid extra buffer copy.
         let mut result = Vec::new();
 
         loop {
-            let (chunk, _, call_again) = py
-                .allow_threads(|| cctx.compress_chunk(&[], flush_mode, write_size))
+            let (written, _, call_again) = py
+                .allow_threads(|| cctx.compress_chunk_into(&[], flush_mode, dest_buffer))
                 .or_else(|msg| {
                     Err(ZstdError::new_err(format!(
                         "error ending compression stream: {}",
@@ -101,11 +179,37 @@ impl ZstdCompressionObj {
                     )))
                 })?;
 
-            result.extend(&chunk);
+            result.extend_from_slice(&dest_buffer[0..written]);
 
             if !call_again {
-                return Ok(PyBytes::new(py, &result));
+                break;
+            }
+        }
+
+        self.bytes_produced += result.len();
+
+        if self.finished {
+            if let Some(hasher) = &self.hasher {
+                self.digest = Some(hasher.call_method0(py, "digest")?);
             }
         }
+
+        Ok(PyBytes::new(py, &result))
+    }
+    /// Emit an empty frame, finishing the compressor.
+    ///
+    /// Only valid before any input has been compressed.
+    fn write_empty_frame<'p>(&mut self, py: Python<'p>) -> PyResult<&'p PyBytes> {
+        if self.finished {
+            return Err(ZstdError::new_err("compressor object already finished"));
+        }
+
+        if self.bytes_ingested > 0 || self.bytes_produced > 0 {
+            return Err(ZstdError::new_err(
+                "cannot call write_empty_frame() after compress()",
+            ));
+        }
+
+        self.flush(py, Some(COMPRESSOBJ_FLUSH_FINISH))
     }
 }
@@ -6,28 +6,112 @@
 
 use {
     crate::{
-        buffers::ZstdBufferWithSegmentsCollection,
+        buffers::ByteBuffer,
         compression_chunker::ZstdCompressionChunker,
         compression_dict::ZstdCompressionDict,
-        compression_parameters::{CCtxParams, ZstdCompressionParameters},
+        compression_parameters::{
+            cached_cctx_params, get_cctx_parameter, CCtxParams, MemoryPlan,
+            ZstdCompressionParameters, PARAMETER_NAMES,
+        },
         compression_reader::ZstdCompressionReader,
         compression_writer::ZstdCompressionWriter,
         compressionobj::ZstdCompressionObj,
         compressor_iterator::ZstdCompressorIterator,
-        compressor_multi::multi_compress_to_buffer,
-        zstd_safe::CCtx,
+        compressor_multi::{multi_compress_to_buffer, multi_compress_to_buffer_async},
+        compressor_split::split_stream,
+        config::{dump_config, load_config},
+        constants::{format_from_int, skippable_header, validate_level},
+        defaults,
+        exceptions::ZstdDestinationTooSmallError,
+        options::apply_kwargs,
+        stream::{
+            check_hasher, copy_stream_result, resolve_fd, CopyDeadline, CopyStreamOptions,
+            CopyStreamResult,
+        },
+        streaming_encoder::StreamingEncoder,
+        zstd_safe::{auto_ldm_settings, CCtx},
         ZstdError,
     },
-    pyo3::{buffer::PyBuffer, exceptions::PyValueError, prelude::*, types::PyBytes},
+    pyo3::{
+        buffer::PyBuffer,
+        class::PyObjectProtocol,
+        exceptions::PyValueError,
+        prelude::*,
+        types::{PyBytes, PyDict, PyTuple, PyType},
+        PyNativeType,
+    },
     std::sync::Arc,
 };
 
+/// Progression of the frame being compressed by `cctx`.
+///
+/// Returns a tuple of `(ingested, consumed, produced)`. With `detailed`,
+/// returns a dict that also has the bytes `flushed`, the `current_job_id` and
+/// the number of `active_workers`, which show where a multithreaded
+/// compression is waiting.
+pub(crate) fn frame_progression(py: Python, cctx: &CCtx, detailed: bool) -> PyResult<PyObject> {
+    let progression = cctx.get_frame_progression();
+
+    if !detailed {
+        return Ok((
+            progression.ingested as usize,
+            progression.consumed as usize,
+            progression.produced as usize,
+        )
+            .into_py(py));
+    }
+
+    let result = PyDict::new(py);
+    result.set_item("ingested", progression.ingested)?;
+    result.set_item("consumed", progression.consumed)?;
+    result.set_item("produced", progression.produced)?;
+    result.set_item("flushed", progression.flushed)?;
+    result.set_item("current_job_id", progression.currentJobID)?;
+    result.set_item("active_workers", progression.nbActiveWorkers)?;
+
+    Ok(result.into_py(py))
+}
+
+/// Low 4 bits of the skippable frame magic number used for padding.
+const PADDING_MAGIC_VARIANT: u32 = 0xe;
+
+/// Build a skippable frame padding `len` bytes of output to a multiple of
+/// `pad_to`.
+///
+/// A skippable frame is at least 8 bytes, so a gap smaller than that is
+/// extended by another `pad_to` bytes.
+pub(crate) fn padding_frame(len: usize, pad_to: usize) -> Vec<u8> {
+    let mut gap = (pad_to - len % pad_to) % pad_to;
+
+    if gap == 0 {
+        return vec![];
+    }
+
+    while gap < 8 {
+        gap += pad_to;
+    }
+
+    let mut frame = Vec::with_capacity(gap);
+    frame.extend_from_slice(&skippable_header(PADDING_MAGIC_VARIANT, (gap - 8) as u32));
+    frame.resize(gap, 0);
+
+    frame
+}
+
+/// Compression level used for data remaining when a deadline is exceeded.
+pub(crate) const DEADLINE_FALLBACK_LEVEL: i32 = 1;
+
 #[pyclass(module = "zstandard.backend_rust")]
 struct ZstdCompressor {
     _threads: i32,
     dict: Option<Py<ZstdCompressionDict>>,
-    params: CCtxParams<'static>,
+    params: Arc<CCtxParams<'static>>,
     cctx: Arc<CCtx<'static>>,
+    /// Requested and applied threading configuration, if `max_native_memory`
+    /// forced a change.
+    memory_adjustment: Option<(MemoryPlan, MemoryPlan)>,
+    /// Parameters saved by `push_params()`.
+    params_stack: Vec<Arc<CCtxParams<'static>>>,
 }
 
 impl ZstdCompressor {
@@ -42,36 +126,161 @@ impl ZstdCompressor {
 
         Ok(())
     }
+
+    /// Reject `api` if the parameters promise stable buffers it can't keep.
+    ///
+    /// One-shot compression hands zstd the whole input and output at once.
+    /// Streaming APIs pass new buffers as data arrives, which zstd rejects
+    /// with `stable_in_buffer` or `stable_out_buffer`. `input` and `output`
+    /// select which of the two `api` breaks.
+    fn check_unstable_buffers(&self, api: &str, input: bool, output: bool) -> PyResult<()> {
+        for (name, param, checked) in [
+            (
+                "stable_in_buffer",
+                zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam9,
+                input,
+            ),
+            (
+                "stable_out_buffer",
+                zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam10,
+                output,
+            ),
+        ] {
+            if checked && get_cctx_parameter(unsafe { self.params.get_raw_ptr() }, param)? != 0 {
+                return Err(PyValueError::new_err(format!(
+                    "{} cannot be used with {}; use one-shot compression such as compress()",
+                    name, api
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for ZstdCompressor {
+    fn __repr__(&self) -> PyResult<String> {
+        let threads = get_cctx_parameter(
+            unsafe { self.params.get_raw_ptr() },
+            zstd_sys::ZSTD_cParameter::ZSTD_c_nbWorkers,
+        )?;
+        let dict_id = Python::with_gil(|py| {
+            self.dict.as_ref().map_or("None".to_string(), |dict| {
+                dict.borrow(py).dict_id().to_string()
+            })
+        });
+
+        Ok(format!(
+            "ZstdCompressor(level={}, threads={}, dict_id={})",
+            self.effective_level()?,
+            threads,
+            dict_id
+        ))
+    }
+}
+
+/// Keyword-only options to `ZstdCompressor()`.
+#[derive(Default)]
+struct CompressorOptions {
+    write_checksum: Option<bool>,
+    write_content_size: Option<bool>,
+    write_dict_id: Option<bool>,
+    threads: Option<i32>,
+    max_native_memory: Option<usize>,
+    format: Option<u32>,
+}
+
+impl CompressorOptions {
+    fn from_kwargs(kwargs: Option<&PyDict>) -> PyResult<Self> {
+        let mut options = Self::default();
+
+        apply_kwargs("ZstdCompressor()", kwargs, |key, value| {
+            match key {
+                "write_checksum" => options.write_checksum = value.extract()?,
+                "write_content_size" => options.write_content_size = value.extract()?,
+                "write_dict_id" => options.write_dict_id = value.extract()?,
+                "threads" => options.threads = value.extract()?,
+                "max_native_memory" => options.max_native_memory = value.extract()?,
+                "format" => options.format = value.extract()?,
+                _ => return Ok(false),
+            }
+
+            Ok(true)
+        })?;
+
+        Ok(options)
+    }
+}
+
+/// Keyword-only options to `ZstdCompressor.stream_writer()`.
+#[derive(Default)]
+struct StreamWriterOptions<'p> {
+    deadline_ms: Option<u64>,
+    pad_to: Option<usize>,
+    hasher: Option<&'p PyAny>,
+    patch_content_size: bool,
+    frame_index: bool,
+}
+
+impl<'p> StreamWriterOptions<'p> {
+    fn from_kwargs(kwargs: Option<&'p PyDict>) -> PyResult<Self> {
+        let mut options = Self::default();
+
+        apply_kwargs("ZstdCompressor.stream_writer()", kwargs, |key, value| {
+            match key {
+                "deadline_ms" => options.deadline_ms = value.extract()?,
+                "pad_to" => options.pad_to = value.extract()?,
+                "hasher" => options.hasher = value.extract()?,
+                "patch_content_size" => options.patch_content_size = value.extract()?,
+                "frame_index" => options.frame_index = value.extract()?,
+                _ => return Ok(false),
+            }
+
+            Ok(true)
+        })?;
+
+        Ok(options)
+    }
 }
 
 #[pymethods]
 impl ZstdCompressor {
     #[new]
     #[args(
-        level = "3",
+        level = "None",
         dict_data = "None",
         compression_params = "None",
-        write_checksum = "None",
-        write_content_size = "None",
-        write_dict_id = "None",
-        threads = "0"
+        kwargs = "**"
     )]
     fn new(
         py: Python,
-        level: i32,
+        level: Option<i32>,
         dict_data: Option<Py<ZstdCompressionDict>>,
         compression_params: Option<Py<ZstdCompressionParameters>>,
-        write_checksum: Option<bool>,
-        write_content_size: Option<bool>,
-        write_dict_id: Option<bool>,
-        threads: i32,
+        kwargs: Option<&PyDict>,
     ) -> PyResult<Self> {
-        if level > zstd_safe::max_c_level() {
-            return Err(PyValueError::new_err(format!(
-                "level must be less than {}",
-                zstd_safe::max_c_level() as i32 + 1
-            )));
-        }
+        let CompressorOptions {
+            write_checksum,
+            write_content_size,
+            write_dict_id,
+            threads,
+            max_native_memory,
+            format,
+        } = CompressorOptions::from_kwargs(kwargs)?;
+
+        // Omitted arguments come from `set_defaults()` and the environment.
+        // Default threads don't conflict with `compression_params`.
+        let defaults = defaults::defaults();
+        let level = level.unwrap_or(defaults.level);
+        let explicit_threads = threads.is_some();
+        let threads = match threads {
+            Some(threads) => threads,
+            None if compression_params.is_some() => 0,
+            None => defaults.threads,
+        };
+
+        validate_level(level)?;
 
         let threads = if threads < 0 {
             num_cpus::get() as i32
@@ -80,9 +289,8 @@ impl ZstdCompressor {
         };
 
         let cctx = Arc::new(CCtx::new().or_else(|msg| Err(PyErr::new::<ZstdError, _>(msg)))?);
-        let params = CCtxParams::create()?;
 
-        if let Some(compression_params) = &compression_params {
+        let params = if let Some(compression_params) = &compression_params {
             if write_checksum.is_some() {
                 return Err(PyValueError::new_err(
                     "cannot define compression_params and write_checksum",
@@ -98,39 +306,83 @@ impl ZstdCompressor {
                     "cannot define compression_params and write_dict_id",
                 ));
             }
-            if threads != 0 {
+            if explicit_threads && threads != 0 {
                 return Err(PyValueError::new_err(
                     "cannot define compression_params and threads",
                 ));
             }
+            if format.is_some() {
+                return Err(PyValueError::new_err(
+                    "cannot define compression_params and format",
+                ));
+            }
 
+            let params = CCtxParams::create()?;
             params.apply_compression_parameters(py, compression_params)?;
 
-        // TODO set parameters from CompressionParameters
+            Arc::new(params)
         } else {
-            params.set_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel, level)?;
-            params.set_parameter(
-                zstd_sys::ZSTD_cParameter::ZSTD_c_contentSizeFlag,
-                if write_content_size.unwrap_or(true) {
-                    1
-                } else {
-                    0
-                },
-            )?;
-            params.set_parameter(
-                zstd_sys::ZSTD_cParameter::ZSTD_c_checksumFlag,
-                if write_checksum.unwrap_or(false) {
-                    1
-                } else {
-                    0
+            let write_content_size = write_content_size.unwrap_or(true);
+            let write_checksum = write_checksum.unwrap_or(false);
+            let write_dict_id = write_dict_id.unwrap_or(true);
+            let format =
+                format_from_int(format.unwrap_or(zstd_sys::ZSTD_format_e::ZSTD_f_zstd1 as _))?;
+
+            // Parameters derived from simple arguments are shared between
+            // instances so bursts of identical compressors are cheap.
+            cached_cctx_params(
+                (
+                    level,
+                    write_content_size,
+                    write_checksum,
+                    write_dict_id,
+                    threads,
+                    format as u32,
+                ),
+                || {
+                    let params = CCtxParams::create()?;
+                    params
+                        .set_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel, level)?;
+                    params.set_parameter(
+                        zstd_sys::ZSTD_cParameter::ZSTD_c_contentSizeFlag,
+                        if write_content_size { 1 } else { 0 },
+                    )?;
+                    params.set_parameter(
+                        zstd_sys::ZSTD_cParameter::ZSTD_c_checksumFlag,
+                        if write_checksum { 1 } else { 0 },
+                    )?;
+                    params.set_parameter(
+                        zstd_sys::ZSTD_cParameter::ZSTD_c_dictIDFlag,
+                        if write_dict_id { 1 } else { 0 },
+                    )?;
+                    if threads != 0 {
+                        params
+                            .set_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_nbWorkers, threads)?;
+                    }
+                    params.set_parameter(
+                        zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam2,
+                        format as i32,
+                    )?;
+
+                    Ok(params)
                 },
-            )?;
-            params.set_parameter(
-                zstd_sys::ZSTD_cParameter::ZSTD_c_dictIDFlag,
-                if write_dict_id.unwrap_or(true) { 1 } else { 0 },
-            )?;
-            if threads != 0 {
-                params.set_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_nbWorkers, threads)?;
+            )?
+        };
+
+        let mut params = params;
+        let mut threads = threads;
+        let mut memory_adjustment = None;
+
+        if let Some(limit) = max_native_memory {
+            let (requested, plan) = params.fit_memory(limit)?;
+
+            if plan != requested {
+                params = Arc::new(params.try_clone_with(&[
+                    (zstd_sys::ZSTD_cParameter::ZSTD_c_nbWorkers, plan.threads),
+                    (zstd_sys::ZSTD_cParameter::ZSTD_c_jobSize, plan.job_size),
+                ])?);
+                threads = plan.threads;
+                memory_adjustment = Some((requested, plan));
             }
         }
 
@@ -139,6 +391,8 @@ impl ZstdCompressor {
             dict: dict_data,
             params,
             cctx,
+            memory_adjustment,
+            params_stack: vec![],
         };
 
         compressor.setup_cctx(py)?;
@@ -146,40 +400,427 @@ impl ZstdCompressor {
         Ok(compressor)
     }
 
-    fn memory_size(&self) -> PyResult<usize> {
-        Ok(self.cctx.memory_size())
+    /// Serialize the settings of this compressor.
+    ///
+    /// The dictionary is referenced by its ID and isn't serialized.
+    fn to_config<'p>(&self, py: Python<'p>) -> PyResult<&'p PyAny> {
+        let params = PyDict::new(py);
+
+        for (name, param) in PARAMETER_NAMES {
+            let value = get_cctx_parameter(unsafe { self.params.get_raw_ptr() }, *param)?;
+            params.set_item(name, value)?;
+        }
+
+        if self.params.auto_ldm() {
+            params.set_item("enable_ldm", "auto")?;
+        }
+
+        let config = PyDict::new(py);
+        config.set_item("params", params)?;
+
+        dump_config(py, "compressor", config, &self.dict)
     }
 
-    fn frame_progression(&self) -> PyResult<(usize, usize, usize)> {
-        let progression = self.cctx.get_frame_progression();
+    /// Construct an instance from the output of `to_config()`.
+    #[classmethod]
+    #[args(config, dict_data = "None")]
+    fn from_config(
+        _cls: &PyType,
+        py: Python,
+        config: &PyAny,
+        dict_data: Option<Py<ZstdCompressionDict>>,
+    ) -> PyResult<Self> {
+        let config = load_config(py, "compressor", config, &dict_data)?;
+
+        let params: &PyDict = config
+            .get_item("params")
+            .ok_or_else(|| PyValueError::new_err("config missing params"))?
+            .downcast()
+            .map_err(|_| PyValueError::new_err("config params must be an object"))?;
 
-        Ok((
-            progression.ingested as usize,
-            progression.consumed as usize,
-            progression.produced as usize,
+        for (key, value) in params.iter() {
+            let key = key.extract::<&str>()?;
+
+            if !PARAMETER_NAMES.iter().any(|(name, _)| *name == key) {
+                return Err(PyValueError::new_err(format!(
+                    "unknown compression parameter in config: {}",
+                    key
+                )));
+            }
+
+            if value.extract::<i32>().is_err()
+                && !(key == "enable_ldm"
+                    && matches!(value.extract::<&str>(), Ok("auto") | Ok("disable")))
+            {
+                return Err(PyValueError::new_err(format!(
+                    "compression parameter {} must be an integer",
+                    key
+                )));
+            }
+        }
+
+        let params = Py::new(
+            py,
+            ZstdCompressionParameters::new(py, PyTuple::empty(py), Some(params))?,
+        )?;
+
+        Self::new(py, None, dict_data, Some(params), None)
+    }
+
+    /// Temporarily replace the compression parameters.
+    ///
+    /// Exactly one of `level` or `compression_params` must be given. With
+    /// `level`, other parameters are retained. Call `pop_params()` to restore
+    /// the previous parameters.
+    #[args(level = "None", compression_params = "None")]
+    fn push_params(
+        &mut self,
+        py: Python,
+        level: Option<i32>,
+        compression_params: Option<Py<ZstdCompressionParameters>>,
+    ) -> PyResult<()> {
+        let params = match (level, compression_params) {
+            (Some(_), Some(_)) => {
+                return Err(PyValueError::new_err(
+                    "cannot define level and compression_params",
+                ));
+            }
+            (None, None) => {
+                return Err(PyValueError::new_err(
+                    "must define level or compression_params",
+                ));
+            }
+            (Some(level), None) => {
+                validate_level(level)?;
+
+                self.params.try_clone_with(&[(
+                    zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel,
+                    level,
+                )])?
+            }
+            (None, Some(compression_params)) => {
+                let params = CCtxParams::create()?;
+                params.apply_compression_parameters(py, &compression_params)?;
+
+                params
+            }
+        };
+
+        let previous = std::mem::replace(&mut self.params, Arc::new(params));
+        self.params_stack.push(previous);
+
+        self.cctx.reset();
+        self.setup_cctx(py)
+    }
+
+    /// Restore the parameters in effect before the last `push_params()`.
+    fn pop_params(&mut self, py: Python) -> PyResult<()> {
+        self.params = self.params_stack.pop().ok_or_else(|| {
+            ZstdError::new_err("cannot call pop_params() without a matching push_params()")
+        })?;
+
+        self.cctx.reset();
+        self.setup_cctx(py)
+    }
+
+    /// Create an encoder whose output is decodable as it is produced.
+    ///
+    /// The encoder has its own compression context, so many can be active at
+    /// once.
+    #[args(write_size = "None")]
+    fn streaming_encoder(
+        &self,
+        py: Python,
+        write_size: Option<usize>,
+    ) -> PyResult<StreamingEncoder> {
+        self.check_unstable_buffers("streaming_encoder()", true, true)?;
+
+        let write_size = write_size.unwrap_or_else(zstd_safe::cstream_out_size);
+
+        if write_size < 1 {
+            return Err(PyValueError::new_err("write_size must be positive"));
+        }
+
+        let cctx = CCtx::new().map_err(ZstdError::new_err)?;
+        cctx.set_parameters(&self.params)
+            .map_err(ZstdError::new_err)?;
+
+        if let Some(dict) = &self.dict {
+            dict.borrow(py).load_into_cctx(&cctx)?;
+        }
+
+        Ok(StreamingEncoder::new(
+            cctx,
+            self.dict.as_ref().map(|d| d.clone_ref(py)),
+            write_size,
         ))
     }
 
-    fn compress<'p>(&self, py: Python<'p>, buffer: PyBuffer<u8>) -> PyResult<&'p PyBytes> {
+    /// The compression level the parameters of this compressor resolve to.
+    ///
+    /// The default level is reported in place of 0. Parameters set explicitly
+    /// by `compression_params` (e.g. `strategy`) still override those implied
+    /// by this level.
+    #[getter]
+    fn effective_level(&self) -> PyResult<i32> {
+        get_cctx_parameter(
+            unsafe { self.params.get_raw_ptr() },
+            zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel,
+        )
+    }
+
+    /// Describes how `max_native_memory` changed the threading configuration.
+    ///
+    /// `None` if no change was needed.
+    #[getter]
+    fn memory_adjustment<'p>(&self, py: Python<'p>) -> PyResult<Option<&'p PyDict>> {
+        let (requested, plan) = match &self.memory_adjustment {
+            Some(adjustment) => adjustment,
+            None => return Ok(None),
+        };
+
+        let result = PyDict::new(py);
+        result.set_item("requested_threads", requested.threads)?;
+        result.set_item("requested_job_size", requested.job_size)?;
+        result.set_item("requested_estimated_size", requested.estimated_size)?;
+        result.set_item("threads", plan.threads)?;
+        result.set_item("job_size", plan.job_size)?;
+        result.set_item("estimated_size", plan.estimated_size)?;
+
+        Ok(Some(result))
+    }
+
+    /// Long distance matching parameters used for a frame of `source_size`
+    /// bytes without a dictionary.
+    ///
+    /// Parameters left at 0 are reported with the values zstd picks for them.
+    /// A negative `source_size` means the size isn't known. `None` if long
+    /// distance matching isn't used for such a frame.
+    #[args(source_size = "-1")]
+    fn ldm_parameters<'p>(&self, py: Python<'p>, source_size: i64) -> PyResult<Option<&'p PyDict>> {
+        let params = unsafe { self.params.get_raw_ptr() };
+        let get = |param| get_cctx_parameter(params, param);
+
+        let size = if source_size < 0 {
+            zstd_sys::ZSTD_CONTENTSIZE_UNKNOWN as u64
+        } else {
+            source_size as u64
+        };
+
+        let level = get(zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel)?;
+
+        let (enable, window_log) = if self.params.auto_ldm() {
+            auto_ldm_settings(level, size)
+        } else {
+            (
+                get(zstd_sys::ZSTD_cParameter::ZSTD_c_enableLongDistanceMatching)?,
+                get(zstd_sys::ZSTD_cParameter::ZSTD_c_windowLog)?,
+            )
+        };
+
+        if enable == 0 {
+            return Ok(None);
+        }
+
+        // Follows ZSTD_getCParamsFromCCtxParams() and
+        // ZSTD_ldm_adjustParameters().
+        let mut cparams = unsafe { zstd_sys::ZSTD_getCParams(level, size, 0) };
+        cparams.windowLog = match window_log {
+            0 => zstd_sys::ZSTD_WINDOWLOG_LIMIT_DEFAULT,
+            value => value as u32,
+        };
+        let window_log = unsafe { zstd_sys::ZSTD_adjustCParams(cparams, size, 0) }.windowLog as i32;
+
+        let hash_log = match get(zstd_sys::ZSTD_cParameter::ZSTD_c_ldmHashLog)? {
+            0 => std::cmp::max(zstd_sys::ZSTD_HASHLOG_MIN as i32, window_log - 7),
+            value => value,
+        };
+        let min_match = match get(zstd_sys::ZSTD_cParameter::ZSTD_c_ldmMinMatch)? {
+            0 => 64,
+            value => value,
+        };
+        let bucket_size_log = match get(zstd_sys::ZSTD_cParameter::ZSTD_c_ldmBucketSizeLog)? {
+            0 => 3,
+            value => value,
+        };
+        let hash_rate_log = match get(zstd_sys::ZSTD_cParameter::ZSTD_c_ldmHashRateLog)? {
+            0 => std::cmp::max(window_log - hash_log, 0),
+            value => value,
+        };
+
+        let result = PyDict::new(py);
+        result.set_item("window_log", window_log)?;
+        result.set_item("ldm_hash_log", hash_log)?;
+        result.set_item("ldm_min_match", min_match)?;
+        result.set_item(
+            "ldm_bucket_size_log",
+            std::cmp::min(bucket_size_log, hash_log),
+        )?;
+        result.set_item("ldm_hash_rate_log", hash_rate_log)?;
+
+        Ok(Some(result))
+    }
+
+    fn memory_size(&self) -> PyResult<usize> {
+        Ok(self.cctx.memory_size())
+    }
+
+    #[args(detailed = "false")]
+    fn frame_progression(&self, py: Python, detailed: bool) -> PyResult<PyObject> {
+        frame_progression(py, &self.cctx, detailed)
+    }
+
+    /// When `deadline_ms` is given and compression takes longer, the frame is
+    /// ended early and the remaining data is compressed into a second frame at
+    /// level 1. The size of the first frame isn't known up front, so it
+    /// doesn't record its content size.
+    ///
+    /// When `pad_to` is given, the output is padded with a skippable frame to
+    /// a multiple of `pad_to` bytes.
+    #[args(
+        buffer,
+        output_size_hint = "None",
+        deadline_ms = "None",
+        pad_to = "None"
+    )]
+    fn compress<'p>(
+        &self,
+        py: Python<'p>,
+        buffer: PyBuffer<u8>,
+        output_size_hint: Option<usize>,
+        deadline_ms: Option<u64>,
+        pad_to: Option<usize>,
+    ) -> PyResult<&'p PyBytes> {
+        if pad_to == Some(0) {
+            return Err(PyValueError::new_err("pad_to must be positive"));
+        }
+
+        // A deadline splits the input and both grow the output.
+        if deadline_ms.is_some() {
+            self.check_unstable_buffers("deadline_ms", true, true)?;
+        }
+        if output_size_hint.is_some() {
+            self.check_unstable_buffers("output_size_hint", false, true)?;
+        }
+
         let source: &[u8] =
             unsafe { std::slice::from_raw_parts(buffer.buf_ptr() as *const _, buffer.len_bytes()) };
 
         let cctx = &self.cctx;
 
+        if deadline_ms.is_some() {
+            let deadline = CopyDeadline::new(deadline_ms);
+
+            let (mut data, fallback) = py
+                .allow_threads(|| {
+                    cctx.compress_with_deadline(source, DEADLINE_FALLBACK_LEVEL, || {
+                        deadline.expired()
+                    })
+                })
+                .map_err(|msg| ZstdError::new_err(format!("cannot compress: {}", msg)))?;
+
+            if fallback {
+                self.cctx.reset();
+                self.setup_cctx(py)?;
+            }
+
+            if let Some(pad_to) = pad_to {
+                data.extend(padding_frame(data.len(), pad_to));
+            }
+
+            return Ok(PyBytes::new(py, &data));
+        }
+
         // TODO implement 0 copy via Py_SIZE().
-        let data = py
-            .allow_threads(|| cctx.compress(source))
+        let mut data = py
+            .allow_threads(|| cctx.compress(source, output_size_hint))
             .or_else(|msg| Err(ZstdError::new_err(format!("cannot compress: {}", msg))))?;
 
+        if let Some(pad_to) = pad_to {
+            data.extend(padding_frame(data.len(), pad_to));
+        }
+
         Ok(PyBytes::new(py, &data))
     }
 
-    #[args(size = "None", chunk_size = "None")]
+    /// Compress data into a writable buffer.
+    ///
+    /// Returns the size in bytes of the frame written to the start of `dest`.
+    /// Raises `ZstdDestinationTooSmallError` if the frame doesn't fit. The
+    /// error message includes the worst case compressed size of the input.
+    #[args(source, dest)]
+    fn compress_into(&self, py: Python, source: ByteBuffer, dest: &PyAny) -> PyResult<usize> {
+        let dest = ByteBuffer::get_writable(dest)?;
+
+        let source = source.as_slice();
+        let dest_slice =
+            unsafe { std::slice::from_raw_parts_mut(dest.as_mut_ptr(), dest.len_bytes()) };
+
+        let cctx = &self.cctx;
+
+        match py
+            .allow_threads(|| cctx.compress_to_slice(source, dest_slice))
+            .map_err(|msg| ZstdError::new_err(format!("cannot compress: {}", msg)))?
+        {
+            Some(size) => Ok(size),
+            None => Err(ZstdDestinationTooSmallError::new_err(format!(
+                "destination buffer too small: buffer is {} bytes; compressed size may be up to {} bytes",
+                dest.len_bytes(),
+                zstd_safe::compress_bound(source.len())
+            ))),
+        }
+    }
+
+    /// Compress `new_data` as a patch against `old_data`, like
+    /// `zstd --patch-from`.
+    ///
+    /// `old_data` is referenced as a raw content prefix in place of the
+    /// compressor's dictionary. The window is widened to cover the larger
+    /// input and long distance matching is enabled when the window exceeds
+    /// what the match finder searches at the configured level. Decompress
+    /// with `ZstdDecompressor.decompress_patch()` and the same `old_data`.
+    #[args(old_data, new_data)]
+    fn compress_patch<'p>(
+        &self,
+        py: Python<'p>,
+        old_data: ByteBuffer,
+        new_data: ByteBuffer,
+    ) -> PyResult<&'p PyBytes> {
+        let old = old_data.as_slice();
+        let new = new_data.as_slice();
+
+        let cctx = &self.cctx;
+        let result = py.allow_threads(|| cctx.compress_patch(old, new));
+
+        // Restore the parameters and dictionary, dropping the prefix if the
+        // frame didn't start.
+        self.cctx.reset();
+        self.cctx.clear_dict().map_err(|msg| {
+            ZstdError::new_err(format!("could not clear compression dictionary: {}", msg))
+        })?;
+        self.setup_cctx(py)?;
+
+        let data = result.map_err(|msg| ZstdError::new_err(format!("cannot compress: {}", msg)))?;
+
+        Ok(PyBytes::new(py, &data))
+    }
+
+    #[args(
+        size = "None",
+        chunk_size = "None",
+        as_memoryview = "false",
+        frame_index = "false"
+    )]
     fn chunker(
         &self,
+        py: Python,
         size: Option<u64>,
         chunk_size: Option<usize>,
+        as_memoryview: bool,
+        frame_index: bool,
     ) -> PyResult<ZstdCompressionChunker> {
+        self.check_unstable_buffers("chunker()", true, true)?;
+
         self.cctx.reset();
 
         let size = size.unwrap_or(zstd_safe::CONTENTSIZE_UNKNOWN);
@@ -192,11 +833,26 @@ impl ZstdCompressor {
             )))
         })?;
 
-        ZstdCompressionChunker::new(self.cctx.clone(), chunk_size)
+        ZstdCompressionChunker::new(
+            py,
+            self.cctx.clone(),
+            chunk_size,
+            as_memoryview,
+            frame_index,
+        )
     }
 
-    #[args(size = "None")]
-    fn compressobj(&self, size: Option<u64>) -> PyResult<ZstdCompressionObj> {
+    #[args(size = "None", hasher = "None")]
+    fn compressobj(
+        &self,
+        py: Python,
+        size: Option<u64>,
+        hasher: Option<&PyAny>,
+    ) -> PyResult<ZstdCompressionObj> {
+        self.check_unstable_buffers("compressobj()", true, true)?;
+
+        check_hasher(hasher)?;
+
         self.cctx.reset();
 
         let size = if let Some(size) = size {
@@ -212,19 +868,35 @@ impl ZstdCompressor {
             )))
         })?;
 
-        ZstdCompressionObj::new(self.cctx.clone())
+        ZstdCompressionObj::new(self.cctx.clone(), hasher.map(|h| h.into_py(py)))
     }
 
-    #[args(ifh, ofh, size = "None", read_size = "None", write_size = "None")]
+    #[args(
+        ifh,
+        ofh,
+        size = "None",
+        read_size = "None",
+        write_size = "None",
+        kwargs = "**"
+    )]
     fn copy_stream(
         &self,
-        py: Python,
         ifh: &PyAny,
         ofh: &PyAny,
         size: Option<u64>,
         read_size: Option<usize>,
         write_size: Option<usize>,
-    ) -> PyResult<(usize, usize)> {
+        kwargs: Option<&PyDict>,
+    ) -> PyResult<CopyStreamResult> {
+        let py = ifh.py();
+        let CopyStreamOptions {
+            hasher,
+            max_time_ms,
+            resume,
+        } = CopyStreamOptions::from_kwargs("ZstdCompressor.copy_stream()", kwargs)?;
+
+        self.check_unstable_buffers("copy_stream()", true, true)?;
+
         let source_size = if let Some(source_size) = size {
             source_size
         } else {
@@ -245,19 +917,32 @@ impl ZstdCompressor {
             ));
         }
 
-        self.cctx.reset();
-        self.cctx
-            .set_pledged_source_size(source_size)
-            .or_else(|msg| {
-                Err(ZstdError::new_err(format!(
-                    "error setting source size: {}",
-                    msg
-                )))
-            })?;
+        // Resuming continues the frame left open by a previous call that ran
+        // out of time.
+        if !resume {
+            self.cctx.reset();
+            self.cctx
+                .set_pledged_source_size(source_size)
+                .or_else(|msg| {
+                    Err(ZstdError::new_err(format!(
+                        "error setting source size: {}",
+                        msg
+                    )))
+                })?;
+        }
+
+        let checksum = get_cctx_parameter(
+            unsafe { self.params.get_raw_ptr() },
+            zstd_sys::ZSTD_cParameter::ZSTD_c_checksumFlag,
+        )? != 0;
 
+        let deadline = CopyDeadline::new(max_time_ms);
         let mut total_read = 0;
         let mut total_write = 0;
 
+        // Output is staged in one buffer for the whole copy.
+        let mut dest_buffer = vec![0; write_size];
+
         loop {
             // Try to read from source stream.
             let read_object = ifh.call_method("read", (read_size,), None)?;
@@ -278,12 +963,13 @@ impl ZstdCompressor {
             let cctx = &self.cctx;
 
             while !source.is_empty() {
+                let dest = &mut dest_buffer;
                 let result = py
                     .allow_threads(|| {
-                        cctx.compress_chunk(
+                        cctx.compress_chunk_into(
                             source,
                             zstd_sys::ZSTD_EndDirective::ZSTD_e_continue,
-                            write_size,
+                            dest,
                         )
                     })
                     .or_else(|msg| {
@@ -292,22 +978,43 @@ impl ZstdCompressor {
 
                 source = result.1;
 
-                let chunk = &result.0;
+                let chunk = &dest_buffer[0..result.0];
 
                 if !chunk.is_empty() {
                     // TODO avoid buffer copy.
                     let data = PyBytes::new(py, chunk);
                     ofh.call_method("write", (data,), None)?;
+                    if let Some(hasher) = hasher {
+                        hasher.call_method1("update", (data,))?;
+                    }
                     total_write += chunk.len();
                 }
             }
+
+            // Yield back to the caller between reads once out of time. At
+            // least one read is processed per call so resuming progresses.
+            if deadline.expired() {
+                return copy_stream_result(
+                    py,
+                    total_read,
+                    total_write,
+                    (0, checksum),
+                    hasher,
+                    &deadline,
+                    false,
+                );
+            }
         }
 
         // We've finished reading. Now flush the compressor stream.
         loop {
             let result = self
                 .cctx
-                .compress_chunk(&[], zstd_sys::ZSTD_EndDirective::ZSTD_e_end, write_size)
+                .compress_chunk_into(
+                    &[],
+                    zstd_sys::ZSTD_EndDirective::ZSTD_e_end,
+                    &mut dest_buffer,
+                )
                 .or_else(|msg| {
                     Err(ZstdError::new_err(format!(
                         "error ending compression stream: {}",
@@ -315,12 +1022,15 @@ impl ZstdCompressor {
                     )))
                 })?;
 
-            let chunk = &result.0;
+            let chunk = &dest_buffer[0..result.0];
 
             if !chunk.is_empty() {
                 // TODO avoid buffer copy.
-                let data = PyBytes::new(py, &chunk);
+                let data = PyBytes::new(py, chunk);
                 ofh.call_method("write", (data,), None)?;
+                if let Some(hasher) = hasher {
+                    hasher.call_method1("update", (data,))?;
+                }
                 total_write += chunk.len();
             }
 
@@ -329,20 +1039,81 @@ impl ZstdCompressor {
             }
         }
 
-        Ok((total_read, total_write))
+        copy_stream_result(
+            py,
+            total_read,
+            total_write,
+            (1, checksum),
+            hasher,
+            &deadline,
+            true,
+        )
     }
 
-    #[args(data, threads = "0")]
+    #[args(data, threads = "0", return_index = "false")]
     fn multi_compress_to_buffer(
         &self,
         py: Python,
         data: &PyAny,
         threads: isize,
-    ) -> PyResult<ZstdBufferWithSegmentsCollection> {
-        multi_compress_to_buffer(py, &self.params, &self.dict, data, threads)
+        return_index: bool,
+    ) -> PyResult<PyObject> {
+        multi_compress_to_buffer(py, &self.params, &self.dict, data, threads, return_index)
+    }
+
+    /// Like `multi_compress_to_buffer()` but returns an awaitable.
+    ///
+    /// Compression runs on a background thread, leaving the event loop free.
+    /// Must be called from a coroutine running in an event loop.
+    #[args(data, threads = "0", return_index = "false")]
+    fn multi_compress_to_buffer_async(
+        &self,
+        py: Python,
+        data: &PyAny,
+        threads: isize,
+        return_index: bool,
+    ) -> PyResult<PyObject> {
+        multi_compress_to_buffer_async(py, &self.params, &self.dict, data, threads, return_index)
+    }
+
+    /// Compress `source` into one frame per writer in `writers`.
+    ///
+    /// Frames have roughly equal compressed sizes, so each can be fetched
+    /// and decompressed independently in similar time. The size of `source`
+    /// must be known from its buffer or given by `size`.
+    #[args(
+        source,
+        writers,
+        size = "None",
+        read_size = "None",
+        write_size = "None"
+    )]
+    fn split_stream(
+        &self,
+        py: Python,
+        source: &PyAny,
+        writers: &PyAny,
+        size: Option<u64>,
+        read_size: Option<usize>,
+        write_size: Option<usize>,
+    ) -> PyResult<Vec<(usize, usize)>> {
+        self.check_unstable_buffers("split_stream()", true, true)?;
+
+        let read_size = read_size.unwrap_or_else(zstd_safe::cstream_in_size);
+        let write_size = write_size.unwrap_or_else(zstd_safe::cstream_out_size);
+
+        self.cctx.reset();
+
+        split_stream(py, &self.cctx, source, writers, size, read_size, write_size)
     }
 
-    #[args(reader, size = "None", read_size = "None", write_size = "None")]
+    #[args(
+        reader,
+        size = "None",
+        read_size = "None",
+        write_size = "None",
+        as_memoryview = "false"
+    )]
     fn read_to_iter(
         &self,
         py: Python,
@@ -350,14 +1121,25 @@ impl ZstdCompressor {
         size: Option<u64>,
         read_size: Option<usize>,
         write_size: Option<usize>,
+        as_memoryview: bool,
     ) -> PyResult<ZstdCompressorIterator> {
+        self.check_unstable_buffers("read_to_iter()", true, true)?;
+
         let size = size.unwrap_or(zstd_safe::CONTENTSIZE_UNKNOWN);
         let read_size = read_size.unwrap_or_else(|| zstd_safe::cstream_in_size());
         let write_size = write_size.unwrap_or_else(|| zstd_safe::cstream_out_size());
 
         self.cctx.reset();
 
-        ZstdCompressorIterator::new(py, self.cctx.clone(), reader, size, read_size, write_size)
+        ZstdCompressorIterator::new(
+            py,
+            self.cctx.clone(),
+            reader,
+            size,
+            read_size,
+            write_size,
+            as_memoryview,
+        )
     }
 
     #[args(source, size = "None", read_size = "None", closefd = "true")]
@@ -369,8 +1151,11 @@ impl ZstdCompressor {
         read_size: Option<usize>,
         closefd: bool,
     ) -> PyResult<ZstdCompressionReader> {
+        self.check_unstable_buffers("stream_reader()", true, true)?;
+
         let size = size.unwrap_or(zstd_safe::CONTENTSIZE_UNKNOWN);
         let read_size = read_size.unwrap_or_else(|| zstd_safe::cstream_in_size());
+        let source = resolve_fd(py, source, "rb", closefd)?;
 
         self.cctx.reset();
 
@@ -382,27 +1167,78 @@ impl ZstdCompressor {
         size = "None",
         write_size = "None",
         write_return_read = "true",
-        closefd = "true"
+        closefd = "true",
+        kwargs = "**"
     )]
     fn stream_writer(
         &self,
-        py: Python,
         writer: &PyAny,
         size: Option<u64>,
         write_size: Option<usize>,
         write_return_read: bool,
         closefd: bool,
+        kwargs: Option<&PyDict>,
     ) -> PyResult<ZstdCompressionWriter> {
+        let py = writer.py();
+        let StreamWriterOptions {
+            deadline_ms,
+            pad_to,
+            hasher,
+            patch_content_size,
+            frame_index,
+        } = StreamWriterOptions::from_kwargs(kwargs)?;
+
+        self.check_unstable_buffers("stream_writer()", true, true)?;
+
+        let writer = resolve_fd(py, writer, "wb", closefd)?;
+
         if !writer.hasattr("write")? {
             return Err(PyValueError::new_err(
                 "must pass object with a write() method",
             ));
         }
 
+        if patch_content_size {
+            let seekable =
+                writer.hasattr("seekable")? && writer.call_method0("seekable")?.is_true()?;
+
+            if !seekable {
+                return Err(PyValueError::new_err(
+                    "patch_content_size requires a seekable writer",
+                ));
+            }
+
+            if get_cctx_parameter(
+                unsafe { self.params.get_raw_ptr() },
+                zstd_sys::ZSTD_cParameter::ZSTD_c_contentSizeFlag,
+            )? == 0
+            {
+                return Err(PyValueError::new_err(
+                    "patch_content_size requires write_content_size=True",
+                ));
+            }
+        }
+
+        if pad_to == Some(0) {
+            return Err(PyValueError::new_err("pad_to must be positive"));
+        }
+
+        check_hasher(hasher)?;
+
+        if size.is_some() && deadline_ms.is_some() {
+            return Err(PyValueError::new_err(
+                "cannot specify both size and deadline_ms",
+            ));
+        }
+
         self.cctx.reset();
 
         let size = size.unwrap_or(zstd_sys::ZSTD_CONTENTSIZE_UNKNOWN as _);
         let write_size = write_size.unwrap_or_else(|| unsafe { zstd_sys::ZSTD_CStreamOutSize() });
+        let level = get_cctx_parameter(
+            unsafe { self.params.get_raw_ptr() },
+            zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel,
+        )?;
 
         ZstdCompressionWriter::new(
             py,
@@ -413,6 +1249,43 @@ impl ZstdCompressor {
             write_return_read,
             closefd,
         )
+        .map(|writer| match deadline_ms {
+            Some(deadline_ms) => writer.with_deadline(deadline_ms, level),
+            None => writer,
+        })
+        .map(|writer| match pad_to {
+            Some(pad_to) => writer.with_padding(pad_to),
+            None => writer,
+        })
+        .map(|writer| match hasher {
+            Some(hasher) => writer.with_hasher(hasher.into_py(py)),
+            None => writer,
+        })
+        .map(|writer| match &self.dict {
+            Some(dict) => writer.with_dict(dict.clone_ref(py)),
+            None => writer,
+        })
+        .and_then(|writer| {
+            if patch_content_size {
+                let format = get_cctx_parameter(
+                    unsafe { self.params.get_raw_ptr() },
+                    zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam2,
+                )?;
+
+                Ok(writer.with_content_size_patching(
+                    format == zstd_sys::ZSTD_format_e::ZSTD_f_zstd1_magicless as _,
+                ))
+            } else {
+                Ok(writer)
+            }
+        })
+        .map(|writer| {
+            if frame_index {
+                writer.with_frame_index()
+            } else {
+                writer
+            }
+        })
     }
 }
 
@@ -7,10 +7,10 @@
 use {
     crate::{
         exceptions::ZstdError,
-        stream::{make_in_buffer_source, InBufferSource},
+        stream::{make_in_buffer_source, ChunkOutput, InBufferSource},
         zstd_safe::CCtx,
     },
-    pyo3::{prelude::*, types::PyBytes, PyIterProtocol},
+    pyo3::{prelude::*, PyIterProtocol},
     std::sync::Arc,
 };
 
@@ -19,6 +19,7 @@ pub struct ZstdCompressorIterator {
     cctx: Arc<CCtx<'static>>,
     source: Box<dyn InBufferSource + Send>,
     write_size: usize,
+    output: ChunkOutput,
     finished_output: bool,
 }
 
@@ -30,6 +31,7 @@ impl ZstdCompressorIterator {
         size: u64,
         read_size: usize,
         write_size: usize,
+        as_memoryview: bool,
     ) -> PyResult<Self> {
         let source = make_in_buffer_source(py, reader, read_size)?;
 
@@ -45,6 +47,7 @@ impl ZstdCompressorIterator {
             cctx,
             source,
             write_size,
+            output: ChunkOutput::new(py, as_memoryview, write_size)?,
             finished_output: false,
         })
     }
@@ -82,9 +85,9 @@ impl PyIterProtocol for ZstdCompressorIterator {
             // Emit compressed data, if available.
             if !dest_buffer.is_empty() {
                 // TODO avoid buffer copy
-                let chunk = PyBytes::new(py, &dest_buffer);
+                let chunk = slf.output.emit(py, &dest_buffer)?;
 
-                return Ok(Some(chunk.into_py(py)));
+                return Ok(Some(chunk));
             }
 
             // Else read another chunk in hopes of producing output data.
@@ -116,9 +119,9 @@ impl PyIterProtocol for ZstdCompressorIterator {
 
         if !dest_buffer.is_empty() {
             // TODO avoid buffer copy.
-            let chunk = PyBytes::new(py, &dest_buffer);
+            let chunk = slf.output.emit(py, &dest_buffer)?;
 
-            return Ok(Some(chunk.into_py(py)));
+            return Ok(Some(chunk));
         }
 
         Ok(None)
@@ -6,10 +6,13 @@
 
 use {
     crate::{
-        buffers::{BufferSegment, ZstdBufferWithSegments, ZstdBufferWithSegmentsCollection},
+        buffers::{
+            ZstdBufferWithSegments, ZstdBufferWithSegmentsBuilder, ZstdBufferWithSegmentsCollection,
+        },
         compression_dict::ZstdCompressionDict,
         compression_parameters::CCtxParams,
         exceptions::ZstdError,
+        future::spawn_future,
         zstd_safe::CCtx,
     },
     pyo3::{
@@ -22,82 +25,201 @@ use {
     rayon::prelude::*,
 };
 
-struct DataSource<'a> {
-    data: &'a [u8],
+/// Inputs to compress.
+///
+/// Holds the Python objects backing each input so inputs remain valid when
+/// compression outlives the call that gathered them.
+struct DataSources {
+    /// Address and length of each input. Addresses are integers since raw
+    /// pointers aren't Send.
+    inputs: Vec<(usize, usize)>,
+    _owners: Vec<PyObject>,
+    _buffers: Vec<PyBuffer<u8>>,
 }
 
-pub fn multi_compress_to_buffer(
-    py: Python,
-    params: &CCtxParams,
-    dict: &Option<Py<ZstdCompressionDict>>,
-    data: &PyAny,
-    threads: isize,
-) -> PyResult<ZstdBufferWithSegmentsCollection> {
-    let threads = if threads < 0 {
-        num_cpus::get()
-    } else if threads < 2 {
-        1
-    } else {
-        threads as _
-    };
+impl DataSources {
+    fn gather(py: Python, data: &PyAny) -> PyResult<Self> {
+        let mut sources = Self {
+            inputs: vec![],
+            _owners: vec![],
+            _buffers: vec![],
+        };
+
+        if let Ok(buffer) = data.extract::<&PyCell<ZstdBufferWithSegments>>() {
+            sources.add_segments(py, buffer);
+        } else if let Ok(collection) = data.extract::<&PyCell<ZstdBufferWithSegmentsCollection>>() {
+            sources.inputs.reserve_exact(collection.borrow().__len__());
+
+            for buffer_obj in &collection.borrow().buffers {
+                sources.add_segments(py, buffer_obj.extract(py)?);
+            }
+        } else if let Ok(list) = data.extract::<&PyList>() {
+            sources.inputs.reserve_exact(list.len());
+
+            for (i, item) in list.iter().enumerate() {
+                let buffer: PyBuffer<u8> = PyBuffer::get(item).map_err(|_| {
+                    PyTypeError::new_err(format!("item {} not a bytes like object", i))
+                })?;
+
+                sources
+                    .inputs
+                    .push((buffer.buf_ptr() as usize, buffer.len_bytes()));
+                sources._buffers.push(buffer);
+            }
+        } else {
+            return Err(PyTypeError::new_err(
+                "argument must be list of BufferWithSegments",
+            ));
+        }
+
+        if sources.inputs.is_empty() {
+            return Err(PyValueError::new_err("no source elements found"));
+        }
 
-    let mut sources = vec![];
-    let mut total_source_size = 0;
+        if sources.inputs.iter().all(|(_, len)| *len == 0) {
+            return Err(PyValueError::new_err("source elements are empty"));
+        }
 
-    if let Ok(buffer) = data.extract::<&PyCell<ZstdBufferWithSegments>>() {
-        sources.reserve_exact(buffer.borrow().segments.len());
+        Ok(sources)
+    }
 
+    fn add_segments(&mut self, py: Python, buffer: &PyCell<ZstdBufferWithSegments>) {
         let borrow = buffer.borrow();
 
         for i in 0..borrow.segments.len() {
             let slice = borrow.get_segment_slice(py, i);
 
-            sources.push(DataSource { data: slice });
-            total_source_size += slice.len();
+            self.inputs.push((slice.as_ptr() as usize, slice.len()));
         }
-    } else if let Ok(collection) = data.extract::<&PyCell<ZstdBufferWithSegmentsCollection>>() {
-        sources.reserve_exact(collection.borrow().__len__());
-
-        for buffer_obj in &collection.borrow().buffers {
-            let buffer = buffer_obj.extract::<&PyCell<ZstdBufferWithSegments>>(py)?;
-            let borrow = buffer.borrow();
 
-            for i in 0..borrow.segments.len() {
-                let slice = borrow.get_segment_slice(py, i);
+        self._owners.push(buffer.into_py(py));
+    }
 
-                sources.push(DataSource { data: slice });
-                total_source_size += slice.len();
-            }
-        }
-    } else if let Ok(list) = data.extract::<&PyList>() {
-        sources.reserve_exact(list.len());
+    fn get(&self, index: usize) -> &[u8] {
+        let (address, len) = self.inputs[index];
 
-        for (i, item) in list.iter().enumerate() {
-            let buffer: PyBuffer<u8> = PyBuffer::get(item)
-                .map_err(|_| PyTypeError::new_err(format!("item {} not a bytes like object", i)))?;
+        unsafe { std::slice::from_raw_parts(address as *const u8, len) }
+    }
 
-            let slice = unsafe {
-                std::slice::from_raw_parts(buffer.buf_ptr() as *const _, buffer.len_bytes())
-            };
+    fn input_lengths(&self) -> Vec<usize> {
+        self.inputs.iter().map(|(_, len)| *len).collect()
+    }
+}
 
-            sources.push(DataSource { data: slice });
-            total_source_size += slice.len();
-        }
+fn resolve_threads(threads: isize) -> usize {
+    if threads < 0 {
+        num_cpus::get()
+    } else if threads < 2 {
+        1
     } else {
-        return Err(PyTypeError::new_err(
-            "argument must be list of BufferWithSegments",
-        ));
+        threads as _
     }
+}
+
+pub fn multi_compress_to_buffer(
+    py: Python,
+    params: &CCtxParams,
+    dict: &Option<Py<ZstdCompressionDict>>,
+    data: &PyAny,
+    threads: isize,
+    return_index: bool,
+) -> PyResult<PyObject> {
+    let sources = DataSources::gather(py, data)?;
+    let cctxs = make_cctxs(py, params, dict, resolve_threads(threads), &sources)?;
+    let results = compress_sources(&cctxs, &sources)?;
+
+    results_to_python(py, results, &sources.input_lengths(), return_index)
+}
 
-    if sources.is_empty() {
-        return Err(PyValueError::new_err("no source elements found"));
+/// Like `multi_compress_to_buffer()` but compresses on a background thread.
+///
+/// Returns an asyncio future of the running event loop that resolves to the
+/// same value.
+pub fn multi_compress_to_buffer_async(
+    py: Python,
+    params: &CCtxParams,
+    dict: &Option<Py<ZstdCompressionDict>>,
+    data: &PyAny,
+    threads: isize,
+    return_index: bool,
+) -> PyResult<PyObject> {
+    let sources = DataSources::gather(py, data)?;
+    let cctxs = make_cctxs(py, params, dict, resolve_threads(threads), &sources)?;
+    // Loaded dictionaries may be referenced by the contexts.
+    let dict = dict.as_ref().map(|dict| dict.clone_ref(py));
+
+    spawn_future(
+        py,
+        move || {
+            let results = compress_sources(&cctxs, &sources);
+
+            (results, sources, dict)
+        },
+        move |py, (results, sources, _dict)| {
+            results_to_python(py, results?, &sources.input_lengths(), return_index)
+        },
+    )
+}
+
+fn results_to_python(
+    py: Python,
+    results: Vec<WorkerResult>,
+    input_lengths: &[usize],
+    return_index: bool,
+) -> PyResult<PyObject> {
+    let collection = results_to_collection(py, results)?;
+
+    if return_index {
+        let index = segment_index(py, &collection, input_lengths)?;
+
+        Ok((Py::new(py, collection)?, index).into_py(py))
+    } else {
+        Ok(Py::new(py, collection)?.into_py(py))
     }
+}
 
-    if total_source_size == 0 {
-        return Err(PyValueError::new_err("source elements are empty"));
+/// Build the packing index for a multi compression result.
+///
+/// The index is a memoryview of unsigned 64-bit integers with shape
+/// `(N, 3)`. Each row holds the offset of the compressed frame as if all
+/// results were concatenated, the compressed length, and the length of
+/// the input that produced it.
+fn segment_index<'p>(
+    py: Python<'p>,
+    collection: &ZstdBufferWithSegmentsCollection,
+    input_lengths: &[usize],
+) -> PyResult<&'p PyAny> {
+    let mut index: Vec<u64> = Vec::with_capacity(input_lengths.len() * 3);
+    let mut offset = 0;
+
+    for buffer_obj in &collection.buffers {
+        let buffer = buffer_obj.extract::<&PyCell<ZstdBufferWithSegments>>(py)?;
+
+        for segment in &buffer.borrow().segments {
+            let input_length = input_lengths[index.len() / 3];
+
+            index.push(offset);
+            index.push(segment.length);
+            index.push(input_length as u64);
+
+            offset += segment.length;
+        }
     }
 
-    compress_from_datasources(py, params, dict, sources, threads)
+    let data = unsafe {
+        PyBytes::from_ptr(
+            py,
+            index.as_ptr() as *const _,
+            index.len() * std::mem::size_of::<u64>(),
+        )
+    };
+
+    let view = py
+        .import("builtins")?
+        .getattr("memoryview")?
+        .call1((data,))?;
+
+    view.call_method1("cast", ("Q", (input_lengths.len(), 3)))
 }
 
 /// Holds results of an individual compression operation.
@@ -107,24 +229,21 @@ struct WorkerResult {
     data: Option<Vec<u8>>,
 }
 
-fn compress_from_datasources(
+/// Create a compression context per thread.
+fn make_cctxs(
     py: Python,
     params: &CCtxParams,
     dict: &Option<Py<ZstdCompressionDict>>,
-    sources: Vec<DataSource>,
     thread_count: usize,
-) -> PyResult<ZstdBufferWithSegmentsCollection> {
+    sources: &DataSources,
+) -> PyResult<Vec<CCtx<'static>>> {
     // More threads than inputs makes no sense.
-    let thread_count = std::cmp::min(thread_count, sources.len());
+    let thread_count = std::cmp::min(thread_count, sources.inputs.len());
 
     // TODO lower thread count when input size is too small and threads
     // would add overhead.
 
     let mut cctxs = Vec::with_capacity(thread_count);
-    let results = std::sync::Mutex::new(Vec::with_capacity(sources.len()));
-
-    // TODO there are tons of inefficiencies in this implementation compared
-    // to the C backend.
 
     for _ in 0..thread_count {
         let cctx = CCtx::new().map_err(|msg| ZstdError::new_err(msg))?;
@@ -140,13 +259,25 @@ fn compress_from_datasources(
         cctxs.push(cctx);
     }
 
+    Ok(cctxs)
+}
+
+/// Compress every source using a thread per context.
+///
+/// Results are in input order.
+fn compress_sources(cctxs: &[CCtx], sources: &DataSources) -> PyResult<Vec<WorkerResult>> {
+    let results = std::sync::Mutex::new(Vec::with_capacity(sources.inputs.len()));
+
+    // TODO there are tons of inefficiencies in this implementation compared
+    // to the C backend.
+
     let pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(thread_count)
+        .num_threads(cctxs.len())
         .build()
         .map_err(|err| ZstdError::new_err(format!("error initializing thread pool: {}", err)))?;
 
     pool.install(|| {
-        sources.par_iter().enumerate().for_each(|(index, source)| {
+        (0..sources.inputs.len()).into_par_iter().for_each(|index| {
             let thread_index = pool.current_thread_index().unwrap();
 
             let cctx = &cctxs[thread_index];
@@ -157,7 +288,7 @@ fn compress_from_datasources(
                 data: None,
             };
 
-            match cctx.compress(source.data) {
+            match cctx.compress(sources.get(index), None) {
                 Ok(chunk) => {
                     result.data = Some(chunk);
                 }
@@ -171,19 +302,23 @@ fn compress_from_datasources(
         });
     });
 
+    let mut results = results.into_inner().unwrap();
+
     // Need to sort results by their input order or else results aren't
     // deterministic.
-    results
-        .lock()
-        .unwrap()
-        .sort_by(|a, b| a.source_offset.cmp(&b.source_offset));
+    results.sort_by(|a, b| a.source_offset.cmp(&b.source_offset));
 
+    Ok(results)
+}
+
+fn results_to_collection(
+    py: Python,
+    results: Vec<WorkerResult>,
+) -> PyResult<ZstdBufferWithSegmentsCollection> {
     // TODO this is horribly inefficient due to memory copies.
     let els = PyTuple::new(
         py,
         results
-            .lock()
-            .unwrap()
             .iter()
             .map(|result| {
                 if let Some(msg) = result.error {
@@ -194,22 +329,10 @@ fn compress_from_datasources(
                 }
 
                 let data = result.data.as_ref().unwrap();
-                let chunk = PyBytes::new(py, data);
-                let segments = vec![BufferSegment {
-                    offset: 0,
-                    length: data.len() as _,
-                }];
-
-                let segments = unsafe {
-                    PyBytes::from_ptr(
-                        py,
-                        segments.as_ptr() as *const _,
-                        segments.len() * std::mem::size_of::<BufferSegment>(),
-                    )
-                };
-                let segments_buffer = PyBuffer::get(segments)?;
-
-                Py::new(py, ZstdBufferWithSegments::new(py, chunk, segments_buffer)?)
+                let mut builder = ZstdBufferWithSegmentsBuilder::with_capacity(py, data.len())?;
+                builder.push(py, data)?;
+
+                Py::new(py, builder.build(py)?)
             })
             .collect::<PyResult<Vec<_>>>()?,
     );
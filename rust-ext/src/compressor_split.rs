@@ -0,0 +1,170 @@
+// Copyright (c) 2021-present, Gregory Szorc
+// All rights reserved.
+//
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+use {
+    crate::{exceptions::ZstdError, stream::make_in_buffer_source, zstd_safe::CCtx},
+    pyo3::{exceptions::PyValueError, prelude::*, types::PyBytes, PyNativeType},
+};
+
+/// Writes compressed output to the writer of the current shard.
+struct ShardOutput<'a> {
+    cctx: &'a CCtx<'a>,
+    writers: Vec<&'a PyAny>,
+    dest_buffer: Vec<u8>,
+    /// Index of the shard being written.
+    shard: usize,
+    /// Input and output sizes of the current shard.
+    read: usize,
+    written: usize,
+}
+
+impl<'a> ShardOutput<'a> {
+    fn compress(
+        &mut self,
+        in_buffer: &mut zstd_sys::ZSTD_inBuffer,
+        directive: zstd_sys::ZSTD_EndDirective,
+    ) -> PyResult<()> {
+        let old_pos = in_buffer.pos;
+
+        loop {
+            let zresult = self
+                .cctx
+                .compress_into_vec(&mut self.dest_buffer, in_buffer, directive)
+                .map_err(|msg| ZstdError::new_err(format!("zstd compress error: {}", msg)))?;
+
+            if !self.dest_buffer.is_empty() {
+                let chunk = PyBytes::new(self.writers[self.shard].py(), &self.dest_buffer);
+                self.writers[self.shard].call_method1("write", (chunk,))?;
+
+                self.written += self.dest_buffer.len();
+                self.dest_buffer.clear();
+            }
+
+            let done = match directive {
+                zstd_sys::ZSTD_EndDirective::ZSTD_e_end => zresult == 0,
+                _ => in_buffer.pos == in_buffer.size,
+            };
+
+            if done {
+                break;
+            }
+        }
+
+        self.read += in_buffer.pos - old_pos;
+
+        Ok(())
+    }
+
+    /// End the current shard's frame and move on to the next writer.
+    fn end_shard(&mut self, results: &mut Vec<(usize, usize)>) -> PyResult<()> {
+        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+            src: std::ptr::null(),
+            size: 0,
+            pos: 0,
+        };
+        self.compress(&mut in_buffer, zstd_sys::ZSTD_EndDirective::ZSTD_e_end)?;
+
+        results.push((self.read, self.written));
+        self.shard += 1;
+        self.read = 0;
+        self.written = 0;
+
+        Ok(())
+    }
+}
+
+/// Compress `source` into one frame per writer with similar compressed sizes.
+///
+/// Shard boundaries are chosen in a single pass: the compression ratio so far
+/// estimates the total compressed size, and a shard ends once it holds its
+/// share of what remains. Shards are therefore only roughly equal, with
+/// error growing with the input's variation in compressibility.
+///
+/// Returns the `(bytes_read, bytes_written)` of each shard.
+pub(crate) fn split_stream(
+    py: Python,
+    cctx: &CCtx,
+    source: &PyAny,
+    writers: &PyAny,
+    size: Option<u64>,
+    read_size: usize,
+    write_size: usize,
+) -> PyResult<Vec<(usize, usize)>> {
+    let writers = writers.iter()?.collect::<PyResult<Vec<&PyAny>>>()?;
+
+    if writers.is_empty() {
+        return Err(PyValueError::new_err("must specify at least one writer"));
+    }
+
+    for writer in &writers {
+        if !writer.hasattr("write")? {
+            return Err(PyValueError::new_err("writers must have a write() method"));
+        }
+    }
+
+    let mut source = make_in_buffer_source(py, source, read_size)?;
+
+    let total_size = match size.or_else(|| source.source_size().map(|size| size as u64)) {
+        Some(size) => size,
+        None => {
+            return Err(PyValueError::new_err(
+                "size must be specified when the source size is unknown",
+            ))
+        }
+    };
+
+    let shards = writers.len();
+    let mut output = ShardOutput {
+        cctx,
+        writers,
+        dest_buffer: Vec::with_capacity(write_size),
+        shard: 0,
+        read: 0,
+        written: 0,
+    };
+
+    let mut results = Vec::with_capacity(shards);
+    let (mut total_read, mut total_written) = (0, 0);
+
+    // Input is fed in slices so boundaries can fall within large reads.
+    let slice_size = zstd_safe::cstream_in_size();
+
+    while let Some(mut in_buffer) = source.input_buffer(py)? {
+        let old_pos = in_buffer.pos;
+        let end = in_buffer.size;
+
+        while in_buffer.pos < end {
+            in_buffer.size = std::cmp::min(in_buffer.pos + slice_size, end);
+            output.compress(&mut in_buffer, zstd_sys::ZSTD_EndDirective::ZSTD_e_continue)?;
+
+            // Output lags input by up to a block, so nothing can be judged
+            // until the shard has produced some.
+            if output.shard + 1 == shards || output.written == 0 {
+                continue;
+            }
+
+            let read = (total_read + output.read) as f64;
+            let written = (total_written + output.written) as f64;
+            let estimated_total = written / read * total_size as f64;
+            let target = (estimated_total - total_written as f64) / (shards - output.shard) as f64;
+
+            if output.written as f64 >= target {
+                total_read += output.read;
+                total_written += output.written;
+                output.end_shard(&mut results)?;
+            }
+        }
+
+        source.record_bytes_read(in_buffer.pos - old_pos);
+    }
+
+    // Every writer receives a frame, even if input ran out early.
+    while output.shard < shards {
+        output.end_shard(&mut results)?;
+    }
+
+    Ok(results)
+}
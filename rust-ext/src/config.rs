@@ -0,0 +1,104 @@
+// Copyright (c) 2021-present, Gregory Szorc
+// All rights reserved.
+//
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+use {
+    crate::compression_dict::ZstdCompressionDict,
+    pyo3::{exceptions::PyValueError, prelude::*, types::PyDict},
+};
+
+/// Version of the serialization format used by `to_config()`.
+const CONFIG_VERSION: u32 = 1;
+
+/// Serialize a config dict for an instance of type `kind` to JSON bytes.
+pub(crate) fn dump_config<'p>(
+    py: Python<'p>,
+    kind: &str,
+    values: &PyDict,
+    dict_data: &Option<Py<ZstdCompressionDict>>,
+) -> PyResult<&'p PyAny> {
+    values.set_item("version", CONFIG_VERSION)?;
+    values.set_item("type", kind)?;
+    values.set_item(
+        "dict_id",
+        dict_data
+            .as_ref()
+            .map(|dict| dict.borrow(py).dict_id().into_py(py)),
+    )?;
+
+    py.import("json")?
+        .call_method1("dumps", (values,))?
+        .call_method1("encode", ("utf-8",))
+}
+
+/// Parse and validate config bytes produced by `dump_config()`.
+///
+/// The dictionary referenced by the config is validated against `dict_data`.
+pub(crate) fn load_config<'p>(
+    py: Python<'p>,
+    kind: &str,
+    config: &PyAny,
+    dict_data: &Option<Py<ZstdCompressionDict>>,
+) -> PyResult<&'p PyDict> {
+    let config = py
+        .import("json")?
+        .call_method1("loads", (config,))
+        .map_err(|_| PyValueError::new_err("config is not valid JSON"))?;
+    let config: &PyDict = config
+        .downcast()
+        .map_err(|_| PyValueError::new_err("config must be a JSON object"))?;
+
+    if !matches!(
+        config.get_item("version").map(|v| v.extract::<u32>()),
+        Some(Ok(CONFIG_VERSION))
+    ) {
+        return Err(PyValueError::new_err("unsupported config version"));
+    }
+
+    if !matches!(
+        config.get_item("type").map(|v| v.extract::<&str>()),
+        Some(Ok(t)) if t == kind
+    ) {
+        return Err(PyValueError::new_err(format!(
+            "config is not for a {}",
+            kind
+        )));
+    }
+
+    let dict_id = match config.get_item("dict_id") {
+        Some(value) if !value.is_none() => Some(
+            value
+                .extract::<u32>()
+                .map_err(|_| PyValueError::new_err("config dict_id must be an integer"))?,
+        ),
+        _ => None,
+    };
+
+    match (dict_id, dict_data) {
+        (None, None) => {}
+        (Some(dict_id), Some(dict)) => {
+            let actual = dict.borrow(py).dict_id();
+
+            if dict_id != actual {
+                return Err(PyValueError::new_err(format!(
+                    "dictionary ID mismatch; config requires {}, got {}",
+                    dict_id, actual
+                )));
+            }
+        }
+        (Some(_), None) => {
+            return Err(PyValueError::new_err(
+                "config requires a dictionary; pass dict_data",
+            ));
+        }
+        (None, Some(_)) => {
+            return Err(PyValueError::new_err(
+                "config does not use a dictionary; cannot pass dict_data",
+            ));
+        }
+    }
+
+    Ok(config)
+}
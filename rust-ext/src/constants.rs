@@ -4,11 +4,79 @@
 // This software may be modified and distributed under the terms
 // of the BSD license. See the LICENSE file for details.
 
-use pyo3::{prelude::*, types::PyBytes};
+use {
+    crate::exceptions::{c_compat_errors, CommonError},
+    pyo3::{exceptions::PyValueError, prelude::*, types::PyBytes, wrap_pyfunction},
+    std::convert::TryInto,
+};
 
 pub(crate) const COMPRESSOBJ_FLUSH_FINISH: i32 = 0;
 pub(crate) const COMPRESSOBJ_FLUSH_BLOCK: i32 = 1;
 
+/// Magic number of the first of the 16 skippable frame variants.
+pub(crate) const MAGIC_SKIPPABLE_START: u32 = 0x184D2A50;
+
+/// Mask selecting the bits shared by all skippable frame magic numbers.
+pub(crate) const MAGIC_SKIPPABLE_MASK: u32 = 0xFFFFFFF0;
+
+/// Ensure `level` is between `MIN_COMPRESSION_LEVEL` and `MAX_COMPRESSION_LEVEL`.
+///
+/// Negative levels select the fast strategy; 0 selects the default level.
+pub(crate) fn validate_level(level: i32) -> PyResult<i32> {
+    if level > zstd_safe::max_c_level() {
+        Err(CommonError::LevelTooHigh.into())
+    } else if level < zstd_safe::min_c_level() && !c_compat_errors() {
+        Err(CommonError::LevelTooLow.into())
+    } else {
+        Ok(level)
+    }
+}
+
+/// Resolve a `FORMAT_*` constant.
+pub(crate) fn format_from_int(format: u32) -> PyResult<zstd_sys::ZSTD_format_e> {
+    if format == zstd_sys::ZSTD_format_e::ZSTD_f_zstd1 as _ {
+        Ok(zstd_sys::ZSTD_format_e::ZSTD_f_zstd1)
+    } else if format == zstd_sys::ZSTD_format_e::ZSTD_f_zstd1_magicless as _ {
+        Ok(zstd_sys::ZSTD_format_e::ZSTD_f_zstd1_magicless)
+    } else {
+        Err(PyValueError::new_err("invalid format value"))
+    }
+}
+
+/// Whether `magic` is the magic number of a skippable frame.
+pub(crate) fn is_skippable_magic(magic: u32) -> bool {
+    magic & MAGIC_SKIPPABLE_MASK == MAGIC_SKIPPABLE_START
+}
+
+/// Header of a skippable frame holding `size` bytes of content.
+///
+/// `variant` selects one of the 16 magic numbers and must be less than 16.
+pub(crate) fn skippable_header(variant: u32, size: u32) -> [u8; 8] {
+    assert!(variant < 16);
+
+    let mut header = [0; 8];
+    header[0..4].copy_from_slice(&(MAGIC_SKIPPABLE_START | variant).to_le_bytes());
+    header[4..8].copy_from_slice(&size.to_le_bytes());
+
+    header
+}
+
+/// Obtain the header of a skippable frame holding `size` bytes of content.
+///
+/// The content must follow the header for the frame to be valid.
+#[pyfunction(size, variant = "0")]
+fn skippable_frame_header<'p>(py: Python<'p>, size: u64, variant: u32) -> PyResult<&'p PyBytes> {
+    if variant >= 16 {
+        return Err(PyValueError::new_err("variant must be less than 16"));
+    }
+
+    let size: u32 = size
+        .try_into()
+        .map_err(|_| PyValueError::new_err("skippable frame content must be less than 4 GiB"))?;
+
+    Ok(PyBytes::new(py, &skippable_header(variant, size)))
+}
+
 pub(crate) fn init_module(py: Python, module: &PyModule) -> PyResult<()> {
     module.add("__version", super::VERSION)?;
     module.add("__doc__", "Rust backend for zstandard bindings")?;
@@ -32,6 +100,7 @@ pub(crate) fn init_module(py: Python, module: &PyModule) -> PyResult<()> {
     module.add("CONTENTSIZE_UNKNOWN", zstd_safe::CONTENTSIZE_UNKNOWN)?;
     module.add("CONTENTSIZE_ERROR", zstd_safe::CONTENTSIZE_ERROR)?;
 
+    module.add("MIN_COMPRESSION_LEVEL", zstd_safe::min_c_level())?;
     module.add("MAX_COMPRESSION_LEVEL", zstd_safe::max_c_level())?;
     module.add(
         "COMPRESSION_RECOMMENDED_INPUT_SIZE",
@@ -51,6 +120,8 @@ pub(crate) fn init_module(py: Python, module: &PyModule) -> PyResult<()> {
     )?;
 
     module.add("MAGIC_NUMBER", zstd_safe::MAGICNUMBER)?;
+    module.add("MAGIC_SKIPPABLE_START", MAGIC_SKIPPABLE_START)?;
+    module.add("MAGIC_SKIPPABLE_MASK", MAGIC_SKIPPABLE_MASK)?;
     module.add("BLOCKSIZELOG_MAX", zstd_safe::BLOCKSIZELOG_MAX)?;
     module.add("BLOCKSIZE_MAX", zstd_safe::BLOCKSIZE_MAX)?;
     module.add("WINDOWLOG_MIN", zstd_safe::WINDOWLOG_MIN)?;
@@ -120,11 +191,35 @@ pub(crate) fn init_module(py: Python, module: &PyModule) -> PyResult<()> {
         zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_fullDict as u32,
     )?;
 
+    module.add(
+        "DICT_LOAD_BY_REF",
+        zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byRef as u32,
+    )?;
+    module.add(
+        "DICT_LOAD_BY_COPY",
+        zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byCopy as u32,
+    )?;
+
     module.add("FORMAT_ZSTD1", zstd_sys::ZSTD_format_e::ZSTD_f_zstd1 as u32)?;
     module.add(
         "FORMAT_ZSTD1_MAGICLESS",
         zstd_sys::ZSTD_format_e::ZSTD_f_zstd1_magicless as u32,
     )?;
 
+    module.add(
+        "ROW_MATCH_FINDER_AUTO",
+        zstd_sys::ZSTD_useRowMatchFinderMode_e::ZSTD_urm_auto as u32,
+    )?;
+    module.add(
+        "ROW_MATCH_FINDER_DISABLED",
+        zstd_sys::ZSTD_useRowMatchFinderMode_e::ZSTD_urm_disableRowMatchFinder as u32,
+    )?;
+    module.add(
+        "ROW_MATCH_FINDER_ENABLED",
+        zstd_sys::ZSTD_useRowMatchFinderMode_e::ZSTD_urm_enableRowMatchFinder as u32,
+    )?;
+
+    module.add_function(wrap_pyfunction!(skippable_frame_header, module)?)?;
+
     Ok(())
 }
@@ -6,12 +6,14 @@
 
 use {
     crate::{
-        exceptions::ZstdError,
+        buffers::BytesBuilder,
+        exceptions::{CommonError, ZstdError},
         stream::{make_in_buffer_source, InBufferSource},
         zstd_safe::DCtx,
     },
     pyo3::{
         buffer::PyBuffer,
+        class::PyObjectProtocol,
         exceptions::{PyOSError, PyValueError},
         prelude::*,
         types::{PyBytes, PyList},
@@ -30,6 +32,8 @@ pub struct ZstdDecompressionReader {
     closed: bool,
     bytes_decompressed: usize,
     finished_output: bool,
+    /// Most output a single read may produce.
+    max_decompress_per_call: Option<usize>,
 }
 
 impl ZstdDecompressionReader {
@@ -50,8 +54,24 @@ impl ZstdDecompressionReader {
             closed: false,
             bytes_decompressed: 0,
             finished_output: false,
+            max_decompress_per_call: None,
         })
     }
+
+    /// Return from each read once `limit` bytes were decompressed, even if
+    /// more were requested.
+    pub fn with_max_decompress_per_call(mut self, limit: usize) -> Self {
+        self.max_decompress_per_call = Some(limit);
+        self
+    }
+
+    /// Clamp a requested read `size` to `max_decompress_per_call`.
+    fn output_limit(&self, size: usize) -> usize {
+        match self.max_decompress_per_call {
+            Some(limit) => min(size, limit),
+            None => size,
+        }
+    }
 }
 
 impl ZstdDecompressionReader {
@@ -73,7 +93,7 @@ impl ZstdDecompressionReader {
 
         let zresult = self
             .dctx
-            .decompress_buffers(out_buffer, &mut in_buffer)
+            .decompress_buffers_allow_threads(py, out_buffer, &mut in_buffer)
             .map_err(|msg| ZstdError::new_err(format!("zstd decompress error: {}", msg)))?;
 
         if in_buffer.pos - old_pos > 0 {
@@ -88,13 +108,29 @@ impl ZstdDecompressionReader {
     }
 }
 
+#[pyproto]
+impl PyObjectProtocol for ZstdDecompressionReader {
+    fn __repr__(&self) -> String {
+        format!(
+            "ZstdDecompressionReader(bytes_decompressed={}, closed={}, finished={})",
+            self.bytes_decompressed,
+            if self.closed { "True" } else { "False" },
+            if self.finished_output {
+                "True"
+            } else {
+                "False"
+            },
+        )
+    }
+}
+
 #[pymethods]
 impl ZstdDecompressionReader {
     fn __enter__<'p>(mut slf: PyRefMut<'p, Self>, _py: Python<'p>) -> PyResult<PyRefMut<'p, Self>> {
         if slf.entered {
             Err(PyValueError::new_err("cannot __enter__ multiple times"))
         } else if slf.closed {
-            Err(PyValueError::new_err("stream is closed"))
+            Err(CommonError::StreamClosed.into())
         } else {
             slf.entered = true;
             Ok(slf)
@@ -215,7 +251,7 @@ impl ZstdDecompressionReader {
     #[args(size = "None")]
     fn read<'p>(&mut self, py: Python<'p>, size: Option<isize>) -> PyResult<&'p PyAny> {
         if self.closed {
-            return Err(PyValueError::new_err("stream is closed"));
+            return Err(CommonError::StreamClosed.into());
         }
 
         let size = size.unwrap_or(-1);
@@ -234,45 +270,21 @@ impl ZstdDecompressionReader {
             return Ok(PyBytes::new(py, &[]));
         }
 
-        let mut dest_buffer: Vec<u8> = Vec::with_capacity(size as _);
-        let mut out_buffer = zstd_sys::ZSTD_outBuffer {
-            dst: dest_buffer.as_mut_ptr() as *mut _,
-            size: dest_buffer.capacity(),
-            pos: 0,
-        };
+        let mut dest = BytesBuilder::with_capacity(py, self.output_limit(size as _))?;
+        let mut out_buffer = dest.out_buffer();
 
-        if self.decompress_into_buffer(py, &mut out_buffer)? {
-            self.bytes_decompressed += out_buffer.pos;
-            unsafe {
-                dest_buffer.set_len(out_buffer.pos);
-            }
-
-            // TODO avoid buffer copy.
-            let chunk = PyBytes::new(py, &dest_buffer);
-            return Ok(chunk);
-        }
-
-        while !self.source.finished() {
-            if self.decompress_into_buffer(py, &mut out_buffer)? {
-                self.bytes_decompressed += out_buffer.pos;
-                unsafe {
-                    dest_buffer.set_len(out_buffer.pos);
+        if !self.decompress_into_buffer(py, &mut out_buffer)? {
+            while !self.source.finished() {
+                if self.decompress_into_buffer(py, &mut out_buffer)? {
+                    break;
                 }
-
-                // TODO avoid buffer copy.
-                let chunk = PyBytes::new(py, &dest_buffer);
-                return Ok(chunk);
             }
         }
 
         self.bytes_decompressed += out_buffer.pos;
-        unsafe {
-            dest_buffer.set_len(out_buffer.pos);
-        }
+        dest.set_len(out_buffer.pos);
 
-        // TODO avoid buffer copy.
-        let chunk = PyBytes::new(py, &dest_buffer);
-        return Ok(chunk);
+        Ok(dest.finish(py)?)
     }
 
     fn readinto(&mut self, py: Python, buffer: PyBuffer<u8>) -> PyResult<usize> {
@@ -281,7 +293,7 @@ impl ZstdDecompressionReader {
         }
 
         if self.closed {
-            return Err(PyValueError::new_err("stream is closed"));
+            return Err(CommonError::StreamClosed.into());
         }
 
         if self.finished_output {
@@ -290,7 +302,7 @@ impl ZstdDecompressionReader {
 
         let mut out_buffer = zstd_sys::ZSTD_outBuffer {
             dst: buffer.buf_ptr() as *mut _,
-            size: buffer.len_bytes(),
+            size: self.output_limit(buffer.len_bytes()),
             pos: 0,
         };
 
@@ -316,7 +328,7 @@ impl ZstdDecompressionReader {
     #[args(size = "None")]
     fn read1<'p>(&mut self, py: Python<'p>, size: Option<isize>) -> PyResult<&'p PyAny> {
         if self.closed {
-            return Err(PyValueError::new_err("stream is closed"));
+            return Err(CommonError::StreamClosed.into());
         }
 
         let size = size.unwrap_or(-1);
@@ -337,12 +349,8 @@ impl ZstdDecompressionReader {
             size => size as _,
         };
 
-        let mut dest_buffer: Vec<u8> = Vec::with_capacity(size);
-        let mut out_buffer = zstd_sys::ZSTD_outBuffer {
-            dst: dest_buffer.as_mut_ptr() as *mut _,
-            size: dest_buffer.capacity(),
-            pos: 0,
-        };
+        let mut dest = BytesBuilder::with_capacity(py, self.output_limit(size))?;
+        let mut out_buffer = dest.out_buffer();
 
         // read1() dictates that we can perform at most 1 call to underlying
         // stream to get input. However, we can't satisfy this restriction with
@@ -356,14 +364,10 @@ impl ZstdDecompressionReader {
             }
         }
 
-        unsafe {
-            dest_buffer.set_len(out_buffer.pos);
-        }
         self.bytes_decompressed += out_buffer.pos;
+        dest.set_len(out_buffer.pos);
 
-        // TODO avoid buffer copy.
-        let chunk = PyBytes::new(py, &dest_buffer);
-        Ok(chunk)
+        Ok(dest.finish(py)?)
     }
 
     fn readinto1(&mut self, py: Python, buffer: PyBuffer<u8>) -> PyResult<usize> {
@@ -372,7 +376,7 @@ impl ZstdDecompressionReader {
         }
 
         if self.closed {
-            return Err(PyValueError::new_err("stream is closed"));
+            return Err(CommonError::StreamClosed.into());
         }
 
         if self.finished_output {
@@ -381,7 +385,7 @@ impl ZstdDecompressionReader {
 
         let mut out_buffer = zstd_sys::ZSTD_outBuffer {
             dst: buffer.buf_ptr(),
-            size: buffer.len_bytes(),
+            size: self.output_limit(buffer.len_bytes()),
             pos: 0,
         };
 
@@ -401,7 +405,7 @@ impl ZstdDecompressionReader {
     #[args(pos, whence = "None")]
     fn seek(&mut self, py: Python, pos: isize, whence: Option<i32>) -> PyResult<usize> {
         if self.closed {
-            return Err(PyValueError::new_err("stream is closed"));
+            return Err(CommonError::StreamClosed.into());
         }
 
         let os = py.import("os")?;
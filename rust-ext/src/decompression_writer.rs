@@ -5,16 +5,37 @@
 // of the BSD license. See the LICENSE file for details.
 
 use {
-    crate::{exceptions::ZstdError, zstd_safe::DCtx},
+    crate::{
+        exceptions::{CommonError, ZstdError},
+        zstd_safe::DCtx,
+    },
     pyo3::{
-        buffer::PyBuffer,
-        exceptions::{PyOSError, PyValueError},
-        prelude::*,
+        buffer::PyBuffer, class::PyObjectProtocol, exceptions::PyValueError, prelude::*,
         types::PyBytes,
     },
     std::sync::Arc,
 };
 
+/// Write all of `data` to file descriptor `fd`.
+fn write_all_fd(fd: libc::c_int, mut data: &[u8]) -> std::io::Result<()> {
+    while !data.is_empty() {
+        let written = unsafe { libc::write(fd, data.as_ptr() as *const _, data.len() as _) };
+
+        if written < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+
+            return Err(err);
+        }
+
+        data = &data[written as usize..];
+    }
+
+    Ok(())
+}
+
 #[pyclass(module = "zstandard.backend_rust")]
 pub struct ZstdDecompressionWriter {
     dctx: Arc<DCtx<'static>>,
@@ -22,6 +43,9 @@ pub struct ZstdDecompressionWriter {
     write_size: usize,
     write_return_read: bool,
     closefd: bool,
+    /// File descriptor of `writer` that decompressed data is written to
+    /// directly, bypassing `writer.write()`.
+    fd: Option<libc::c_int>,
     entered: bool,
     closing: bool,
     closed: bool,
@@ -35,13 +59,34 @@ impl ZstdDecompressionWriter {
         write_size: usize,
         write_return_read: bool,
         closefd: bool,
+        direct_fd: bool,
     ) -> PyResult<Self> {
+        let fd = if direct_fd {
+            let fd = writer
+                .call_method0("fileno")
+                .and_then(|fd| fd.extract::<libc::c_int>())
+                .map_err(|_| {
+                    PyValueError::new_err("direct_fd requires a writer with a file descriptor")
+                })?;
+
+            // Anything buffered in the writer must reach the descriptor before
+            // data we write to it.
+            if writer.hasattr("flush")? {
+                writer.call_method0("flush")?;
+            }
+
+            Some(fd)
+        } else {
+            None
+        };
+
         Ok(Self {
             dctx,
             writer: writer.into_py(py),
             write_size,
             write_return_read,
             closefd,
+            fd,
             entered: false,
             closing: false,
             closed: false,
@@ -49,11 +94,22 @@ impl ZstdDecompressionWriter {
     }
 }
 
+#[pyproto]
+impl PyObjectProtocol for ZstdDecompressionWriter {
+    fn __repr__(&self) -> String {
+        format!(
+            "ZstdDecompressionWriter(write_size={}, closed={})",
+            self.write_size,
+            if self.closed { "True" } else { "False" },
+        )
+    }
+}
+
 #[pymethods]
 impl ZstdDecompressionWriter {
     fn __enter__<'p>(mut slf: PyRefMut<'p, Self>, _py: Python<'p>) -> PyResult<PyRefMut<'p, Self>> {
         if slf.closed {
-            Err(PyValueError::new_err("stream is closed"))
+            Err(CommonError::StreamClosed.into())
         } else if slf.entered {
             Err(ZstdError::new_err("cannot __enter__ multiple times"))
         } else {
@@ -112,15 +168,13 @@ impl ZstdDecompressionWriter {
         if let Ok(fileno) = self.writer.getattr(py, "fileno") {
             fileno.call0(py)
         } else {
-            Err(PyOSError::new_err(
-                "filenot not available on underlying writer",
-            ))
+            Err(CommonError::NoFileno.into())
         }
     }
 
     fn flush(&self, py: Python) -> PyResult<PyObject> {
         if self.closed {
-            return Err(PyValueError::new_err("stream is closed"));
+            return Err(CommonError::StreamClosed.into());
         }
 
         if let Ok(flush) = self.writer.getattr(py, "flush") {
@@ -242,7 +296,7 @@ impl ZstdDecompressionWriter {
 
     fn write(&self, py: Python, buffer: PyBuffer<u8>) -> PyResult<usize> {
         if self.closed {
-            return Err(PyValueError::new_err("stream is closed"));
+            return Err(CommonError::StreamClosed.into());
         }
 
         let mut total_write = 0;
@@ -257,13 +311,17 @@ impl ZstdDecompressionWriter {
 
         while in_buffer.pos < in_buffer.size {
             self.dctx
-                .decompress_into_vec(&mut dest_buffer, &mut in_buffer)
+                .decompress_into_vec_allow_threads(py, &mut dest_buffer, &mut in_buffer)
                 .map_err(|msg| ZstdError::new_err(format!("zstd decompress error: {}", msg)))?;
 
             if !dest_buffer.is_empty() {
-                // TODO avoid buffer copy.
-                let chunk = PyBytes::new(py, &dest_buffer);
-                self.writer.call_method1(py, "write", (chunk,))?;
+                if let Some(fd) = self.fd {
+                    py.allow_threads(|| write_all_fd(fd, &dest_buffer))?;
+                } else {
+                    // TODO avoid buffer copy.
+                    let chunk = PyBytes::new(py, &dest_buffer);
+                    self.writer.call_method1(py, "write", (chunk,))?;
+                }
                 total_write += dest_buffer.len();
                 dest_buffer.clear();
             }
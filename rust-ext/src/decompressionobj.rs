@@ -8,6 +8,7 @@ use {
     crate::{exceptions::ZstdError, zstd_safe::DCtx},
     pyo3::{
         buffer::PyBuffer,
+        class::PyObjectProtocol,
         prelude::*,
         types::{PyBytes, PyList},
     },
@@ -19,28 +20,58 @@ pub struct ZstdDecompressionObj {
     dctx: Arc<DCtx<'static>>,
     write_size: usize,
     finished: bool,
+    /// Whether NUL bytes fed after the end of the frame are accepted.
+    tolerate_padding: bool,
+    /// Input following the end of the frame.
+    unused_data: Vec<u8>,
 }
 
 impl ZstdDecompressionObj {
-    pub fn new(dctx: Arc<DCtx<'static>>, write_size: usize) -> PyResult<Self> {
+    pub fn new(
+        dctx: Arc<DCtx<'static>>,
+        write_size: usize,
+        tolerate_padding: bool,
+    ) -> PyResult<Self> {
         Ok(ZstdDecompressionObj {
             dctx,
             write_size,
             finished: false,
+            tolerate_padding,
+            unused_data: vec![],
         })
     }
 }
 
+#[pyproto]
+impl PyObjectProtocol for ZstdDecompressionObj {
+    fn __repr__(&self) -> String {
+        format!(
+            "ZstdDecompressionObj(write_size={}, finished={})",
+            self.write_size,
+            if self.finished { "True" } else { "False" },
+        )
+    }
+}
+
 #[pymethods]
 impl ZstdDecompressionObj {
     fn decompress<'p>(&mut self, py: Python<'p>, data: PyBuffer<u8>) -> PyResult<&'p PyAny> {
+        let source = unsafe {
+            std::slice::from_raw_parts::<u8>(data.buf_ptr() as *const _, data.len_bytes())
+        };
+
         if self.finished {
+            if self.tolerate_padding && source.iter().all(|b| *b == 0) {
+                self.unused_data.extend_from_slice(source);
+                return Ok(PyBytes::new(py, &[]));
+            }
+
             return Err(ZstdError::new_err(
                 "cannot use a decompressobj multiple times",
             ));
         }
 
-        if data.len_bytes() == 0 {
+        if source.is_empty() {
             return Ok(PyBytes::new(py, &[]));
         }
 
@@ -57,11 +88,13 @@ impl ZstdDecompressionObj {
         loop {
             let zresult = self
                 .dctx
-                .decompress_into_vec(&mut dest_buffer, &mut in_buffer)
+                .decompress_into_vec_allow_threads(py, &mut dest_buffer, &mut in_buffer)
                 .map_err(|msg| ZstdError::new_err(format!("zstd decompress error: {}", msg)))?;
 
             if zresult == 0 {
                 self.finished = true;
+                self.unused_data
+                    .extend_from_slice(&source[in_buffer.pos..in_buffer.size]);
                 // TODO clear out decompressor?
             }
 
@@ -82,6 +115,25 @@ impl ZstdDecompressionObj {
         empty.call_method1("join", (chunks,))
     }
 
+    /// Reset the instance so it can decompress a new frame.
+    ///
+    /// The dictionary and decompression parameters are retained.
+    fn reset(&mut self) -> PyResult<()> {
+        self.dctx.reset().map_err(|msg| {
+            ZstdError::new_err(format!("unable to reset decompression context: {}", msg))
+        })?;
+        self.finished = false;
+        self.unused_data.clear();
+
+        Ok(())
+    }
+
+    /// Input following the end of the frame.
+    #[getter]
+    fn unused_data<'p>(&self, py: Python<'p>) -> &'p PyBytes {
+        PyBytes::new(py, &self.unused_data)
+    }
+
     #[allow(unused_variables)]
     fn flush<'p>(&self, py: Python<'p>, length: Option<usize>) -> PyResult<&'p PyBytes> {
         Ok(PyBytes::new(py, &[]))
@@ -6,94 +6,606 @@
 
 use {
     crate::{
-        buffers::ZstdBufferWithSegmentsCollection, compression_dict::ZstdCompressionDict,
+        buffers::{ByteBuffer, BytesBuilder},
+        compression_dict::ZstdCompressionDict,
+        config::{dump_config, load_config},
+        constants::format_from_int,
         decompression_reader::ZstdDecompressionReader,
-        decompression_writer::ZstdDecompressionWriter, decompressionobj::ZstdDecompressionObj,
+        decompression_writer::ZstdDecompressionWriter,
+        decompressionobj::ZstdDecompressionObj,
+        decompressor_frames::ZstdFrameIterator,
         decompressor_iterator::ZstdDecompressorIterator,
-        decompressor_multi::multi_decompress_to_buffer, exceptions::ZstdError, zstd_safe::DCtx,
+        decompressor_multi::{
+            multi_decompress_to_buffer, multi_decompress_to_buffer_async, MultiDecompressOptions,
+        },
+        decompressor_records::ZstdRecordIterator,
+        exceptions::{OnError, ZstdError},
+        options::apply_kwargs,
+        stream::{
+            copy_stream_result, resolve_fd, CopyDeadline, CopyStreamOptions, CopyStreamResult,
+        },
+        streaming_decoder::StreamingDecoder,
+        zstd_safe::{
+            patch_window_size, window_log_for_size, window_log_max_bounds, DCtx, DictResolver,
+        },
     },
     pyo3::{
         buffer::PyBuffer,
-        exceptions::{PyMemoryError, PyValueError},
+        class::PyObjectProtocol,
+        exceptions::{PyMemoryError, PyTypeError, PyValueError},
         prelude::*,
-        types::{PyBytes, PyList},
-        wrap_pyfunction,
+        types::{PyBytes, PyDict, PyList, PyTuple, PyType},
+        wrap_pyfunction, PyNativeType,
     },
-    std::sync::Arc,
+    std::{any::Any, collections::HashSet, sync::Arc},
 };
 
+/// Maximum size of the up-front allocation in `decompress()` when trusting a
+/// frame's declared content size. Larger outputs grow as they are produced.
+const DECOMPRESS_PREALLOCATE_MAX: usize = 16 * 1024 * 1024;
+
+/// Largest output `decompress(bound_fallback=True)` will size from
+/// `ZSTD_decompressBound()` for frames without a declared content size.
+const DECOMPRESS_BOUND_FALLBACK_MAX: u64 = 256 * 1024 * 1024;
+
+/// Keyword-only options to `ZstdDecompressor()`.
+#[derive(Default)]
+struct DecompressorOptions {
+    require_checksum: bool,
+    use_dict_registry: bool,
+    track_window_usage: bool,
+    ignore_dict_id: bool,
+    ignore_checksum: bool,
+}
+
+impl DecompressorOptions {
+    fn from_kwargs(kwargs: Option<&PyDict>) -> PyResult<Self> {
+        let mut options = Self::default();
+
+        apply_kwargs("ZstdDecompressor()", kwargs, |key, value| {
+            match key {
+                "require_checksum" => options.require_checksum = value.extract()?,
+                "use_dict_registry" => options.use_dict_registry = value.extract()?,
+                "track_window_usage" => options.track_window_usage = value.extract()?,
+                "ignore_dict_id" => options.ignore_dict_id = value.extract()?,
+                "ignore_checksum" => options.ignore_checksum = value.extract()?,
+                _ => return Ok(false),
+            }
+
+            Ok(true)
+        })?;
+
+        Ok(options)
+    }
+}
+
+/// Keyword-only options to `ZstdDecompressor.decompress()`.
+struct DecompressOptions {
+    bound_fallback: bool,
+    streaming_fallback: bool,
+    read_across_frames: bool,
+    allow_extra_data: bool,
+}
+
+impl DecompressOptions {
+    fn from_kwargs(kwargs: Option<&PyDict>) -> PyResult<Self> {
+        let mut options = Self {
+            bound_fallback: false,
+            streaming_fallback: false,
+            read_across_frames: false,
+            allow_extra_data: true,
+        };
+
+        apply_kwargs("ZstdDecompressor.decompress()", kwargs, |key, value| {
+            match key {
+                "bound_fallback" => options.bound_fallback = value.extract()?,
+                "streaming_fallback" => options.streaming_fallback = value.extract()?,
+                "read_across_frames" => options.read_across_frames = value.extract()?,
+                "allow_extra_data" => options.allow_extra_data = value.extract()?,
+                _ => return Ok(false),
+            }
+
+            Ok(true)
+        })?;
+
+        Ok(options)
+    }
+}
+
+/// Keyword-only options to `ZstdDecompressor.read_to_iter()`.
+struct ReadToIterOptions<'p> {
+    as_memoryview: bool,
+    on_error: &'p str,
+}
+
+impl<'p> ReadToIterOptions<'p> {
+    fn from_kwargs(kwargs: Option<&'p PyDict>) -> PyResult<Self> {
+        let mut options = Self {
+            as_memoryview: false,
+            on_error: "raise",
+        };
+
+        apply_kwargs("ZstdDecompressor.read_to_iter()", kwargs, |key, value| {
+            match key {
+                "as_memoryview" => options.as_memoryview = value.extract()?,
+                "on_error" => options.on_error = value.extract()?,
+                _ => return Ok(false),
+            }
+
+            Ok(true)
+        })?;
+
+        Ok(options)
+    }
+}
+
+/// Keyword-only options to `ZstdDecompressor.stream_reader()`.
+#[derive(Default)]
+struct StreamReaderOptions {
+    require_checksum: Option<bool>,
+    max_decompress_per_call: Option<usize>,
+}
+
+impl StreamReaderOptions {
+    fn from_kwargs(kwargs: Option<&PyDict>) -> PyResult<Self> {
+        let mut options = Self::default();
+
+        apply_kwargs("ZstdDecompressor.stream_reader()", kwargs, |key, value| {
+            match key {
+                "require_checksum" => options.require_checksum = value.extract()?,
+                "max_decompress_per_call" => options.max_decompress_per_call = value.extract()?,
+                _ => return Ok(false),
+            }
+
+            Ok(true)
+        })?;
+
+        Ok(options)
+    }
+}
+
+/// Keyword-only options to `ZstdDecompressor.stream_writer()`.
+#[derive(Default)]
+struct StreamWriterOptions {
+    direct_fd: bool,
+    max_window_size: Option<usize>,
+    format: Option<u32>,
+}
+
+impl StreamWriterOptions {
+    fn from_kwargs(kwargs: Option<&PyDict>) -> PyResult<Self> {
+        let mut options = Self::default();
+
+        apply_kwargs("ZstdDecompressor.stream_writer()", kwargs, |key, value| {
+            match key {
+                "direct_fd" => options.direct_fd = value.extract()?,
+                "max_window_size" => options.max_window_size = value.extract()?,
+                "format" => options.format = value.extract()?,
+                _ => return Ok(false),
+            }
+
+            Ok(true)
+        })?;
+
+        Ok(options)
+    }
+}
+
 #[pyclass(module = "zstandard.backend_rust")]
 struct ZstdDecompressor {
     dict_data: Option<Py<ZstdCompressionDict>>,
+    /// Dictionaries to select from by frame dict ID when `dict_data` was a
+    /// list.
+    dicts: Vec<Py<ZstdCompressionDict>>,
     max_window_size: usize,
     format: zstd_sys::ZSTD_format_e,
+    require_checksum: bool,
+    /// Whether to skip verification of frame content checksums.
+    ignore_checksum: bool,
+    /// Whether to select dictionaries from `dict_registry` by frame dict ID.
+    use_dict_registry: bool,
+    /// Whether to record the window size of decoded frames.
+    track_window_usage: bool,
+    /// Whether to decode every frame with `dict_data` whatever its dict ID.
+    ignore_dict_id: bool,
     dctx: Arc<DCtx<'static>>,
 }
 
 impl ZstdDecompressor {
+    fn create(
+        py: Python,
+        dict_data: Option<&PyAny>,
+        max_window_size: usize,
+        format: u32,
+        options: DecompressorOptions,
+    ) -> PyResult<Self> {
+        let DecompressorOptions {
+            require_checksum,
+            use_dict_registry,
+            track_window_usage,
+            ignore_dict_id,
+            ignore_checksum,
+        } = options;
+
+        if dict_data.is_some() && use_dict_registry {
+            return Err(PyValueError::new_err(
+                "cannot specify both dict_data and use_dict_registry",
+            ));
+        }
+
+        if ignore_dict_id && dict_data.is_none() {
+            return Err(PyValueError::new_err("ignore_dict_id requires dict_data"));
+        }
+
+        // A list of dictionaries is selected from by frame dict ID.
+        let (dict_data, dicts) = match dict_data {
+            Some(value) if value.is_instance::<PyList>()? || value.is_instance::<PyTuple>()? => {
+                if ignore_dict_id {
+                    return Err(PyValueError::new_err(
+                        "ignore_dict_id requires a single dictionary",
+                    ));
+                }
+
+                let dicts = value
+                    .extract::<Vec<Py<ZstdCompressionDict>>>()
+                    .map_err(|_| {
+                        PyTypeError::new_err(
+                            "dict_data items must be ZstdCompressionDict instances",
+                        )
+                    })?;
+
+                if dicts.is_empty() {
+                    return Err(PyValueError::new_err("dict_data must not be empty"));
+                }
+
+                let mut dict_ids = HashSet::new();
+                for dict in &dicts {
+                    let dict_id = dict.borrow(py).dict_id();
+
+                    if dict_id == 0 {
+                        return Err(PyValueError::new_err(
+                            "dictionaries in a list must have a dictionary ID",
+                        ));
+                    }
+                    if !dict_ids.insert(dict_id) {
+                        return Err(PyValueError::new_err(format!(
+                            "multiple dictionaries have dictionary ID {}",
+                            dict_id
+                        )));
+                    }
+                }
+
+                (None, dicts)
+            }
+            Some(value) => (Some(value.extract::<Py<ZstdCompressionDict>>()?), vec![]),
+            None => (None, vec![]),
+        };
+
+        if require_checksum && ignore_checksum {
+            return Err(PyValueError::new_err(
+                "cannot specify both require_checksum and ignore_checksum",
+            ));
+        }
+
+        let format = format_from_int(format)?;
+
+        let dctx = Arc::new(DCtx::new().map_err(|_| PyMemoryError::new_err(()))?);
+        dctx.set_track_window_usage(track_window_usage);
+
+        Ok(Self {
+            dict_data,
+            dicts,
+            max_window_size,
+            format,
+            require_checksum,
+            ignore_checksum,
+            use_dict_registry,
+            track_window_usage,
+            ignore_dict_id,
+            dctx,
+        })
+    }
+
     fn setup_dctx(&self, py: Python, load_dict: bool) -> PyResult<()> {
+        self.setup_dctx_with(py, load_dict, self.max_window_size, self.format)
+    }
+
+    /// Like `setup_dctx()` but with the window size and format overridden.
+    ///
+    /// Both are applied unconditionally since a session reset retains
+    /// parameters, so values from a previous operation would otherwise leak.
+    fn setup_dctx_with(
+        &self,
+        py: Python,
+        load_dict: bool,
+        max_window_size: usize,
+        format: zstd_sys::ZSTD_format_e,
+    ) -> PyResult<()> {
         self.dctx.reset().map_err(|msg| {
             ZstdError::new_err(format!("unable to reset decompression context: {}", msg))
         })?;
 
-        if self.max_window_size != 0 {
-            self.dctx
-                .set_max_window_size(self.max_window_size)
-                .map_err(|msg| {
-                    ZstdError::new_err(format!("unable to set max window size: {}", msg))
-                })?;
-        }
+        self.dctx
+            .set_max_window_size(max_window_size)
+            .map_err(|msg| ZstdError::new_err(format!("unable to set max window size: {}", msg)))?;
 
         self.dctx
-            .set_format(self.format)
+            .set_format(format)
             .map_err(|msg| ZstdError::new_err(format!("unable to set decoding format: {}", msg)))?;
 
+        self.dctx
+            .set_ignore_checksum(self.ignore_checksum)
+            .map_err(|msg| ZstdError::new_err(format!("unable to set ignore checksum: {}", msg)))?;
+
+        self.dctx.set_require_checksum(self.require_checksum);
+        self.dctx.set_track_headers(false);
+        self.dctx.set_track_window_usage(self.track_window_usage);
+        self.dctx.set_ignore_dict_id(self.ignore_dict_id);
+        self.dctx.set_dict_resolver(if self.use_dict_registry {
+            Some(Arc::new(crate::dict_registry::resolve))
+        } else if !self.dicts.is_empty() {
+            Some(self.dicts_resolver(py)?)
+        } else {
+            None
+        });
+
         if let Some(dict_data) = &self.dict_data {
             if load_dict {
-                dict_data.try_borrow_mut(py)?.load_into_dctx(&self.dctx)?;
+                dict_data.try_borrow(py)?.load_into_dctx(&self.dctx)?;
             }
         }
 
         Ok(())
     }
+
+    /// Build a resolver selecting among `dicts` by dictionary ID.
+    ///
+    /// Dictionaries are prepared up front so frames can be resolved without
+    /// the GIL.
+    fn dicts_resolver(&self, py: Python) -> PyResult<DictResolver> {
+        let mut prepared = Vec::with_capacity(self.dicts.len());
+        for dict in &self.dicts {
+            let dict = dict.try_borrow(py)?;
+            // Pointers are stored as integers since raw pointers aren't Send.
+            prepared.push((dict.dict_id(), dict.prepared_ddict()? as usize));
+        }
+
+        let owner: Arc<dyn Any + Send + Sync> = Arc::new(
+            self.dicts
+                .iter()
+                .map(|dict| dict.clone_ref(py))
+                .collect::<Vec<_>>(),
+        );
+
+        Ok(Arc::new(move |dict_id| {
+            prepared
+                .iter()
+                .find(|(id, _)| *id == dict_id)
+                .map(|(_, ptr)| (*ptr as *const zstd_sys::ZSTD_DDict, owner.clone()))
+                .ok_or("no dictionary matches the frame's dictionary ID")
+        }))
+    }
+
+    /// Resolve an `on_error` argument.
+    ///
+    /// Skipping resumes at frame magic numbers, so it needs frames to have
+    /// them.
+    fn resolve_on_error(&self, on_error: &str) -> PyResult<OnError> {
+        let on_error = OnError::parse(on_error)?;
+
+        if on_error == OnError::Skip
+            && self.format == zstd_sys::ZSTD_format_e::ZSTD_f_zstd1_magicless
+        {
+            return Err(PyValueError::new_err(
+                "on_error=\"skip\" cannot be used with FORMAT_ZSTD1_MAGICLESS",
+            ));
+        }
+
+        Ok(on_error)
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for ZstdDecompressor {
+    fn __repr__(&self) -> String {
+        let dict_id = Python::with_gil(|py| {
+            self.dict_data.as_ref().map_or("None".to_string(), |dict| {
+                dict.borrow(py).dict_id().to_string()
+            })
+        });
+
+        format!(
+            "ZstdDecompressor(dict_id={}, max_window_size={}, format={})",
+            dict_id, self.max_window_size, self.format as u32
+        )
+    }
 }
 
 #[pymethods]
 impl ZstdDecompressor {
     #[new]
-    #[args(dict_data = "None", max_window_size = "0", format = "0")]
+    #[args(dict_data = "None", max_window_size = "0", format = "0", kwargs = "**")]
     fn new(
-        dict_data: Option<Py<ZstdCompressionDict>>,
+        py: Python,
+        dict_data: Option<&PyAny>,
         max_window_size: usize,
         format: u32,
+        kwargs: Option<&PyDict>,
     ) -> PyResult<Self> {
-        let format = if format == zstd_sys::ZSTD_format_e::ZSTD_f_zstd1 as _ {
-            zstd_sys::ZSTD_format_e::ZSTD_f_zstd1
-        } else if format == zstd_sys::ZSTD_format_e::ZSTD_f_zstd1_magicless as _ {
-            zstd_sys::ZSTD_format_e::ZSTD_f_zstd1_magicless
-        } else {
-            return Err(PyValueError::new_err(format!("invalid format value")));
+        Self::create(
+            py,
+            dict_data,
+            max_window_size,
+            format,
+            DecompressorOptions::from_kwargs(kwargs)?,
+        )
+    }
+
+    /// Serialize the settings of this decompressor.
+    ///
+    /// The dictionary is referenced by its ID and isn't serialized.
+    fn to_config<'p>(&self, py: Python<'p>) -> PyResult<&'p PyAny> {
+        if !self.dicts.is_empty() {
+            return Err(PyValueError::new_err(
+                "cannot serialize a decompressor with multiple dictionaries",
+            ));
+        }
+
+        let config = PyDict::new(py);
+        config.set_item("max_window_size", self.max_window_size)?;
+        config.set_item("format", self.format as u32)?;
+        config.set_item("require_checksum", self.require_checksum)?;
+        config.set_item("use_dict_registry", self.use_dict_registry)?;
+        config.set_item("track_window_usage", self.track_window_usage)?;
+        config.set_item("ignore_dict_id", self.ignore_dict_id)?;
+        config.set_item("ignore_checksum", self.ignore_checksum)?;
+
+        dump_config(py, "decompressor", config, &self.dict_data)
+    }
+
+    /// Construct an instance from the output of `to_config()`.
+    #[classmethod]
+    #[args(config, dict_data = "None")]
+    fn from_config(
+        _cls: &PyType,
+        py: Python,
+        config: &PyAny,
+        dict_data: Option<Py<ZstdCompressionDict>>,
+    ) -> PyResult<Self> {
+        let config = load_config(py, "decompressor", config, &dict_data)?;
+
+        let max_window_size = match config.get_item("max_window_size") {
+            Some(value) => value
+                .extract::<usize>()
+                .map_err(|_| PyValueError::new_err("max_window_size must be an integer"))?,
+            None => 0,
+        };
+        let format = match config.get_item("format") {
+            Some(value) => value
+                .extract::<u32>()
+                .map_err(|_| PyValueError::new_err("format must be an integer"))?,
+            None => 0,
         };
 
-        let dctx = Arc::new(DCtx::new().map_err(|_| PyMemoryError::new_err(()))?);
+        let require_checksum = match config.get_item("require_checksum") {
+            Some(value) => value
+                .extract::<bool>()
+                .map_err(|_| PyValueError::new_err("require_checksum must be a boolean"))?,
+            None => false,
+        };
 
-        Ok(Self {
-            dict_data,
+        let use_dict_registry = match config.get_item("use_dict_registry") {
+            Some(value) => value
+                .extract::<bool>()
+                .map_err(|_| PyValueError::new_err("use_dict_registry must be a boolean"))?,
+            None => false,
+        };
+
+        let track_window_usage = match config.get_item("track_window_usage") {
+            Some(value) => value
+                .extract::<bool>()
+                .map_err(|_| PyValueError::new_err("track_window_usage must be a boolean"))?,
+            None => false,
+        };
+
+        let ignore_dict_id = match config.get_item("ignore_dict_id") {
+            Some(value) => value
+                .extract::<bool>()
+                .map_err(|_| PyValueError::new_err("ignore_dict_id must be a boolean"))?,
+            None => false,
+        };
+
+        let ignore_checksum = match config.get_item("ignore_checksum") {
+            Some(value) => value
+                .extract::<bool>()
+                .map_err(|_| PyValueError::new_err("ignore_checksum must be a boolean"))?,
+            None => false,
+        };
+
+        Self::create(
+            py,
+            dict_data.as_ref().map(|dict| dict.as_ref(py).as_ref()),
             max_window_size,
             format,
-            dctx,
-        })
+            DecompressorOptions {
+                require_checksum,
+                use_dict_registry,
+                track_window_usage,
+                ignore_dict_id,
+                ignore_checksum,
+            },
+        )
+    }
+
+    /// Base 2 log of the largest window size frames may use.
+    ///
+    /// 0 means zstd's default limit. Takes effect from the next operation.
+    #[getter]
+    fn max_window_log(&self) -> u32 {
+        window_log_for_size(self.max_window_size)
+    }
+
+    #[setter]
+    fn set_max_window_log(&mut self, value: u32) -> PyResult<()> {
+        if value != 0 {
+            let (lower, upper) = window_log_max_bounds().map_err(|msg| {
+                ZstdError::new_err(format!("unable to get window log bounds: {}", msg))
+            })?;
+
+            if value < lower || value > upper {
+                return Err(PyValueError::new_err(format!(
+                    "max_window_log must be between {} and {}; got {}",
+                    lower, upper, value
+                )));
+            }
+        }
+
+        self.max_window_size = if value == 0 { 0 } else { 1 << value };
+
+        Ok(())
+    }
+
+    /// Describe the window sizes required by frames decoded so far.
+    ///
+    /// Requires `track_window_usage=True`. The largest window size any frame
+    /// declared is reported next to the limit frames are decoded under, to
+    /// help choose `max_window_size`. With `reset`, statistics start over.
+    #[args(reset = "false")]
+    fn window_usage<'p>(&self, py: Python<'p>, reset: bool) -> PyResult<&'p PyDict> {
+        let usage = self.dctx.window_usage(reset).ok_or_else(|| {
+            ZstdError::new_err("window usage is not tracked; pass track_window_usage=True")
+        })?;
+
+        let limit = if self.max_window_size != 0 {
+            self.max_window_size as u64
+        } else {
+            1 << zstd_sys::ZSTD_WINDOWLOG_LIMIT_DEFAULT
+        };
+
+        let result = PyDict::new(py);
+        result.set_item("frames", usage.frames)?;
+        result.set_item("max_window_size", usage.max_window_size)?;
+        result.set_item("window_size_limit", limit)?;
+
+        Ok(result)
     }
 
-    #[args(ifh, ofh, read_size = "None", write_size = "None")]
+    #[args(ifh, ofh, read_size = "None", write_size = "None", kwargs = "**")]
     fn copy_stream(
         &self,
-        py: Python,
         ifh: &PyAny,
         ofh: &PyAny,
         read_size: Option<usize>,
         write_size: Option<usize>,
-    ) -> PyResult<(usize, usize)> {
+        kwargs: Option<&PyDict>,
+    ) -> PyResult<CopyStreamResult> {
+        let py = ifh.py();
+        let CopyStreamOptions {
+            hasher,
+            max_time_ms,
+            resume,
+        } = CopyStreamOptions::from_kwargs("ZstdDecompressor.copy_stream()", kwargs)?;
+
         let read_size = read_size.unwrap_or_else(|| zstd_safe::dstream_in_size());
         let write_size = write_size.unwrap_or_else(|| zstd_safe::dstream_out_size());
 
@@ -103,13 +615,42 @@ impl ZstdDecompressor {
             ));
         }
 
-        if !ofh.hasattr("write")? {
+        // Decompressed output can be fanned out to multiple writers.
+        let writers = if ofh.hasattr("write")? {
+            vec![ofh]
+        } else if ofh.is_instance::<PyList>()? || ofh.is_instance::<PyTuple>()? {
+            let writers = ofh.iter()?.collect::<PyResult<Vec<_>>>()?;
+
+            if writers.is_empty() {
+                return Err(PyValueError::new_err(
+                    "second argument must contain at least 1 writer",
+                ));
+            }
+
+            for (i, writer) in writers.iter().enumerate() {
+                if !writer.hasattr("write")? {
+                    return Err(PyValueError::new_err(format!(
+                        "writer {} must have a write() method",
+                        i
+                    )));
+                }
+            }
+
+            writers
+        } else {
             return Err(PyValueError::new_err(
                 "second argument must have a write() method",
             ));
+        };
+
+        // Resuming continues the stream left open by a previous call that ran
+        // out of time.
+        if !resume {
+            self.setup_dctx(py, true)?;
         }
 
-        self.setup_dctx(py, true)?;
+        // Headers are parsed to report on the checksums of decoded frames.
+        self.dctx.set_track_headers(true);
 
         let mut dest_buffer: Vec<u8> = Vec::with_capacity(write_size);
 
@@ -119,8 +660,11 @@ impl ZstdDecompressor {
             pos: 0,
         };
 
+        let deadline = CopyDeadline::new(max_time_ms);
         let mut total_read = 0;
         let mut total_write = 0;
+        let mut frames = 0;
+        let mut checksum = true;
 
         // Read all available input.
         loop {
@@ -140,60 +684,185 @@ impl ZstdDecompressor {
 
             // Flush all read data to output.
             while in_buffer.pos < in_buffer.size {
-                self.dctx
+                let zresult = self
+                    .dctx
                     .decompress_into_vec(&mut dest_buffer, &mut in_buffer)
                     .map_err(|msg| ZstdError::new_err(format!("zstd decompress error: {}", msg)))?;
 
+                if zresult == 0 {
+                    if let Some(header) = self.dctx.last_frame_header() {
+                        if header.frameType == zstd_sys::ZSTD_frameType_e::ZSTD_frame {
+                            frames += 1;
+                            checksum &= header.checksumFlag != 0;
+                        }
+                    }
+                }
+
                 if !dest_buffer.is_empty() {
                     // TODO avoid buffer copy.
                     let data = PyBytes::new(py, &dest_buffer);
 
-                    ofh.call_method1("write", (data,))?;
+                    for writer in &writers {
+                        writer.call_method1("write", (data,))?;
+                    }
+                    if let Some(hasher) = hasher {
+                        hasher.call_method1("update", (data,))?;
+                    }
                     total_write += dest_buffer.len();
                     dest_buffer.clear();
                 }
             }
-            // Continue loop to keep reading.
+
+            // Yield back to the caller between reads once out of time. At
+            // least one read is processed per call so resuming progresses.
+            if deadline.expired() {
+                return copy_stream_result(
+                    py,
+                    total_read,
+                    total_write,
+                    (frames, checksum),
+                    hasher,
+                    &deadline,
+                    false,
+                );
+            }
         }
 
-        Ok((total_read, total_write))
+        copy_stream_result(
+            py,
+            total_read,
+            total_write,
+            (frames, checksum),
+            hasher,
+            &deadline,
+            true,
+        )
     }
 
-    #[args(buffer, max_output_size = "0")]
+    /// With `read_across_frames`, every frame in `buffer` is decompressed and
+    /// the output concatenated. Otherwise only the first frame is and any
+    /// data after it is an error unless `allow_extra_data`.
+    #[args(buffer, max_output_size = "0", kwargs = "**")]
     fn decompress<'p>(
         &mut self,
         py: Python<'p>,
         buffer: PyBuffer<u8>,
         max_output_size: usize,
+        kwargs: Option<&PyDict>,
     ) -> PyResult<&'p PyBytes> {
+        let DecompressOptions {
+            bound_fallback,
+            streaming_fallback,
+            read_across_frames,
+            allow_extra_data,
+        } = DecompressOptions::from_kwargs(kwargs)?;
+
         self.setup_dctx(py, true)?;
 
-        let output_size =
-            unsafe { zstd_sys::ZSTD_getFrameContentSize(buffer.buf_ptr(), buffer.len_bytes()) };
+        // Whether output size is unknown and the buffer grows without limit.
+        let mut unbounded = false;
 
-        let (output_buffer_size, output_size) =
-            if output_size == zstd_sys::ZSTD_CONTENTSIZE_ERROR as _ {
-                return Err(ZstdError::new_err(
-                    "error determining content size from frame header",
-                ));
-            } else if output_size == 0 {
-                return Ok(PyBytes::new(py, &[]));
-            } else if output_size == zstd_sys::ZSTD_CONTENTSIZE_UNKNOWN as _ {
-                if max_output_size == 0 {
+        // Input that can contribute output: the first frame, unless reading
+        // across frames. Errors finding the frame are reported by
+        // decompression.
+        let frame_size = if read_across_frames {
+            buffer.len_bytes()
+        } else {
+            let size = unsafe {
+                zstd_sys::ZSTD_findFrameCompressedSize(buffer.buf_ptr(), buffer.len_bytes())
+            };
+
+            if unsafe { zstd_sys::ZSTD_isError(size) } != 0 {
+                buffer.len_bytes()
+            } else {
+                size
+            }
+        };
+
+        if !read_across_frames && !allow_extra_data && frame_size < buffer.len_bytes() {
+            return Err(ZstdError::new_err(format!(
+                "compressed input contains {} bytes of unused data at offset {}, \
+                 which is disallowed",
+                buffer.len_bytes() - frame_size,
+                frame_size
+            )));
+        }
+
+        let output_size = if read_across_frames {
+            unsafe { zstd_sys::ZSTD_findDecompressedSize(buffer.buf_ptr(), buffer.len_bytes()) }
+        } else {
+            unsafe { zstd_sys::ZSTD_getFrameContentSize(buffer.buf_ptr(), buffer.len_bytes()) }
+        };
+
+        let (output_buffer_size, output_size) = if output_size
+            == zstd_sys::ZSTD_CONTENTSIZE_ERROR as _
+        {
+            return Err(ZstdError::new_err(
+                "error determining content size from frame header",
+            ));
+        } else if output_size == 0 {
+            return Ok(PyBytes::new(py, &[]));
+        } else if output_size == zstd_sys::ZSTD_CONTENTSIZE_UNKNOWN as _ {
+            if max_output_size != 0 {
+                (max_output_size, 0)
+            } else if bound_fallback {
+                // The bound is derived from the block structure, so the
+                // frame must be complete for it to be known.
+                let bound = unsafe { zstd_sys::ZSTD_decompressBound(buffer.buf_ptr(), frame_size) };
+
+                if bound == zstd_sys::ZSTD_CONTENTSIZE_ERROR as _ {
                     return Err(ZstdError::new_err(
-                        "could not determine content size in frame header",
+                        "could not determine content size in frame header or bound from frame",
                     ));
+                } else if bound > DECOMPRESS_BOUND_FALLBACK_MAX {
+                    return Err(ZstdError::new_err(format!(
+                        "frame may decompress to {} bytes, exceeding the {} byte fallback limit; \
+                         specify max_output_size",
+                        bound, DECOMPRESS_BOUND_FALLBACK_MAX
+                    )));
                 }
 
-                (max_output_size, 0)
+                (bound as usize, 0)
+            } else if streaming_fallback {
+                unbounded = true;
+
+                // Start from a typical compression ratio and grow from there.
+                let size = std::cmp::max(
+                    buffer.len_bytes().saturating_mul(4),
+                    zstd_safe::dstream_out_size(),
+                );
+
+                (std::cmp::min(size, DECOMPRESS_PREALLOCATE_MAX), 0)
             } else {
-                (output_size as _, output_size)
-            };
+                return Err(ZstdError::new_err(
+                    "could not determine content size in frame header",
+                ));
+            }
+        } else {
+            // The header could be lying about the content size. Cross-check
+            // it against the frame structure and don't trust it for more
+            // than a bounded up-front allocation.
+            let bound = unsafe { zstd_sys::ZSTD_decompressBound(buffer.buf_ptr(), frame_size) };
 
-        let mut dest_buffer: Vec<u8> = Vec::new();
-        dest_buffer
-            .try_reserve_exact(output_buffer_size)
-            .map_err(|_| PyMemoryError::new_err(()))?;
+            if bound == zstd_sys::ZSTD_CONTENTSIZE_ERROR as _ {
+                return Err(ZstdError::new_err(
+                    "decompression error: input is not a valid or complete zstd frame",
+                ));
+            } else if output_size > bound {
+                return Err(ZstdError::new_err(format!(
+                        "decompression error: frame declares {} bytes of content but can produce at most {}",
+                        output_size, bound
+                    )));
+            }
+
+            (
+                std::cmp::min(output_size, DECOMPRESS_PREALLOCATE_MAX as _) as _,
+                output_size,
+            )
+        };
+
+        // Output goes directly into the returned bytes.
+        let mut dest_buffer = BytesBuilder::with_capacity(py, output_buffer_size)?;
 
         let mut in_buffer = zstd_sys::ZSTD_inBuffer {
             src: buffer.buf_ptr(),
@@ -201,10 +870,40 @@ impl ZstdDecompressor {
             pos: 0,
         };
 
-        let zresult = self
-            .dctx
-            .decompress_into_vec(&mut dest_buffer, &mut in_buffer)
-            .map_err(|msg| ZstdError::new_err(format!("decompression error: {}", msg)))?;
+        let zresult = loop {
+            let mut out_buffer = dest_buffer.out_buffer();
+            let zresult = self
+                .dctx
+                .decompress_buffers(&mut out_buffer, &mut in_buffer)
+                .map_err(|msg| ZstdError::new_err(format!("decompression error: {}", msg)))?;
+            dest_buffer.set_len(out_buffer.pos);
+
+            // Another frame follows the one just finished.
+            let next_frame = zresult == 0 && in_buffer.pos < in_buffer.size;
+
+            // Grow the buffer towards the declared size as output materializes.
+            if (zresult != 0 || (read_across_frames && next_frame))
+                && dest_buffer.len() == dest_buffer.capacity()
+                && (unbounded || (dest_buffer.len() as u64) < output_size)
+            {
+                let additional = if unbounded {
+                    dest_buffer.capacity()
+                } else {
+                    std::cmp::min(
+                        output_size as usize - dest_buffer.len(),
+                        dest_buffer.capacity(),
+                    )
+                };
+                dest_buffer.reserve_exact(py, additional)?;
+                continue;
+            }
+
+            if next_frame && read_across_frames {
+                continue;
+            }
+
+            break zresult;
+        };
 
         if zresult != 0 {
             Err(ZstdError::new_err(
@@ -216,8 +915,218 @@ impl ZstdDecompressor {
                 zresult, output_size
             )))
         } else {
-            // TODO avoid memory copy
-            Ok(PyBytes::new(py, &dest_buffer))
+            dest_buffer.finish(py)
+        }
+    }
+
+    /// Decompress a frame directly into a writable buffer such as an array.
+    ///
+    /// The frame must declare its content size, which must fit in `dest` and
+    /// be a whole number of `dest` items. Returns the number of items written.
+    ///
+    /// The GIL is released while decompressing.
+    #[args(source, dest)]
+    fn decompress_into(&mut self, py: Python, source: ByteBuffer, dest: &PyAny) -> PyResult<usize> {
+        let dest = ByteBuffer::get_writable(dest)?;
+
+        self.setup_dctx(py, true)?;
+
+        let source = source.as_slice();
+
+        let content_size = unsafe {
+            zstd_sys::ZSTD_getFrameContentSize(source.as_ptr() as *const _, source.len())
+        };
+
+        if content_size == zstd_sys::ZSTD_CONTENTSIZE_ERROR as _ {
+            return Err(ZstdError::new_err(
+                "error determining content size from frame header",
+            ));
+        } else if content_size == zstd_sys::ZSTD_CONTENTSIZE_UNKNOWN as _ {
+            return Err(ZstdError::new_err(
+                "could not determine content size in frame header",
+            ));
+        } else if content_size > dest.len_bytes() as _ {
+            return Err(PyValueError::new_err(format!(
+                "destination buffer too small: frame content is {} bytes; buffer is {} bytes",
+                content_size,
+                dest.len_bytes()
+            )));
+        } else if content_size % dest.item_size() as u64 != 0 {
+            return Err(PyValueError::new_err(format!(
+                "frame content size {} is not a multiple of the destination item size {}",
+                content_size,
+                dest.item_size()
+            )));
+        }
+
+        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+            src: source.as_ptr() as *const _,
+            size: source.len(),
+            pos: 0,
+        };
+        let mut out_buffer = zstd_sys::ZSTD_outBuffer {
+            dst: dest.as_mut_ptr() as *mut _,
+            size: content_size as _,
+            pos: 0,
+        };
+
+        let zresult = loop {
+            let (in_pos, out_pos) = (in_buffer.pos, out_buffer.pos);
+
+            let zresult = self
+                .dctx
+                .decompress_buffers_allow_threads(py, &mut out_buffer, &mut in_buffer)
+                .map_err(|msg| ZstdError::new_err(format!("decompression error: {}", msg)))?;
+
+            // Stop once the frame ends or no further progress can be made.
+            if zresult == 0 || (in_buffer.pos == in_pos && out_buffer.pos == out_pos) {
+                break zresult;
+            }
+        };
+
+        if zresult != 0 {
+            Err(ZstdError::new_err(
+                "decompression error: did not decompress full frame",
+            ))
+        } else if out_buffer.pos as u64 != content_size {
+            Err(ZstdError::new_err(format!(
+                "decompression error: decompressed {} bytes; expected {}",
+                out_buffer.pos, content_size
+            )))
+        } else {
+            Ok(out_buffer.pos / dest.item_size())
+        }
+    }
+
+    /// Decompress a patch from `ZstdCompressor.compress_patch()`, or
+    /// `zstd --patch-from`, against the `old_data` it was created from.
+    ///
+    /// Unless `max_window_size` was given, the window limit is raised to
+    /// cover `old_data` and the output. Dictionaries aren't used. The frame
+    /// must declare its content size.
+    ///
+    /// The GIL is released while decompressing.
+    #[args(old_data, patch)]
+    fn decompress_patch<'p>(
+        &self,
+        py: Python<'p>,
+        old_data: ByteBuffer,
+        patch: ByteBuffer,
+    ) -> PyResult<&'p PyBytes> {
+        let old = old_data.as_slice();
+        let source = patch.as_slice();
+
+        let content_size = unsafe {
+            zstd_sys::ZSTD_getFrameContentSize(source.as_ptr() as *const _, source.len())
+        };
+
+        if content_size == zstd_sys::ZSTD_CONTENTSIZE_ERROR as _ {
+            return Err(ZstdError::new_err(
+                "error determining content size from frame header",
+            ));
+        } else if content_size == zstd_sys::ZSTD_CONTENTSIZE_UNKNOWN as _ {
+            return Err(ZstdError::new_err(
+                "could not determine content size in frame header",
+            ));
+        }
+
+        let bound =
+            unsafe { zstd_sys::ZSTD_decompressBound(source.as_ptr() as *const _, source.len()) };
+
+        if bound == zstd_sys::ZSTD_CONTENTSIZE_ERROR as _ {
+            return Err(ZstdError::new_err(
+                "decompression error: input is not a valid or complete zstd frame",
+            ));
+        } else if content_size > bound {
+            return Err(ZstdError::new_err(format!(
+                "decompression error: frame declares {} bytes of content but can produce at most {}",
+                content_size, bound
+            )));
+        }
+
+        let max_window_size = if self.max_window_size == 0 {
+            let (_, max_log) = window_log_max_bounds().map_err(|msg| {
+                ZstdError::new_err(format!("unable to set max window size: {}", msg))
+            })?;
+
+            std::cmp::min(
+                std::cmp::max(
+                    patch_window_size(old.len(), content_size),
+                    1 << zstd_sys::ZSTD_WINDOWLOG_LIMIT_DEFAULT,
+                ),
+                1 << max_log,
+            )
+        } else {
+            self.max_window_size
+        };
+
+        self.setup_dctx_with(py, false, max_window_size, self.format)?;
+        self.dctx.set_dict_resolver(None);
+        self.dctx
+            .ref_prefix(old)
+            .map_err(|msg| ZstdError::new_err(format!("could not reference prefix: {}", msg)))?;
+
+        // Output goes directly into the returned bytes.
+        let mut dest_buffer = BytesBuilder::with_capacity(
+            py,
+            std::cmp::min(content_size, DECOMPRESS_PREALLOCATE_MAX as _) as _,
+        )?;
+
+        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+            src: source.as_ptr() as *const _,
+            size: source.len(),
+            pos: 0,
+        };
+
+        let result = loop {
+            let (in_pos, out_len) = (in_buffer.pos, dest_buffer.len());
+
+            let mut out_buffer = dest_buffer.out_buffer();
+            let zresult =
+                self.dctx
+                    .decompress_buffers_allow_threads(py, &mut out_buffer, &mut in_buffer);
+            dest_buffer.set_len(out_buffer.pos);
+
+            match zresult {
+                Ok(0) => break Ok(0),
+                Ok(zresult) => {
+                    if dest_buffer.len() == dest_buffer.capacity()
+                        && (dest_buffer.len() as u64) < content_size
+                    {
+                        let additional = std::cmp::min(
+                            content_size as usize - dest_buffer.len(),
+                            dest_buffer.capacity(),
+                        );
+                        if let Err(err) = dest_buffer.reserve_exact(py, additional) {
+                            break Err(err);
+                        }
+                    } else if in_buffer.pos == in_pos && dest_buffer.len() == out_len {
+                        break Ok(zresult);
+                    }
+                }
+                Err(msg) => break Err(ZstdError::new_err(format!("decompression error: {}", msg))),
+            }
+        };
+
+        // Don't leave a reference to `old_data` if the frame didn't start.
+        self.dctx.clear_dict().map_err(|msg| {
+            ZstdError::new_err(format!("could not clear decompression dictionary: {}", msg))
+        })?;
+
+        let zresult = result?;
+
+        if zresult != 0 {
+            Err(ZstdError::new_err(
+                "decompression error: did not decompress full frame",
+            ))
+        } else if dest_buffer.len() as u64 != content_size {
+            Err(ZstdError::new_err(format!(
+                "decompression error: decompressed {} bytes; expected {}",
+                dest_buffer.len(),
+                content_size
+            )))
+        } else {
+            dest_buffer.finish(py)
         }
     }
 
@@ -356,11 +1265,29 @@ impl ZstdDecompressor {
         Ok(PyBytes::new(py, &last_buffer))
     }
 
-    #[args(write_size = "None")]
+    /// Iterate over the frames in `data`, yielding the content of each.
+    ///
+    /// Skippable frames are skipped.
+    fn iter_frames(&self, py: Python, data: PyBuffer<u8>) -> PyResult<ZstdFrameIterator> {
+        if self.format == zstd_sys::ZSTD_format_e::ZSTD_f_zstd1_magicless {
+            return Err(PyValueError::new_err(
+                "iter_frames() does not support magicless frames",
+            ));
+        }
+
+        self.setup_dctx(py, true)?;
+
+        Ok(ZstdFrameIterator::new(self.dctx.clone(), data))
+    }
+
+    /// With `tolerate_padding`, NUL bytes fed after the end of the frame are
+    /// added to `unused_data` instead of raising.
+    #[args(write_size = "None", "*", tolerate_padding = "false")]
     fn decompressobj(
         &self,
         py: Python,
         write_size: Option<usize>,
+        tolerate_padding: bool,
     ) -> PyResult<ZstdDecompressionObj> {
         if let Some(write_size) = write_size {
             if write_size < 1 {
@@ -368,18 +1295,26 @@ impl ZstdDecompressor {
             }
         }
 
-        let write_size = write_size.unwrap_or_else(|| zstd_safe::dstream_out_size());
+        let write_size = write_size.unwrap_or_else(zstd_safe::dstream_out_size);
 
         self.setup_dctx(py, true)?;
 
-        ZstdDecompressionObj::new(self.dctx.clone(), write_size)
+        ZstdDecompressionObj::new(self.dctx.clone(), write_size, tolerate_padding)
     }
 
     fn memory_size(&self) -> usize {
         self.dctx.memory_size()
     }
 
-    #[args(frames, decompressed_sizes = "None", threads = "0")]
+    /// When `out` is given, each frame is decompressed directly into `out` at
+    /// the corresponding entry of `offsets` and the total number of bytes
+    /// written is returned.
+    ///
+    /// With `on_error="skip"`, frames that fail to decompress don't raise.
+    /// A `(collection, errors)` tuple is returned, where failed frames have
+    /// an empty segment in `collection` and `errors` lists the
+    /// `(index, message)` of each.
+    #[args(frames, decompressed_sizes = "None", threads = "0", kwargs = "**")]
     #[allow(unused_variables)]
     fn multi_decompress_to_buffer(
         &self,
@@ -387,13 +1322,60 @@ impl ZstdDecompressor {
         frames: &PyAny,
         decompressed_sizes: Option<&PyAny>,
         threads: isize,
-    ) -> PyResult<ZstdBufferWithSegmentsCollection> {
+        kwargs: Option<&PyDict>,
+    ) -> PyResult<PyObject> {
+        let options = MultiDecompressOptions::from_kwargs(kwargs)?;
+
+        self.setup_dctx(py, true)?;
+
+        multi_decompress_to_buffer(py, &self.dctx, frames, decompressed_sizes, threads, options)
+    }
+
+    /// Like `multi_decompress_to_buffer()` but returns an awaitable.
+    ///
+    /// Decompression runs on a background thread, leaving the event loop
+    /// free. Must be called from a coroutine running in an event loop.
+    #[args(
+        frames,
+        decompressed_sizes = "None",
+        threads = "0",
+        on_error = "\"raise\""
+    )]
+    fn multi_decompress_to_buffer_async(
+        &self,
+        py: Python,
+        frames: &PyAny,
+        decompressed_sizes: Option<&PyAny>,
+        threads: isize,
+        on_error: &str,
+    ) -> PyResult<PyObject> {
+        let on_error = OnError::parse(on_error)?;
+
         self.setup_dctx(py, true)?;
 
-        multi_decompress_to_buffer(py, &self.dctx, frames, decompressed_sizes, threads)
+        multi_decompress_to_buffer_async(
+            py,
+            &self.dctx,
+            &self.dict_data,
+            frames,
+            decompressed_sizes,
+            threads,
+            on_error,
+        )
     }
 
-    #[args(reader, read_size = "None", write_size = "None", skip_bytes = "None")]
+    /// With `on_error="skip"`, frames that fail to decompress don't end
+    /// iteration. The error is recorded in the `errors` attribute of the
+    /// iterator and decompression resumes at the next frame magic number.
+    /// Every frame in the input is decompressed, not just the first. Output
+    /// already emitted for a damaged frame is not retracted.
+    #[args(
+        reader,
+        read_size = "None",
+        write_size = "None",
+        skip_bytes = "None",
+        kwargs = "**"
+    )]
     fn read_to_iter(
         &self,
         py: Python,
@@ -401,10 +1383,16 @@ impl ZstdDecompressor {
         read_size: Option<usize>,
         write_size: Option<usize>,
         skip_bytes: Option<usize>,
+        kwargs: Option<&PyDict>,
     ) -> PyResult<ZstdDecompressorIterator> {
+        let ReadToIterOptions {
+            as_memoryview,
+            on_error,
+        } = ReadToIterOptions::from_kwargs(kwargs)?;
+
         let read_size = read_size.unwrap_or_else(|| zstd_safe::dstream_in_size());
-        let write_size = write_size.unwrap_or_else(|| zstd_safe::dstream_out_size());
         let skip_bytes = skip_bytes.unwrap_or(0);
+        let on_error = self.resolve_on_error(on_error)?;
 
         if skip_bytes >= read_size {
             return Err(PyValueError::new_err(
@@ -412,29 +1400,96 @@ impl ZstdDecompressor {
             ));
         }
 
-        if !reader.hasattr("read")? && !reader.hasattr("__getitem__")? {
+        if !reader.hasattr("read")?
+            && !reader.hasattr("__getitem__")?
+            && !reader.is_callable()
+            && !reader.hasattr("__iter__")?
+        {
             return Err(PyValueError::new_err(
-                "must pass an object with a read() method or conforms to buffer protocol",
+                "must pass an object with a read() method, a callable returning chunks, an iterable of chunks, or conforms to buffer protocol",
             ));
         }
 
         self.setup_dctx(py, true)?;
 
         ZstdDecompressorIterator::new(
-            py,
             self.dctx.clone(),
             reader,
             read_size,
             write_size,
             skip_bytes,
+            as_memoryview,
+            on_error,
         )
     }
 
+    /// Like `read_to_iter()` but yields records of exactly `record_size` bytes.
+    ///
+    /// The final record is shorter if the decompressed size isn't a multiple
+    /// of `record_size`.
+    #[args(reader, record_size, read_size = "None")]
+    fn read_records(
+        &self,
+        py: Python,
+        reader: &PyAny,
+        record_size: usize,
+        read_size: Option<usize>,
+    ) -> PyResult<ZstdRecordIterator> {
+        if record_size == 0 {
+            return Err(PyValueError::new_err("record_size must be positive"));
+        }
+
+        let read_size = read_size.unwrap_or_else(zstd_safe::dstream_in_size);
+
+        if !reader.hasattr("read")?
+            && !reader.hasattr("__getitem__")?
+            && !reader.is_callable()
+            && !reader.hasattr("__iter__")?
+        {
+            return Err(PyValueError::new_err(
+                "must pass an object with a read() method, a callable returning chunks, an iterable of chunks, or conforms to buffer protocol",
+            ));
+        }
+
+        self.setup_dctx(py, true)?;
+
+        ZstdRecordIterator::new(py, self.dctx.clone(), reader, read_size, record_size)
+    }
+
+    /// Create a decoder for content arriving incrementally, e.g. from a socket.
+    ///
+    /// The decoder has its own decompression context, so many can be active
+    /// at once.
+    #[args(write_size = "None", multiple_frames = "true")]
+    fn streaming_decoder(
+        &self,
+        py: Python,
+        write_size: Option<usize>,
+        multiple_frames: bool,
+    ) -> PyResult<StreamingDecoder> {
+        let write_size = write_size.unwrap_or_else(|| zstd_safe::dstream_out_size());
+
+        if write_size < 1 {
+            return Err(PyValueError::new_err("write_size must be positive"));
+        }
+
+        self.setup_dctx(py, true)?;
+        let dctx = self.dctx.try_clone().map_err(ZstdError::new_err)?;
+
+        Ok(StreamingDecoder::new(
+            dctx,
+            self.dict_data.as_ref().map(|d| d.clone_ref(py)),
+            write_size,
+            multiple_frames,
+        ))
+    }
+
     #[args(
         source,
         read_size = "None",
         read_across_frames = "false",
-        closefd = "true"
+        closefd = "true",
+        kwargs = "**"
     )]
     fn stream_reader(
         &self,
@@ -443,11 +1498,29 @@ impl ZstdDecompressor {
         read_size: Option<usize>,
         read_across_frames: bool,
         closefd: bool,
+        kwargs: Option<&PyDict>,
     ) -> PyResult<ZstdDecompressionReader> {
+        let StreamReaderOptions {
+            require_checksum,
+            max_decompress_per_call,
+        } = StreamReaderOptions::from_kwargs(kwargs)?;
+
+        if max_decompress_per_call == Some(0) {
+            return Err(PyValueError::new_err(
+                "max_decompress_per_call must be positive",
+            ));
+        }
+
         let read_size = read_size.unwrap_or_else(|| zstd_safe::dstream_in_size());
+        let source = resolve_fd(py, source, "rb", closefd)?;
 
         self.setup_dctx(py, true)?;
 
+        // The reader can override the decompressor's checksum policy.
+        if let Some(require_checksum) = require_checksum {
+            self.dctx.set_require_checksum(require_checksum);
+        }
+
         ZstdDecompressionReader::new(
             py,
             self.dctx.clone(),
@@ -456,13 +1529,18 @@ impl ZstdDecompressor {
             read_across_frames,
             closefd,
         )
+        .map(|reader| match max_decompress_per_call {
+            Some(limit) => reader.with_max_decompress_per_call(limit),
+            None => reader,
+        })
     }
 
     #[args(
         writer,
         write_size = "None",
         write_return_read = "true",
-        closefd = "true"
+        closefd = "true",
+        kwargs = "**"
     )]
     fn stream_writer(
         &self,
@@ -471,10 +1549,28 @@ impl ZstdDecompressor {
         write_size: Option<usize>,
         write_return_read: bool,
         closefd: bool,
+        kwargs: Option<&PyDict>,
     ) -> PyResult<ZstdDecompressionWriter> {
+        let StreamWriterOptions {
+            direct_fd,
+            max_window_size,
+            format,
+        } = StreamWriterOptions::from_kwargs(kwargs)?;
+
         let write_size = write_size.unwrap_or_else(|| zstd_safe::dstream_out_size());
+        let writer = resolve_fd(py, writer, "wb", closefd)?;
 
-        self.setup_dctx(py, true)?;
+        let format = match format {
+            Some(format) => format_from_int(format)?,
+            None => self.format,
+        };
+
+        self.setup_dctx_with(
+            py,
+            true,
+            max_window_size.unwrap_or(self.max_window_size),
+            format,
+        )?;
 
         ZstdDecompressionWriter::new(
             py,
@@ -483,6 +1579,7 @@ impl ZstdDecompressor {
             write_size,
             write_return_read,
             closefd,
+            direct_fd,
         )
     }
 }
@@ -0,0 +1,129 @@
+// Copyright (c) 2021-present, Gregory Szorc
+// All rights reserved.
+//
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+use {
+    crate::{
+        buffers::BytesBuilder, constants::is_skippable_magic, exceptions::ZstdError,
+        zstd_safe::DCtx,
+    },
+    pyo3::{buffer::PyBuffer, prelude::*, PyIterProtocol},
+    std::sync::Arc,
+};
+
+/// Maximum size of the up-front allocation for a frame's declared content
+/// size. Larger outputs grow as they are produced.
+const FRAME_PREALLOCATE_MAX: usize = 16 * 1024 * 1024;
+
+/// Iterates over the frames in a buffer, yielding each frame's content.
+#[pyclass(module = "zstandard.backend_rust")]
+pub struct ZstdFrameIterator {
+    dctx: Arc<DCtx<'static>>,
+    data: PyBuffer<u8>,
+    /// Offset of the next frame in `data`.
+    offset: usize,
+}
+
+impl ZstdFrameIterator {
+    pub fn new(dctx: Arc<DCtx<'static>>, data: PyBuffer<u8>) -> Self {
+        Self {
+            dctx,
+            data,
+            offset: 0,
+        }
+    }
+
+    /// Decompress the complete frame `frame`.
+    fn decompress_frame<'p>(&self, py: Python<'p>, frame: &[u8]) -> PyResult<&'p PyAny> {
+        let content_size =
+            unsafe { zstd_sys::ZSTD_getFrameContentSize(frame.as_ptr() as *const _, frame.len()) };
+
+        let capacity = if content_size == zstd_sys::ZSTD_CONTENTSIZE_UNKNOWN as _
+            || content_size == zstd_sys::ZSTD_CONTENTSIZE_ERROR as _
+        {
+            std::cmp::max(frame.len().saturating_mul(4), zstd_safe::dstream_out_size())
+        } else {
+            content_size as usize
+        };
+
+        let mut dest_buffer =
+            BytesBuilder::with_capacity(py, std::cmp::min(capacity, FRAME_PREALLOCATE_MAX))?;
+
+        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+            src: frame.as_ptr() as *const _,
+            size: frame.len(),
+            pos: 0,
+        };
+
+        loop {
+            let mut out_buffer = dest_buffer.out_buffer();
+            let zresult = self
+                .dctx
+                .decompress_buffers_allow_threads(py, &mut out_buffer, &mut in_buffer)
+                .map_err(|msg| ZstdError::new_err(format!("decompression error: {}", msg)))?;
+            dest_buffer.set_len(out_buffer.pos);
+
+            if zresult == 0 {
+                break;
+            }
+
+            if dest_buffer.len() == dest_buffer.capacity() {
+                let additional = dest_buffer.capacity();
+                dest_buffer.reserve_exact(py, additional)?;
+            } else if in_buffer.pos == in_buffer.size {
+                return Err(ZstdError::new_err(
+                    "decompression error: did not decompress full frame",
+                ));
+            }
+        }
+
+        Ok(dest_buffer.finish(py)?)
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for ZstdFrameIterator {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let py = unsafe { Python::assume_gil_acquired() };
+
+        let data = unsafe {
+            std::slice::from_raw_parts::<u8>(slf.data.buf_ptr() as *const _, slf.data.len_bytes())
+        };
+
+        while slf.offset < data.len() {
+            let remaining = &data[slf.offset..];
+
+            let size = unsafe {
+                zstd_sys::ZSTD_findFrameCompressedSize(
+                    remaining.as_ptr() as *const _,
+                    remaining.len(),
+                )
+            };
+            if unsafe { zstd_sys::ZSTD_isError(size) } != 0 {
+                return Err(ZstdError::new_err(format!(
+                    "error finding frame at offset {}: {}",
+                    slf.offset,
+                    zstd_safe::get_error_name(size)
+                )));
+            }
+
+            let frame = &remaining[0..size];
+            slf.offset += size;
+
+            let magic = u32::from_le_bytes([frame[0], frame[1], frame[2], frame[3]]);
+            if is_skippable_magic(magic) {
+                continue;
+            }
+
+            return Ok(Some(slf.decompress_frame(py, frame)?.into_py(py)));
+        }
+
+        Ok(None)
+    }
+}
@@ -6,33 +6,91 @@
 
 use {
     crate::{
-        exceptions::ZstdError,
-        stream::{make_in_buffer_source, InBufferSource},
+        exceptions::{OnError, ZstdError},
+        stream::{make_in_buffer_source, ChunkOutput, InBufferSource},
         zstd_safe::DCtx,
     },
-    pyo3::{exceptions::PyValueError, prelude::*, types::PyBytes, PyIterProtocol},
+    pyo3::{exceptions::PyValueError, prelude::*, PyIterProtocol, PyNativeType},
     std::{cmp::min, sync::Arc},
 };
 
+/// Upper bound for a `write_size` picked from a frame header.
+const AUTO_WRITE_SIZE_MAX: usize = 8 * 1024 * 1024;
+
+/// Pick an output chunk size for the frame whose header starts `data`.
+///
+/// Chunks hold up to a window of output, bounded by the frame's content size
+/// when known, rounded down to a power of 2. Returns `None` if the header is
+/// incomplete or invalid.
+fn auto_write_size(data: &[u8]) -> Option<usize> {
+    let mut header = zstd_sys::ZSTD_frameHeader {
+        frameContentSize: 0,
+        windowSize: 0,
+        blockSizeMax: 0,
+        frameType: zstd_sys::ZSTD_frameType_e::ZSTD_frame,
+        headerSize: 0,
+        dictID: 0,
+        checksumFlag: 0,
+    };
+
+    let zresult = unsafe {
+        zstd_sys::ZSTD_getFrameHeader(&mut header, data.as_ptr() as *const _, data.len())
+    };
+    if zresult != 0 || header.frameType != zstd_sys::ZSTD_frameType_e::ZSTD_frame {
+        return None;
+    }
+
+    let size = min(header.windowSize, header.frameContentSize).clamp(
+        zstd_safe::dstream_out_size() as u64,
+        AUTO_WRITE_SIZE_MAX as u64,
+    );
+
+    // The bounds are powers of 2, so this stays within them.
+    Some(1 << (63 - size.leading_zeros()))
+}
+
+/// Progress searching for the next frame after a decompression error.
+struct Resync {
+    /// Input bytes to pass over before searching.
+    skip: usize,
+    /// Leading bytes of a magic number found at the end of the last chunk.
+    matched: usize,
+}
+
 #[pyclass(module = "zstandard.backend_rust")]
 pub struct ZstdDecompressorIterator {
     dctx: Arc<DCtx<'static>>,
     source: Box<dyn InBufferSource + Send>,
     write_size: usize,
+    /// Whether `write_size` is yet to be picked from the first frame header.
+    tune_write_size: bool,
+    output: ChunkOutput,
     finished_output: bool,
+    on_error: OnError,
+    /// Input offset and message of each frame that failed to decompress.
+    errors: Vec<(u64, String)>,
+    /// Number of input bytes consumed, including skipped bytes.
+    input_offset: u64,
+    /// Input offset of the frame being decompressed.
+    frame_start: u64,
+    /// Set while looking for the next frame after an error.
+    resync: Option<Resync>,
 }
 
 impl ZstdDecompressorIterator {
     pub fn new(
-        py: Python,
         dctx: Arc<DCtx<'static>>,
         reader: &PyAny,
         read_size: usize,
-        write_size: usize,
+        write_size: Option<usize>,
         skip_bytes: usize,
+        as_memoryview: bool,
+        on_error: OnError,
     ) -> PyResult<Self> {
+        let py = reader.py();
         let mut source = make_in_buffer_source(py, reader, read_size)?;
 
+        let input_offset = skip_bytes as u64;
         let mut skip_bytes = skip_bytes;
         while skip_bytes > 0 {
             let in_buffer = source
@@ -44,13 +102,96 @@ impl ZstdDecompressorIterator {
             skip_bytes -= read;
         }
 
+        let tune_write_size = write_size.is_none();
+        let write_size = write_size.unwrap_or_else(zstd_safe::dstream_out_size);
+
         Ok(Self {
             dctx,
             source,
             write_size,
+            tune_write_size,
+            output: ChunkOutput::new(py, as_memoryview, write_size)?,
             finished_output: false,
+            on_error,
+            errors: vec![],
+            input_offset,
+            frame_start: input_offset,
+            resync: None,
         })
     }
+
+    /// Look for the start of the next frame in `data`.
+    ///
+    /// Returns the number of bytes to pass over. Decompression resumes once
+    /// `resync` is cleared.
+    fn resynchronize(&mut self, data: &[u8], dest_buffer: &mut Vec<u8>) -> PyResult<usize> {
+        let magic = zstd_sys::ZSTD_MAGICNUMBER.to_le_bytes();
+        let state = self.resync.as_mut().unwrap();
+
+        let start = min(state.skip, data.len());
+        state.skip -= start;
+        if state.skip > 0 {
+            return Ok(start);
+        }
+
+        // A magic number may be split across chunks.
+        if state.matched > 0 {
+            let wanted = &magic[state.matched..];
+            let available = min(wanted.len(), data.len() - start);
+
+            if data[start..start + available] == wanted[..available] {
+                if available < wanted.len() {
+                    state.matched += available;
+                    return Ok(start + available);
+                }
+
+                // The bytes from earlier chunks were passed over, so feed
+                // them to the decompressor before the rest of the frame.
+                let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+                    src: magic.as_ptr() as *const _,
+                    size: state.matched,
+                    pos: 0,
+                };
+                self.resync = None;
+                self.dctx
+                    .decompress_into_vec(dest_buffer, &mut in_buffer)
+                    .map_err(|msg| ZstdError::new_err(format!("zstd decompress error: {}", msg)))?;
+
+                return Ok(start);
+            }
+
+            state.matched = 0;
+        }
+
+        let data = &data[start..];
+
+        if let Some(index) = data.windows(magic.len()).position(|window| window == magic) {
+            self.resync = None;
+            self.frame_start = self.input_offset + (start + index) as u64;
+
+            return Ok(start + index);
+        }
+
+        let matched = (1..magic.len())
+            .rev()
+            .find(|&count| data.ends_with(&magic[..count]))
+            .unwrap_or(0);
+        state.matched = matched;
+        self.frame_start = self.input_offset + (start + data.len() - matched) as u64;
+
+        Ok(start + data.len())
+    }
+}
+
+#[pymethods]
+impl ZstdDecompressorIterator {
+    /// `(offset, message)` of each frame skipped with `on_error="skip"`.
+    ///
+    /// `offset` is the position of the start of the frame in the input.
+    #[getter]
+    fn errors(&self) -> Vec<(u64, String)> {
+        self.errors.clone()
+    }
 }
 
 #[pyproto]
@@ -66,28 +207,95 @@ impl PyIterProtocol for ZstdDecompressorIterator {
 
         let py = unsafe { Python::assume_gil_acquired() };
 
+        if slf.tune_write_size {
+            slf.tune_write_size = false;
+
+            if let Some(in_buffer) = slf.source.input_buffer(py)? {
+                let data = unsafe {
+                    std::slice::from_raw_parts(
+                        (in_buffer.src as *const u8).add(in_buffer.pos),
+                        in_buffer.size - in_buffer.pos,
+                    )
+                };
+
+                if let Some(size) = auto_write_size(data) {
+                    slf.write_size = size;
+                }
+            }
+        }
+
         let mut dest_buffer: Vec<u8> = Vec::with_capacity(slf.write_size);
 
         // While input is available.
         while let Some(mut in_buffer) = slf.source.input_buffer(py)? {
+            if slf.resync.is_some() {
+                let data = unsafe {
+                    std::slice::from_raw_parts(
+                        (in_buffer.src as *const u8).add(in_buffer.pos),
+                        in_buffer.size - in_buffer.pos,
+                    )
+                };
+
+                let count = slf.resynchronize(data, &mut dest_buffer)?;
+                slf.source.record_bytes_read(count);
+                slf.input_offset += count as u64;
+
+                continue;
+            }
+
             let old_pos = in_buffer.pos;
 
-            let zresult = slf
+            let result = slf
                 .dctx
-                .decompress_into_vec(&mut dest_buffer, &mut in_buffer)
-                .map_err(|msg| ZstdError::new_err(format!("zstd decompress error: {}", msg)))?;
+                .decompress_into_vec(&mut dest_buffer, &mut in_buffer);
 
             slf.source.record_bytes_read(in_buffer.pos - old_pos);
+            slf.input_offset += (in_buffer.pos - old_pos) as u64;
+
+            match result {
+                // Keep going with the frames that follow when skipping
+                // damaged ones.
+                Ok(0) if slf.on_error == OnError::Skip => {
+                    slf.frame_start = slf.input_offset;
+                }
+                Ok(0) => {
+                    slf.finished_output = true;
+                }
+                Ok(_) => {}
+                Err(msg) if slf.on_error == OnError::Skip => {
+                    let frame_start = slf.frame_start;
+                    slf.errors.push((frame_start, msg.to_string()));
+                    slf.dctx.reset().map_err(|msg| {
+                        ZstdError::new_err(format!(
+                            "unable to reset decompression context: {}",
+                            msg
+                        ))
+                    })?;
 
-            if zresult == 0 {
-                slf.finished_output = true;
+                    // Don't find the damaged frame again if nothing of it
+                    // was consumed.
+                    slf.resync = Some(Resync {
+                        skip: if slf.input_offset == frame_start {
+                            1
+                        } else {
+                            0
+                        },
+                        matched: 0,
+                    });
+                }
+                Err(msg) => {
+                    return Err(ZstdError::new_err(format!(
+                        "zstd decompress error: {}",
+                        msg
+                    )));
+                }
             }
 
             // Emit chunk if output buffer has data.
             if !dest_buffer.is_empty() {
                 // TODO avoid buffer copy.
-                let chunk = PyBytes::new(py, &dest_buffer);
-                return Ok(Some(chunk.into_py(py)));
+                let chunk = slf.output.emit(py, &dest_buffer)?;
+                return Ok(Some(chunk));
             }
 
             // Repeat loop to collect more input data.
@@ -97,8 +305,8 @@ impl PyIterProtocol for ZstdDecompressorIterator {
         // Input is exhausted. Emit what we have or finish.
         if !dest_buffer.is_empty() {
             // TODO avoid buffer copy.
-            let chunk = PyBytes::new(py, &dest_buffer);
-            Ok(Some(chunk.into_py(py)))
+            let chunk = slf.output.emit(py, &dest_buffer)?;
+            Ok(Some(chunk))
         } else {
             Ok(None)
         }
@@ -6,18 +6,25 @@
 
 use {
     crate::{
-        buffers::{BufferSegment, ZstdBufferWithSegments, ZstdBufferWithSegmentsCollection},
-        exceptions::ZstdError,
+        buffers::{
+            ByteBuffer, ZstdBufferWithSegments, ZstdBufferWithSegmentsBuilder,
+            ZstdBufferWithSegmentsCollection,
+        },
+        compression_dict::ZstdCompressionDict,
+        exceptions::{OnError, ZstdError},
+        future::spawn_future,
+        options::apply_kwargs,
         zstd_safe::DCtx,
     },
     pyo3::{
         buffer::PyBuffer,
         exceptions::{PyTypeError, PyValueError},
         prelude::*,
-        types::{PyBytes, PyList, PyTuple},
+        types::{PyDict, PyList, PyTuple},
         PySequenceProtocol,
     },
     rayon::prelude::*,
+    std::convert::TryInto,
 };
 
 struct DataSource<'a> {
@@ -25,111 +32,245 @@ struct DataSource<'a> {
     decompressed_size: usize,
 }
 
-pub fn multi_decompress_to_buffer(
-    py: Python,
-    dctx: &DCtx,
-    frames: &PyAny,
-    decompressed_sizes: Option<&PyAny>,
-    threads: isize,
-) -> PyResult<ZstdBufferWithSegmentsCollection> {
-    let threads = if threads < 0 {
-        num_cpus::get()
-    } else if threads < 2 {
-        1
-    } else {
-        threads as _
-    };
+/// Frames to decompress.
+///
+/// Holds the Python objects backing each frame so frames remain valid when
+/// decompression outlives the call that gathered them.
+struct DataSources {
+    /// Address, length and expected decompressed size of each frame.
+    /// Addresses are integers since raw pointers aren't Send.
+    frames: Vec<(usize, usize, usize)>,
+    _owners: Vec<PyObject>,
+    _buffers: Vec<PyBuffer<u8>>,
+}
 
-    let frame_sizes: &[u64] = if let Some(frames_sizes) = decompressed_sizes {
-        let buffer: PyBuffer<u8> = PyBuffer::get(frames_sizes)?;
-        unsafe { std::slice::from_raw_parts(buffer.buf_ptr() as *const _, buffer.len_bytes() / 8) }
-    } else {
-        &[]
-    };
+impl DataSources {
+    fn gather(py: Python, frames: &PyAny, decompressed_sizes: Option<&PyAny>) -> PyResult<Self> {
+        let sizes_buffer = match decompressed_sizes {
+            Some(sizes) => Some(PyBuffer::<u8>::get(sizes)?),
+            None => None,
+        };
+        let frame_sizes: &[u64] = if let Some(buffer) = &sizes_buffer {
+            unsafe {
+                std::slice::from_raw_parts(buffer.buf_ptr() as *const _, buffer.len_bytes() / 8)
+            }
+        } else {
+            &[]
+        };
+
+        let mut sources = Self {
+            frames: vec![],
+            _owners: vec![],
+            _buffers: vec![],
+        };
+
+        if let Ok(buffer) = frames.extract::<&PyCell<ZstdBufferWithSegments>>() {
+            if decompressed_sizes.is_some() && frame_sizes.len() != buffer.len()? {
+                return Err(PyValueError::new_err(format!(
+                    "decompressed_sizes size mismatch; expected {}, got {}",
+                    buffer.len()?,
+                    frame_sizes.len()
+                )));
+            }
 
-    let mut sources = vec![];
+            sources.add_segments(py, buffer, frame_sizes);
+        } else if let Ok(collection) = frames.extract::<&PyCell<ZstdBufferWithSegmentsCollection>>()
+        {
+            let frames_count = collection.borrow().__len__();
+
+            if decompressed_sizes.is_some() && frame_sizes.len() != frames_count {
+                return Err(PyValueError::new_err(format!(
+                    "decompressed_sizes size mismatch; expected {}, got {}",
+                    frames_count,
+                    frame_sizes.len()
+                )));
+            }
 
-    if let Ok(buffer) = frames.extract::<&PyCell<ZstdBufferWithSegments>>() {
-        if decompressed_sizes.is_some() && frame_sizes.len() != buffer.len()? {
-            return Err(PyValueError::new_err(format!(
-                "decompressed_sizes size mismatch; expected {}, got {}",
-                buffer.len()?,
-                frame_sizes.len()
-            )));
+            sources.frames.reserve_exact(frames_count);
+
+            for buffer_obj in &collection.borrow().buffers {
+                let offset = sources.frames.len();
+                let sizes = frame_sizes.get(offset..).unwrap_or(&[]);
+
+                sources.add_segments(py, buffer_obj.extract(py)?, sizes);
+            }
+        } else if let Ok(list) = frames.extract::<&PyList>() {
+            if decompressed_sizes.is_some() && frame_sizes.len() != list.len() {
+                return Err(PyValueError::new_err(format!(
+                    "decompressed_sizes size mismatch; expected {}; got {}",
+                    list.len(),
+                    frame_sizes.len()
+                )));
+            }
+
+            sources.frames.reserve_exact(list.len());
+
+            for (i, item) in list.iter().enumerate() {
+                let buffer: PyBuffer<u8> = PyBuffer::get(item).map_err(|_| {
+                    PyTypeError::new_err(format!("item {} not a bytes like object", i))
+                })?;
+
+                sources.frames.push((
+                    buffer.buf_ptr() as usize,
+                    buffer.len_bytes(),
+                    *frame_sizes.get(i).unwrap_or(&0) as _,
+                ));
+                sources._buffers.push(buffer);
+            }
+        } else {
+            return Err(PyTypeError::new_err(
+                "argument must be list of BufferWithSegments",
+            ));
         }
 
-        let borrow = buffer.borrow();
+        Ok(sources)
+    }
 
-        sources.reserve_exact(borrow.segments.len());
+    fn add_segments(
+        &mut self,
+        py: Python,
+        buffer: &PyCell<ZstdBufferWithSegments>,
+        frame_sizes: &[u64],
+    ) {
+        let borrow = buffer.borrow();
 
         for i in 0..borrow.segments.len() {
             let slice = borrow.get_segment_slice(py, i);
 
-            sources.push(DataSource {
-                data: slice,
-                decompressed_size: *frame_sizes.get(i).unwrap_or(&0) as _,
-            });
+            self.frames.push((
+                slice.as_ptr() as usize,
+                slice.len(),
+                *frame_sizes.get(i).unwrap_or(&0) as _,
+            ));
         }
-    } else if let Ok(collection) = frames.extract::<&PyCell<ZstdBufferWithSegmentsCollection>>() {
-        let frames_count = collection.borrow().__len__();
 
-        if decompressed_sizes.is_some() && frame_sizes.len() != frames_count {
-            return Err(PyValueError::new_err(format!(
-                "decompressed_sizes size mismatch; expected {}, got {}",
-                frames_count,
-                frame_sizes.len()
-            )));
-        }
+        self._owners.push(buffer.into_py(py));
+    }
 
-        sources.reserve_exact(frames_count);
+    fn sources(&self) -> Vec<DataSource<'_>> {
+        self.frames
+            .iter()
+            .map(|(address, len, decompressed_size)| DataSource {
+                data: unsafe { std::slice::from_raw_parts(*address as *const u8, *len) },
+                decompressed_size: *decompressed_size,
+            })
+            .collect()
+    }
+}
 
-        let mut offset = 0;
-        for buffer_obj in &collection.borrow().buffers {
-            let buffer = buffer_obj.extract::<&PyCell<ZstdBufferWithSegments>>(py)?;
-            let borrow = buffer.borrow();
+fn resolve_threads(threads: isize) -> usize {
+    if threads < 0 {
+        num_cpus::get()
+    } else if threads < 2 {
+        1
+    } else {
+        threads as _
+    }
+}
 
-            for i in 0..borrow.segments.len() {
-                let slice = borrow.get_segment_slice(py, i);
+/// Keyword-only options to `ZstdDecompressor.multi_decompress_to_buffer()`.
+pub struct MultiDecompressOptions<'p> {
+    /// Buffer to decompress into, at `offsets`.
+    pub out: Option<&'p PyAny>,
+    pub offsets: Option<&'p PyAny>,
+    pub on_error: OnError,
+}
 
-                sources.push(DataSource {
-                    data: slice,
-                    decompressed_size: *frame_sizes.get(offset).unwrap_or(&0) as _,
-                });
+impl<'p> MultiDecompressOptions<'p> {
+    pub fn from_kwargs(kwargs: Option<&'p PyDict>) -> PyResult<Self> {
+        let mut options = Self {
+            out: None,
+            offsets: None,
+            on_error: OnError::Raise,
+        };
+
+        apply_kwargs(
+            "ZstdDecompressor.multi_decompress_to_buffer()",
+            kwargs,
+            |key, value| {
+                match key {
+                    "out" => options.out = value.extract()?,
+                    "offsets" => options.offsets = value.extract()?,
+                    "on_error" => options.on_error = OnError::parse(value.extract()?)?,
+                    _ => return Ok(false),
+                }
 
-                offset += 1;
-            }
-        }
-    } else if let Ok(list) = frames.extract::<&PyList>() {
-        if decompressed_sizes.is_some() && frame_sizes.len() != list.len() {
-            return Err(PyValueError::new_err(format!(
-                "decompressed_sizes size mismatch; expected {}; got {}",
-                list.len(),
-                frame_sizes.len()
-            )));
-        }
+                Ok(true)
+            },
+        )?;
 
-        sources.reserve_exact(list.len());
+        Ok(options)
+    }
+}
+
+pub fn multi_decompress_to_buffer(
+    py: Python,
+    dctx: &DCtx<'static>,
+    frames: &PyAny,
+    decompressed_sizes: Option<&PyAny>,
+    threads: isize,
+    options: MultiDecompressOptions,
+) -> PyResult<PyObject> {
+    let MultiDecompressOptions {
+        out,
+        offsets,
+        on_error,
+    } = options;
+
+    if out.is_some() && on_error == OnError::Skip {
+        return Err(PyValueError::new_err(
+            "on_error=\"skip\" cannot be used with out",
+        ));
+    }
 
-        for (i, item) in list.iter().enumerate() {
-            let buffer: PyBuffer<u8> = PyBuffer::get(item)
-                .map_err(|_| PyTypeError::new_err(format!("item {} not a bytes like object", i)))?;
+    let threads = resolve_threads(threads);
+    let data_sources = DataSources::gather(py, frames, decompressed_sizes)?;
+    let sources = data_sources.sources();
 
-            let slice = unsafe {
-                std::slice::from_raw_parts(buffer.buf_ptr() as *const _, buffer.len_bytes())
-            };
+    match (out, offsets) {
+        (Some(out), Some(offsets)) => {
+            decompress_datasources_into(dctx, sources, threads, ByteBuffer::get(out)?, offsets)
+                .map(|size| size.into_py(py))
+        }
+        (Some(_), None) => Err(PyValueError::new_err("offsets must be specified with out")),
+        (None, Some(_)) => Err(PyValueError::new_err("offsets requires out")),
+        (None, None) => {
+            let dctxs = make_dctxs(dctx, threads, sources.len())?;
+            let results = decompress_from_datasources(&dctxs, sources)?;
 
-            sources.push(DataSource {
-                data: slice,
-                decompressed_size: *frame_sizes.get(i).unwrap_or(&0) as _,
-            });
+            results_to_output(py, results, on_error)
         }
-    } else {
-        return Err(PyTypeError::new_err(
-            "argument must be list of BufferWithSegments",
-        ));
     }
+}
 
-    decompress_from_datasources(py, dctx, sources, threads)
+/// Like `multi_decompress_to_buffer()` but decompresses on a background
+/// thread.
+///
+/// Returns an asyncio future of the running event loop that resolves to the
+/// same value.
+pub fn multi_decompress_to_buffer_async(
+    py: Python,
+    dctx: &DCtx<'static>,
+    dict: &Option<Py<ZstdCompressionDict>>,
+    frames: &PyAny,
+    decompressed_sizes: Option<&PyAny>,
+    threads: isize,
+    on_error: OnError,
+) -> PyResult<PyObject> {
+    let data_sources = DataSources::gather(py, frames, decompressed_sizes)?;
+    let dctxs = make_dctxs(dctx, resolve_threads(threads), data_sources.frames.len())?;
+    // Loaded dictionaries are referenced by the contexts.
+    let dict = dict.as_ref().map(|dict| dict.clone_ref(py));
+
+    spawn_future(
+        py,
+        move || {
+            let results = decompress_from_datasources(&dctxs, data_sources.sources());
+
+            (results, data_sources, dict)
+        },
+        move |py, (results, _data_sources, _dict)| results_to_output(py, results?, on_error),
+    )
 }
 
 #[derive(Debug, PartialEq)]
@@ -139,6 +280,17 @@ enum WorkerError {
     Zstd(&'static str),
 }
 
+impl WorkerError {
+    /// Describe the error for the errors reported by `on_error="skip"`.
+    fn message(&self) -> Option<String> {
+        match self {
+            Self::None => None,
+            Self::NoSize => Some("could not determine decompressed size".to_string()),
+            Self::Zstd(msg) => Some(msg.to_string()),
+        }
+    }
+}
+
 /// Holds results of an individual compression operation.
 struct WorkerResult {
     source_offset: usize,
@@ -146,31 +298,178 @@ struct WorkerResult {
     data: Option<Vec<u8>>,
 }
 
-fn decompress_from_datasources(
-    py: Python,
+/// Decompress every source in parallel into its region of `out`.
+///
+/// `offsets` holds one native-endian u64 per source. Returns the total number of
+/// bytes written.
+fn decompress_datasources_into(
     dctx: &DCtx,
     sources: Vec<DataSource>,
     thread_count: usize,
-) -> PyResult<ZstdBufferWithSegmentsCollection> {
+    out: ByteBuffer,
+    offsets: &PyAny,
+) -> PyResult<usize> {
+    if out.readonly() {
+        return Err(PyValueError::new_err("out buffer is not writable"));
+    }
+
+    let offsets = ByteBuffer::get(offsets)?;
+    let offsets = offsets
+        .as_slice()
+        .chunks_exact(8)
+        .map(|chunk| u64::from_ne_bytes(chunk.try_into().unwrap()) as usize)
+        .collect::<Vec<_>>();
+
+    if offsets.len() != sources.len() {
+        return Err(PyValueError::new_err(format!(
+            "offsets size mismatch; expected {}, got {}",
+            sources.len(),
+            offsets.len()
+        )));
+    }
+
+    // Resolve each destination region up front so regions can be validated
+    // before any writes happen.
+    let mut regions = Vec::with_capacity(sources.len());
+    for (index, source) in sources.iter().enumerate() {
+        let size = if source.decompressed_size == 0 {
+            let frame_size = zstd_safe::get_frame_content_size(source.data);
+
+            if frame_size == zstd_safe::CONTENTSIZE_ERROR
+                || frame_size == zstd_safe::CONTENTSIZE_UNKNOWN
+            {
+                return Err(PyValueError::new_err(format!(
+                    "could not determine decompressed size of item {}",
+                    index
+                )));
+            }
+
+            frame_size as usize
+        } else {
+            source.decompressed_size
+        };
+
+        match offsets[index].checked_add(size) {
+            Some(end) if end <= out.len_bytes() => {}
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "item {} of {} bytes at offset {} exceeds out buffer of {} bytes",
+                    index,
+                    size,
+                    offsets[index],
+                    out.len_bytes()
+                )));
+            }
+        }
+
+        regions.push((offsets[index], size, index));
+    }
+
+    let mut sorted = regions.clone();
+    sorted.sort_unstable();
+    for pair in sorted.windows(2) {
+        if pair[0].0 + pair[0].1 > pair[1].0 {
+            return Err(PyValueError::new_err(format!(
+                "out regions for items {} and {} overlap",
+                pair[0].2, pair[1].2
+            )));
+        }
+    }
+
+    let thread_count = std::cmp::max(std::cmp::min(thread_count, sources.len()), 1);
+
+    let mut dctxs = Vec::with_capacity(thread_count);
+    for _ in 0..thread_count {
+        dctxs.push(dctx.try_clone().map_err(ZstdError::new_err)?);
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(thread_count)
+        .build()
+        .map_err(|err| ZstdError::new_err(format!("error initializing thread pool: {}", err)))?;
+
+    // Regions are disjoint, so workers never write the same memory. The
+    // address is passed as an integer since raw pointers aren't Send.
+    let out_address = out.as_mut_ptr() as usize;
+
+    let mut errors = pool.install(|| {
+        sources
+            .par_iter()
+            .zip(regions.par_iter())
+            .filter_map(|(source, (offset, size, index))| {
+                let dctx = &dctxs[pool.current_thread_index().unwrap()];
+
+                let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+                    src: source.data.as_ptr() as *const _,
+                    size: source.data.len(),
+                    pos: 0,
+                };
+                let mut out_buffer = zstd_sys::ZSTD_outBuffer {
+                    dst: (out_address + offset) as *mut _,
+                    size: *size,
+                    pos: 0,
+                };
+
+                match dctx.decompress_buffers(&mut out_buffer, &mut in_buffer) {
+                    Ok(0) if out_buffer.pos == *size => None,
+                    Ok(_) => Some((
+                        *index,
+                        format!("decompressed size differs from expected {} bytes", size),
+                    )),
+                    Err(msg) => Some((*index, msg.to_string())),
+                }
+            })
+            .collect::<Vec<_>>()
+    });
+
+    // Report the first failing item so errors are deterministic.
+    errors.sort_unstable();
+    if let Some((index, msg)) = errors.into_iter().next() {
+        return Err(ZstdError::new_err(format!(
+            "error decompressing item {}: {}",
+            index, msg
+        )));
+    }
+
+    Ok(regions.iter().map(|(_, size, _)| size).sum())
+}
+
+/// Create a decompression context per thread.
+fn make_dctxs(
+    dctx: &DCtx<'static>,
+    thread_count: usize,
+    source_count: usize,
+) -> PyResult<Vec<DCtx<'static>>> {
     // More threads than inputs makes no sense.
-    let thread_count = std::cmp::min(thread_count, sources.len());
+    let thread_count = std::cmp::min(thread_count, source_count);
 
     // TODO lower thread count when input size is too small and threads
     // would add overhead.
 
     let mut dctxs = Vec::with_capacity(thread_count);
-    let results = std::sync::Mutex::new(Vec::with_capacity(sources.len()));
-
-    // TODO there are tons of inefficiencies in this implementation compared
-    // to the C backend.
 
     for _ in 0..thread_count {
         let dctx = dctx.try_clone().map_err(ZstdError::new_err)?;
         dctxs.push(dctx);
     }
 
+    Ok(dctxs)
+}
+
+/// Decompress every source using a thread per context.
+///
+/// Results are in input order.
+fn decompress_from_datasources(
+    dctxs: &[DCtx],
+    sources: Vec<DataSource>,
+) -> PyResult<Vec<WorkerResult>> {
+    let results = std::sync::Mutex::new(Vec::with_capacity(sources.len()));
+
+    // TODO there are tons of inefficiencies in this implementation compared
+    // to the C backend.
+
     let pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(thread_count)
+        .num_threads(dctxs.len())
         .build()
         .map_err(|err| ZstdError::new_err(format!("error initializing thread pool: {}", err)))?;
 
@@ -225,19 +524,23 @@ fn decompress_from_datasources(
             });
     });
 
+    let mut results = results.into_inner().unwrap();
+
     // Need to sort results by their input order or else results aren't
     // deterministic.
-    results
-        .lock()
-        .unwrap()
-        .sort_by(|a, b| a.source_offset.cmp(&b.source_offset));
+    results.sort_by(|a, b| a.source_offset.cmp(&b.source_offset));
+
+    Ok(results)
+}
 
+fn results_to_collection(
+    py: Python,
+    results: Vec<WorkerResult>,
+) -> PyResult<ZstdBufferWithSegmentsCollection> {
     // TODO this is horribly inefficient due to memory copies.
     let els = PyTuple::new(
         py,
         results
-            .lock()
-            .unwrap()
             .iter()
             .map(|result| {
                 match result.error {
@@ -253,25 +556,47 @@ fn decompress_from_datasources(
                 }?;
 
                 let data = result.data.as_ref().unwrap();
-                let chunk = PyBytes::new(py, data);
-                let segments = vec![BufferSegment {
-                    offset: 0,
-                    length: data.len() as _,
-                }];
-
-                let segments = unsafe {
-                    PyBytes::from_ptr(
-                        py,
-                        segments.as_ptr() as *const _,
-                        segments.len() * std::mem::size_of::<BufferSegment>(),
-                    )
-                };
-                let segments_buffer = PyBuffer::get(segments)?;
+                let mut builder = ZstdBufferWithSegmentsBuilder::with_capacity(py, data.len())?;
+                builder.push(py, data)?;
 
-                Py::new(py, ZstdBufferWithSegments::new(py, chunk, segments_buffer)?)
+                Py::new(py, builder.build(py)?)
             })
             .collect::<PyResult<Vec<_>>>()?,
     );
 
     ZstdBufferWithSegmentsCollection::new(py, els)
 }
+
+/// Convert worker results to the value returned to Python.
+///
+/// With `OnError::Skip`, a `(collection, errors)` tuple is returned instead of
+/// raising. Items that failed have an empty segment in `collection` and an
+/// `(index, message)` entry in `errors`.
+fn results_to_output(
+    py: Python,
+    results: Vec<WorkerResult>,
+    on_error: OnError,
+) -> PyResult<PyObject> {
+    if on_error == OnError::Raise {
+        return results_to_collection(py, results).map(|result| result.into_py(py));
+    }
+
+    let size = results
+        .iter()
+        .map(|result| result.data.as_ref().map_or(0, |data| data.len()))
+        .sum();
+    let mut builder = ZstdBufferWithSegmentsBuilder::with_capacity(py, size)?;
+    let mut errors = vec![];
+
+    for result in &results {
+        builder.push(py, result.data.as_deref().unwrap_or(&[]))?;
+
+        if let Some(msg) = result.error.message() {
+            errors.push((result.source_offset, msg));
+        }
+    }
+
+    let collection = ZstdBufferWithSegmentsCollection::from_buffer(py, builder.build(py)?)?;
+
+    Ok((collection, errors).into_py(py))
+}
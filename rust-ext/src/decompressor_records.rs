@@ -0,0 +1,108 @@
+// Copyright (c) 2021-present, Gregory Szorc
+// All rights reserved.
+//
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+use {
+    crate::{
+        exceptions::ZstdError,
+        stream::{make_in_buffer_source, InBufferSource},
+        zstd_safe::DCtx,
+    },
+    pyo3::{prelude::*, types::PyBytes, PyIterProtocol},
+    std::sync::Arc,
+};
+
+/// Iterates over decompressed output in fixed-size records.
+#[pyclass(module = "zstandard.backend_rust")]
+pub struct ZstdRecordIterator {
+    dctx: Arc<DCtx<'static>>,
+    source: Box<dyn InBufferSource + Send>,
+    record_size: usize,
+    /// Decompressed data not yet emitted, starting at `pending_offset`.
+    pending: Vec<u8>,
+    pending_offset: usize,
+    finished_input: bool,
+}
+
+impl ZstdRecordIterator {
+    pub fn new(
+        py: Python,
+        dctx: Arc<DCtx<'static>>,
+        reader: &PyAny,
+        read_size: usize,
+        record_size: usize,
+    ) -> PyResult<Self> {
+        Ok(Self {
+            dctx,
+            source: make_in_buffer_source(py, reader, read_size)?,
+            record_size,
+            pending: Vec::new(),
+            pending_offset: 0,
+            finished_input: false,
+        })
+    }
+
+    /// Decompress more input into `pending`.
+    fn fill(&mut self, py: Python) -> PyResult<()> {
+        // Discard data that has already been emitted.
+        self.pending.drain(0..self.pending_offset);
+        self.pending_offset = 0;
+        self.pending.reserve(std::cmp::max(
+            self.record_size,
+            zstd_safe::dstream_out_size(),
+        ));
+
+        let mut in_buffer = match self.source.input_buffer(py)? {
+            Some(in_buffer) => in_buffer,
+            None => {
+                self.finished_input = true;
+                return Ok(());
+            }
+        };
+
+        let old_pos = in_buffer.pos;
+
+        let zresult = self
+            .dctx
+            .decompress_into_vec(&mut self.pending, &mut in_buffer)
+            .map_err(|msg| ZstdError::new_err(format!("zstd decompress error: {}", msg)))?;
+
+        self.source.record_bytes_read(in_buffer.pos - old_pos);
+
+        if zresult == 0 {
+            self.finished_input = true;
+        }
+
+        Ok(())
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for ZstdRecordIterator {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let py = unsafe { Python::assume_gil_acquired() };
+
+        while slf.pending.len() - slf.pending_offset < slf.record_size && !slf.finished_input {
+            slf.fill(py)?;
+        }
+
+        let start = slf.pending_offset;
+        let end = std::cmp::min(start + slf.record_size, slf.pending.len());
+
+        if start == end {
+            return Ok(None);
+        }
+
+        // The last record is short if the output isn't a multiple of
+        // `record_size`.
+        slf.pending_offset = end;
+
+        Ok(Some(PyBytes::new(py, &slf.pending[start..end]).into_py(py)))
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright (c) 2021-present, Gregory Szorc
+// All rights reserved.
+//
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+use {
+    crate::constants::validate_level,
+    pyo3::{exceptions::PyValueError, prelude::*, types::PyDict, wrap_pyfunction},
+    std::sync::Mutex,
+};
+
+/// Environment variable providing the default compression level.
+const LEVEL_ENV: &str = "ZSTANDARD_DEFAULT_LEVEL";
+
+/// Environment variable providing the default number of compression threads.
+const THREADS_ENV: &str = "ZSTANDARD_THREADS";
+
+/// Values used when `ZstdCompressor` arguments are omitted.
+#[derive(Clone, Copy)]
+pub(crate) struct Defaults {
+    pub level: i32,
+    pub threads: i32,
+}
+
+static DEFAULTS: Mutex<Defaults> = Mutex::new(Defaults {
+    level: 3,
+    threads: 0,
+});
+
+/// Obtain the current defaults.
+pub(crate) fn defaults() -> Defaults {
+    *DEFAULTS.lock().unwrap()
+}
+
+/// Read an integer from environment variable `name`, if set.
+fn env_int(name: &str) -> PyResult<Option<i32>> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse::<i32>()
+            .map(Some)
+            .map_err(|_| PyValueError::new_err(format!("{} must be an integer", name))),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(std::env::VarError::NotUnicode(_)) => Err(PyValueError::new_err(format!(
+            "{} must be an integer",
+            name
+        ))),
+    }
+}
+
+fn defaults_dict<'p>(py: Python<'p>, defaults: Defaults) -> PyResult<&'p PyDict> {
+    let result = PyDict::new(py);
+    result.set_item("level", defaults.level)?;
+    result.set_item("threads", defaults.threads)?;
+
+    Ok(result)
+}
+
+/// Override the defaults used when `ZstdCompressor` arguments are omitted.
+///
+/// Arguments that are `None` are left unchanged. Returns the defaults now in
+/// effect.
+#[pyfunction(level = "None", threads = "None")]
+fn set_defaults<'p>(
+    py: Python<'p>,
+    level: Option<i32>,
+    threads: Option<i32>,
+) -> PyResult<&'p PyDict> {
+    let level = level.map(validate_level).transpose()?;
+
+    let mut defaults = DEFAULTS.lock().unwrap();
+
+    if let Some(level) = level {
+        defaults.level = level;
+    }
+    if let Some(threads) = threads {
+        defaults.threads = threads;
+    }
+
+    defaults_dict(py, *defaults)
+}
+
+/// Obtain the defaults used when `ZstdCompressor` arguments are omitted.
+#[pyfunction]
+fn get_defaults<'p>(py: Python<'p>) -> PyResult<&'p PyDict> {
+    defaults_dict(py, defaults())
+}
+
+pub(crate) fn init_module(module: &PyModule) -> PyResult<()> {
+    let level = env_int(LEVEL_ENV)?.map(validate_level).transpose()?;
+    let threads = env_int(THREADS_ENV)?;
+
+    {
+        let mut defaults = DEFAULTS.lock().unwrap();
+
+        if let Some(level) = level {
+            defaults.level = level;
+        }
+        if let Some(threads) = threads {
+            defaults.threads = threads;
+        }
+    }
+
+    module.add_function(wrap_pyfunction!(get_defaults, module)?)?;
+    module.add_function(wrap_pyfunction!(set_defaults, module)?)?;
+
+    Ok(())
+}
@@ -0,0 +1,110 @@
+// Copyright (c) 2021-present, Gregory Szorc
+// All rights reserved.
+//
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+use {
+    crate::compression_dict::ZstdCompressionDict,
+    pyo3::{class::PySequenceProtocol, exceptions::PyValueError, prelude::*},
+    std::{
+        any::Any,
+        collections::BTreeMap,
+        sync::{Arc, Mutex},
+    },
+};
+
+/// Dictionaries registered process-wide, keyed by dictionary ID.
+static REGISTRY: Mutex<BTreeMap<u32, Py<ZstdCompressionDict>>> = Mutex::new(BTreeMap::new());
+
+/// Resolve a dictionary ID to a prepared dictionary from the registry.
+///
+/// Used as a `DictResolver` by decompressors with `use_dict_registry`.
+pub(crate) fn resolve(
+    dict_id: u32,
+) -> Result<(*const zstd_sys::ZSTD_DDict, Arc<dyn Any + Send + Sync>), &'static str> {
+    let missing = "no registered dictionary matches the frame's dictionary ID";
+    let dict = REGISTRY
+        .lock()
+        .unwrap()
+        .get(&dict_id)
+        .ok_or(missing)?
+        .clone();
+
+    Python::with_gil(|py| {
+        let ptr = dict
+            .try_borrow(py)
+            .ok()
+            .and_then(|dict| dict.prepared_ddict().ok())
+            .ok_or(missing)?;
+
+        Ok((ptr, Arc::new(dict) as Arc<dyn Any + Send + Sync>))
+    })
+}
+
+/// Process-wide mapping of dictionary IDs to `ZstdCompressionDict`.
+///
+/// A single instance is exposed as `zstandard.dict_registry`.
+#[pyclass(module = "zstandard.backend_rust")]
+pub struct DictRegistry {}
+
+#[pymethods]
+impl DictRegistry {
+    /// Register a dictionary under its dictionary ID, replacing any previous one.
+    ///
+    /// Returns the dictionary ID.
+    fn register(&self, py: Python, dict_data: Py<ZstdCompressionDict>) -> PyResult<u32> {
+        let dict_id = dict_data.borrow(py).dict_id();
+
+        if dict_id == 0 {
+            return Err(PyValueError::new_err(
+                "dictionary does not have a dictionary ID",
+            ));
+        }
+
+        REGISTRY.lock().unwrap().insert(dict_id, dict_data);
+
+        Ok(dict_id)
+    }
+
+    /// Remove the dictionary with the given ID. Returns whether one was removed.
+    fn unregister(&self, dict_id: u32) -> bool {
+        REGISTRY.lock().unwrap().remove(&dict_id).is_some()
+    }
+
+    #[args(dict_id)]
+    fn get(&self, py: Python, dict_id: u32) -> Option<Py<ZstdCompressionDict>> {
+        REGISTRY
+            .lock()
+            .unwrap()
+            .get(&dict_id)
+            .map(|dict| dict.clone_ref(py))
+    }
+
+    /// Registered dictionary IDs, in ascending order.
+    fn dict_ids(&self) -> Vec<u32> {
+        REGISTRY.lock().unwrap().keys().copied().collect()
+    }
+
+    fn clear(&self) {
+        REGISTRY.lock().unwrap().clear();
+    }
+}
+
+#[pyproto]
+impl PySequenceProtocol for DictRegistry {
+    fn __len__(&self) -> usize {
+        REGISTRY.lock().unwrap().len()
+    }
+
+    fn __contains__(&self, dict_id: u32) -> bool {
+        REGISTRY.lock().unwrap().contains_key(&dict_id)
+    }
+}
+
+pub(crate) fn init_module(py: Python, module: &PyModule) -> PyResult<()> {
+    module.add_class::<DictRegistry>()?;
+    module.add("dict_registry", Py::new(py, DictRegistry {})?)?;
+
+    Ok(())
+}
@@ -4,12 +4,160 @@
 // This software may be modified and distributed under the terms
 // of the BSD license. See the LICENSE file for details.
 
-use pyo3::{create_exception, exceptions::PyException, prelude::*};
+use {
+    pyo3::{
+        create_exception,
+        exceptions::{PyException, PyOSError, PyOverflowError, PyValueError},
+        prelude::*,
+        wrap_pyfunction,
+    },
+    std::sync::atomic::{AtomicBool, Ordering},
+};
 
 create_exception!(module, ZstdError, PyException);
+create_exception!(module, ZstdDestinationTooSmallError, ZstdError);
+
+/// Environment variable enabling C backend compatible errors.
+const C_COMPAT_ENV: &str = "ZSTANDARD_C_COMPAT_ERRORS";
+
+static C_COMPAT_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// Whether errors should match those raised by the C backend.
+pub(crate) fn c_compat_errors() -> bool {
+    C_COMPAT_ERRORS.load(Ordering::Relaxed)
+}
+
+/// Errors that every backend raises.
+///
+/// Construct the Python exception through `From<CommonError> for PyErr`
+/// rather than by hand so the type and message can follow the C backend when
+/// `set_c_compat_errors(True)` is in effect.
+pub(crate) enum CommonError {
+    /// Compression level greater than `MAX_COMPRESSION_LEVEL`.
+    LevelTooHigh,
+    /// Compression level less than `MIN_COMPRESSION_LEVEL`.
+    ///
+    /// The C backend lets zstd clamp the level instead. Check
+    /// `c_compat_errors()` before raising this.
+    LevelTooLow,
+    /// Operation on a closed stream.
+    StreamClosed,
+    /// `flush_mode` not recognized by `ZstdCompressionWriter.flush()`.
+    UnknownFlushMode(i64),
+    /// `flush_mode` not recognized by `ZstdCompressionObj.flush()`.
+    UnrecognizedFlushMode,
+    /// Underlying writer doesn't have a `fileno()`.
+    NoFileno,
+}
+
+impl From<CommonError> for PyErr {
+    fn from(err: CommonError) -> Self {
+        let compat = c_compat_errors();
+
+        match err {
+            CommonError::LevelTooHigh => PyValueError::new_err(format!(
+                "level must be less than {}",
+                zstd_safe::max_c_level() + 1
+            )),
+            CommonError::LevelTooLow => PyValueError::new_err(format!(
+                "level must be at least {}",
+                zstd_safe::min_c_level()
+            )),
+            CommonError::StreamClosed => PyValueError::new_err("stream is closed"),
+            // The C backend parses the mode as a C int.
+            CommonError::UnknownFlushMode(mode) if mode < 0 && !compat => {
+                PyOverflowError::new_err("can't convert negative int to unsigned")
+            }
+            CommonError::UnknownFlushMode(mode) => {
+                PyValueError::new_err(format!("unknown flush_mode: {}", mode as i32))
+            }
+            CommonError::UnrecognizedFlushMode => {
+                PyValueError::new_err("flush mode not recognized")
+            }
+            CommonError::NoFileno if compat => {
+                PyOSError::new_err("fileno not available on underlying writer")
+            }
+            CommonError::NoFileno => {
+                PyOSError::new_err("filenot not available on underlying writer")
+            }
+        }
+    }
+}
+
+/// How to react to input that fails to decompress.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OnError {
+    /// Raise an exception.
+    Raise,
+    /// Record the error and continue with the next frame.
+    Skip,
+}
+
+impl OnError {
+    /// Resolve the value of an `on_error` argument.
+    pub fn parse(value: &str) -> PyResult<Self> {
+        match value {
+            "raise" => Ok(Self::Raise),
+            "skip" => Ok(Self::Skip),
+            _ => Err(PyValueError::new_err(format!(
+                "on_error must be \"raise\" or \"skip\"; got {}",
+                value
+            ))),
+        }
+    }
+}
+
+/// Make the Rust backend raise the same exception types and messages as the
+/// C backend.
+///
+/// Returns the previous setting.
+#[pyfunction]
+fn set_c_compat_errors(enabled: bool) -> bool {
+    C_COMPAT_ERRORS.swap(enabled, Ordering::Relaxed)
+}
+
+/// Whether the Rust backend raises C backend compatible errors.
+#[pyfunction]
+fn get_c_compat_errors() -> bool {
+    c_compat_errors()
+}
+
+/// Emit a `ResourceWarning` from a destructor.
+///
+/// Errors (e.g. from warnings configured as errors) can't be propagated out
+/// of a destructor and are discarded.
+pub(crate) fn warn_resource(py: Python, message: &str) {
+    if let Ok(category) = py
+        .import("builtins")
+        .and_then(|m| m.getattr("ResourceWarning"))
+    {
+        let _ = PyErr::warn(py, category, message, 1);
+    }
+}
 
 pub(crate) fn init_module(py: Python, module: &PyModule) -> PyResult<()> {
     module.add("ZstdError", py.get_type::<ZstdError>())?;
+    module.add(
+        "ZstdDestinationTooSmallError",
+        py.get_type::<ZstdDestinationTooSmallError>(),
+    )?;
+
+    if let Ok(value) = std::env::var(C_COMPAT_ENV) {
+        let enabled = match value.trim() {
+            "" | "0" => false,
+            "1" => true,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "{} must be 0 or 1",
+                    C_COMPAT_ENV
+                )))
+            }
+        };
+        C_COMPAT_ERRORS.store(enabled, Ordering::Relaxed);
+    }
+
+    module.add_function(wrap_pyfunction!(get_c_compat_errors, module)?)?;
+    module.add_function(wrap_pyfunction!(set_c_compat_errors, module)?)?;
 
     Ok(())
 }
@@ -5,8 +5,15 @@
 // of the BSD license. See the LICENSE file for details.
 
 use {
-    crate::ZstdError,
-    pyo3::{buffer::PyBuffer, prelude::*, wrap_pyfunction},
+    crate::{constants::is_skippable_magic, ZstdError},
+    pyo3::{
+        buffer::PyBuffer,
+        class::PyObjectProtocol,
+        exceptions::PyValueError,
+        prelude::*,
+        types::{PyBytes, PyDict, PyList},
+        wrap_pyfunction,
+    },
 };
 
 #[pyclass(module = "zstandard.backend_rust")]
@@ -14,6 +21,23 @@ struct FrameParameters {
     header: zstd_sys::ZSTD_frameHeader,
 }
 
+#[pyproto]
+impl PyObjectProtocol for FrameParameters {
+    fn __repr__(&self) -> String {
+        format!(
+            "FrameParameters(content_size={}, window_size={}, dict_id={}, has_checksum={})",
+            self.header.frameContentSize,
+            self.header.windowSize,
+            self.header.dictID,
+            if self.header.checksumFlag != 0 {
+                "True"
+            } else {
+                "False"
+            },
+        )
+    }
+}
+
 #[pymethods]
 impl FrameParameters {
     #[getter]
@@ -66,11 +90,33 @@ fn frame_header_size(data: PyBuffer<u8>) -> PyResult<usize> {
     Ok(zresult)
 }
 
+/// Whether `data` begins with the magic number of a frame zstd recognizes.
+///
+/// Like `ZSTD_isFrame()`, skippable frames are considered zstd frames.
 #[pyfunction]
-fn get_frame_parameters(py: Python, buffer: PyBuffer<u8>) -> PyResult<Py<FrameParameters>> {
-    let raw_data = unsafe {
-        std::slice::from_raw_parts::<u8>(buffer.buf_ptr() as *const _, buffer.len_bytes())
-    };
+fn is_zstd_frame(data: PyBuffer<u8>) -> bool {
+    unsafe { zstd_sys::ZSTD_isFrame(data.buf_ptr(), data.len_bytes()) != 0 }
+}
+
+/// Whether `data` begins with the magic number of a skippable frame.
+#[pyfunction]
+fn is_skippable_frame(data: PyBuffer<u8>) -> bool {
+    let raw_data =
+        unsafe { std::slice::from_raw_parts::<u8>(data.buf_ptr() as *const _, data.len_bytes()) };
+
+    if raw_data.len() < 4 {
+        return false;
+    }
+
+    let magic = u32::from_le_bytes([raw_data[0], raw_data[1], raw_data[2], raw_data[3]]);
+
+    is_skippable_magic(magic)
+}
+
+/// Parse the header of the frame at the start of `data`.
+pub(crate) fn parse_frame_header(data: &PyBuffer<u8>) -> PyResult<zstd_sys::ZSTD_frameHeader> {
+    let raw_data =
+        unsafe { std::slice::from_raw_parts::<u8>(data.buf_ptr() as *const _, data.len_bytes()) };
 
     let mut header = zstd_sys::ZSTD_frameHeader {
         frameContentSize: 0,
@@ -96,8 +142,249 @@ fn get_frame_parameters(py: Python, buffer: PyBuffer<u8>) -> PyResult<Py<FramePa
             zresult
         )))
     } else {
-        Py::new(py, FrameParameters { header })
+        Ok(header)
+    }
+}
+
+#[pyfunction]
+fn get_frame_parameters(py: Python, buffer: PyBuffer<u8>) -> PyResult<Py<FrameParameters>> {
+    let header = parse_frame_header(&buffer)?;
+
+    Py::new(py, FrameParameters { header })
+}
+
+/// Check the header of a frame against a policy.
+///
+/// Returns a list of `(rule, limit, actual)` tuples describing each violated
+/// rule. An empty list means the frame conforms. Skippable frames always
+/// conform.
+#[pyfunction]
+fn validate_frame(py: Python, data: PyBuffer<u8>, policy: &PyDict) -> PyResult<PyObject> {
+    let mut max_window_size = None;
+    let mut require_checksum = false;
+    let mut allowed_dict_ids = None;
+    let mut max_content_size = None;
+
+    for (key, value) in policy.iter() {
+        match key.extract::<&str>()? {
+            "max_window_size" => max_window_size = Some(value.extract::<u64>()?),
+            "require_checksum" => require_checksum = value.extract::<bool>()?,
+            "allowed_dict_ids" => {
+                allowed_dict_ids = Some(
+                    value
+                        .iter()?
+                        .map(|id| id.and_then(|id| id.extract::<u32>()))
+                        .collect::<PyResult<Vec<_>>>()?,
+                )
+            }
+            "max_content_size" => max_content_size = Some(value.extract::<u64>()?),
+            key => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown frame policy rule: {}",
+                    key
+                )))
+            }
+        }
+    }
+
+    let header = parse_frame_header(&data)?;
+    let violations = PyList::empty(py);
+
+    if header.frameType == zstd_sys::ZSTD_frameType_e::ZSTD_skippableFrame {
+        return Ok(violations.into_py(py));
+    }
+
+    if let Some(limit) = max_window_size {
+        if header.windowSize > limit {
+            violations.append(("max_window_size", limit, header.windowSize))?;
+        }
     }
+
+    if require_checksum && header.checksumFlag == 0 {
+        violations.append(("require_checksum", true, false))?;
+    }
+
+    if let Some(allowed) = allowed_dict_ids {
+        if header.dictID != 0 && !allowed.contains(&header.dictID) {
+            violations.append(("allowed_dict_ids", allowed, header.dictID))?;
+        }
+    }
+
+    if let Some(limit) = max_content_size {
+        // A frame without a content size can't be shown to be within the cap.
+        if header.frameContentSize == zstd_sys::ZSTD_CONTENTSIZE_UNKNOWN as _ {
+            violations.append(("max_content_size", limit, py.None()))?;
+        } else if header.frameContentSize > limit {
+            violations.append(("max_content_size", limit, header.frameContentSize))?;
+        }
+    }
+
+    Ok(violations.into_py(py))
+}
+
+/// Smallest window descriptor byte describing a window of at least `size` bytes.
+fn window_descriptor(size: u64) -> PyResult<u8> {
+    for exponent in 0..=(zstd_sys::ZSTD_WINDOWLOG_MAX_64 - 10) as u8 {
+        let base = 1u64 << (10 + exponent);
+
+        for mantissa in 0..8u8 {
+            if base + mantissa as u64 * (base / 8) >= size {
+                return Ok(exponent << 3 | mantissa);
+            }
+        }
+    }
+
+    Err(ZstdError::new_err(format!(
+        "content size {} exceeds maximum window size",
+        size
+    )))
+}
+
+/// Rewrite the header of the frame in `data` without touching its blocks.
+///
+/// `dict_id` of 0 removes the dictionary ID field and `content_size` of -1
+/// removes the content size field. Checksums can be removed but not added,
+/// as that would require decompressing the frame.
+#[pyfunction(dict_id = "None", content_size = "None", checksum = "None")]
+fn rewrite_frame_header(
+    py: Python,
+    data: PyBuffer<u8>,
+    dict_id: Option<u32>,
+    content_size: Option<i64>,
+    checksum: Option<bool>,
+) -> PyResult<Py<PyBytes>> {
+    let header = parse_frame_header(&data)?;
+
+    if header.frameType == zstd_sys::ZSTD_frameType_e::ZSTD_skippableFrame {
+        return Err(ZstdError::new_err(
+            "cannot rewrite header of skippable frame",
+        ));
+    }
+
+    let raw_data =
+        unsafe { std::slice::from_raw_parts::<u8>(data.buf_ptr() as *const _, data.len_bytes()) };
+
+    let frame_size = unsafe {
+        zstd_sys::ZSTD_findFrameCompressedSize(raw_data.as_ptr() as *const _, raw_data.len())
+    };
+    if unsafe { zstd_sys::ZSTD_isError(frame_size) } != 0 {
+        return Err(ZstdError::new_err(format!(
+            "error finding frame size: {}",
+            zstd_safe::get_error_name(frame_size)
+        )));
+    }
+    if frame_size != raw_data.len() {
+        return Err(ZstdError::new_err(format!(
+            "data contains {} bytes after the frame",
+            raw_data.len() - frame_size
+        )));
+    }
+
+    let had_checksum = header.checksumFlag != 0;
+    let blocks =
+        &raw_data[header.headerSize as usize..frame_size - if had_checksum { 4 } else { 0 }];
+    let single_segment = raw_data[4] & 0x20 != 0;
+
+    let old_content_size = if header.frameContentSize == zstd_sys::ZSTD_CONTENTSIZE_UNKNOWN as _ {
+        None
+    } else {
+        Some(header.frameContentSize)
+    };
+
+    let new_content_size = match content_size {
+        None => old_content_size,
+        Some(-1) => None,
+        Some(size) if size < 0 => {
+            return Err(PyValueError::new_err(
+                "content_size must be non-negative or -1",
+            ))
+        }
+        Some(size) => {
+            let size = size as u64;
+
+            match old_content_size {
+                Some(old) if old != size => {
+                    return Err(ZstdError::new_err(format!(
+                        "frame content size is {}; cannot set to {}",
+                        old, size
+                    )));
+                }
+                None => {
+                    let bound = unsafe {
+                        zstd_sys::ZSTD_decompressBound(
+                            raw_data.as_ptr() as *const _,
+                            raw_data.len(),
+                        )
+                    };
+                    if size > bound {
+                        return Err(ZstdError::new_err(format!(
+                            "content size {} exceeds frame decompression bound of {}",
+                            size, bound
+                        )));
+                    }
+                }
+                _ => {}
+            }
+
+            Some(size)
+        }
+    };
+
+    let new_checksum = match checksum {
+        None => had_checksum,
+        Some(true) if !had_checksum => {
+            return Err(ZstdError::new_err(
+                "cannot add a checksum to a frame without one",
+            ));
+        }
+        Some(value) => value,
+    };
+
+    let new_dict_id = dict_id.unwrap_or(header.dictID);
+
+    // A single segment frame has no window descriptor and uses the content
+    // size as its window size, so it must stay single segment to keep its
+    // content size and gain a window descriptor to lose it.
+    let window = match (single_segment, new_content_size) {
+        (true, Some(_)) => None,
+        (true, None) => Some(window_descriptor(header.windowSize.max(1024))?),
+        (false, _) => Some(raw_data[5]),
+    };
+
+    let (dict_id_flag, dict_id_bytes): (u8, Vec<u8>) = match new_dict_id {
+        0 => (0, vec![]),
+        1..=0xff => (1, vec![new_dict_id as u8]),
+        0x100..=0xffff => (2, (new_dict_id as u16).to_le_bytes().to_vec()),
+        _ => (3, new_dict_id.to_le_bytes().to_vec()),
+    };
+
+    let (size_flag, size_bytes): (u8, Vec<u8>) = match new_content_size {
+        None => (0, vec![]),
+        Some(size) if size <= 0xff && window.is_none() => (0, vec![size as u8]),
+        Some(size) if (256..=65791).contains(&size) => {
+            (1, ((size - 256) as u16).to_le_bytes().to_vec())
+        }
+        Some(size) if size <= u32::MAX as u64 => (2, (size as u32).to_le_bytes().to_vec()),
+        Some(size) => (3, size.to_le_bytes().to_vec()),
+    };
+
+    let descriptor = size_flag << 6
+        | if window.is_none() { 0x20 } else { 0 }
+        | if new_checksum { 0x04 } else { 0 }
+        | dict_id_flag;
+
+    let mut frame = Vec::with_capacity(raw_data.len() + 16);
+    frame.extend_from_slice(&raw_data[0..4]);
+    frame.push(descriptor);
+    frame.extend(window);
+    frame.extend_from_slice(&dict_id_bytes);
+    frame.extend_from_slice(&size_bytes);
+    frame.extend_from_slice(blocks);
+    if new_checksum {
+        frame.extend_from_slice(&raw_data[frame_size - 4..frame_size]);
+    }
+
+    Ok(PyBytes::new(py, &frame).into())
 }
 
 pub(crate) fn init_module(module: &PyModule) -> PyResult<()> {
@@ -105,6 +392,10 @@ pub(crate) fn init_module(module: &PyModule) -> PyResult<()> {
     module.add_function(wrap_pyfunction!(frame_content_size, module)?)?;
     module.add_function(wrap_pyfunction!(frame_header_size, module)?)?;
     module.add_function(wrap_pyfunction!(get_frame_parameters, module)?)?;
+    module.add_function(wrap_pyfunction!(is_skippable_frame, module)?)?;
+    module.add_function(wrap_pyfunction!(is_zstd_frame, module)?)?;
+    module.add_function(wrap_pyfunction!(rewrite_frame_header, module)?)?;
+    module.add_function(wrap_pyfunction!(validate_frame, module)?)?;
 
     Ok(())
 }
@@ -0,0 +1,67 @@
+// Copyright (c) 2021-present, Gregory Szorc
+// All rights reserved.
+//
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+use {
+    crate::exceptions::ZstdError,
+    pyo3::{prelude::*, wrap_pyfunction},
+};
+
+/// Resolve `future` unless it was cancelled while work was running.
+#[pyfunction]
+fn resolve_future(future: &PyAny, result: PyObject, error: Option<&PyAny>) -> PyResult<()> {
+    if future.call_method0("cancelled")?.is_true()? {
+        return Ok(());
+    }
+
+    match error {
+        Some(error) => future.call_method1("set_exception", (error,))?,
+        None => future.call_method1("set_result", (result,))?,
+    };
+
+    Ok(())
+}
+
+/// Run `work` on a new thread and resolve an asyncio future from it.
+///
+/// `work` runs without the GIL. `finish` converts its output to the result of
+/// the future with the GIL held. The future belongs to the running event loop
+/// and is resolved through `call_soon_threadsafe()`.
+pub(crate) fn spawn_future<T, W, F>(py: Python, work: W, finish: F) -> PyResult<PyObject>
+where
+    T: Send + 'static,
+    W: FnOnce() -> T + Send + 'static,
+    F: FnOnce(Python, T) -> PyResult<PyObject> + Send + 'static,
+{
+    let event_loop: PyObject = py.import("asyncio")?.call0("get_running_loop")?.into();
+    let future = event_loop.call_method0(py, "create_future")?;
+    let callback: PyObject = wrap_pyfunction!(resolve_future, py)?.into();
+
+    let thread_future = future.clone_ref(py);
+
+    std::thread::Builder::new()
+        .name("zstd-future".to_string())
+        .spawn(move || {
+            let output = work();
+
+            Python::with_gil(|py| {
+                let (result, error) = match finish(py, output) {
+                    Ok(result) => (result, py.None()),
+                    Err(err) => (py.None(), err.instance(py).into_py(py)),
+                };
+
+                // This fails if the loop was closed, in which case nothing
+                // awaits the future.
+                let _ = event_loop.call_method1(
+                    py,
+                    "call_soon_threadsafe",
+                    (callback, thread_future, result, error),
+                );
+            });
+        })
+        .map_err(|err| ZstdError::new_err(format!("error spawning thread: {}", err)))?;
+
+    Ok(future)
+}
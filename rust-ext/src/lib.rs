@@ -8,6 +8,8 @@
 
 use pyo3::{prelude::*, types::PySet};
 
+#[cfg(feature = "block-api")]
+mod block;
 mod buffers;
 mod compression_chunker;
 mod compression_dict;
@@ -18,42 +20,161 @@ mod compressionobj;
 mod compressor;
 mod compressor_iterator;
 mod compressor_multi;
+mod compressor_split;
+mod config;
 mod constants;
 mod decompression_reader;
 mod decompression_writer;
 mod decompressionobj;
 mod decompressor;
+mod decompressor_frames;
 mod decompressor_iterator;
 mod decompressor_multi;
+mod decompressor_records;
+mod defaults;
+mod dict_registry;
 mod exceptions;
 mod frame_parameters;
+mod future;
+mod options;
+mod paths;
+mod recompress;
 mod stream;
+mod streaming_decoder;
+mod streaming_encoder;
 mod zstd_safe;
 
 use exceptions::ZstdError;
 
 const VERSION: &'static str = "0.16.0.dev0";
 
+/// Members of `backend_features`, as checked by `zstandard.require()`.
+///
+/// Besides the features shared with the C backend, this names the APIs and
+/// arguments only this backend implements.
+const FEATURES: &[&str] = &[
+    "allow_extra_data",
+    "as_memoryview",
+    "auto_write_size",
+    "bound_fallback",
+    "buffer_types",
+    "buffer_with_segments_builder",
+    "byte_counters",
+    "c_compat_errors",
+    "callable_sources",
+    "chunker_add_metadata",
+    "chunker_context_manager",
+    "chunker_flush_end_frame",
+    "compress_into",
+    "compress_vectored",
+    "config",
+    "content_size_checks",
+    "copy_stream_max_time_ms",
+    "copy_stream_multiple_writers",
+    "copy_stream_result",
+    "deadline",
+    "decompress_into",
+    "decompression_writer_overrides",
+    "decompressobj_reset",
+    "defaults",
+    "deterministic_ref_prefix",
+    "dict_data_list",
+    "dict_info",
+    "dict_load_method",
+    "dict_precompute_in_use",
+    "dict_registry",
+    "dict_shared_memory",
+    "direct_fd",
+    "effective_level",
+    "fd_streams",
+    "flush_result",
+    "format",
+    "frame_dict",
+    "frame_index",
+    "frame_predicates",
+    "frame_progression",
+    "hasher",
+    "ignore_checksum",
+    "ignore_dict_id",
+    "iter_frames",
+    "iterable_sources",
+    "ldm_auto",
+    "ldm_disable",
+    "ldm_parameters",
+    "match_finder_parameters",
+    "max_decompress_per_call",
+    "max_native_memory",
+    "max_window_log",
+    "multi_buffer_async",
+    "multi_compress_to_buffer",
+    "multi_decompress_out",
+    "multi_decompress_to_buffer",
+    "negative_levels",
+    "on_error",
+    "output_size_hint",
+    "pad_to",
+    "parameter_bounds",
+    "parameters_as_dict",
+    "parameters_builder",
+    "parameters_equality",
+    "parameters_from_frame",
+    "parameters_replace",
+    "patch_content_size",
+    "patch_from",
+    "paths",
+    "push_params",
+    "read_across_frames",
+    "read_records",
+    "recompress",
+    "repr",
+    "require_checksum",
+    "return_index",
+    "rewrite_frame_header",
+    "skippable_frame_header",
+    "split_stream",
+    "stable_buffers",
+    "streaming_decoder",
+    "streaming_encoder",
+    "streaming_fallback",
+    "target_cblock_size",
+    "tolerate_padding",
+    "train_algorithm",
+    "train_buffer_samples",
+    "train_sample_count",
+    "train_sample_sizes",
+    "train_shrink_dict",
+    "unfinished_frame_warning",
+    "unused_data",
+    "validate_frame",
+    "window_usage",
+    "write_empty_frame",
+    "write_vectored",
+];
+
 #[pymodule]
 fn backend_rust(py: Python, module: &PyModule) -> PyResult<()> {
-    let features = PySet::new(
-        py,
-        &[
-            "buffer_types",
-            "multi_compress_to_buffer",
-            "multi_decompress_to_buffer",
-        ],
-    )?;
+    let features = PySet::new(py, FEATURES)?;
+    if cfg!(feature = "block-api") {
+        features.add("block_api")?;
+    }
     module.add("backend_features", features)?;
 
+    #[cfg(feature = "block-api")]
+    crate::block::init_module(module)?;
     crate::buffers::init_module(module)?;
     crate::compression_dict::init_module(module)?;
     crate::compression_parameters::init_module(module)?;
     crate::compressor::init_module(module)?;
     crate::constants::init_module(py, module)?;
     crate::decompressor::init_module(module)?;
+    crate::defaults::init_module(module)?;
+    crate::dict_registry::init_module(py, module)?;
     crate::exceptions::init_module(py, module)?;
     crate::frame_parameters::init_module(module)?;
+    crate::paths::init_module(module)?;
+    crate::recompress::init_module(module)?;
+    crate::streaming_decoder::init_module(module)?;
+    crate::streaming_encoder::init_module(module)?;
 
     Ok(())
 }
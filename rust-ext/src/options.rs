@@ -0,0 +1,31 @@
+// Copyright (c) 2021-present, Gregory Szorc
+// All rights reserved.
+//
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+use pyo3::{exceptions::PyTypeError, prelude::*, types::PyDict};
+
+/// Feed keyword-only options in `kwargs` to `apply`.
+///
+/// Functions with many options collect them with `**kwargs` into a struct.
+/// `apply` returns `false` for a key it doesn't know, which raises the same
+/// `TypeError` as an unknown keyword to a declared signature.
+pub(crate) fn apply_kwargs<'p>(
+    function: &str,
+    kwargs: Option<&'p PyDict>,
+    mut apply: impl FnMut(&str, &'p PyAny) -> PyResult<bool>,
+) -> PyResult<()> {
+    for (key, value) in kwargs.into_iter().flatten() {
+        let key = key.extract::<&str>()?;
+
+        if !apply(key, value)? {
+            return Err(PyTypeError::new_err(format!(
+                "{} got an unexpected keyword argument: {}",
+                function, key
+            )));
+        }
+    }
+
+    Ok(())
+}
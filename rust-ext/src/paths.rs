@@ -0,0 +1,479 @@
+// Copyright (c) 2021-present, Gregory Szorc
+// All rights reserved.
+//
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+use {
+    crate::{
+        constants::validate_level,
+        defaults,
+        exceptions::ZstdError,
+        zstd_safe::{CCtx, DCtx},
+    },
+    pyo3::{
+        class::PyObjectProtocol, exceptions::PyValueError, prelude::*, types::PyDict,
+        wrap_pyfunction,
+    },
+    rayon::prelude::*,
+    std::{
+        collections::HashSet,
+        ffi::OsString,
+        fs::File,
+        io::{Read, Write},
+        path::{Path, PathBuf},
+    },
+};
+
+/// Outcome of processing a single path.
+#[pyclass(module = "zstandard.backend_rust")]
+pub struct ZstdPathResult {
+    /// Path that was read.
+    #[pyo3(get)]
+    source: String,
+    /// Path that was written.
+    #[pyo3(get)]
+    dest: String,
+    #[pyo3(get)]
+    bytes_read: u64,
+    #[pyo3(get)]
+    bytes_written: u64,
+    /// Why processing failed. Partially written output is removed.
+    #[pyo3(get)]
+    error: Option<String>,
+}
+
+#[pyproto]
+impl PyObjectProtocol for ZstdPathResult {
+    fn __repr__(&self) -> PyResult<String> {
+        Python::with_gil(|py| {
+            Ok(format!(
+                "ZstdPathResult(source={}, dest={}, bytes_read={}, bytes_written={}, error={})",
+                self.source.to_object(py).as_ref(py).repr()?,
+                self.dest.to_object(py).as_ref(py).repr()?,
+                self.bytes_read,
+                self.bytes_written,
+                self.error.to_object(py).as_ref(py).repr()?,
+            ))
+        })
+    }
+}
+
+/// Convert a `str` or path-like object to a path.
+fn to_path(py: Python, path: &PyAny) -> PyResult<PathBuf> {
+    Ok(PathBuf::from(
+        py.import("os")?
+            .call1("fspath", (path,))?
+            .extract::<String>()?,
+    ))
+}
+
+/// Resolve `paths` to `(source, dest)` pairs.
+///
+/// Destinations are in `dest_dir` and named by `dest_name` from the source
+/// file name.
+fn path_pairs(
+    py: Python,
+    paths: &PyAny,
+    dest_dir: &Path,
+    dest_name: fn(&Path) -> PyResult<OsString>,
+) -> PyResult<Vec<(PathBuf, PathBuf)>> {
+    let mut pairs = vec![];
+    let mut seen = HashSet::new();
+
+    for path in paths.iter()? {
+        let source = to_path(py, path?)?;
+
+        let dest = dest_dir.join(dest_name(&source)?);
+        if !seen.insert(dest.clone()) {
+            return Err(PyValueError::new_err(format!(
+                "multiple paths would be written to {}",
+                dest.display()
+            )));
+        }
+
+        pairs.push((source, dest));
+    }
+
+    Ok(pairs)
+}
+
+fn file_name(path: &Path) -> PyResult<OsString> {
+    path.file_name()
+        .map(|name| name.to_os_string())
+        .ok_or_else(|| PyValueError::new_err(format!("path has no file name: {}", path.display())))
+}
+
+/// Number of workers for `threads` and `jobs` inputs.
+fn pool_size(threads: isize, jobs: usize) -> usize {
+    let thread_count = if threads < 0 {
+        num_cpus::get()
+    } else if threads < 2 {
+        1
+    } else {
+        threads as _
+    };
+
+    // More threads than inputs makes no sense.
+    std::cmp::min(thread_count, jobs)
+}
+
+/// Run `process` on every `(source, dest)` pair on a pool with one worker per
+/// context in `contexts`.
+///
+/// `process` is given the opened source and the created destination file. If
+/// it fails, the destination is removed.
+fn process_paths<C: Sync>(
+    py: Python,
+    pairs: Vec<(PathBuf, PathBuf)>,
+    contexts: Vec<C>,
+    process: impl Fn(&C, &mut File, &mut File, &Path, &Path) -> Result<(u64, u64), String> + Sync,
+) -> PyResult<Vec<ZstdPathResult>> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(contexts.len())
+        .build()
+        .map_err(|err| ZstdError::new_err(format!("error initializing thread pool: {}", err)))?;
+
+    let outcomes: Vec<Result<(u64, u64), String>> = py.allow_threads(|| {
+        pool.install(|| {
+            pairs
+                .par_iter()
+                .map(|(source, dest)| {
+                    let context = &contexts[pool.current_thread_index().unwrap()];
+
+                    let mut ifh = File::open(source)
+                        .map_err(|err| format!("error opening {}: {}", source.display(), err))?;
+                    let mut ofh = File::create(dest)
+                        .map_err(|err| format!("error creating {}: {}", dest.display(), err))?;
+
+                    let result = process(context, &mut ifh, &mut ofh, source, dest);
+
+                    if result.is_err() {
+                        drop(ofh);
+                        let _ = std::fs::remove_file(dest);
+                    }
+
+                    result
+                })
+                .collect()
+        })
+    });
+
+    Ok(pairs
+        .into_iter()
+        .zip(outcomes)
+        .map(|((source, dest), outcome)| {
+            let (bytes_read, bytes_written, error) = match outcome {
+                Ok((read, written)) => (read, written, None),
+                Err(msg) => (0, 0, Some(msg)),
+            };
+
+            ZstdPathResult {
+                source: source.to_string_lossy().into_owned(),
+                dest: dest.to_string_lossy().into_owned(),
+                bytes_read,
+                bytes_written,
+                error,
+            }
+        })
+        .collect())
+}
+
+/// Compress `ifh` into `ofh`.
+///
+/// Returns the number of bytes read and written.
+fn compress_file(
+    cctx: &CCtx,
+    ifh: &mut File,
+    ofh: &mut File,
+    source: &Path,
+    dest: &Path,
+) -> Result<(u64, u64), String> {
+    let size = ifh
+        .metadata()
+        .map_err(|err| format!("error reading {}: {}", source.display(), err))?
+        .len();
+
+    cctx.reset();
+    cctx.set_pledged_source_size(size)
+        .map_err(|msg| format!("error setting source size: {}", msg))?;
+
+    let mut in_buffer = vec![0; zstd_safe::cstream_in_size()];
+    let mut out_buffer = vec![0; zstd_safe::cstream_out_size()];
+    let mut total_read = 0;
+    let mut total_write = 0;
+
+    loop {
+        let read_size = ifh
+            .read(&mut in_buffer)
+            .map_err(|err| format!("error reading {}: {}", source.display(), err))?;
+        total_read += read_size as u64;
+
+        let end_mode = if read_size == 0 {
+            zstd_sys::ZSTD_EndDirective::ZSTD_e_end
+        } else {
+            zstd_sys::ZSTD_EndDirective::ZSTD_e_continue
+        };
+
+        let mut chunk = &in_buffer[0..read_size];
+
+        loop {
+            let (written, remaining, more) = cctx
+                .compress_chunk_into(chunk, end_mode, &mut out_buffer)
+                .map_err(|msg| format!("zstd compress error: {}", msg))?;
+            chunk = remaining;
+
+            ofh.write_all(&out_buffer[0..written])
+                .map_err(|err| format!("error writing {}: {}", dest.display(), err))?;
+            total_write += written as u64;
+
+            let done = if end_mode == zstd_sys::ZSTD_EndDirective::ZSTD_e_end {
+                !more
+            } else {
+                chunk.is_empty()
+            };
+            if done {
+                break;
+            }
+        }
+
+        if read_size == 0 {
+            return Ok((total_read, total_write));
+        }
+    }
+}
+
+/// Limits guarding `decompress_paths()` against decompression bombs.
+#[derive(Default)]
+struct SafetyLimits {
+    /// Maximum decompressed size of a file.
+    max_output_size: Option<u64>,
+    /// Maximum ratio of decompressed to compressed size of a file.
+    max_ratio: Option<f64>,
+    /// Maximum window size of frames. 0 uses the zstd default.
+    max_window_size: usize,
+}
+
+impl SafetyLimits {
+    fn from_dict(limits: Option<&PyDict>) -> PyResult<Self> {
+        let mut result = Self::default();
+
+        for (key, value) in limits.into_iter().flatten() {
+            match key.extract::<&str>()? {
+                "max_output_size" => result.max_output_size = Some(value.extract()?),
+                "max_ratio" => {
+                    let ratio = value.extract::<f64>()?;
+                    if ratio.is_nan() || ratio <= 0.0 {
+                        return Err(PyValueError::new_err("max_ratio must be positive"));
+                    }
+                    result.max_ratio = Some(ratio);
+                }
+                "max_window_size" => result.max_window_size = value.extract()?,
+                key => {
+                    return Err(PyValueError::new_err(format!(
+                        "unknown safety limit: {}",
+                        key
+                    )))
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// Decompress the zstd frames in `ifh` into `ofh`.
+///
+/// Returns the number of bytes read and written.
+fn decompress_file(
+    dctx: &DCtx,
+    limits: &SafetyLimits,
+    ifh: &mut File,
+    ofh: &mut File,
+    source: &Path,
+    dest: &Path,
+) -> Result<(u64, u64), String> {
+    let size = ifh
+        .metadata()
+        .map_err(|err| format!("error reading {}: {}", source.display(), err))?
+        .len();
+
+    let max_output_size = match (limits.max_output_size, limits.max_ratio) {
+        (Some(size_limit), Some(ratio)) => {
+            Some(std::cmp::min(size_limit, (size as f64 * ratio) as u64))
+        }
+        (Some(size_limit), None) => Some(size_limit),
+        (None, Some(ratio)) => Some((size as f64 * ratio) as u64),
+        (None, None) => None,
+    };
+
+    dctx.reset()
+        .map_err(|msg| format!("unable to reset decompression context: {}", msg))?;
+
+    let mut in_data = vec![0; zstd_safe::dstream_in_size()];
+    let mut out_data = vec![0; zstd_safe::dstream_out_size()];
+    let mut frame_complete = true;
+    let mut total_read = 0;
+    let mut total_write = 0;
+
+    loop {
+        let read_size = ifh
+            .read(&mut in_data)
+            .map_err(|err| format!("error reading {}: {}", source.display(), err))?;
+        if read_size == 0 {
+            break;
+        }
+        total_read += read_size as u64;
+
+        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+            src: in_data.as_ptr() as *const _,
+            size: read_size,
+            pos: 0,
+        };
+
+        loop {
+            let mut out_buffer = zstd_sys::ZSTD_outBuffer {
+                dst: out_data.as_mut_ptr() as *mut _,
+                size: out_data.len(),
+                pos: 0,
+            };
+
+            let zresult = dctx
+                .decompress_buffers(&mut out_buffer, &mut in_buffer)
+                .map_err(|msg| format!("zstd decompress error: {}", msg))?;
+            frame_complete = zresult == 0;
+
+            total_write += out_buffer.pos as u64;
+            if let Some(limit) = max_output_size {
+                if total_write > limit {
+                    return Err(format!(
+                        "decompressed size exceeds safety limit of {} bytes",
+                        limit
+                    ));
+                }
+            }
+
+            ofh.write_all(&out_data[0..out_buffer.pos])
+                .map_err(|err| format!("error writing {}: {}", dest.display(), err))?;
+
+            // A full output buffer may mean the decompressor has more to emit,
+            // unless the frame is complete.
+            if in_buffer.pos == in_buffer.size
+                && (frame_complete || out_buffer.pos < out_buffer.size)
+            {
+                break;
+            }
+        }
+    }
+
+    if !frame_complete {
+        return Err("input ends with an incomplete frame".to_string());
+    }
+
+    Ok((total_read, total_write))
+}
+
+/// Compress each file in `paths` to a `.zst` file of the same name in
+/// `dest_dir`.
+///
+/// Files are read, compressed and written by a pool of `threads` workers
+/// without holding the GIL. A negative value uses one worker per CPU. Returns
+/// a `ZstdPathResult` per path, in input order. Failures are reported in the
+/// result's `error` attribute instead of raising.
+#[pyfunction(paths, dest_dir, level = "None", threads = "0")]
+fn compress_paths(
+    py: Python,
+    paths: &PyAny,
+    dest_dir: &PyAny,
+    level: Option<i32>,
+    threads: isize,
+) -> PyResult<Vec<ZstdPathResult>> {
+    let level = validate_level(level.unwrap_or_else(|| defaults::defaults().level))?;
+
+    let dest_dir = to_path(py, dest_dir)?;
+    let pairs = path_pairs(py, paths, &dest_dir, |source| {
+        let mut name = file_name(source)?;
+        name.push(".zst");
+        Ok(name)
+    })?;
+
+    if pairs.is_empty() {
+        return Ok(vec![]);
+    }
+
+    std::fs::create_dir_all(&dest_dir)?;
+
+    let thread_count = pool_size(threads, pairs.len());
+    let mut cctxs = Vec::with_capacity(thread_count);
+    for _ in 0..thread_count {
+        let cctx = CCtx::new().map_err(ZstdError::new_err)?;
+        cctx.set_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel, level)
+            .map_err(|msg| {
+                ZstdError::new_err(format!("unable to set compression parameter: {}", msg))
+            })?;
+        cctxs.push(cctx);
+    }
+
+    process_paths(py, pairs, cctxs, compress_file)
+}
+
+/// Decompress each `.zst` file in `paths` to a file of the same name without
+/// the `.zst` extension in `dest_dir`.
+///
+/// Works like `compress_paths()`. Files may contain multiple frames.
+///
+/// `safety_limits` is a dict bounding the output of each file, to guard
+/// against decompression bombs. Its keys are `max_output_size` (bytes),
+/// `max_ratio` (decompressed size relative to the compressed file size) and
+/// `max_window_size` (bytes, as for `ZstdDecompressor`). A file exceeding a
+/// limit fails and its output is removed.
+#[pyfunction(paths, dest_dir, threads = "0", safety_limits = "None")]
+fn decompress_paths(
+    py: Python,
+    paths: &PyAny,
+    dest_dir: &PyAny,
+    threads: isize,
+    safety_limits: Option<&PyDict>,
+) -> PyResult<Vec<ZstdPathResult>> {
+    let limits = SafetyLimits::from_dict(safety_limits)?;
+
+    let dest_dir = to_path(py, dest_dir)?;
+    let pairs = path_pairs(py, paths, &dest_dir, |source| {
+        let name = file_name(source)?;
+
+        match name.to_str().and_then(|name| name.strip_suffix(".zst")) {
+            Some(stem) if !stem.is_empty() => Ok(stem.into()),
+            _ => Err(PyValueError::new_err(format!(
+                "path does not have a .zst extension: {}",
+                source.display()
+            ))),
+        }
+    })?;
+
+    if pairs.is_empty() {
+        return Ok(vec![]);
+    }
+
+    std::fs::create_dir_all(&dest_dir)?;
+
+    let thread_count = pool_size(threads, pairs.len());
+    let mut dctxs = Vec::with_capacity(thread_count);
+    for _ in 0..thread_count {
+        let dctx = DCtx::new().map_err(ZstdError::new_err)?;
+        dctx.set_max_window_size(limits.max_window_size)
+            .map_err(|msg| ZstdError::new_err(format!("unable to set max window size: {}", msg)))?;
+        dctxs.push(dctx);
+    }
+
+    process_paths(py, pairs, dctxs, |dctx, ifh, ofh, source, dest| {
+        decompress_file(dctx, &limits, ifh, ofh, source, dest)
+    })
+}
+
+pub(crate) fn init_module(module: &PyModule) -> PyResult<()> {
+    module.add_class::<ZstdPathResult>()?;
+    module.add_function(wrap_pyfunction!(compress_paths, module)?)?;
+    module.add_function(wrap_pyfunction!(decompress_paths, module)?)?;
+
+    Ok(())
+}
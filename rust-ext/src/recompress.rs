@@ -0,0 +1,174 @@
+// Copyright (c) 2021-present, Gregory Szorc
+// All rights reserved.
+//
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+use {
+    crate::{
+        constants::validate_level,
+        exceptions::ZstdError,
+        zstd_safe::{CCtx, DCtx},
+    },
+    pyo3::{exceptions::PyValueError, prelude::*, types::PyBytes, wrap_pyfunction},
+};
+
+/// Decompress `source` and feed the result to `cctx`, appending compressed
+/// output to `dest`.
+///
+/// Returns whether the decompressor ended on a frame boundary.
+fn pump(
+    dctx: &DCtx,
+    cctx: &CCtx,
+    source: &[u8],
+    decompressed: &mut Vec<u8>,
+    dest: &mut Vec<u8>,
+) -> Result<bool, String> {
+    let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+        src: source.as_ptr() as *const _,
+        size: source.len(),
+        pos: 0,
+    };
+
+    loop {
+        decompressed.clear();
+        let zresult = dctx
+            .decompress_into_vec(decompressed, &mut in_buffer)
+            .map_err(|msg| format!("zstd decompress error: {}", msg))?;
+
+        let mut mid_buffer = zstd_sys::ZSTD_inBuffer {
+            src: decompressed.as_ptr() as *const _,
+            size: decompressed.len(),
+            pos: 0,
+        };
+
+        while mid_buffer.pos < mid_buffer.size {
+            if dest.len() == dest.capacity() {
+                dest.reserve(dest.capacity());
+            }
+
+            cctx.compress_into_vec(
+                dest,
+                &mut mid_buffer,
+                zstd_sys::ZSTD_EndDirective::ZSTD_e_continue,
+            )
+            .map_err(|msg| format!("zstd compress error: {}", msg))?;
+        }
+
+        // A full output buffer may mean the decompressor has more to emit.
+        if in_buffer.pos == in_buffer.size && decompressed.len() < decompressed.capacity() {
+            return Ok(zresult == 0);
+        }
+    }
+}
+
+/// Recompress zstd data read from `ifh` into a single frame written to `ofh`.
+///
+/// Decompression and compression are coupled in native code without the
+/// GIL, so only compressed data passes through Python. Returns a tuple of
+/// `(bytes_read, bytes_written)`.
+#[pyfunction(
+    ifh,
+    ofh,
+    level = "19",
+    threads = "0",
+    read_size = "None",
+    write_size = "None"
+)]
+fn recompress(
+    py: Python,
+    ifh: &PyAny,
+    ofh: &PyAny,
+    level: i32,
+    threads: i32,
+    read_size: Option<usize>,
+    write_size: Option<usize>,
+) -> PyResult<(usize, usize)> {
+    validate_level(level)?;
+
+    if !ifh.hasattr("read")? {
+        return Err(PyValueError::new_err(
+            "first argument must have a read() method",
+        ));
+    }
+    if !ofh.hasattr("write")? {
+        return Err(PyValueError::new_err(
+            "second argument must have a write() method",
+        ));
+    }
+
+    let threads = if threads < 0 {
+        num_cpus::get() as i32
+    } else {
+        threads
+    };
+
+    let read_size = read_size.unwrap_or_else(zstd_safe::dstream_in_size);
+    let write_size = write_size.unwrap_or_else(zstd_safe::cstream_out_size);
+
+    let dctx = DCtx::new().map_err(ZstdError::new_err)?;
+    let cctx = CCtx::new().map_err(ZstdError::new_err)?;
+
+    cctx.set_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel, level)
+        .and_then(|_| cctx.set_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_nbWorkers, threads))
+        .map_err(|msg| {
+            ZstdError::new_err(format!("unable to set compression parameter: {}", msg))
+        })?;
+
+    let mut decompressed = Vec::with_capacity(zstd_safe::dstream_out_size());
+    let mut dest = Vec::with_capacity(write_size);
+    let mut frame_complete = true;
+    let mut total_read = 0;
+    let mut total_write = 0;
+
+    loop {
+        let read_object = ifh.call_method1("read", (read_size,))?;
+        let read_bytes: &PyBytes = read_object.downcast()?;
+        let read_data = read_bytes.as_bytes();
+
+        if read_data.is_empty() {
+            break;
+        }
+
+        total_read += read_data.len();
+
+        frame_complete = py
+            .allow_threads(|| pump(&dctx, &cctx, read_data, &mut decompressed, &mut dest))
+            .map_err(ZstdError::new_err)?;
+
+        if !dest.is_empty() {
+            ofh.call_method1("write", (PyBytes::new(py, &dest),))?;
+            total_write += dest.len();
+            dest.clear();
+        }
+    }
+
+    if !frame_complete {
+        return Err(ZstdError::new_err("input ends with an incomplete frame"));
+    }
+
+    loop {
+        let (chunk, _, call_again) = cctx
+            .compress_chunk(&[], zstd_sys::ZSTD_EndDirective::ZSTD_e_end, write_size)
+            .map_err(|msg| {
+                ZstdError::new_err(format!("error ending compression stream: {}", msg))
+            })?;
+
+        if !chunk.is_empty() {
+            ofh.call_method1("write", (PyBytes::new(py, &chunk),))?;
+            total_write += chunk.len();
+        }
+
+        if !call_again {
+            break;
+        }
+    }
+
+    Ok((total_read, total_write))
+}
+
+pub(crate) fn init_module(module: &PyModule) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(recompress, module)?)?;
+
+    Ok(())
+}
@@ -5,7 +5,16 @@
 // of the BSD license. See the LICENSE file for details.
 
 use {
-    pyo3::{buffer::PyBuffer, exceptions::PyValueError, prelude::*},
+    crate::options::apply_kwargs,
+    pyo3::{
+        basic::CompareOp,
+        buffer::PyBuffer,
+        class::{PyIterProtocol, PyObjectProtocol, PySequenceProtocol},
+        exceptions::PyValueError,
+        prelude::*,
+        types::{PyBool, PyByteArray, PyBytes, PyDict, PyLong, PySlice, PyTuple},
+        PyNativeType,
+    },
     zstd_sys::ZSTD_inBuffer,
 };
 
@@ -27,9 +36,23 @@ pub trait InBufferSource {
     fn finished(&self) -> bool;
 }
 
-/// A data source where data is obtaine by calling `read()`.
+/// How a `ReadSource` obtains the next chunk of data.
+enum ReadMethod {
+    /// Call `read(read_size)` on the source.
+    Read,
+    /// Call the source with the read size.
+    Call,
+    /// Advance an iterator over the source.
+    Next(PyObject),
+}
+
+/// A data source where data is obtained in chunks of unknown size.
+///
+/// An empty chunk from `read()` or a callable, or an exhausted iterator,
+/// signals EOF.
 struct ReadSource {
     source: PyObject,
+    method: ReadMethod,
     buffer: Option<PyBuffer<u8>>,
     read_size: usize,
     finished: bool,
@@ -57,8 +80,28 @@ impl InBufferSource for ReadSource {
             }))
         // Attempt to read new data.
         } else {
-            let data = self.source.call_method1(py, "read", (self.read_size,))?;
-            let buffer = PyBuffer::get(data.as_ref(py))?;
+            let buffer = match &self.method {
+                ReadMethod::Read => PyBuffer::get(
+                    self.source
+                        .call_method1(py, "read", (self.read_size,))?
+                        .as_ref(py),
+                )?,
+                ReadMethod::Call => {
+                    PyBuffer::get(self.source.call1(py, (self.read_size,))?.as_ref(py))?
+                }
+                // Iterators may yield empty parts, so only exhaustion is EOF.
+                ReadMethod::Next(it) => loop {
+                    match it.as_ref(py).iter()?.next() {
+                        Some(data) => {
+                            let buffer: PyBuffer<u8> = PyBuffer::get(data?)?;
+                            if buffer.len_bytes() > 0 {
+                                break buffer;
+                            }
+                        }
+                        None => break PyBuffer::get(PyBytes::new(py, &[]))?,
+                    }
+                },
+            };
 
             if buffer.len_bytes() == 0 {
                 self.finished = true;
@@ -140,22 +183,314 @@ pub(crate) fn make_in_buffer_source(
     if source.hasattr("read")? {
         Ok(Box::new(ReadSource {
             source: source.into_py(py),
+            method: ReadMethod::Read,
             buffer: None,
             read_size,
             finished: false,
             offset: 0,
         }))
-    } else {
-        let buffer = PyBuffer::get(source).map_err(|_| {
-            PyValueError::new_err(
-                "must pass an object with a read() method or conforms to buffer protocol",
-            )
-        })?;
-
+    } else if let Ok(buffer) = PyBuffer::get(source) {
         Ok(Box::new(BufferSource {
             source: source.into_py(py),
             buffer,
             offset: 0,
         }))
+    } else if source.is_callable() {
+        Ok(Box::new(ReadSource {
+            source: source.into_py(py),
+            method: ReadMethod::Call,
+            buffer: None,
+            read_size,
+            finished: false,
+            offset: 0,
+        }))
+    } else if let Ok(it) = source.iter() {
+        Ok(Box::new(ReadSource {
+            source: source.into_py(py),
+            method: ReadMethod::Next(it.into_py(py)),
+            buffer: None,
+            read_size,
+            finished: false,
+            offset: 0,
+        }))
+    } else {
+        Err(PyValueError::new_err(
+            "must pass an object with a read() method, a callable returning chunks, an iterable of chunks, or conforms to buffer protocol",
+        ))
+    }
+}
+
+/// Resolve a stream factory argument that may be a raw file descriptor.
+///
+/// Integers are wrapped in an unbuffered `io.FileIO` opened with `mode`. The
+/// wrapper owns the descriptor only if `closefd` is true. Other objects are
+/// returned as is.
+pub(crate) fn resolve_fd<'p>(
+    py: Python<'p>,
+    obj: &'p PyAny,
+    mode: &str,
+    closefd: bool,
+) -> PyResult<&'p PyAny> {
+    if !obj.is_instance::<PyLong>()? || obj.is_instance::<PyBool>()? {
+        return Ok(obj);
     }
+
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("closefd", closefd)?;
+
+    py.import("io")?
+        .getattr("FileIO")?
+        .call((obj, mode), Some(kwargs))
+}
+
+/// Describes how output chunks are handed to Python.
+pub(crate) enum ChunkOutput {
+    /// Each chunk is a new `bytes` instance.
+    Bytes,
+    /// Chunks are written into a reusable `bytearray` and emitted as
+    /// `memoryview` slices of it.
+    ///
+    /// An emitted view is only valid until the next chunk is emitted.
+    MemoryView(Py<PyByteArray>),
+}
+
+impl ChunkOutput {
+    pub fn new(py: Python, as_memoryview: bool, size: usize) -> PyResult<Self> {
+        if as_memoryview {
+            let buffer = PyByteArray::new_with(py, size, |_| Ok(()))?;
+
+            Ok(Self::MemoryView(buffer.into()))
+        } else {
+            Ok(Self::Bytes)
+        }
+    }
+
+    pub fn clone_ref(&self, py: Python) -> Self {
+        match self {
+            Self::Bytes => Self::Bytes,
+            Self::MemoryView(buffer) => Self::MemoryView(buffer.clone_ref(py)),
+        }
+    }
+
+    /// Convert a chunk of data to a Python object.
+    pub fn emit(&mut self, py: Python, data: &[u8]) -> PyResult<PyObject> {
+        match self {
+            Self::Bytes => Ok(PyBytes::new(py, data).into_py(py)),
+            Self::MemoryView(buffer) => {
+                if buffer.as_ref(py).len() < data.len() {
+                    // Views of the old buffer may still be exported, which
+                    // prevents resizing. So swap in a new buffer.
+                    *buffer = PyByteArray::new_with(py, data.len(), |_| Ok(()))?.into();
+                }
+
+                let buffer = buffer.as_ref(py);
+
+                unsafe {
+                    buffer.as_bytes_mut()[0..data.len()].copy_from_slice(data);
+                }
+
+                let view = py
+                    .import("builtins")?
+                    .getattr("memoryview")?
+                    .call1((buffer,))?;
+
+                Ok(view
+                    .get_item(PySlice::new(py, 0, data.len() as _, 1))?
+                    .into_py(py))
+            }
+        }
+    }
+}
+
+/// Keyword-only options to `copy_stream()`.
+#[derive(Default)]
+pub(crate) struct CopyStreamOptions<'p> {
+    pub hasher: Option<&'p PyAny>,
+    pub max_time_ms: Option<u64>,
+    pub resume: bool,
+}
+
+impl<'p> CopyStreamOptions<'p> {
+    pub fn from_kwargs(function: &str, kwargs: Option<&'p PyDict>) -> PyResult<Self> {
+        let mut options = Self::default();
+
+        apply_kwargs(function, kwargs, |key, value| {
+            match key {
+                "hasher" => options.hasher = value.extract()?,
+                "max_time_ms" => options.max_time_ms = value.extract()?,
+                "resume" => options.resume = value.extract()?,
+                _ => return Ok(false),
+            }
+
+            Ok(true)
+        })?;
+
+        check_hasher(options.hasher)?;
+
+        Ok(options)
+    }
+}
+
+/// Tracks the time slice of a `copy_stream()` call.
+pub(crate) struct CopyDeadline {
+    start: std::time::Instant,
+    limit: Option<std::time::Duration>,
+}
+
+impl CopyDeadline {
+    pub fn new(max_time_ms: Option<u64>) -> Self {
+        Self {
+            start: std::time::Instant::now(),
+            limit: max_time_ms.map(std::time::Duration::from_millis),
+        }
+    }
+
+    /// Whether the time slice has been exhausted.
+    pub fn expired(&self) -> bool {
+        matches!(self.limit, Some(limit) if self.start.elapsed() >= limit)
+    }
+}
+
+/// Ensure an object passed as `hasher` looks like a `hashlib` hash object.
+pub(crate) fn check_hasher(hasher: Option<&PyAny>) -> PyResult<()> {
+    if let Some(hasher) = hasher {
+        if !hasher.hasattr("update")? || !hasher.hasattr("digest")? {
+            return Err(PyValueError::new_err(
+                "hasher must have update() and digest() methods",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Result of a `copy_stream()` call.
+///
+/// Unpacks and compares as the tuple returned by other backends:
+/// `(bytes_read, bytes_written)`, followed by the digest if a hasher was
+/// given and whether the copy finished if it had a time slice.
+#[pyclass(module = "zstandard.backend_rust")]
+pub struct CopyStreamResult {
+    /// Bytes read from the source.
+    #[pyo3(get)]
+    bytes_read: usize,
+    /// Bytes written to the destination.
+    #[pyo3(get)]
+    bytes_written: usize,
+    /// Number of complete zstd frames written or decoded.
+    #[pyo3(get)]
+    frames: usize,
+    /// Whether every frame carries a content checksum. When decompressing,
+    /// checksums present are always verified.
+    #[pyo3(get)]
+    checksum: bool,
+    /// Seconds spent in the call.
+    #[pyo3(get)]
+    duration: f64,
+    /// Digest of the hasher, once the copy has finished.
+    #[pyo3(get)]
+    digest: Option<PyObject>,
+    /// Whether the copy ran to completion rather than running out of time.
+    #[pyo3(get)]
+    finished: bool,
+    /// The equivalent tuple.
+    items: Py<PyTuple>,
+}
+
+#[pyproto]
+impl PyObjectProtocol for CopyStreamResult {
+    fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyResult<PyObject> {
+        let py = other.py();
+        let items = self.items.as_ref(py);
+
+        let other = match other.extract::<PyRef<CopyStreamResult>>() {
+            Ok(other) => other.items.clone_ref(py).into_ref(py).as_ref(),
+            Err(_) => other,
+        };
+
+        items.rich_compare(other, op).map(|res| res.into_py(py))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CopyStreamResult(bytes_read={}, bytes_written={}, frames={}, checksum={}, \
+             duration={:.6}, finished={})",
+            self.bytes_read,
+            self.bytes_written,
+            self.frames,
+            if self.checksum { "True" } else { "False" },
+            self.duration,
+            if self.finished { "True" } else { "False" },
+        )
+    }
+}
+
+#[pyproto]
+impl PySequenceProtocol for CopyStreamResult {
+    fn __len__(&self) -> usize {
+        Python::with_gil(|py| self.items.as_ref(py).len())
+    }
+
+    fn __getitem__(&self, idx: isize) -> PyResult<PyObject> {
+        Python::with_gil(|py| {
+            self.items
+                .as_ref(py)
+                .call_method1("__getitem__", (idx,))
+                .map(|item| item.into_py(py))
+        })
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for CopyStreamResult {
+    fn __iter__(slf: PyRef<Self>) -> PyResult<PyObject> {
+        let py = slf.py();
+
+        Ok(slf.items.as_ref(py).as_ref().iter()?.into_py(py))
+    }
+}
+
+/// Resolve the return value of a `copy_stream()` call.
+///
+/// `frames` is the number of complete frames and whether all of them carry a
+/// checksum. The digest is only obtained once the copy has finished. The
+/// trailing `finished` member of the equivalent tuple is only present when
+/// the call had a time slice.
+pub(crate) fn copy_stream_result(
+    py: Python,
+    total_read: usize,
+    total_write: usize,
+    (frames, checksum): (usize, bool),
+    hasher: Option<&PyAny>,
+    deadline: &CopyDeadline,
+    finished: bool,
+) -> PyResult<CopyStreamResult> {
+    let mut items = vec![total_read.into_py(py), total_write.into_py(py)];
+
+    let digest = match hasher {
+        Some(hasher) if finished => Some(hasher.call_method0("digest")?.into_py(py)),
+        _ => None,
+    };
+
+    if hasher.is_some() {
+        items.push(match &digest {
+            Some(digest) => digest.clone_ref(py),
+            None => py.None(),
+        });
+    }
+
+    if deadline.limit.is_some() {
+        items.push(finished.into_py(py));
+    }
+
+    Ok(CopyStreamResult {
+        bytes_read: total_read,
+        bytes_written: total_write,
+        frames,
+        checksum: frames > 0 && checksum,
+        duration: deadline.start.elapsed().as_secs_f64(),
+        digest,
+        finished,
+        items: PyTuple::new(py, items).into(),
+    })
 }
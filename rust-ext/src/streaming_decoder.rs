@@ -0,0 +1,181 @@
+// Copyright (c) 2021-present, Gregory Szorc
+// All rights reserved.
+//
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+use {
+    crate::{compression_dict::ZstdCompressionDict, exceptions::ZstdError, zstd_safe::DCtx},
+    pyo3::{
+        buffer::PyBuffer,
+        prelude::*,
+        types::{PyBytes, PyList},
+    },
+};
+
+/// Incremental decoder for zstd content arriving in arbitrary slices.
+///
+/// Unlike `ZstdDecompressionObj`, this owns its decompression context, spans
+/// multiple frames, and reports frame boundaries as events. It is intended for
+/// HTTP `Content-Encoding: zstd` (RFC 8878) proxies and servers.
+#[pyclass(module = "zstandard.backend_rust")]
+pub struct StreamingDecoder {
+    dctx: DCtx<'static>,
+    /// Keeps the dictionary referenced by `dctx` alive.
+    _dict_data: Option<Py<ZstdCompressionDict>>,
+    multiple_frames: bool,
+    dest_buffer: Vec<u8>,
+    in_frame: bool,
+    finished: bool,
+    /// Whether a single frame has been decoded in single frame mode.
+    #[pyo3(get)]
+    eof: bool,
+    #[pyo3(get)]
+    frames_completed: usize,
+    unused_data: Vec<u8>,
+}
+
+impl StreamingDecoder {
+    pub fn new(
+        dctx: DCtx<'static>,
+        dict_data: Option<Py<ZstdCompressionDict>>,
+        write_size: usize,
+        multiple_frames: bool,
+    ) -> Self {
+        dctx.set_track_headers(true);
+
+        Self {
+            dctx,
+            _dict_data: dict_data,
+            multiple_frames,
+            dest_buffer: Vec::with_capacity(write_size),
+            in_frame: false,
+            finished: false,
+            eof: false,
+            frames_completed: 0,
+            unused_data: Vec::new(),
+        }
+    }
+
+    /// Record the end of the current frame and emit its event.
+    fn complete_frame(&mut self, py: Python, events: &PyList) -> PyResult<()> {
+        self.in_frame = false;
+
+        match self.dctx.last_frame_header() {
+            Some(header) if header.frameType == zstd_sys::ZSTD_frameType_e::ZSTD_skippableFrame => {
+                events.append(("skippable_frame", py.None()))?;
+                return Ok(());
+            }
+            // Checksums are verified by zstd, so a present checksum has passed.
+            Some(header) => events.append(("frame_complete", header.checksumFlag != 0))?,
+            None => events.append(("frame_complete", py.None()))?,
+        }
+
+        self.frames_completed += 1;
+        if !self.multiple_frames {
+            self.eof = true;
+        }
+
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl StreamingDecoder {
+    /// Feed compressed bytes.
+    ///
+    /// Returns a `(data, events)` tuple of decoded bytes and a list of
+    /// `(kind, value)` events that occurred while decoding: `frame_complete`
+    /// (value is whether a content checksum was verified), `skippable_frame`,
+    /// and `unused_data` (value is the number of bytes after the frame in
+    /// single frame mode).
+    fn feed<'p>(
+        &mut self,
+        py: Python<'p>,
+        data: PyBuffer<u8>,
+    ) -> PyResult<(&'p PyAny, &'p PyList)> {
+        if self.finished {
+            return Err(ZstdError::new_err("cannot call feed() after finish()"));
+        }
+
+        let source = unsafe {
+            std::slice::from_raw_parts::<u8>(data.buf_ptr() as *const _, data.len_bytes())
+        };
+
+        let chunks = PyList::empty(py);
+        let events = PyList::empty(py);
+
+        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+            src: source.as_ptr() as *const _,
+            size: source.len(),
+            pos: 0,
+        };
+        let mut output_pending = false;
+
+        while in_buffer.pos < in_buffer.size || output_pending {
+            if self.eof {
+                let remaining = &source[in_buffer.pos..];
+                self.unused_data.extend_from_slice(remaining);
+                events.append(("unused_data", remaining.len()))?;
+                break;
+            }
+
+            let old_pos = in_buffer.pos;
+
+            let zresult = self
+                .dctx
+                .decompress_into_vec(&mut self.dest_buffer, &mut in_buffer)
+                .map_err(|msg| ZstdError::new_err(format!("zstd decompress error: {}", msg)))?;
+
+            if in_buffer.pos > old_pos {
+                self.in_frame = true;
+            }
+
+            output_pending = self.dest_buffer.len() == self.dest_buffer.capacity();
+
+            if !self.dest_buffer.is_empty() {
+                chunks.append(PyBytes::new(py, &self.dest_buffer))?;
+                self.dest_buffer.clear();
+            }
+
+            if zresult == 0 && self.in_frame {
+                self.complete_frame(py, events)?;
+            }
+        }
+
+        let data = PyBytes::new(py, &[]).call_method1("join", (chunks,))?;
+
+        Ok((data, events))
+    }
+
+    /// Signal the end of input.
+    ///
+    /// Raises if input ended in the middle of a frame.
+    fn finish(&mut self) -> PyResult<()> {
+        self.finished = true;
+
+        if self.in_frame {
+            Err(ZstdError::new_err("input ended in the middle of a frame"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Data following the frame in single frame mode.
+    #[getter]
+    fn unused_data<'p>(&self, py: Python<'p>) -> &'p PyBytes {
+        PyBytes::new(py, &self.unused_data)
+    }
+
+    /// Whether a frame is partially decoded.
+    #[getter]
+    fn in_frame(&self) -> bool {
+        self.in_frame
+    }
+}
+
+pub(crate) fn init_module(module: &PyModule) -> PyResult<()> {
+    module.add_class::<StreamingDecoder>()?;
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+// Copyright (c) 2021-present, Gregory Szorc
+// All rights reserved.
+//
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+use {
+    crate::{compression_dict::ZstdCompressionDict, exceptions::ZstdError, zstd_safe::CCtx},
+    pyo3::{buffer::PyBuffer, prelude::*, types::PyBytes},
+};
+
+/// Incremental encoder producing output that is decodable as it arrives.
+///
+/// The counterpart to `StreamingDecoder`. It owns its compression context,
+/// so many can be active at once, e.g. one per HTTP response.
+#[pyclass(module = "zstandard.backend_rust")]
+pub struct StreamingEncoder {
+    cctx: CCtx<'static>,
+    /// Keeps the dictionary referenced by `cctx` alive.
+    _dict_data: Option<Py<ZstdCompressionDict>>,
+    write_size: usize,
+    #[pyo3(get)]
+    finished: bool,
+}
+
+impl StreamingEncoder {
+    pub fn new(
+        cctx: CCtx<'static>,
+        dict_data: Option<Py<ZstdCompressionDict>>,
+        write_size: usize,
+    ) -> Self {
+        Self {
+            cctx,
+            _dict_data: dict_data,
+            write_size,
+            finished: false,
+        }
+    }
+
+    /// Run `end_mode` with no new input until zstd reports completion.
+    ///
+    /// With multi-threaded compression a single call may not flush everything,
+    /// so calls are repeated until nothing remains.
+    fn drain(
+        &self,
+        py: Python,
+        end_mode: zstd_sys::ZSTD_EndDirective,
+        output: &mut Vec<u8>,
+    ) -> PyResult<()> {
+        let cctx = &self.cctx;
+        let write_size = self.write_size;
+
+        loop {
+            let (chunk, _, call_again) = py
+                .allow_threads(|| cctx.compress_chunk(&[], end_mode, write_size))
+                .map_err(|msg| ZstdError::new_err(format!("zstd compress error: {}", msg)))?;
+
+            output.extend_from_slice(&chunk);
+
+            if !call_again {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl StreamingEncoder {
+    /// Compress `data` into the current frame.
+    ///
+    /// When `flush` is true, the current block is ended so all output so far
+    /// can be decoded by the receiver on arrival.
+    #[args(data, flush = "true")]
+    fn encode<'p>(
+        &mut self,
+        py: Python<'p>,
+        data: PyBuffer<u8>,
+        flush: bool,
+    ) -> PyResult<&'p PyBytes> {
+        if self.finished {
+            return Err(ZstdError::new_err("cannot call encode() after finish()"));
+        }
+
+        let mut source = unsafe {
+            std::slice::from_raw_parts::<u8>(data.buf_ptr() as *const _, data.len_bytes())
+        };
+
+        let mut output = Vec::new();
+        let cctx = &self.cctx;
+        let write_size = self.write_size;
+
+        while !source.is_empty() {
+            let (chunk, remaining, _) = py
+                .allow_threads(|| {
+                    cctx.compress_chunk(
+                        source,
+                        zstd_sys::ZSTD_EndDirective::ZSTD_e_continue,
+                        write_size,
+                    )
+                })
+                .map_err(|msg| ZstdError::new_err(format!("zstd compress error: {}", msg)))?;
+
+            output.extend_from_slice(&chunk);
+            source = remaining;
+        }
+
+        if flush {
+            self.drain(py, zstd_sys::ZSTD_EndDirective::ZSTD_e_flush, &mut output)?;
+        }
+
+        Ok(PyBytes::new(py, &output))
+    }
+
+    /// End the frame, returning the remaining output.
+    fn finish<'p>(&mut self, py: Python<'p>) -> PyResult<&'p PyBytes> {
+        if self.finished {
+            return Err(ZstdError::new_err("encoder already finished"));
+        }
+
+        self.finished = true;
+
+        let mut output = Vec::new();
+        self.drain(py, zstd_sys::ZSTD_EndDirective::ZSTD_e_end, &mut output)?;
+
+        Ok(PyBytes::new(py, &output))
+    }
+}
+
+pub(crate) fn init_module(module: &PyModule) -> PyResult<()> {
+    module.add_class::<StreamingEncoder>()?;
+
+    Ok(())
+}
@@ -4,26 +4,60 @@
 // This software may be modified and distributed under the terms
 // of the BSD license. See the LICENSE file for details.
 
-use {crate::compression_parameters::CCtxParams, std::marker::PhantomData};
+use {
+    crate::compression_parameters::CCtxParams,
+    std::{
+        any::Any,
+        marker::PhantomData,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+    },
+};
+
+/// Dictionary content referenced by `CDict`, `DDict` and `CCtx`.
+///
+/// Unless asked to copy it (`ZSTD_dlm_byCopy`), zstd references dictionary
+/// content in place (`ZSTD_dlm_byRef`). Whatever references the content
+/// therefore holds a reference to it, which keeps the content alive and at
+/// the same address for as long as zstd may read it. Any type exposing
+/// stable bytes can back a dictionary; its bytes must not change while it is
+/// shared.
+pub type DictContent = Arc<dyn AsRef<[u8]> + Send + Sync>;
+
+/// `data` if zstd references it when loaded with `method`.
+fn referenced_content(
+    data: DictContent,
+    method: zstd_sys::ZSTD_dictLoadMethod_e,
+) -> Option<DictContent> {
+    match method {
+        zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byRef => Some(data),
+        zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byCopy => None,
+    }
+}
 
 /// Safe wrapper for ZSTD_CDict instances.
-pub struct CDict<'a> {
+pub struct CDict {
     ptr: *mut zstd_sys::ZSTD_CDict,
-    _phantom: PhantomData<&'a ()>,
+    /// Content `ptr` references, if any. Declared after it so it outlives it.
+    _data: Option<DictContent>,
 }
 
-impl<'a> CDict<'a> {
-    // TODO annotate lifetime of data to ensure outlives Self
+impl CDict {
     pub fn from_data(
-        data: &[u8],
+        data: DictContent,
         content_type: zstd_sys::ZSTD_dictContentType_e,
+        method: zstd_sys::ZSTD_dictLoadMethod_e,
         params: zstd_sys::ZSTD_compressionParameters,
     ) -> Result<Self, &'static str> {
+        let content = (*data).as_ref();
+
         let ptr = unsafe {
             zstd_sys::ZSTD_createCDict_advanced(
-                data.as_ptr() as *const _,
-                data.len(),
-                zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byRef,
+                content.as_ptr() as *const _,
+                content.len(),
+                method,
                 content_type,
                 params,
                 zstd_sys::ZSTD_customMem {
@@ -38,13 +72,13 @@ impl<'a> CDict<'a> {
         } else {
             Ok(Self {
                 ptr,
-                _phantom: PhantomData,
+                _data: referenced_content(data, method),
             })
         }
     }
 }
 
-impl<'a> Drop for CDict<'a> {
+impl Drop for CDict {
     fn drop(&mut self) {
         unsafe {
             zstd_sys::ZSTD_freeCDict(self.ptr);
@@ -52,20 +86,21 @@ impl<'a> Drop for CDict<'a> {
     }
 }
 
-unsafe impl<'a> Send for CDict<'a> {}
+unsafe impl Send for CDict {}
 
-unsafe impl<'a> Sync for CDict<'a> {}
+unsafe impl Sync for CDict {}
 
 /// Safe wrapper for ZSTD_DDict instances.
-pub struct DDict<'a> {
+pub struct DDict {
     ptr: *mut zstd_sys::ZSTD_DDict,
-    _phantom: PhantomData<&'a ()>,
+    /// Content `ptr` references, if any. Declared after it so it outlives it.
+    _data: Option<DictContent>,
 }
 
-unsafe impl<'a> Send for DDict<'a> {}
-unsafe impl<'a> Sync for DDict<'a> {}
+unsafe impl Send for DDict {}
+unsafe impl Sync for DDict {}
 
-impl<'a> Drop for DDict<'a> {
+impl Drop for DDict {
     fn drop(&mut self) {
         unsafe {
             zstd_sys::ZSTD_freeDDict(self.ptr);
@@ -73,17 +108,23 @@ impl<'a> Drop for DDict<'a> {
     }
 }
 
-impl<'a> DDict<'a> {
-    // TODO lifetime of data should be annotated to ensure it outlives Self
+impl DDict {
+    pub fn as_ptr(&self) -> *const zstd_sys::ZSTD_DDict {
+        self.ptr
+    }
+
     pub fn from_data(
-        data: &[u8],
+        data: DictContent,
         content_type: zstd_sys::ZSTD_dictContentType_e,
+        method: zstd_sys::ZSTD_dictLoadMethod_e,
     ) -> Result<Self, &'static str> {
+        let content = (*data).as_ref();
+
         let ptr = unsafe {
             zstd_sys::ZSTD_createDDict_advanced(
-                data.as_ptr() as *const _,
-                data.len(),
-                zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byRef,
+                content.as_ptr() as *const _,
+                content.len(),
+                method,
                 content_type,
                 zstd_sys::ZSTD_customMem {
                     customAlloc: None,
@@ -97,13 +138,23 @@ impl<'a> DDict<'a> {
         } else {
             Ok(Self {
                 ptr,
-                _phantom: PhantomData,
+                _data: referenced_content(data, method),
             })
         }
     }
 }
 
-pub struct CCtx<'a>(*mut zstd_sys::ZSTD_CCtx, PhantomData<&'a ()>);
+/// Safe wrapper for ZSTD_CCtx instances.
+///
+/// The second field records whether long distance matching is configured
+/// per frame from the pledged source size (`enable_ldm="auto"`). The last
+/// field holds the dictionary the context references, keeping it alive.
+pub struct CCtx<'a>(
+    *mut zstd_sys::ZSTD_CCtx,
+    AtomicBool,
+    PhantomData<&'a ()>,
+    Mutex<Option<Arc<dyn Any + Send + Sync>>>,
+);
 
 impl<'a> Drop for CCtx<'a> {
     fn drop(&mut self) {
@@ -123,7 +174,12 @@ impl<'a> CCtx<'a> {
             return Err("could not allocate ZSTD_CCtx instance");
         }
 
-        Ok(Self(cctx, PhantomData))
+        Ok(Self(
+            cctx,
+            AtomicBool::new(false),
+            PhantomData,
+            Mutex::new(None),
+        ))
     }
 
     pub fn cctx(&self) -> *mut zstd_sys::ZSTD_CCtx {
@@ -138,23 +194,126 @@ impl<'a> CCtx<'a> {
             return Err(zstd_safe::get_error_name(zresult).to_string());
         }
 
+        self.1.store(params.auto_ldm(), Ordering::Relaxed);
+
         Ok(())
     }
 
+    pub fn set_parameter(
+        &self,
+        param: zstd_sys::ZSTD_cParameter,
+        value: i32,
+    ) -> Result<(), &'static str> {
+        let zresult = unsafe { zstd_sys::ZSTD_CCtx_setParameter(self.0, param, value) };
+        if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+            Err(zstd_safe::get_error_name(zresult))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn get_parameter(&self, param: zstd_sys::ZSTD_cParameter) -> Result<i32, &'static str> {
+        let mut value = 0;
+        let zresult = unsafe { zstd_sys::ZSTD_CCtx_getParameter(self.0, param, &mut value) };
+        if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+            Err(zstd_safe::get_error_name(zresult))
+        } else {
+            Ok(value)
+        }
+    }
+
     pub fn memory_size(&self) -> usize {
         unsafe { zstd_sys::ZSTD_sizeof_CCtx(self.0 as *const _) }
     }
 
-    pub fn reset(&self) -> usize {
+    /// Compress `source` as a single block without a frame.
+    ///
+    /// The block is independent of any previous one. Returns an empty vector
+    /// if the data isn't compressible, in which case the caller must store it
+    /// uncompressed.
+    #[cfg(feature = "block-api")]
+    pub fn compress_block(
+        &self,
+        level: i32,
+        dict: &[u8],
+        source: &[u8],
+    ) -> Result<Vec<u8>, String> {
+        let zresult = unsafe {
+            zstd_sys::ZSTD_compressBegin_usingDict(
+                self.0,
+                dict.as_ptr() as *const _,
+                dict.len(),
+                level,
+            )
+        };
+        if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+            return Err(zstd_safe::get_error_name(zresult).to_string());
+        }
+
+        let block_size = unsafe { zstd_sys::ZSTD_getBlockSize(self.0) };
+        if source.len() > block_size {
+            return Err(format!("data exceeds maximum block size of {}", block_size));
+        }
+
+        let mut dest: Vec<u8> =
+            Vec::with_capacity(unsafe { zstd_sys::ZSTD_compressBound(source.len()) });
+
+        let zresult = unsafe {
+            zstd_sys::ZSTD_compressBlock(
+                self.0,
+                dest.as_mut_ptr() as *mut _,
+                dest.capacity(),
+                source.as_ptr() as *const _,
+                source.len(),
+            )
+        };
+        if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+            return Err(zstd_safe::get_error_name(zresult).to_string());
+        }
+
         unsafe {
+            dest.set_len(zresult);
+        }
+
+        Ok(dest)
+    }
+
+    pub fn reset(&self) -> usize {
+        let zresult = unsafe {
             zstd_sys::ZSTD_CCtx_reset(
                 self.0,
                 zstd_sys::ZSTD_ResetDirective::ZSTD_reset_session_only,
             )
+        };
+
+        // Frames started without a pledged size get the unknown size policy.
+        // The parameter values it sets are always within bounds.
+        let _ = self.apply_auto_ldm(zstd_sys::ZSTD_CONTENTSIZE_UNKNOWN as _);
+
+        zresult
+    }
+
+    /// Configure long distance matching for a frame of `size` bytes if the
+    /// parameters asked for `enable_ldm="auto"`.
+    fn apply_auto_ldm(&self, size: u64) -> Result<(), &'static str> {
+        if !self.1.load(Ordering::Relaxed) {
+            return Ok(());
         }
+
+        let level = self.get_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel)?;
+
+        let (enable, window_log) = auto_ldm_settings(level, size);
+
+        self.set_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_windowLog, window_log)?;
+        self.set_parameter(
+            zstd_sys::ZSTD_cParameter::ZSTD_c_enableLongDistanceMatching,
+            enable,
+        )
     }
 
     pub fn set_pledged_source_size(&self, size: u64) -> Result<(), &'static str> {
+        self.apply_auto_ldm(size)?;
+
         let zresult = unsafe { zstd_sys::ZSTD_CCtx_setPledgedSrcSize(self.0, size) };
         if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
             Err(zstd_safe::get_error_name(zresult))
@@ -163,29 +322,48 @@ impl<'a> CCtx<'a> {
         }
     }
 
-    pub fn load_computed_dict<'b: 'a>(&'a self, cdict: &'b CDict) -> Result<(), &'static str> {
+    pub fn load_computed_dict(&self, cdict: &Arc<CDict>) -> Result<(), &'static str> {
         let zresult = unsafe { zstd_sys::ZSTD_CCtx_refCDict(self.0, cdict.ptr) };
         if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
-            Err(zstd_safe::get_error_name(zresult))
-        } else {
-            Ok(())
+            return Err(zstd_safe::get_error_name(zresult));
         }
+
+        *self.3.lock().unwrap() = Some(cdict.clone());
+
+        Ok(())
     }
 
-    pub fn load_dict_data<'b: 'a>(
-        &'a self,
-        data: &'b [u8],
+    pub fn load_dict_data(
+        &self,
+        data: DictContent,
         content_type: zstd_sys::ZSTD_dictContentType_e,
+        method: zstd_sys::ZSTD_dictLoadMethod_e,
     ) -> Result<(), &'static str> {
+        let content = (*data).as_ref();
+
         let zresult = unsafe {
             zstd_sys::ZSTD_CCtx_loadDictionary_advanced(
                 self.0,
-                data.as_ptr() as *const _,
-                data.len(),
-                zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byRef,
+                content.as_ptr() as *const _,
+                content.len(),
+                method,
                 content_type,
             )
         };
+        if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+            return Err(zstd_safe::get_error_name(zresult));
+        }
+
+        *self.3.lock().unwrap() = referenced_content(data, method)
+            .map(|data| Arc::new(data) as Arc<dyn Any + Send + Sync>);
+
+        Ok(())
+    }
+
+    /// Reference `data` as a raw content prefix for the next frame only.
+    pub fn ref_prefix<'b: 'a>(&'a self, data: &'b [u8]) -> Result<(), &'static str> {
+        let zresult =
+            unsafe { zstd_sys::ZSTD_CCtx_refPrefix(self.0, data.as_ptr() as *const _, data.len()) };
         if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
             Err(zstd_safe::get_error_name(zresult))
         } else {
@@ -193,18 +371,94 @@ impl<'a> CCtx<'a> {
         }
     }
 
+    /// Compress `source` as a single frame referencing `prefix`, like
+    /// `zstd --patch-from`.
+    ///
+    /// The window and long distance matching are adjusted by
+    /// `patch_from_settings()`. Those parameters are left modified.
+    pub fn compress_patch(&self, prefix: &[u8], source: &[u8]) -> Result<Vec<u8>, &'static str> {
+        self.reset();
+        self.set_pledged_source_size(source.len() as _)?;
+
+        let level = self.get_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel)?;
+        let cparams = unsafe { zstd_sys::ZSTD_getCParams(level, source.len() as _, prefix.len()) };
+
+        // Explicit parameters take precedence over those of the level.
+        let chain_log = match self.get_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_chainLog)? {
+            0 => cparams.chainLog,
+            value => value as u32,
+        };
+        let strategy = match self.get_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_strategy)? {
+            0 => cparams.strategy as u32,
+            value => value as u32,
+        };
+
+        let (window_log, enable_ldm) =
+            patch_from_settings(chain_log, strategy, prefix.len(), source.len())?;
+
+        self.set_parameter(zstd_sys::ZSTD_cParameter::ZSTD_c_windowLog, window_log)?;
+        if enable_ldm {
+            self.set_parameter(
+                zstd_sys::ZSTD_cParameter::ZSTD_c_enableLongDistanceMatching,
+                1,
+            )?;
+        }
+
+        let zresult = unsafe {
+            zstd_sys::ZSTD_CCtx_refPrefix(self.0, prefix.as_ptr() as *const _, prefix.len())
+        };
+        if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+            return Err(zstd_safe::get_error_name(zresult));
+        }
+
+        let mut dest: Vec<u8> =
+            Vec::with_capacity(unsafe { zstd_sys::ZSTD_compressBound(source.len()) });
+
+        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+            src: source.as_ptr() as *const _,
+            size: source.len(),
+            pos: 0,
+        };
+        self.end_frame_into_vec_from(&mut dest, &mut in_buffer)?;
+
+        Ok(dest)
+    }
+
+    /// Stop using any dictionary or prefix.
+    pub fn clear_dict(&self) -> Result<(), &'static str> {
+        let zresult = unsafe { zstd_sys::ZSTD_CCtx_loadDictionary(self.0, std::ptr::null(), 0) };
+        if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+            return Err(zstd_safe::get_error_name(zresult));
+        }
+
+        *self.3.lock().unwrap() = None;
+
+        Ok(())
+    }
+
     pub fn get_frame_progression(&self) -> zstd_sys::ZSTD_frameProgression {
         unsafe { zstd_sys::ZSTD_getFrameProgression(self.0) }
     }
 
-    pub fn compress(&self, source: &[u8]) -> Result<Vec<u8>, &'static str> {
+    /// Compress input data as a single frame.
+    ///
+    /// The output buffer is initially sized to `output_size_hint` if defined
+    /// or `ZSTD_compressBound()` otherwise. The hint is capped at the bound,
+    /// which always fits the frame. It grows if the compressed frame does not
+    /// fit.
+    pub fn compress(
+        &self,
+        source: &[u8],
+        output_size_hint: Option<usize>,
+    ) -> Result<Vec<u8>, &'static str> {
         self.reset();
 
-        let dest_len = unsafe { zstd_sys::ZSTD_compressBound(source.len()) };
+        let bound = unsafe { zstd_sys::ZSTD_compressBound(source.len()) };
+        let dest_len = output_size_hint.map_or(bound, |hint| std::cmp::min(hint, bound));
 
         let mut dest: Vec<u8> = Vec::with_capacity(dest_len);
 
-        self.set_pledged_source_size(dest_len as _)?;
+        self.set_pledged_source_size(source.len() as _)?;
 
         let mut in_buffer = zstd_sys::ZSTD_inBuffer {
             src: source.as_ptr() as *const _,
@@ -212,31 +466,169 @@ impl<'a> CCtx<'a> {
             pos: 0,
         };
 
+        // By avoiding ZSTD_compress(), we don't necessarily write out content
+        // size. This means the parameters to control frame parameters are honored.
+        loop {
+            let zresult = self.compress_into_vec(
+                &mut dest,
+                &mut in_buffer,
+                zstd_sys::ZSTD_EndDirective::ZSTD_e_end,
+            )?;
+
+            if zresult == 0 {
+                return Ok(dest);
+            }
+
+            // The frame didn't fit. zresult is a lower bound on what remains
+            // to be flushed. Grow by at least the current capacity to avoid
+            // many small reallocations.
+            if dest.len() == dest.capacity() {
+                dest.reserve(std::cmp::max(zresult, dest.capacity()));
+            }
+        }
+    }
+
+    /// Compress a complete frame into `dest`.
+    ///
+    /// Returns the size of the frame or `None` if it doesn't fit in `dest`.
+    pub fn compress_to_slice(
+        &self,
+        source: &[u8],
+        dest: &mut [u8],
+    ) -> Result<Option<usize>, &'static str> {
+        self.reset();
+        self.set_pledged_source_size(source.len() as _)?;
+
+        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+            src: source.as_ptr() as *const _,
+            size: source.len(),
+            pos: 0,
+        };
         let mut out_buffer = zstd_sys::ZSTD_outBuffer {
             dst: dest.as_mut_ptr() as *mut _,
-            size: dest.capacity(),
+            size: dest.len(),
             pos: 0,
         };
 
-        // By avoiding ZSTD_compress(), we don't necessarily write out content
-        // size. This means the parameters to control frame parameters are honored.
-        let zresult = unsafe {
-            zstd_sys::ZSTD_compressStream2(
-                self.0,
-                &mut out_buffer as *mut _,
-                &mut in_buffer as *mut _,
+        loop {
+            let zresult = self.compress_buffers(
+                &mut out_buffer,
+                &mut in_buffer,
                 zstd_sys::ZSTD_EndDirective::ZSTD_e_end,
-            )
+            )?;
+
+            if zresult == 0 {
+                return Ok(Some(out_buffer.pos));
+            }
+
+            if out_buffer.pos == out_buffer.size {
+                // Discard the partial frame so the context can be reused.
+                self.reset();
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Compress `source`, ending the frame early once `expired()` returns true.
+    ///
+    /// Input is fed in `ZSTD_CStreamInSize()` slices and `expired()` is checked
+    /// between them. On expiry, the frame is ended and the remaining input is
+    /// compressed into a second frame at `fallback_level`. Since its size isn't
+    /// known up front, the first frame never records a content size.
+    ///
+    /// Returns the compressed data and whether the fallback was used. The
+    /// compression level is left modified when it was.
+    pub fn compress_with_deadline(
+        &self,
+        source: &[u8],
+        fallback_level: i32,
+        expired: impl Fn() -> bool,
+    ) -> Result<(Vec<u8>, bool), &'static str> {
+        self.reset();
+
+        let mut dest: Vec<u8> =
+            Vec::with_capacity(unsafe { zstd_sys::ZSTD_compressBound(source.len()) });
+        let slice_size = zstd_safe::cstream_in_size();
+
+        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+            src: source.as_ptr() as *const _,
+            size: 0,
+            pos: 0,
         };
 
-        if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
-            Err(zstd_safe::get_error_name(zresult))
-        } else if zresult > 0 {
-            Err("unexpected partial frame flush")
-        } else {
-            unsafe { dest.set_len(out_buffer.pos) }
+        while in_buffer.pos < source.len() && !expired() {
+            in_buffer.size = std::cmp::min(in_buffer.pos + slice_size, source.len());
+
+            while in_buffer.pos < in_buffer.size {
+                self.compress_into_vec(
+                    &mut dest,
+                    &mut in_buffer,
+                    zstd_sys::ZSTD_EndDirective::ZSTD_e_continue,
+                )?;
+
+                if dest.len() == dest.capacity() {
+                    dest.reserve(dest.capacity());
+                }
+            }
+        }
+
+        let fallback = in_buffer.pos < source.len();
+
+        // Nothing consumed yet means there is no frame to end early. The whole
+        // input goes into a single frame at the fallback level.
+        if fallback {
+            if in_buffer.pos > 0 {
+                self.end_frame_into_vec(&mut dest)?;
+            }
+
+            self.set_parameter(
+                zstd_sys::ZSTD_cParameter::ZSTD_c_compressionLevel,
+                fallback_level,
+            )?;
+            self.set_pledged_source_size((source.len() - in_buffer.pos) as _)?;
+        }
+
+        // zstd derives the size of a frame started with ZSTD_e_end from the
+        // buffer size, ignoring its position. So give it only what remains.
+        let remaining = &source[in_buffer.pos..];
+        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+            src: remaining.as_ptr() as *const _,
+            size: remaining.len(),
+            pos: 0,
+        };
+        self.end_frame_into_vec_from(&mut dest, &mut in_buffer)?;
+
+        Ok((dest, fallback))
+    }
+
+    /// End the current frame, appending output to `dest`.
+    fn end_frame_into_vec(&self, dest: &mut Vec<u8>) -> Result<(), &'static str> {
+        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+            src: std::ptr::null(),
+            size: 0,
+            pos: 0,
+        };
+
+        self.end_frame_into_vec_from(dest, &mut in_buffer)
+    }
+
+    /// Consume the rest of `in_buffer` and end the frame, appending to `dest`.
+    fn end_frame_into_vec_from(
+        &self,
+        dest: &mut Vec<u8>,
+        in_buffer: &mut zstd_sys::ZSTD_inBuffer,
+    ) -> Result<(), &'static str> {
+        loop {
+            let zresult =
+                self.compress_into_vec(dest, in_buffer, zstd_sys::ZSTD_EndDirective::ZSTD_e_end)?;
+
+            if zresult == 0 {
+                return Ok(());
+            }
 
-            Ok(dest)
+            if dest.len() == dest.capacity() {
+                dest.reserve(std::cmp::max(zresult, dest.capacity()));
+            }
         }
     }
 
@@ -250,24 +642,60 @@ impl<'a> CCtx<'a> {
         end_mode: zstd_sys::ZSTD_EndDirective,
         output_size: usize,
     ) -> Result<(Vec<u8>, &'a [u8], bool), &'static str> {
-        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
-            src: source.as_ptr() as *const _,
-            size: source.len() as _,
+        let mut dest: Vec<u8> = Vec::with_capacity(output_size);
+
+        let mut out_buffer = zstd_sys::ZSTD_outBuffer {
+            dst: dest.as_mut_ptr() as *mut _,
+            size: dest.capacity(),
             pos: 0,
         };
 
-        let mut dest: Vec<u8> = Vec::with_capacity(output_size);
+        let (remaining, more) = self.compress_chunk_buffer(source, end_mode, &mut out_buffer)?;
 
+        unsafe {
+            dest.set_len(out_buffer.pos);
+        }
+
+        Ok((dest, remaining, more))
+    }
+
+    /// Compress input data as part of a stream into `dest`.
+    ///
+    /// Like `compress_chunk()` but output is written to the start of `dest`
+    /// and the number of bytes written is returned in its place.
+    pub fn compress_chunk_into(
+        &self,
+        source: &'a [u8],
+        end_mode: zstd_sys::ZSTD_EndDirective,
+        dest: &mut [u8],
+    ) -> Result<(usize, &'a [u8], bool), &'static str> {
         let mut out_buffer = zstd_sys::ZSTD_outBuffer {
             dst: dest.as_mut_ptr() as *mut _,
-            size: dest.capacity(),
+            size: dest.len(),
+            pos: 0,
+        };
+
+        let (remaining, more) = self.compress_chunk_buffer(source, end_mode, &mut out_buffer)?;
+
+        Ok((out_buffer.pos, remaining, more))
+    }
+
+    fn compress_chunk_buffer(
+        &self,
+        source: &'a [u8],
+        end_mode: zstd_sys::ZSTD_EndDirective,
+        out_buffer: &mut zstd_sys::ZSTD_outBuffer,
+    ) -> Result<(&'a [u8], bool), &'static str> {
+        let mut in_buffer = zstd_sys::ZSTD_inBuffer {
+            src: source.as_ptr() as *const _,
+            size: source.len() as _,
             pos: 0,
         };
 
         let zresult = unsafe {
             zstd_sys::ZSTD_compressStream2(
                 self.0,
-                &mut out_buffer as *mut _,
+                out_buffer as *mut _,
                 &mut in_buffer as *mut _,
                 end_mode,
             )
@@ -277,13 +705,9 @@ impl<'a> CCtx<'a> {
             return Err(zstd_safe::get_error_name(zresult));
         }
 
-        unsafe {
-            dest.set_len(out_buffer.pos);
-        }
-
         let remaining = &source[in_buffer.pos..source.len()];
 
-        Ok((dest, remaining, zresult != 0))
+        Ok((remaining, zresult != 0))
     }
 
     pub fn compress_buffers(
@@ -334,7 +758,233 @@ impl<'a> CCtx<'a> {
     }
 }
 
-pub struct DCtx<'a>(*mut zstd_sys::ZSTD_DCtx, PhantomData<&'a ()>);
+pub struct DCtx<'a>(
+    *mut zstd_sys::ZSTD_DCtx,
+    PhantomData<&'a ()>,
+    Mutex<FrameCheck>,
+);
+
+/// Finds the prepared dictionary for a dictionary ID.
+///
+/// Returns the dictionary and an owner keeping it alive while referenced, or
+/// the error to report if no dictionary has the ID.
+pub type DictResolver = Arc<
+    dyn Fn(u32) -> Result<(*const zstd_sys::ZSTD_DDict, Arc<dyn Any + Send + Sync>), &'static str>
+        + Send
+        + Sync,
+>;
+
+/// Window sizes required by the frames a context has decoded.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WindowUsage {
+    /// Number of zstd frames whose header was parsed.
+    pub frames: u64,
+    /// Largest window size declared by those frames.
+    pub max_window_size: u64,
+}
+
+/// Tracks the header of the current frame to enforce frame policies zstd doesn't.
+#[derive(Clone)]
+struct FrameCheck {
+    require_checksum: bool,
+    /// Whether to parse headers even when no policy needs them.
+    track_headers: bool,
+    format: zstd_sys::ZSTD_format_e,
+    header: Vec<u8>,
+    checked: bool,
+    /// Header of the most recently started frame.
+    last_header: Option<zstd_sys::ZSTD_frameHeader>,
+    /// Selects the dictionary of each frame from its header.
+    resolver: Option<DictResolver>,
+    /// Owner of the dictionary selected by `resolver`.
+    resolved_dict: Option<Arc<dyn Any + Send + Sync>>,
+    /// Dictionary referenced by `load_prepared_dict()`.
+    prepared_dict: Option<Arc<DDict>>,
+    /// Window requirements of decoded frames, if being recorded.
+    window_usage: Option<WindowUsage>,
+    /// Whether to remove dictionary IDs from frame headers before decoding.
+    ignore_dict_id: bool,
+}
+
+impl Default for FrameCheck {
+    fn default() -> Self {
+        Self {
+            require_checksum: false,
+            track_headers: false,
+            format: zstd_sys::ZSTD_format_e::ZSTD_f_zstd1,
+            header: Vec::new(),
+            checked: false,
+            last_header: None,
+            resolver: None,
+            resolved_dict: None,
+            prepared_dict: None,
+            window_usage: None,
+            ignore_dict_id: false,
+        }
+    }
+}
+
+impl FrameCheck {
+    /// Remember `header` as the header of the frame being decoded.
+    fn set_header(&mut self, header: zstd_sys::ZSTD_frameHeader) {
+        self.last_header = Some(header);
+
+        if let Some(usage) = &mut self.window_usage {
+            if header.frameType == zstd_sys::ZSTD_frameType_e::ZSTD_frame {
+                usage.frames += 1;
+                usage.max_window_size = std::cmp::max(usage.max_window_size, header.windowSize);
+            }
+        }
+    }
+
+    /// Move input into `header` until the frame header is complete.
+    ///
+    /// Unlike `feed()`, only header bytes are taken. Returns the number of
+    /// additional bytes wanted if the header is still incomplete.
+    fn take_header(
+        &mut self,
+        in_buffer: &mut zstd_sys::ZSTD_inBuffer,
+    ) -> Result<Option<usize>, &'static str> {
+        if self.header.is_empty() {
+            self.last_header = None;
+        }
+
+        loop {
+            let mut header = zstd_sys::ZSTD_frameHeader {
+                frameContentSize: 0,
+                windowSize: 0,
+                blockSizeMax: 0,
+                frameType: zstd_sys::ZSTD_frameType_e::ZSTD_frame,
+                headerSize: 0,
+                dictID: 0,
+                checksumFlag: 0,
+            };
+            let zresult = unsafe {
+                zstd_sys::ZSTD_getFrameHeader_advanced(
+                    &mut header,
+                    self.header.as_ptr() as *const _,
+                    self.header.len(),
+                    self.format,
+                )
+            };
+
+            if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+                // Let the decompressor report the malformed header.
+                self.checked = true;
+                return Ok(None);
+            } else if zresult == 0 {
+                self.checked = true;
+                self.set_header(header);
+
+                if self.require_checksum
+                    && header.frameType == zstd_sys::ZSTD_frameType_e::ZSTD_frame
+                    && header.checksumFlag == 0
+                {
+                    return Err("frame does not have a content checksum");
+                }
+
+                return Ok(None);
+            }
+
+            let wanted = zresult - self.header.len();
+            let available = in_buffer.size - in_buffer.pos;
+
+            if available == 0 {
+                return Ok(Some(wanted));
+            }
+
+            let count = std::cmp::min(wanted, available);
+            self.header.extend_from_slice(unsafe {
+                std::slice::from_raw_parts((in_buffer.src as *const u8).add(in_buffer.pos), count)
+            });
+            in_buffer.pos += count;
+        }
+    }
+
+    /// Feed the next input bytes, validating the frame header once it is complete.
+    fn feed(&mut self, data: &[u8]) -> Result<(), &'static str> {
+        if !(self.require_checksum || self.track_headers || self.window_usage.is_some())
+            || self.checked
+            || data.is_empty()
+        {
+            return Ok(());
+        }
+
+        if self.header.is_empty() {
+            self.last_header = None;
+        }
+
+        let wanted = zstd_sys::ZSTD_FRAMEHEADERSIZE_MAX as usize - self.header.len();
+        self.header
+            .extend_from_slice(&data[0..std::cmp::min(wanted, data.len())]);
+
+        let mut header = zstd_sys::ZSTD_frameHeader {
+            frameContentSize: 0,
+            windowSize: 0,
+            blockSizeMax: 0,
+            frameType: zstd_sys::ZSTD_frameType_e::ZSTD_frame,
+            headerSize: 0,
+            dictID: 0,
+            checksumFlag: 0,
+        };
+        let zresult = unsafe {
+            zstd_sys::ZSTD_getFrameHeader_advanced(
+                &mut header,
+                self.header.as_ptr() as *const _,
+                self.header.len(),
+                self.format,
+            )
+        };
+
+        if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+            // Let the decompressor report the malformed header.
+            self.checked = true;
+        } else if zresult == 0 {
+            self.checked = true;
+            self.set_header(header);
+
+            if self.require_checksum
+                && header.frameType == zstd_sys::ZSTD_frameType_e::ZSTD_frame
+                && header.checksumFlag == 0
+            {
+                return Err("frame does not have a content checksum");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove the dictionary ID field from the complete frame header in `header`.
+    ///
+    /// zstd then decodes the frame with whatever dictionary is referenced
+    /// instead of rejecting a dictionary with a different ID.
+    fn strip_dict_id(&mut self) {
+        match self.last_header {
+            Some(header) if header.frameType == zstd_sys::ZSTD_frameType_e::ZSTD_frame => {}
+            _ => return,
+        }
+
+        let start = if self.format == zstd_sys::ZSTD_format_e::ZSTD_f_zstd1 {
+            4
+        } else {
+            0
+        };
+        let descriptor = self.header[start];
+        let dict_id_size = [0, 1, 2, 4][(descriptor & 0x03) as usize];
+        let single_segment = descriptor & 0x20 != 0;
+        let dict_id_start = start + if single_segment { 1 } else { 2 };
+
+        self.header
+            .drain(dict_id_start..dict_id_start + dict_id_size);
+        self.header[start] = descriptor & !0x03;
+    }
+
+    /// Prepare for the next frame.
+    fn finish_frame(&mut self) {
+        self.header.clear();
+        self.checked = false;
+    }
+}
 
 impl<'a> Drop for DCtx<'a> {
     fn drop(&mut self) {
@@ -354,7 +1004,7 @@ impl<'a> DCtx<'a> {
             return Err("could not allocate ZSTD_DCtx instance");
         }
 
-        Ok(Self(dctx, PhantomData))
+        Ok(Self(dctx, PhantomData, Mutex::new(FrameCheck::default())))
     }
 
     /// Attempt to create a copy of this instance.
@@ -365,6 +1015,8 @@ impl<'a> DCtx<'a> {
             zstd_sys::ZSTD_copyDCtx(dctx.0, self.0);
         }
 
+        *dctx.2.lock().unwrap() = self.2.lock().unwrap().clone();
+
         Ok(dctx)
     }
 
@@ -376,6 +1028,43 @@ impl<'a> DCtx<'a> {
         unsafe { zstd_sys::ZSTD_sizeof_DCtx(self.0) }
     }
 
+    /// Decompress a single block produced by `CCtx::compress_block()`.
+    #[cfg(feature = "block-api")]
+    pub fn decompress_block(
+        &self,
+        dict: &[u8],
+        source: &[u8],
+        max_output_size: usize,
+    ) -> Result<Vec<u8>, &'static str> {
+        let zresult = unsafe {
+            zstd_sys::ZSTD_decompressBegin_usingDict(self.0, dict.as_ptr() as *const _, dict.len())
+        };
+        if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+            return Err(zstd_safe::get_error_name(zresult));
+        }
+
+        let mut dest: Vec<u8> = Vec::with_capacity(max_output_size);
+
+        let zresult = unsafe {
+            zstd_sys::ZSTD_decompressBlock(
+                self.0,
+                dest.as_mut_ptr() as *mut _,
+                dest.capacity(),
+                source.as_ptr() as *const _,
+                source.len(),
+            )
+        };
+        if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+            return Err(zstd_safe::get_error_name(zresult));
+        }
+
+        unsafe {
+            dest.set_len(zresult);
+        }
+
+        Ok(dest)
+    }
+
     pub fn reset(&self) -> Result<(), &'static str> {
         let zresult = unsafe {
             zstd_sys::ZSTD_DCtx_reset(
@@ -386,12 +1075,89 @@ impl<'a> DCtx<'a> {
         if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
             Err(zstd_safe::get_error_name(zresult))
         } else {
+            let mut check = self.2.lock().unwrap();
+            check.finish_frame();
+            check.last_header = None;
             Ok(())
         }
     }
 
+    /// Reject frames that don't have a content checksum.
+    pub fn set_require_checksum(&self, value: bool) {
+        self.2.lock().unwrap().require_checksum = value;
+    }
+
+    /// Record frame headers so `last_frame_header()` is available.
+    pub fn set_track_headers(&self, value: bool) {
+        self.2.lock().unwrap().track_headers = value;
+    }
+
+    /// Record the window size of each frame for `window_usage()`.
+    ///
+    /// Enabling keeps statistics already recorded.
+    pub fn set_track_window_usage(&self, value: bool) {
+        let mut check = self.2.lock().unwrap();
+
+        check.window_usage = match (value, check.window_usage) {
+            (true, Some(usage)) => Some(usage),
+            (true, None) => Some(WindowUsage::default()),
+            (false, _) => None,
+        };
+    }
+
+    /// Window requirements of frames decoded since tracking was enabled or
+    /// last reset.
+    pub fn window_usage(&self, reset: bool) -> Option<WindowUsage> {
+        let mut check = self.2.lock().unwrap();
+        let usage = check.window_usage;
+
+        if reset && usage.is_some() {
+            check.window_usage = Some(WindowUsage::default());
+        }
+
+        usage
+    }
+
+    /// Header of the most recently started frame, if tracked and parsed.
+    pub fn last_frame_header(&self) -> Option<zstd_sys::ZSTD_frameHeader> {
+        self.2.lock().unwrap().last_header
+    }
+
+    /// Set the maximum window size.
+    ///
+    /// The size is rounded down to a power of 2. A size of 0 restores the
+    /// default limit.
     pub fn set_max_window_size(&self, size: usize) -> Result<(), &'static str> {
-        let zresult = unsafe { zstd_sys::ZSTD_DCtx_setMaxWindowSize(self.0, size) };
+        self.set_window_log_max(window_log_for_size(size))
+    }
+
+    /// Set the base 2 log of the maximum window size. A value of 0 restores
+    /// the default limit.
+    pub fn set_window_log_max(&self, log: u32) -> Result<(), &'static str> {
+        let zresult = unsafe {
+            zstd_sys::ZSTD_DCtx_setParameter(
+                self.0,
+                zstd_sys::ZSTD_dParameter::ZSTD_d_windowLogMax,
+                log as _,
+            )
+        };
+        if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+            Err(zstd_safe::get_error_name(zresult))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Skip verification of frame content checksums.
+    pub fn set_ignore_checksum(&self, value: bool) -> Result<(), &'static str> {
+        // ZSTD_d_forceIgnoreChecksum.
+        let zresult = unsafe {
+            zstd_sys::ZSTD_DCtx_setParameter(
+                self.0,
+                zstd_sys::ZSTD_dParameter::ZSTD_d_experimentalParam3,
+                value as _,
+            )
+        };
         if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
             Err(zstd_safe::get_error_name(zresult))
         } else {
@@ -404,12 +1170,26 @@ impl<'a> DCtx<'a> {
         if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
             Err(zstd_safe::get_error_name(zresult))
         } else {
+            self.2.lock().unwrap().format = format;
             Ok(())
         }
     }
 
-    pub fn load_prepared_dict<'b: 'a>(&'a self, dict: &'b DDict) -> Result<(), &'static str> {
+    pub fn load_prepared_dict(&self, dict: &Arc<DDict>) -> Result<(), &'static str> {
         let zresult = unsafe { zstd_sys::ZSTD_DCtx_refDDict(self.0, dict.ptr) };
+        if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+            return Err(zstd_safe::get_error_name(zresult));
+        }
+
+        self.2.lock().unwrap().prepared_dict = Some(dict.clone());
+
+        Ok(())
+    }
+
+    /// Reference `data` as a raw content prefix for the next frame only.
+    pub fn ref_prefix<'b: 'a>(&'a self, data: &'b [u8]) -> Result<(), &'static str> {
+        let zresult =
+            unsafe { zstd_sys::ZSTD_DCtx_refPrefix(self.0, data.as_ptr() as *const _, data.len()) };
         if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
             Err(zstd_safe::get_error_name(zresult))
         } else {
@@ -417,11 +1197,148 @@ impl<'a> DCtx<'a> {
         }
     }
 
+    /// Stop using any dictionary or prefix.
+    pub fn clear_dict(&self) -> Result<(), &'static str> {
+        let zresult = unsafe { zstd_sys::ZSTD_DCtx_refDDict(self.0, std::ptr::null()) };
+        if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+            return Err(zstd_safe::get_error_name(zresult));
+        }
+
+        self.2.lock().unwrap().prepared_dict = None;
+
+        Ok(())
+    }
+
+    /// Decode frames with the referenced dictionary whatever dictionary ID
+    /// their header declares.
+    pub fn set_ignore_dict_id(&self, value: bool) {
+        self.2.lock().unwrap().ignore_dict_id = value;
+    }
+
+    /// Select each frame's dictionary with `resolver`.
+    ///
+    /// Replaces any dictionary loaded into this context.
+    pub fn set_dict_resolver(&self, resolver: Option<DictResolver>) {
+        self.2.lock().unwrap().resolver = resolver;
+    }
+
+    /// Reference the dictionary wanted by the frame whose header was just parsed.
+    fn select_frame_dict(&self, check: &mut FrameCheck) -> Result<(), &'static str> {
+        let dict_id = match check.last_header {
+            Some(header) if header.frameType == zstd_sys::ZSTD_frameType_e::ZSTD_frame => {
+                header.dictID
+            }
+            _ => 0,
+        };
+
+        let (ptr, owner) = if dict_id == 0 {
+            (std::ptr::null(), None)
+        } else {
+            match &check.resolver {
+                Some(resolver) => {
+                    let (ptr, owner) = resolver(dict_id)?;
+                    (ptr, Some(owner))
+                }
+                None => return Err("no dictionary resolver installed"),
+            }
+        };
+
+        let zresult = unsafe { zstd_sys::ZSTD_DCtx_refDDict(self.0, ptr) };
+        if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+            return Err(zstd_safe::get_error_name(zresult));
+        }
+
+        check.resolved_dict = owner;
+
+        Ok(())
+    }
+
+    /// `decompress_buffers()` when a dictionary resolver is installed or
+    /// dictionary IDs are ignored.
+    ///
+    /// Input is held back until the frame header is complete so the frame's
+    /// dictionary can be referenced, or the header rewritten, before zstd
+    /// starts decoding the frame.
+    fn decompress_buffers_resolving(
+        &self,
+        check: &mut FrameCheck,
+        out_buffer: &mut zstd_sys::ZSTD_outBuffer,
+        in_buffer: &mut zstd_sys::ZSTD_inBuffer,
+    ) -> Result<usize, &'static str> {
+        if !check.checked {
+            if let Some(wanted) = check.take_header(in_buffer)? {
+                return Ok(wanted);
+            }
+
+            if check.resolver.is_some() {
+                self.select_frame_dict(check)?;
+            }
+            if check.ignore_dict_id {
+                check.strip_dict_id();
+            }
+
+            let header = std::mem::take(&mut check.header);
+            let mut header_buffer = zstd_sys::ZSTD_inBuffer {
+                src: header.as_ptr() as *const _,
+                size: header.len(),
+                pos: 0,
+            };
+
+            let zresult = unsafe {
+                zstd_sys::ZSTD_decompressStream(
+                    self.0,
+                    out_buffer as *mut _,
+                    &mut header_buffer as *mut _,
+                )
+            };
+
+            if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+                return Err(zstd_safe::get_error_name(zresult));
+            } else if header_buffer.pos != header_buffer.size {
+                return Err("frame header not consumed by decompressor");
+            } else if zresult == 0 {
+                // A frame consisting of only a header, such as an empty
+                // skippable frame.
+                check.finish_frame();
+                return Ok(0);
+            }
+        }
+
+        let zresult = unsafe {
+            zstd_sys::ZSTD_decompressStream(self.0, out_buffer as *mut _, in_buffer as *mut _)
+        };
+
+        if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
+            Err(zstd_safe::get_error_name(zresult))
+        } else {
+            if zresult == 0 {
+                check.finish_frame();
+            }
+
+            Ok(zresult)
+        }
+    }
+
     pub fn decompress_buffers(
         &self,
         out_buffer: &mut zstd_sys::ZSTD_outBuffer,
         in_buffer: &mut zstd_sys::ZSTD_inBuffer,
     ) -> Result<usize, &'static str> {
+        let mut check = self.2.lock().unwrap();
+
+        if check.resolver.is_some() || check.ignore_dict_id {
+            return self.decompress_buffers_resolving(&mut check, out_buffer, in_buffer);
+        }
+
+        if in_buffer.pos < in_buffer.size {
+            check.feed(unsafe {
+                std::slice::from_raw_parts(
+                    (in_buffer.src as *const u8).add(in_buffer.pos),
+                    in_buffer.size - in_buffer.pos,
+                )
+            })?;
+        }
+
         let zresult = unsafe {
             zstd_sys::ZSTD_decompressStream(self.0, out_buffer as *mut _, in_buffer as *mut _)
         };
@@ -429,10 +1346,65 @@ impl<'a> DCtx<'a> {
         if unsafe { zstd_sys::ZSTD_isError(zresult) } != 0 {
             Err(zstd_safe::get_error_name(zresult))
         } else {
+            if zresult == 0 {
+                check.finish_frame();
+            }
+
             Ok(zresult)
         }
     }
 
+    /// `decompress_buffers()` with the GIL released.
+    ///
+    /// The GIL is kept when a dictionary resolver is installed, as resolving
+    /// reacquires it while this context is locked.
+    pub fn decompress_buffers_allow_threads(
+        &self,
+        py: pyo3::Python,
+        out_buffer: &mut zstd_sys::ZSTD_outBuffer,
+        in_buffer: &mut zstd_sys::ZSTD_inBuffer,
+    ) -> Result<usize, &'static str> {
+        if self.2.lock().unwrap().resolver.is_some() {
+            return self.decompress_buffers(out_buffer, in_buffer);
+        }
+
+        // The buffers only point at memory the caller keeps alive.
+        struct Buffers<'b>(
+            &'b mut zstd_sys::ZSTD_outBuffer,
+            &'b mut zstd_sys::ZSTD_inBuffer,
+        );
+        unsafe impl<'b> Send for Buffers<'b> {}
+
+        let buffers = Buffers(out_buffer, in_buffer);
+
+        py.allow_threads(|| {
+            let buffers = buffers;
+            self.decompress_buffers(buffers.0, buffers.1)
+        })
+    }
+
+    /// `decompress_into_vec()` with the GIL released.
+    pub fn decompress_into_vec_allow_threads(
+        &self,
+        py: pyo3::Python,
+        dest_buffer: &mut Vec<u8>,
+        in_buffer: &mut zstd_sys::ZSTD_inBuffer,
+    ) -> Result<usize, &'static str> {
+        let mut out_buffer = zstd_sys::ZSTD_outBuffer {
+            dst: dest_buffer.as_mut_ptr() as *mut _,
+            size: dest_buffer.capacity(),
+            pos: dest_buffer.len(),
+        };
+
+        let zresult = self.decompress_buffers_allow_threads(py, &mut out_buffer, in_buffer)?;
+
+        unsafe {
+            dest_buffer.set_len(out_buffer.pos);
+        }
+
+        Ok(zresult)
+    }
+
     pub fn decompress_into_vec(
         &self,
         dest_buffer: &mut Vec<u8>,
@@ -454,6 +1426,137 @@ impl<'a> DCtx<'a> {
     }
 }
 
+/// Base 2 log of `size` rounded down, or 0 for 0.
+pub fn window_log_for_size(size: usize) -> u32 {
+    if size == 0 {
+        0
+    } else {
+        usize::BITS - 1 - size.leading_zeros()
+    }
+}
+
+/// `(enable, window_log)` parameters for `enable_ldm="auto"` with a frame of
+/// `size` bytes at compression `level`.
+///
+/// Inputs no larger than the level's default window gain nothing from long
+/// distance matching and are left alone. Larger or unknown inputs enable it
+/// with a window covering the input, capped at the window size decompressors
+/// accept by default (like `zstd --long`).
+pub fn auto_ldm_settings(level: i32, size: u64) -> (i32, i32) {
+    if size == zstd_sys::ZSTD_CONTENTSIZE_UNKNOWN as u64 {
+        return (1, zstd_sys::ZSTD_WINDOWLOG_LIMIT_DEFAULT as i32);
+    }
+
+    let default_window_log = unsafe { zstd_sys::ZSTD_getCParams(level, 0, 0) }.windowLog;
+
+    if size <= 1 << default_window_log {
+        (0, 0)
+    } else {
+        let needed = 64 - (size - 1).leading_zeros() as i32;
+
+        (
+            1,
+            std::cmp::min(needed, zstd_sys::ZSTD_WINDOWLOG_LIMIT_DEFAULT as i32),
+        )
+    }
+}
+
+/// `(window_log, enable_ldm)` for compressing `source_size` bytes against a
+/// prefix of `prefix_size` bytes with the given chain log and strategy.
+///
+/// Follows `zstd --patch-from`: the window covers the larger input and long
+/// distance matching is needed once the window exceeds the distance the match
+/// finder searches.
+pub fn patch_from_settings(
+    chain_log: u32,
+    strategy: u32,
+    prefix_size: usize,
+    source_size: usize,
+) -> Result<(i32, bool), &'static str> {
+    let bounds =
+        unsafe { zstd_sys::ZSTD_cParam_getBounds(zstd_sys::ZSTD_cParameter::ZSTD_c_windowLog) };
+    if unsafe { zstd_sys::ZSTD_isError(bounds.error) } != 0 {
+        return Err(zstd_safe::get_error_name(bounds.error));
+    }
+
+    let size = std::cmp::max(prefix_size, source_size) as u64;
+    let window_log = std::cmp::min(
+        std::cmp::max(64 - size.leading_zeros() as i32, bounds.lowerBound),
+        bounds.upperBound,
+    );
+
+    // ZSTD_cycleLog().
+    let cycle_log = if strategy >= zstd_sys::ZSTD_strategy::ZSTD_btlazy2 as u32 {
+        chain_log as i32 - 1
+    } else {
+        chain_log as i32
+    };
+
+    Ok((window_log, window_log > cycle_log))
+}
+
+/// Size in bytes of the window needed to decompress a patch of a
+/// `prefix_size` byte prefix producing `content_size` bytes.
+pub fn patch_window_size(prefix_size: usize, content_size: u64) -> usize {
+    std::cmp::max(prefix_size as u64, content_size)
+        .checked_next_power_of_two()
+        .map_or(usize::MAX, |size| size as usize)
+}
+
+/// Range of values accepted for `ZSTD_d_windowLogMax`.
+pub fn window_log_max_bounds() -> Result<(u32, u32), &'static str> {
+    let bounds =
+        unsafe { zstd_sys::ZSTD_dParam_getBounds(zstd_sys::ZSTD_dParameter::ZSTD_d_windowLogMax) };
+    if unsafe { zstd_sys::ZSTD_isError(bounds.error) } != 0 {
+        Err(zstd_safe::get_error_name(bounds.error))
+    } else {
+        Ok((bounds.lowerBound as u32, bounds.upperBound as u32))
+    }
+}
+
+/// Size of the header and entropy tables of a dictionary in zstd format.
+///
+/// Errors if the header is corrupt or the entropy tables are invalid.
+pub fn dict_header_size(data: &[u8]) -> Result<usize, &'static str> {
+    let zresult =
+        unsafe { zstd_sys::ZDICT_getDictHeaderSize(data.as_ptr() as *const _, data.len()) };
+    if unsafe { zstd_sys::ZDICT_isError(zresult) } != 0 {
+        Err(zstd_safe::get_error_name(zresult))
+    } else {
+        Ok(zresult)
+    }
+}
+
+/// Train a dictionary with the COVER algorithm.
+///
+/// zstd stores the parameters it settled on in `params`.
+pub fn train_dictionary_cover(
+    dict_buffer: &mut Vec<u8>,
+    samples_buffer: &[u8],
+    samples_sizes: &[usize],
+    params: &mut zstd_sys::ZDICT_cover_params_t,
+) -> Result<(), &'static str> {
+    let zresult = unsafe {
+        zstd_sys::ZDICT_optimizeTrainFromBuffer_cover(
+            dict_buffer.as_mut_ptr() as *mut _,
+            dict_buffer.capacity(),
+            samples_buffer.as_ptr() as *const _,
+            samples_sizes.as_ptr(),
+            samples_sizes.len() as _,
+            params,
+        )
+    };
+    if unsafe { zstd_sys::ZDICT_isError(zresult) } != 0 {
+        Err(zstd_safe::get_error_name(zresult))
+    } else {
+        unsafe {
+            dict_buffer.set_len(zresult);
+        }
+
+        Ok(())
+    }
+}
+
 pub fn train_dictionary_fastcover(
     dict_buffer: &mut Vec<u8>,
     samples_buffer: &[u8],
@@ -49,6 +49,7 @@ WARNINGS_AS_ERRORS = False
 C_BACKEND = True
 CFFI_BACKEND = True
 RUST_BACKEND = False
+RUST_FEATURES = []
 
 if os.environ.get("ZSTD_WARNINGS_AS_ERRORS", ""):
     WARNINGS_AS_ERRORS = True
@@ -81,6 +82,10 @@ if "--rust-backend" in sys.argv:
     RUST_BACKEND = True
     sys.argv.remove("--rust-backend")
 
+if "--rust-block-api" in sys.argv:
+    RUST_FEATURES.append("block-api")
+    sys.argv.remove("--rust-block-api")
+
 # Code for obtaining the Extension instance is in its own module to
 # facilitate reuse in other projects.
 extensions = []
@@ -95,7 +100,7 @@ if C_BACKEND:
     )
 
 if RUST_BACKEND:
-    extensions.append(setup_zstd.get_rust_extension())
+    extensions.append(setup_zstd.get_rust_extension(features=RUST_FEATURES))
 
 if CFFI_BACKEND and cffi:
     import make_cffi
@@ -122,10 +122,11 @@ def get_c_extension(
 
 
 class RustExtension(distutils.extension.Extension):
-    def __init__(self, name, root):
+    def __init__(self, name, root, features=None):
         super().__init__(name, [])
 
         self.root = root
+        self.features = features or []
 
         self.depends.extend(
             [
@@ -148,6 +149,9 @@ class RustExtension(distutils.extension.Extension):
             str(build_dir),
         ]
 
+        if self.features:
+            args.extend(["--features", ",".join(self.features)])
+
         subprocess.run(args, env=env, cwd=self.root, check=True)
 
         dest_path = get_ext_path_fn(self.name)
@@ -180,8 +184,9 @@ class RustBuildExt(distutils.command.build_ext.build_ext):
 
 def get_rust_extension(
     root=None,
+    features=None,
 ):
     actual_root = os.path.abspath(os.path.dirname(__file__))
     root = root or actual_root
 
-    return RustExtension("zstandard.backend_rust", root)
+    return RustExtension("zstandard.backend_rust", root, features=features)
@@ -1,5 +1,8 @@
+import contextlib
 import io
 import os
+import sys
+import tempfile
 
 from typing import List
 
@@ -136,3 +139,25 @@ if hypothesis:
     hypothesis.settings.load_profile(
         os.environ.get("HYPOTHESIS_PROFILE", "default")
     )
+
+
+@contextlib.contextmanager
+def captured_stderr():
+    """Capture what native code writes to file descriptor 2.
+
+    Yields a ``bytearray`` that holds the output once the block exits.
+    """
+    output = bytearray()
+
+    with tempfile.TemporaryFile() as fh:
+        sys.stderr.flush()
+        saved = os.dup(2)
+        os.dup2(fh.fileno(), 2)
+        try:
+            yield output
+        finally:
+            os.dup2(saved, 2)
+            os.close(saved)
+
+        fh.seek(0)
+        output.extend(fh.read())
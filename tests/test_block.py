@@ -0,0 +1,62 @@
+import unittest
+
+import zstandard as zstd
+
+from .common import (
+    generate_samples,
+    get_optimal_dict_size_heuristically,
+)
+
+
+@unittest.skipUnless(
+    "block_api" in zstd.backend_features, "block API not available"
+)
+class TestBlock(unittest.TestCase):
+    def test_roundtrip(self):
+        source = b"".join(b"foo%dbar" % i for i in range(1000))
+
+        block = zstd.ZstdBlockCompressor(level=3).compress(source)
+        self.assertLess(len(block), len(source))
+        self.assertNotEqual(block[0:4], b"\x28\xb5\x2f\xfd")
+
+        dbc = zstd.ZstdBlockDecompressor()
+        self.assertEqual(dbc.decompress(block), source)
+
+        # Blocks are independent, so can be decoded in any order.
+        other = zstd.ZstdBlockCompressor().compress(b"foobar" * 100)
+        self.assertEqual(dbc.decompress(other), b"foobar" * 100)
+        self.assertEqual(dbc.decompress(block), source)
+
+    def test_incompressible(self):
+        self.assertEqual(zstd.ZstdBlockCompressor().compress(b"foo"), b"")
+
+    def test_too_large(self):
+        with self.assertRaisesRegex(
+            zstd.ZstdError,
+            "cannot compress block: data exceeds maximum block size of %d"
+            % zstd.BLOCKSIZE_MAX,
+        ):
+            zstd.ZstdBlockCompressor().compress(b"x" * (zstd.BLOCKSIZE_MAX + 1))
+
+    def test_max_output_size(self):
+        block = zstd.ZstdBlockCompressor().compress(b"foobar" * 100)
+
+        with self.assertRaisesRegex(zstd.ZstdError, "cannot decompress block"):
+            zstd.ZstdBlockDecompressor().decompress(block, max_output_size=100)
+
+    def test_dict(self):
+        samples = generate_samples()
+        d = zstd.train_dictionary(
+            get_optimal_dict_size_heuristically(samples), samples, k=64, d=8
+        )
+
+        source = samples[0]
+        block = zstd.ZstdBlockCompressor(dict_data=d).compress(source)
+        self.assertLess(len(block), len(source))
+
+        # The block references dictionary content.
+        with self.assertRaisesRegex(zstd.ZstdError, "cannot decompress block"):
+            zstd.ZstdBlockDecompressor().decompress(block)
+
+        dbc = zstd.ZstdBlockDecompressor(dict_data=d)
+        self.assertEqual(dbc.decompress(block), source)
@@ -64,6 +64,94 @@ class TestBufferWithSegments(unittest.TestCase):
         self.assertEqual(b[1].tobytes(), b"foox")
         self.assertEqual(b[2].tobytes(), b"fooxy")
 
+    def test_segments_outlive_parent(self):
+        data = b"".join(b"%04d" % i for i in range(1000))
+        b = zstd.BufferWithSegments(
+            data, b"".join(ss.pack(i * 4, 4) for i in range(1000))
+        )
+
+        segments = [b[i] for i in range(len(b))]
+        del b
+
+        for i, segment in enumerate(segments):
+            self.assertEqual(segment.tobytes(), b"%04d" % i)
+
+    @unittest.skipUnless(
+        "repr" in zstd.backend_features,
+        "__repr__ only implemented in Rust backend",
+    )
+    def test_repr(self):
+        b = zstd.BufferWithSegments(
+            b"foofoox", b"".join([ss.pack(0, 3), ss.pack(3, 4)])
+        )
+        self.assertEqual(repr(b), "BufferWithSegments(size=7, segments=2)")
+        self.assertEqual(repr(b[1]), "BufferSegment(offset=3, size=4)")
+
+        c = zstd.BufferWithSegmentsCollection(b, b)
+        self.assertEqual(
+            repr(c), "BufferWithSegmentsCollection(buffers=2, segments=4)"
+        )
+
+
+@unittest.skipUnless(
+    "buffer_with_segments_builder" in zstd.backend_features,
+    "BufferWithSegmentsBuilder only implemented in Rust backend",
+)
+class TestBufferWithSegmentsBuilder(unittest.TestCase):
+    def test_empty(self):
+        builder = zstd.BufferWithSegmentsBuilder()
+        self.assertEqual(len(builder), 0)
+        self.assertEqual(builder.size, 0)
+
+        b = builder.finish()
+        self.assertEqual(len(b), 0)
+        self.assertEqual(b.size, 0)
+
+    def test_append(self):
+        builder = zstd.BufferWithSegmentsBuilder(capacity=2)
+        builder.append(b"foo")
+        builder.append(bytearray(b""))
+        builder.extend([b"bar", memoryview(b"bazz")])
+
+        frame = zstd.ZstdCompressor().compress(b"data" * 1024)
+        builder.append(frame)
+
+        self.assertEqual(len(builder), 5)
+        self.assertEqual(builder.size, 10 + len(frame))
+        self.assertEqual(
+            repr(builder),
+            "BufferWithSegmentsBuilder(size=%d, segments=5)"
+            % (10 + len(frame)),
+        )
+
+        b = builder.finish()
+        self.assertIsInstance(b, zstd.BufferWithSegments)
+        self.assertEqual(len(b), 5)
+        self.assertEqual(b.tobytes(), b"foobarbazz" + frame)
+        self.assertEqual(
+            [segment.tobytes() for segment in b],
+            [b"foo", b"", b"bar", b"bazz", frame],
+        )
+        self.assertEqual(b[3].offset, 6)
+        self.assertEqual(
+            zstd.ZstdDecompressor().decompress(b[4]), b"data" * 1024
+        )
+
+    def test_finished(self):
+        builder = zstd.BufferWithSegmentsBuilder()
+        builder.append(b"foo")
+        builder.finish()
+
+        with self.assertRaisesRegex(
+            ValueError, "builder has already been finished"
+        ):
+            builder.append(b"bar")
+
+        with self.assertRaisesRegex(
+            ValueError, "builder has already been finished"
+        ):
+            builder.finish()
+
 
 @unittest.skipUnless(
     "buffer_types" in zstd.backend_features, "buffer types not available"
@@ -128,3 +216,26 @@ class TestBufferWithSegmentsCollection(unittest.TestCase):
         self.assertEqual(c[0].tobytes(), b"foo")
         self.assertEqual(c[1].tobytes(), b"bar")
         self.assertEqual(c[2].tobytes(), b"baz")
+
+    def test_getitem_many_buffers(self):
+        buffers = []
+        expected = []
+
+        for count in (1, 3, 2, 5, 1):
+            values = [b"%d-%d" % (len(buffers), i) for i in range(count)]
+            offsets = []
+            offset = 0
+            for value in values:
+                offsets.append(ss.pack(offset, len(value)))
+                offset += len(value)
+
+            buffers.append(
+                zstd.BufferWithSegments(b"".join(values), b"".join(offsets))
+            )
+            expected.extend(values)
+
+        c = zstd.BufferWithSegmentsCollection(*buffers)
+        self.assertEqual(len(c), len(expected))
+
+        for i, value in enumerate(expected):
+            self.assertEqual(c[i].tobytes(), value)
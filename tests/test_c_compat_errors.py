@@ -0,0 +1,114 @@
+import io
+import os
+import subprocess
+import sys
+import unittest
+
+import zstandard as zstd
+
+
+class NoFilenoWriter(object):
+    def write(self, data):
+        return len(data)
+
+
+@unittest.skipUnless(
+    "c_compat_errors" in zstd.backend_features,
+    "set_c_compat_errors() only implemented in Rust backend",
+)
+class TestCCompatErrors(unittest.TestCase):
+    def setUp(self):
+        self.orig = zstd.set_c_compat_errors(False)
+
+    def tearDown(self):
+        zstd.set_c_compat_errors(self.orig)
+
+    def test_set(self):
+        self.assertFalse(zstd.get_c_compat_errors())
+        self.assertFalse(zstd.set_c_compat_errors(True))
+        self.assertTrue(zstd.get_c_compat_errors())
+        self.assertTrue(zstd.set_c_compat_errors(False))
+        self.assertFalse(zstd.get_c_compat_errors())
+
+    def test_level(self):
+        level = zstd.MIN_COMPRESSION_LEVEL - 1
+
+        with self.assertRaisesRegex(ValueError, "level must be at least"):
+            zstd.ZstdCompressor(level=level)
+
+        zstd.set_c_compat_errors(True)
+
+        # Like the C backend, zstd clamps the level.
+        cctx = zstd.ZstdCompressor(level=level)
+        self.assertEqual(
+            zstd.decompress(cctx.compress(b"foo" * 64)), b"foo" * 64
+        )
+
+        with self.assertRaisesRegex(ValueError, "level must be less than"):
+            zstd.ZstdCompressor(level=zstd.MAX_COMPRESSION_LEVEL + 1)
+
+    def test_flush_mode(self):
+        cctx = zstd.ZstdCompressor()
+        writer = cctx.stream_writer(io.BytesIO())
+
+        with self.assertRaises(OverflowError):
+            writer.flush(-1)
+
+        zstd.set_c_compat_errors(True)
+
+        with self.assertRaisesRegex(ValueError, "unknown flush_mode: -1"):
+            writer.flush(-1)
+        with self.assertRaisesRegex(ValueError, "unknown flush_mode: 42"):
+            writer.flush(42)
+        with self.assertRaisesRegex(ValueError, "flush mode not recognized"):
+            cctx.compressobj().flush(42)
+
+    def test_fileno(self):
+        writer = zstd.ZstdCompressor().stream_writer(NoFilenoWriter())
+
+        with self.assertRaisesRegex(OSError, "filenot not available"):
+            writer.fileno()
+
+        zstd.set_c_compat_errors(True)
+
+        for writer in (
+            zstd.ZstdCompressor().stream_writer(NoFilenoWriter()),
+            zstd.ZstdDecompressor().stream_writer(NoFilenoWriter()),
+        ):
+            with self.assertRaisesRegex(
+                OSError, "^fileno not available on underlying writer$"
+            ):
+                writer.fileno()
+
+    def test_closed(self):
+        zstd.set_c_compat_errors(True)
+
+        writer = zstd.ZstdCompressor().stream_writer(io.BytesIO())
+        writer.close()
+
+        with self.assertRaisesRegex(ValueError, "stream is closed"):
+            writer.write(b"foo")
+
+        reader = zstd.ZstdDecompressor().stream_reader(b"")
+        reader.close()
+
+        with self.assertRaisesRegex(ValueError, "stream is closed"):
+            reader.read(1)
+
+    def test_environment(self):
+        env = dict(os.environ)
+        env["ZSTANDARD_C_COMPAT_ERRORS"] = "1"
+
+        code = "import zstandard; print(zstandard.get_c_compat_errors())"
+        output = subprocess.check_output([sys.executable, "-c", code], env=env)
+        self.assertEqual(output.strip(), b"True")
+
+        env["ZSTANDARD_C_COMPAT_ERRORS"] = "yes"
+        res = subprocess.run(
+            [sys.executable, "-c", code],
+            env=env,
+            stdout=subprocess.PIPE,
+            stderr=subprocess.PIPE,
+        )
+        self.assertNotEqual(res.returncode, 0)
+        self.assertIn(b"ZSTANDARD_C_COMPAT_ERRORS must be 0 or 1", res.stderr)
@@ -8,6 +8,246 @@ class TestCompressor(unittest.TestCase):
         with self.assertRaises(ValueError):
             zstd.ZstdCompressor(level=23)
 
+    def test_unknown_argument(self):
+        with self.assertRaises(TypeError):
+            zstd.ZstdCompressor(level=3, bogus=True)
+
+    @unittest.skipUnless(
+        "negative_levels" in zstd.backend_features,
+        "MIN_COMPRESSION_LEVEL only implemented in Rust backend",
+    )
+    def test_negative_levels(self):
+        self.assertLess(zstd.MIN_COMPRESSION_LEVEL, 0)
+
+        with self.assertRaisesRegex(
+            ValueError, "level must be at least %d" % zstd.MIN_COMPRESSION_LEVEL
+        ):
+            zstd.ZstdCompressor(level=zstd.MIN_COMPRESSION_LEVEL - 1)
+
+        source = b"".join(b"%d foo bar\n" % i for i in range(4096))
+        dctx = zstd.ZstdDecompressor()
+
+        sizes = []
+        for level in (zstd.MIN_COMPRESSION_LEVEL, -50, -5, -1, 1):
+            frame = zstd.ZstdCompressor(level=level).compress(source)
+            self.assertEqual(dctx.decompress(frame), source)
+            sizes.append(len(frame))
+
+        self.assertEqual(sizes, sorted(sizes, reverse=True))
+
+    @unittest.skipUnless(
+        "effective_level" in zstd.backend_features,
+        "effective_level only implemented in Rust backend",
+    )
+    def test_effective_level(self):
+        self.assertEqual(zstd.ZstdCompressor(level=7).effective_level, 7)
+        self.assertEqual(zstd.ZstdCompressor(level=-3).effective_level, -3)
+        self.assertEqual(zstd.ZstdCompressor(level=0).effective_level, 3)
+
+        params = zstd.ZstdCompressionParameters(
+            compression_level=12, strategy=1
+        )
+        cctx = zstd.ZstdCompressor(compression_params=params)
+        self.assertEqual(cctx.effective_level, 12)
+
+    @unittest.skipUnless(
+        "ldm_parameters" in zstd.backend_features,
+        "ldm_parameters only implemented in Rust backend",
+    )
+    def test_ldm_parameters(self):
+        self.assertIsNone(zstd.ZstdCompressor().ldm_parameters())
+
+        params = zstd.ZstdCompressionParameters(
+            enable_ldm=True, window_log=24, ldm_min_match=16
+        )
+        cctx = zstd.ZstdCompressor(compression_params=params)
+        self.assertEqual(
+            cctx.ldm_parameters(),
+            {
+                "window_log": 24,
+                "ldm_hash_log": 17,
+                "ldm_min_match": 16,
+                "ldm_bucket_size_log": 3,
+                "ldm_hash_rate_log": 7,
+            },
+        )
+
+        # The window shrinks to fit small inputs.
+        self.assertEqual(cctx.ldm_parameters(1000)["window_log"], 10)
+
+        params = zstd.ZstdCompressionParameters.from_level(
+            3, enable_ldm="auto"
+        )
+        cctx = zstd.ZstdCompressor(compression_params=params)
+        self.assertEqual(cctx.ldm_parameters()["window_log"], 27)
+        self.assertIsNone(cctx.ldm_parameters(1000))
+        self.assertEqual(cctx.ldm_parameters(50000000)["window_log"], 26)
+
     def test_memory_size(self):
         cctx = zstd.ZstdCompressor(level=1)
         self.assertGreater(cctx.memory_size(), 100)
+
+    @unittest.skipUnless(
+        "config" in zstd.backend_features,
+        "to_config() only implemented in Rust backend",
+    )
+    def test_config_roundtrip(self):
+        cctx = zstd.ZstdCompressor(level=7, write_checksum=True)
+        config = cctx.to_config()
+        self.assertIsInstance(config, bytes)
+
+        cctx2 = zstd.ZstdCompressor.from_config(config)
+        self.assertEqual(cctx2.to_config(), config)
+        self.assertEqual(
+            cctx2.compress(b"foobar" * 64), cctx.compress(b"foobar" * 64)
+        )
+
+    @unittest.skipUnless(
+        "config" in zstd.backend_features,
+        "to_config() only implemented in Rust backend",
+    )
+    def test_config_dict(self):
+        samples = [b"foo" * 64, b"bar" * 64, b"foobar" * 64] * 32
+        d = zstd.train_dictionary(8192, samples)
+        other = zstd.ZstdCompressionDict(b"\x00" * 1024)
+
+        config = zstd.ZstdCompressor(dict_data=d).to_config()
+
+        with self.assertRaisesRegex(ValueError, "requires a dictionary"):
+            zstd.ZstdCompressor.from_config(config)
+
+        with self.assertRaisesRegex(ValueError, "dictionary ID mismatch"):
+            zstd.ZstdCompressor.from_config(config, dict_data=other)
+
+        cctx = zstd.ZstdCompressor.from_config(config, dict_data=d)
+        self.assertEqual(cctx.to_config(), config)
+
+    @unittest.skipUnless(
+        "config" in zstd.backend_features,
+        "from_config() only implemented in Rust backend",
+    )
+    def test_config_invalid(self):
+        with self.assertRaisesRegex(ValueError, "not valid JSON"):
+            zstd.ZstdCompressor.from_config(b"foo")
+
+        with self.assertRaisesRegex(ValueError, "unsupported config version"):
+            zstd.ZstdCompressor.from_config(b'{"version": 42}')
+
+        config = zstd.ZstdDecompressor().to_config()
+        with self.assertRaisesRegex(ValueError, "not for a compressor"):
+            zstd.ZstdCompressor.from_config(config)
+
+        with self.assertRaisesRegex(
+            ValueError, "unknown compression parameter"
+        ):
+            zstd.ZstdCompressor.from_config(
+                b'{"version": 1, "type": "compressor", "params": {"bad": 1}}'
+            )
+
+    def test_repeated_construction(self):
+        data = b"foobar" * 256
+
+        frames = {
+            zstd.ZstdCompressor(level=1).compress(data) for i in range(100)
+        }
+        self.assertEqual(len(frames), 1)
+
+        # Instances differing only by flags must not share parameters.
+        a = zstd.ZstdCompressor(level=1).compress(data)
+        b = zstd.ZstdCompressor(level=1, write_checksum=True).compress(data)
+        c = zstd.ZstdCompressor(level=1, write_content_size=False).compress(
+            data
+        )
+        self.assertFalse(zstd.get_frame_parameters(a).has_checksum)
+        self.assertTrue(zstd.get_frame_parameters(b).has_checksum)
+        self.assertEqual(
+            zstd.get_frame_parameters(c).content_size, zstd.CONTENTSIZE_UNKNOWN
+        )
+
+    @unittest.skipUnless(
+        "max_native_memory" in zstd.backend_features,
+        "max_native_memory only implemented in Rust backend",
+    )
+    def test_max_native_memory(self):
+        cctx = zstd.ZstdCompressor(level=19, threads=8, max_native_memory=2**40)
+        self.assertIsNone(cctx.memory_adjustment)
+
+        cctx = zstd.ZstdCompressor(
+            level=19, threads=8, max_native_memory=400 * 1048576
+        )
+        adjustment = cctx.memory_adjustment
+        self.assertEqual(adjustment["requested_threads"], 8)
+        self.assertLess(adjustment["threads"], 8)
+        self.assertGreater(
+            adjustment["requested_estimated_size"], 400 * 1048576
+        )
+        self.assertLessEqual(adjustment["estimated_size"], 400 * 1048576)
+
+        data = b"foobar" * 65536
+        frame = cctx.compress(data)
+        self.assertEqual(zstd.ZstdDecompressor().decompress(frame), data)
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "max_native_memory of 1024 bytes is too small"
+        ):
+            zstd.ZstdCompressor(max_native_memory=1024)
+
+    @unittest.skipUnless(
+        "push_params" in zstd.backend_features,
+        "push_params() only implemented in Rust backend",
+    )
+    def test_push_pop_params(self):
+        data = b"".join(b"foo%dbar" % i for i in range(8192))
+
+        cctx = zstd.ZstdCompressor(level=19, write_checksum=True)
+        original = cctx.compress(data)
+
+        cctx.push_params(level=1)
+        fast = cctx.compress(data)
+        self.assertNotEqual(fast, original)
+        expected = zstd.ZstdCompressor(level=1, write_checksum=True)
+        self.assertEqual(fast, expected.compress(data))
+        self.assertTrue(zstd.get_frame_parameters(fast).has_checksum)
+
+        params = zstd.ZstdCompressionParameters.from_level(3)
+        cctx.push_params(compression_params=params)
+        self.assertEqual(
+            cctx.compress(data),
+            zstd.ZstdCompressor(compression_params=params).compress(data),
+        )
+
+        cctx.pop_params()
+        self.assertEqual(cctx.compress(data), fast)
+        cctx.pop_params()
+        self.assertEqual(cctx.compress(data), original)
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError,
+            "cannot call pop_params\\(\\) without a matching push_params\\(\\)",
+        ):
+            cctx.pop_params()
+
+        with self.assertRaisesRegex(
+            ValueError, "must define level or compression_params"
+        ):
+            cctx.push_params()
+
+        with self.assertRaisesRegex(
+            ValueError, "cannot define level and compression_params"
+        ):
+            cctx.push_params(level=1, compression_params=params)
+
+    @unittest.skipUnless(
+        "repr" in zstd.backend_features,
+        "__repr__ only implemented in Rust backend",
+    )
+    def test_repr(self):
+        self.assertEqual(
+            repr(zstd.ZstdCompressor()),
+            "ZstdCompressor(level=3, threads=0, dict_id=None)",
+        )
+
+        self.assertEqual(
+            repr(zstd.ZstdCompressor(level=22, threads=2)),
+            "ZstdCompressor(level=22, threads=2, dict_id=None)",
+        )
@@ -1,4 +1,6 @@
+import gc
 import unittest
+import warnings
 
 import zstandard as zstd
 
@@ -191,3 +193,296 @@ class TestCompressor_chunker(unittest.TestCase):
             zstd.ZstdError, r"cannot call finish\(\) after compression finished"
         ):
             list(chunker.finish())
+
+    @unittest.skipUnless(
+        "chunker_context_manager" in zstd.backend_features,
+        "context manager only implemented in Rust backend",
+    )
+    def test_context_manager(self):
+        cctx = zstd.ZstdCompressor()
+
+        with cctx.chunker(chunk_size=32768) as chunker:
+            self.assertIsNone(chunker.final_chunk)
+            chunks = list(chunker.compress(b"foobar" * 1024))
+
+            with self.assertRaisesRegex(
+                zstd.ZstdError, "cannot __enter__ multiple times"
+            ):
+                with chunker:
+                    pass
+
+        self.assertIsInstance(chunker.final_chunk, bytes)
+        chunks.append(chunker.final_chunk)
+
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(
+            dctx.decompress(b"".join(chunks), max_output_size=6144),
+            b"foobar" * 1024,
+        )
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, r"cannot call compress\(\) after compression finished"
+        ):
+            list(chunker.compress(b"foo"))
+
+    @unittest.skipUnless(
+        "chunker_context_manager" in zstd.backend_features,
+        "context manager only implemented in Rust backend",
+    )
+    def test_context_manager_exception(self):
+        cctx = zstd.ZstdCompressor()
+
+        with self.assertRaisesRegex(Exception, "oops"):
+            with cctx.chunker() as chunker:
+                list(chunker.compress(b"foo"))
+                raise Exception("oops")
+
+        self.assertIsNone(chunker.final_chunk)
+        list(chunker.finish())
+
+    @unittest.skipUnless(
+        "chunker_context_manager" in zstd.backend_features,
+        "context manager only implemented in Rust backend",
+    )
+    def test_context_manager_after_finish(self):
+        cctx = zstd.ZstdCompressor()
+
+        with cctx.chunker() as chunker:
+            list(chunker.compress(b"foo"))
+            list(chunker.finish())
+
+        self.assertIsNone(chunker.final_chunk)
+
+    @unittest.skipUnless(
+        "as_memoryview" in zstd.backend_features,
+        "as_memoryview only implemented in Rust backend",
+    )
+    def test_as_memoryview(self):
+        cctx = zstd.ZstdCompressor()
+        source = b"".join(bytes([i]) * 16384 for i in range(255))
+
+        chunker = cctx.chunker(chunk_size=256)
+        expected = list(chunker.compress(source))
+        expected.extend(chunker.finish())
+
+        chunker = cctx.chunker(chunk_size=256, as_memoryview=True)
+
+        chunks = []
+        for chunk in chunker.compress(source):
+            self.assertIsInstance(chunk, memoryview)
+            chunks.append(chunk.tobytes())
+
+        for chunk in chunker.finish():
+            self.assertIsInstance(chunk, memoryview)
+            chunks.append(chunk.tobytes())
+
+        self.assertEqual(chunks, expected)
+
+    @unittest.skipUnless(
+        "chunker_add_metadata" in zstd.backend_features,
+        "add_metadata() only implemented in Rust backend",
+    )
+    def test_add_metadata(self):
+        cctx = zstd.ZstdCompressor()
+        chunker = cctx.chunker(chunk_size=32)
+
+        # Metadata before any input doesn't emit an empty frame.
+        chunks = list(chunker.add_metadata(b"header"))
+        self.assertEqual(chunks, [b"\x50\x2a\x4d\x18\x06\x00\x00\x00header"])
+
+        chunks.extend(chunker.compress(b"foo" * 1024))
+        chunks.extend(chunker.add_metadata(b"boundary"))
+        self.assertEqual(chunks[-1], b"\x50\x2a\x4d\x18\x08\x00\x00\x00boundary")
+
+        chunks.extend(chunker.compress(b"bar" * 1024))
+        chunks.extend(chunker.finish())
+
+        dctx = zstd.ZstdDecompressor()
+        with dctx.stream_reader(b"".join(chunks), read_across_frames=True) as r:
+            self.assertEqual(r.read(), b"foo" * 1024 + b"bar" * 1024)
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "cannot call add_metadata\\(\\) after compression finished"
+        ):
+            chunker.add_metadata(b"foo")
+
+    @unittest.skipUnless(
+        "chunker_add_metadata" in zstd.backend_features,
+        "add_metadata() only implemented in Rust backend",
+    )
+    def test_add_metadata_pending_output(self):
+        cctx = zstd.ZstdCompressor()
+        chunker = cctx.chunker(chunk_size=32)
+
+        it = chunker.compress(b"foo" * 1024)
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError,
+            "cannot call add_metadata\\(\\) before consuming output from previous operation",
+        ):
+            chunker.add_metadata(b"foo")
+
+        list(it)
+        list(chunker.finish())
+
+    @unittest.skipUnless(
+        "chunker_flush_end_frame" in zstd.backend_features,
+        "flush(end_frame=True) only implemented in Rust backend",
+    )
+    def test_flush_end_frame(self):
+        cctx = zstd.ZstdCompressor()
+        dctx = zstd.ZstdDecompressor()
+
+        chunker = cctx.chunker(chunk_size=32)
+
+        # Nothing is emitted without a started frame.
+        self.assertEqual(list(chunker.flush(end_frame=True)), [])
+        list(chunker.compress(b""))
+        self.assertEqual(list(chunker.flush(end_frame=True)), [])
+
+        chunks = list(chunker.compress(b"foo" * 1024))
+        chunks.extend(chunker.flush(end_frame=True))
+        self.assertTrue(all(len(c) == 32 for c in chunks[:-1]))
+
+        # Output so far is a complete frame.
+        first = b"".join(chunks)
+        self.assertEqual(
+            dctx.decompress(first, max_output_size=3072), b"foo" * 1024
+        )
+
+        self.assertEqual(list(chunker.flush(end_frame=True)), [])
+
+        # The chunker remains usable and later input starts a new frame.
+        chunks.extend(chunker.compress(b"bar" * 1024))
+        chunks.extend(chunker.flush())
+        chunks.extend(chunker.compress(b"baz"))
+        chunks.extend(chunker.finish())
+
+        with dctx.stream_reader(b"".join(chunks), read_across_frames=True) as r:
+            self.assertEqual(r.read(), b"foo" * 1024 + b"bar" * 1024 + b"baz")
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError,
+            "cannot call flush\\(\\) after compression finished",
+        ):
+            chunker.flush(end_frame=True)
+
+    @unittest.skipUnless(
+        "frame_index" in zstd.backend_features,
+        "frame_index only implemented in Rust backend",
+    )
+    def test_frame_index(self):
+        cctx = zstd.ZstdCompressor()
+        dctx = zstd.ZstdDecompressor()
+
+        self.assertIsNone(cctx.chunker().frame_index)
+
+        for chunk_size in (4, 32, 4096):
+            chunker = cctx.chunker(chunk_size=chunk_size, frame_index=True)
+            self.assertEqual(chunker.frame_index, [])
+
+            chunks = list(chunker.compress(b"foo" * 1024))
+            chunks.extend(chunker.flush())
+            self.assertEqual(chunker.frame_index, [])
+            chunks.extend(chunker.flush(end_frame=True))
+
+            chunks.extend(chunker.compress(b"bar"))
+            chunks.extend(chunker.add_metadata(b"metadata"))
+            chunks.extend(chunker.write_empty_frame())
+            chunks.extend(chunker.compress(b"baz" * 4096))
+            chunks.extend(chunker.finish())
+
+            data = b"".join(chunks)
+            index = chunker.frame_index
+            sources = [b"foo" * 1024, b"bar", b"", b"baz" * 4096]
+            self.assertEqual([entry[2] for entry in index], [3072, 3, 0, 12288])
+
+            # The skippable frame isn't indexed.
+            self.assertEqual(index[1][0] + index[1][1] + 16, index[2][0])
+            self.assertEqual(index[-1][0] + index[-1][1], len(data))
+
+            for (offset, size, content_size), source in zip(index, sources):
+                self.assertEqual(
+                    dctx.decompress(
+                        data[offset : offset + size],
+                        max_output_size=len(source),
+                    ),
+                    source,
+                )
+
+    @unittest.skipUnless(
+        "write_empty_frame" in zstd.backend_features,
+        "write_empty_frame() only implemented in Rust backend",
+    )
+    def test_write_empty_frame(self):
+        cctx = zstd.ZstdCompressor()
+        empty = cctx.compress(b"")
+
+        chunker = cctx.chunker(chunk_size=32)
+        self.assertEqual(list(chunker.write_empty_frame()), [empty])
+
+        # Empty input doesn't start a frame.
+        list(chunker.compress(b""))
+        self.assertEqual(list(chunker.write_empty_frame()), [empty])
+
+        # Including after a frame ended.
+        list(chunker.compress(b"foo"))
+        list(chunker.flush(end_frame=True))
+        list(chunker.compress(b""))
+        self.assertEqual(list(chunker.write_empty_frame()), [empty])
+        self.assertEqual(list(chunker.flush(end_frame=True)), [])
+
+        # An open frame is ended first.
+        chunks = list(chunker.compress(b"foo" * 1024))
+        chunks.extend(chunker.write_empty_frame())
+        data = b"".join(chunks)
+        self.assertTrue(data.endswith(empty))
+
+        dctx = zstd.ZstdDecompressor()
+        frame = data[: -len(empty)]
+        self.assertEqual(
+            dctx.decompress(frame, max_output_size=3072), b"foo" * 1024
+        )
+
+        # Output smaller than a chunk is handled.
+        chunker = cctx.chunker(chunk_size=4)
+        chunks = list(chunker.compress(b"bar"))
+        chunks.extend(chunker.write_empty_frame())
+        self.assertTrue(all(len(c) == 4 for c in chunks[:-1]))
+        self.assertTrue(b"".join(chunks).endswith(empty))
+
+        chunks.extend(chunker.compress(b"baz"))
+        chunks.extend(chunker.finish())
+
+        with dctx.stream_reader(b"".join(chunks), read_across_frames=True) as r:
+            self.assertEqual(r.read(), b"barbaz")
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError,
+            "cannot call write_empty_frame\\(\\) after compression finished",
+        ):
+            chunker.write_empty_frame()
+
+    @unittest.skipUnless(
+        "unfinished_frame_warning" in zstd.backend_features,
+        "ResourceWarning only implemented in Rust backend",
+    )
+    def test_unfinished_frame_warning(self):
+        cctx = zstd.ZstdCompressor()
+
+        chunker = cctx.chunker()
+        list(chunker.compress(b"foobar"))
+        with self.assertWarnsRegex(
+            ResourceWarning,
+            "ZstdCompressionChunker destroyed with an unfinished frame",
+        ):
+            del chunker
+            gc.collect()
+
+        chunker = cctx.chunker()
+        list(chunker.compress(b"foobar"))
+        list(chunker.finish())
+        with warnings.catch_warnings():
+            warnings.simplefilter("error")
+            del chunker
+            gc.collect()
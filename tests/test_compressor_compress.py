@@ -1,3 +1,4 @@
+import random
 import struct
 import unittest
 
@@ -67,6 +68,32 @@ class TestCompressor_compress(unittest.TestCase):
             b"\x02\x09\x00\x00\x6f",
         )
 
+    @unittest.skipUnless(
+        "output_size_hint" in zstd.backend_features,
+        "output_size_hint only implemented in Rust backend",
+    )
+    def test_output_size_hint(self):
+        chunks = []
+        for i in range(255):
+            chunks.append(struct.Struct(">B").pack(i) * 16384)
+
+        source = b"".join(chunks)
+
+        cctx = zstd.ZstdCompressor(level=3, write_content_size=False)
+        expected = cctx.compress(source)
+
+        # Hints that are too small should grow the output buffer.
+        for hint in (0, 1, 16, len(expected), len(expected) * 4):
+            self.assertEqual(
+                cctx.compress(source, output_size_hint=hint), expected
+            )
+
+        # Huge hints are capped at the compression bound.
+        self.assertEqual(
+            cctx.compress(b"abc", output_size_hint=2 ** 62),
+            cctx.compress(b"abc"),
+        )
+
     def test_negative_level(self):
         cctx = zstd.ZstdCompressor(level=-4)
         result = cctx.compress(b"foo" * 256)
@@ -87,6 +114,36 @@ class TestCompressor_compress(unittest.TestCase):
         self.assertEqual(magic[0:4], b"\x28\xb5\x2f\xfd")
         self.assertEqual(magic[4:], no_magic)
 
+    @unittest.skipUnless(
+        "format" in zstd.backend_features,
+        "format only implemented in Rust backend",
+    )
+    def test_format_argument(self):
+        magic = zstd.ZstdCompressor(level=1).compress(b"foobar")
+
+        cctx = zstd.ZstdCompressor(
+            level=1, format=zstd.FORMAT_ZSTD1_MAGICLESS
+        )
+        no_magic = cctx.compress(b"foobar")
+        self.assertEqual(magic[4:], no_magic)
+
+        cctx = zstd.ZstdCompressor(level=1, format=zstd.FORMAT_ZSTD1)
+        self.assertEqual(cctx.compress(b"foobar"), magic)
+
+        dctx = zstd.ZstdDecompressor(format=zstd.FORMAT_ZSTD1_MAGICLESS)
+        self.assertEqual(b"".join(dctx.read_to_iter(no_magic)), b"foobar")
+
+        with self.assertRaisesRegex(ValueError, "invalid format value"):
+            zstd.ZstdCompressor(format=42)
+
+        with self.assertRaisesRegex(
+            ValueError, "cannot define compression_params and format"
+        ):
+            zstd.ZstdCompressor(
+                compression_params=zstd.ZstdCompressionParameters(),
+                format=zstd.FORMAT_ZSTD1_MAGICLESS,
+            )
+
     def test_write_checksum(self):
         cctx = zstd.ZstdCompressor(level=1)
         no_checksum = cctx.compress(b"foobar")
@@ -165,6 +222,34 @@ class TestCompressor_compress(unittest.TestCase):
         for i in range(32):
             cctx.compress(b"foo bar foobar foo bar foobar")
 
+    @unittest.skipUnless(
+        "match_finder_parameters" in zstd.backend_features,
+        "match finder parameters only implemented in Rust backend",
+    )
+    def test_match_finder_parameters(self):
+        samples = []
+        for i in range(128):
+            samples.append(b"foo" * 64)
+            samples.append(b"bar" * 64)
+            samples.append(b"foobar" * 64)
+
+        d = zstd.train_dictionary(8192, samples)
+        source = b"foo bar foobar foo bar foobar" * 32
+
+        for mode in (
+            zstd.ROW_MATCH_FINDER_AUTO,
+            zstd.ROW_MATCH_FINDER_DISABLED,
+            zstd.ROW_MATCH_FINDER_ENABLED,
+        ):
+            params = zstd.ZstdCompressionParameters.from_level(
+                5, enable_dedicated_dict_search=1, use_row_match_finder=mode
+            )
+            cctx = zstd.ZstdCompressor(dict_data=d, compression_params=params)
+            frame = cctx.compress(source)
+
+            dctx = zstd.ZstdDecompressor(dict_data=d)
+            self.assertEqual(dctx.decompress(frame), source)
+
     def test_multithreaded(self):
         chunk_size = multithreaded_chunk_size(1)
         source = b"".join([b"x" * chunk_size, b"y" * chunk_size])
@@ -213,3 +298,163 @@ class TestCompressor_compress(unittest.TestCase):
         self.assertEqual(
             result, b"\x28\xb5\x2f\xfd\x20\x03\x19\x00\x00\x66\x6f\x6f"
         )
+
+    @unittest.skipUnless(
+        "pad_to" in zstd.backend_features,
+        "pad_to only implemented in Rust backend",
+    )
+    def test_pad_to(self):
+        cctx = zstd.ZstdCompressor()
+        dctx = zstd.ZstdDecompressor()
+
+        frame = cctx.compress(b"foobar" * 100)
+
+        for pad_to in (512, 4096, len(frame), len(frame) + 4, 1):
+            padded = cctx.compress(b"foobar" * 100, pad_to=pad_to)
+            self.assertEqual(len(padded) % pad_to, 0)
+            self.assertTrue(padded.startswith(frame))
+            self.assertEqual(dctx.decompress(padded), b"foobar" * 100)
+            self.assertEqual(
+                b"".join(dctx.read_to_iter(padded)), b"foobar" * 100
+            )
+
+        # Output already a multiple isn't padded.
+        self.assertEqual(
+            cctx.compress(b"foobar" * 100, pad_to=len(frame)), frame
+        )
+
+        # A gap too small for a skippable frame is extended by a record.
+        padded = cctx.compress(b"foobar" * 100, pad_to=len(frame) + 4)
+        self.assertEqual(len(padded), 2 * (len(frame) + 4))
+        self.assertTrue(zstd.is_skippable_frame(padded[len(frame) :]))
+
+        with self.assertRaisesRegex(ValueError, "pad_to must be positive"):
+            cctx.compress(b"foobar", pad_to=0)
+
+    @unittest.skipUnless(
+        "ldm_auto" in zstd.backend_features,
+        "enable_ldm='auto' only implemented in Rust backend",
+    )
+    def test_enable_ldm_auto(self):
+        params = zstd.ZstdCompressionParameters(
+            compression_level=3, enable_ldm="auto", write_content_size=False
+        )
+        cctx = zstd.ZstdCompressor(compression_params=params)
+        dctx = zstd.ZstdDecompressor()
+
+        def window_size(frame):
+            return zstd.get_frame_parameters(frame).window_size
+
+        # Unknown size: the default decoder window limit.
+        cobj = cctx.compressobj()
+        frame = cobj.compress(b"foobar" * 1000) + cobj.flush()
+        self.assertEqual(window_size(frame), 2 ** 27)
+        self.assertEqual(
+            dctx.decompressobj().decompress(frame), b"foobar" * 1000
+        )
+
+        # Within the level's default window: left to zstd.
+        frame = cctx.compress(b"foobar" * 1000)
+        self.assertEqual(window_size(frame), 8192)
+
+        # Larger: a window covering the input.
+        data = b"".join(b"foo%dbar" % i for i in range(400000))
+        self.assertGreater(len(data), 2 ** 22)
+        self.assertLess(len(data), 2 ** 23)
+        frame = cctx.compress(data)
+        self.assertEqual(window_size(frame), 2 ** 23)
+        self.assertEqual(dctx.decompressobj().decompress(frame), data)
+
+        # Parameters for one frame don't leak into the next.
+        cobj = cctx.compressobj()
+        frame = cobj.compress(b"foobar") + cobj.flush()
+        self.assertEqual(window_size(frame), 2 ** 27)
+
+        config = cctx.to_config()
+        self.assertEqual(
+            zstd.ZstdCompressor.from_config(config).to_config(), config
+        )
+
+    @unittest.skipUnless(
+        "deadline" in zstd.backend_features,
+        "deadline_ms only implemented in Rust backend",
+    )
+    def test_deadline(self):
+        data = b"".join(
+            b"foo%dbar%d" % (i, i * 7 % 1000) for i in range(400000)
+        )
+
+        def frames(value):
+            decoder = zstd.ZstdDecompressor().streaming_decoder()
+            output, events = decoder.feed(value)
+            self.assertEqual(output, data)
+            return len([e for e in events if e[0] == "frame_complete"])
+
+        cctx = zstd.ZstdCompressor(level=19)
+
+        # Not exceeded: one frame, without content size.
+        result = cctx.compress(data[0:1000], deadline_ms=60000)
+        self.assertEqual(
+            zstd.get_frame_parameters(result).content_size,
+            zstd.CONTENTSIZE_UNKNOWN,
+        )
+        self.assertEqual(
+            zstd.ZstdDecompressor().decompressobj().decompress(result),
+            data[0:1000],
+        )
+
+        # Exceeded before starting: a single level 1 frame.
+        result = cctx.compress(data, deadline_ms=0)
+        self.assertEqual(result, zstd.ZstdCompressor(level=1).compress(data))
+
+        # Exceeded part way: the frame ends early and a second frame follows.
+        result = cctx.compress(data, deadline_ms=1)
+        self.assertEqual(frames(result), 2)
+        self.assertEqual(
+            zstd.get_frame_parameters(result).content_size,
+            zstd.CONTENTSIZE_UNKNOWN,
+        )
+
+        # The original level is restored afterwards.
+        self.assertEqual(
+            cctx.compress(data[0:1000]),
+            zstd.ZstdCompressor(level=19).compress(data[0:1000]),
+        )
+
+    @unittest.skipUnless(
+        "patch_from" in zstd.backend_features,
+        "compress_patch() only implemented in Rust backend",
+    )
+    def test_compress_patch(self):
+        size = 2 ** 20
+        old = random.Random(42).getrandbits(8 * size).to_bytes(size, "little")
+        new = old[0:1000] + b"patched" + old[1000:500000] + old[600000:]
+
+        cctx = zstd.ZstdCompressor(level=1)
+        patch = cctx.compress_patch(old, new)
+
+        # Random data only compresses by referencing old_data, which is
+        # further back than the level's default window.
+        self.assertLess(len(patch), 1024)
+        self.assertEqual(
+            zstd.get_frame_parameters(patch).content_size, len(new)
+        )
+        self.assertEqual(
+            zstd.ZstdDecompressor().decompress_patch(old, patch), new
+        )
+
+        # Parameters are restored afterwards.
+        self.assertEqual(
+            cctx.compress(new), zstd.ZstdCompressor(level=1).compress(new)
+        )
+
+        # The dictionary isn't used for the patch but is restored afterwards.
+        d = zstd.ZstdCompressionDict(
+            b"foobar" * 100, dict_type=zstd.DICT_TYPE_RAWCONTENT
+        )
+        cctx = zstd.ZstdCompressor(level=1, dict_data=d)
+        self.assertEqual(cctx.compress_patch(old, new), patch)
+        self.assertEqual(
+            cctx.compress(b"foobar" * 10),
+            zstd.ZstdCompressor(level=1, dict_data=d).compress(b"foobar" * 10),
+        )
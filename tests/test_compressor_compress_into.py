@@ -0,0 +1,67 @@
+import array
+import unittest
+
+import zstandard as zstd
+
+
+@unittest.skipUnless(
+    "compress_into" in zstd.backend_features,
+    "compress_into() only implemented in Rust backend",
+)
+class TestCompressor_compress_into(unittest.TestCase):
+    def test_simple(self):
+        cctx = zstd.ZstdCompressor()
+        expected = cctx.compress(b"foobar" * 1024)
+
+        dest = bytearray(1024)
+        size = cctx.compress_into(b"foobar" * 1024, dest)
+        self.assertEqual(size, len(expected))
+        self.assertEqual(dest[:size], expected)
+
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(dctx.decompress(dest[:size]), b"foobar" * 1024)
+
+    def test_buffer_types(self):
+        source = array.array("d", range(4096))
+        expected = zstd.ZstdCompressor().compress(source.tobytes())
+
+        dest = memoryview(bytearray(len(expected) + 16))
+        size = zstd.ZstdCompressor().compress_into(source, dest)
+        self.assertEqual(dest[:size].tobytes(), expected)
+
+        dest = array.array("I", [0] * len(expected))
+        size = zstd.ZstdCompressor().compress_into(source, dest)
+        self.assertEqual(dest.tobytes()[:size], expected)
+
+    def test_readonly(self):
+        cctx = zstd.ZstdCompressor()
+
+        # The buffer protocol refuses writable views of these.
+        for dest in (b"\x00" * 64, memoryview(bytearray(64)).toreadonly()):
+            with self.assertRaises(BufferError):
+                cctx.compress_into(b"foobar", dest)
+
+    def test_too_small(self):
+        cctx = zstd.ZstdCompressor()
+        source = bytes(range(256)) * 64
+
+        with self.assertRaisesRegex(
+            zstd.ZstdDestinationTooSmallError,
+            "destination buffer too small: buffer is 64 bytes",
+        ):
+            cctx.compress_into(source, bytearray(64))
+
+        self.assertTrue(
+            issubclass(zstd.ZstdDestinationTooSmallError, zstd.ZstdError)
+        )
+
+        # The compressor remains usable after the failure.
+        dest = bytearray(len(source) + 64)
+        size = cctx.compress_into(source, dest)
+        self.assertEqual(dest[:size], cctx.compress(source))
+
+    def test_empty(self):
+        cctx = zstd.ZstdCompressor()
+        dest = bytearray(64)
+        size = cctx.compress_into(b"", dest)
+        self.assertEqual(dest[:size], cctx.compress(b""))
@@ -1,3 +1,5 @@
+import array
+import hashlib
 import io
 import struct
 import unittest
@@ -185,3 +187,123 @@ class TestCompressor_compressobj(unittest.TestCase):
         # Try another operation on the compressor.
         cctx.compressobj(size=4)
         cctx.compress(b"foobar")
+
+    @unittest.skipUnless(
+        "compress_vectored" in zstd.backend_features,
+        "compress_vectored() only implemented in Rust backend",
+    )
+    def test_compress_vectored(self):
+        buffers = [b"foo" * 1024, bytearray(b"bar" * 512), memoryview(b"baz")]
+
+        cctx = zstd.ZstdCompressor()
+        cobj = cctx.compressobj()
+        frame = cobj.compress_vectored(buffers) + cobj.flush()
+
+        cobj = cctx.compressobj()
+        expected = cobj.compress(b"".join(buffers)) + cobj.flush()
+
+        self.assertEqual(frame, expected)
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError,
+            "cannot call compress_vectored\\(\\) after compressor finished",
+        ):
+            cobj.compress_vectored([b"foo"])
+
+        with self.assertRaises(TypeError):
+            cctx.compressobj().compress_vectored([b"foo", None])
+
+    @unittest.skipUnless(
+        "hasher" in zstd.backend_features,
+        "hasher only implemented in Rust backend",
+    )
+    def test_hasher(self):
+        cctx = zstd.ZstdCompressor()
+        cobj = cctx.compressobj(hasher=hashlib.sha256())
+
+        frame = cobj.compress(b"foo" * 1024)
+        frame += cobj.compress_vectored([b"bar", bytearray(b"baz")])
+        self.assertIsNone(cobj.digest)
+
+        frame += cobj.flush(zstd.COMPRESSOBJ_FLUSH_BLOCK)
+        self.assertIsNone(cobj.digest)
+
+        frame += cobj.flush()
+        self.assertEqual(
+            cobj.digest, hashlib.sha256(b"foo" * 1024 + b"barbaz").digest()
+        )
+        self.assertEqual(
+            zstd.ZstdDecompressor().decompress(frame, max_output_size=4096),
+            b"foo" * 1024 + b"barbaz",
+        )
+
+        with self.assertRaisesRegex(
+            ValueError, "hasher must have update\\(\\) and digest\\(\\) methods"
+        ):
+            cctx.compressobj(hasher=object())
+
+    @unittest.skipUnless(
+        "byte_counters" in zstd.backend_features,
+        "byte counters only implemented in Rust backend",
+    )
+    def test_byte_counters(self):
+        cobj = zstd.ZstdCompressor().compressobj()
+        self.assertEqual(cobj.bytes_ingested, 0)
+        self.assertEqual(cobj.bytes_produced, 0)
+
+        frame = cobj.compress(b"foo" * 1024)
+        self.assertEqual(cobj.bytes_ingested, 3072)
+        self.assertEqual(cobj.bytes_produced, len(frame))
+
+        frame += cobj.compress_vectored([b"bar", array.array("I", [0] * 4)])
+        self.assertEqual(cobj.bytes_ingested, 3091)
+        self.assertEqual(cobj.bytes_produced, len(frame))
+
+        frame += cobj.flush(zstd.COMPRESSOBJ_FLUSH_BLOCK)
+        self.assertEqual(cobj.bytes_produced, len(frame))
+
+        frame += cobj.flush()
+        self.assertEqual(cobj.bytes_ingested, 3091)
+        self.assertEqual(cobj.bytes_produced, len(frame))
+
+    @unittest.skipUnless(
+        "write_empty_frame" in zstd.backend_features,
+        "write_empty_frame() only implemented in Rust backend",
+    )
+    def test_write_empty_frame(self):
+        cctx = zstd.ZstdCompressor()
+
+        cobj = cctx.compressobj()
+        self.assertEqual(cobj.write_empty_frame(), cctx.compress(b""))
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "compressor object already finished"
+        ):
+            cobj.write_empty_frame()
+
+        cobj = cctx.compressobj()
+        cobj.compress(b"foo")
+        with self.assertRaisesRegex(
+            zstd.ZstdError,
+            "cannot call write_empty_frame\\(\\) after compress\\(\\)",
+        ):
+            cobj.write_empty_frame()
+
+    def test_multidimensional_buffers(self):
+        raw = bytes(range(256)) * 64
+
+        sources = [
+            array.array("d", range(2048)),
+            memoryview(raw).cast("B", shape=[64, 256]),
+            memoryview(raw).cast("I", shape=[16, 256]),
+        ]
+
+        cctx = zstd.ZstdCompressor()
+        for source in sources:
+            cobj = cctx.compressobj()
+            frame = cobj.compress(source) + cobj.flush()
+
+            cobj = cctx.compressobj()
+            expected = cobj.compress(memoryview(source).tobytes()) + cobj.flush()
+
+            self.assertEqual(frame, expected)
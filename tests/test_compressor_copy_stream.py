@@ -1,3 +1,4 @@
+import hashlib
 import io
 import struct
 import unittest
@@ -26,6 +27,11 @@ class TestCompressor_copy_stream(unittest.TestCase):
         with self.assertRaises(ValueError):
             cctx.copy_stream(source, dest)
 
+    def test_unknown_argument(self):
+        cctx = zstd.ZstdCompressor()
+        with self.assertRaises(TypeError):
+            cctx.copy_stream(io.BytesIO(), io.BytesIO(), bogus=True)
+
     def test_empty(self):
         source = io.BytesIO()
         dest = io.BytesIO()
@@ -193,3 +199,128 @@ class TestCompressor_copy_stream(unittest.TestCase):
 
         with self.assertRaisesRegex(IOError, "write"):
             cctx.copy_stream(source, dest)
+
+    @unittest.skipUnless(
+        "hasher" in zstd.backend_features,
+        "hasher only implemented in Rust backend",
+    )
+    def test_hasher(self):
+        source = io.BytesIO(b"foobar" * 8192)
+        dest = io.BytesIO()
+
+        cctx = zstd.ZstdCompressor()
+        r, w, digest = cctx.copy_stream(
+            source, dest, write_size=64, hasher=hashlib.sha256()
+        )
+
+        self.assertEqual(r, len(source.getvalue()))
+        self.assertEqual(w, len(dest.getvalue()))
+        self.assertEqual(digest, hashlib.sha256(dest.getvalue()).digest())
+
+        with self.assertRaisesRegex(
+            ValueError, "hasher must have update\\(\\) and digest\\(\\) methods"
+        ):
+            cctx.copy_stream(io.BytesIO(), io.BytesIO(), hasher=object())
+
+    @unittest.skipUnless(
+        "copy_stream_max_time_ms" in zstd.backend_features,
+        "max_time_ms only implemented in Rust backend",
+    )
+    def test_max_time_ms(self):
+        source = io.BytesIO(b"foobar" * 8192)
+        dest = io.BytesIO()
+
+        cctx = zstd.ZstdCompressor(write_checksum=True)
+
+        # A zero time slice yields after a single read.
+        r, w, digest, finished = cctx.copy_stream(
+            source,
+            dest,
+            size=len(source.getvalue()),
+            read_size=1024,
+            hasher=hashlib.sha256(),
+            max_time_ms=0,
+        )
+        self.assertEqual((r, digest, finished), (1024, None, False))
+
+        cctx.copy_stream(
+            source, dest, read_size=1024, max_time_ms=0, resume=True
+        )
+        self.assertEqual(source.tell(), 2048)
+
+        hasher = hashlib.sha256()
+        r, w, digest, finished = cctx.copy_stream(
+            source,
+            dest,
+            read_size=1024,
+            hasher=hasher,
+            max_time_ms=60000,
+            resume=True,
+        )
+        self.assertTrue(finished)
+        self.assertEqual(r, len(source.getvalue()) - 2048)
+        self.assertEqual(w, len(dest.getvalue()))
+        self.assertEqual(digest, hashlib.sha256(dest.getvalue()).digest())
+
+        params = zstd.get_frame_parameters(dest.getvalue())
+        self.assertEqual(params.content_size, len(source.getvalue()))
+        self.assertEqual(
+            zstd.ZstdDecompressor().decompress(dest.getvalue()),
+            source.getvalue(),
+        )
+
+    @unittest.skipUnless(
+        "copy_stream_max_time_ms" in zstd.backend_features,
+        "max_time_ms only implemented in Rust backend",
+    )
+    def test_max_time_ms_resume_until_finished(self):
+        source = io.BytesIO(b"foobar" * 8192)
+        dest = io.BytesIO()
+
+        cctx = zstd.ZstdCompressor()
+        result = cctx.copy_stream(source, dest, read_size=4096, max_time_ms=0)
+        calls = 1
+        while not result.finished:
+            self.assertGreater(result.bytes_read, 0)
+            result = cctx.copy_stream(
+                source, dest, read_size=4096, max_time_ms=0, resume=True
+            )
+            calls += 1
+
+        self.assertEqual(calls, 13)
+        dobj = zstd.ZstdDecompressor().decompressobj()
+        self.assertEqual(dobj.decompress(dest.getvalue()), source.getvalue())
+
+    @unittest.skipUnless(
+        "copy_stream_result" in zstd.backend_features,
+        "CopyStreamResult only implemented in Rust backend",
+    )
+    def test_result(self):
+        source = io.BytesIO(b"foobar" * 8192)
+        dest = io.BytesIO()
+
+        cctx = zstd.ZstdCompressor(write_checksum=True)
+        result = cctx.copy_stream(source, dest)
+
+        self.assertEqual(
+            result, (len(source.getvalue()), len(dest.getvalue()))
+        )
+        self.assertEqual(
+            list(result), [result.bytes_read, result.bytes_written]
+        )
+        self.assertEqual(result.bytes_read, len(source.getvalue()))
+        self.assertEqual(result.bytes_written, len(dest.getvalue()))
+        self.assertEqual(result.frames, 1)
+        self.assertTrue(result.checksum)
+        self.assertTrue(result.finished)
+        self.assertGreaterEqual(result.duration, 0.0)
+        self.assertIn("CopyStreamResult(bytes_read=49152", repr(result))
+
+        cctx = zstd.ZstdCompressor()
+        result = cctx.copy_stream(
+            io.BytesIO(b"foobar"), io.BytesIO(), read_size=2, max_time_ms=0
+        )
+        self.assertEqual(result.bytes_read, 2)
+        self.assertEqual(result.frames, 0)
+        self.assertFalse(result.checksum)
+        self.assertFalse(result.finished)
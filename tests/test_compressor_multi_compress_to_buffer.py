@@ -1,3 +1,4 @@
+import asyncio
 import struct
 import unittest
 
@@ -129,3 +130,65 @@ class TestCompressor_multi_compress_to_buffer(unittest.TestCase):
                 self.assertEqual(result[i].tobytes(), reference[0])
             else:
                 self.assertEqual(result[i].tobytes(), reference[1])
+
+    @unittest.skipUnless(
+        "return_index" in zstd.backend_features,
+        "return_index only implemented in Rust backend",
+    )
+    def test_return_index(self):
+        cctx = zstd.ZstdCompressor(write_checksum=True)
+
+        original = [b"foo" * 12, b"bar" * 6, b"baz" * 32]
+        frames = [cctx.compress(c) for c in original]
+
+        result, index = cctx.multi_compress_to_buffer(
+            original, return_index=True
+        )
+
+        self.assertIsInstance(result, zstd.BufferWithSegmentsCollection)
+        self.assertIsInstance(index, memoryview)
+        self.assertEqual(index.format, "Q")
+        self.assertEqual(index.shape, (3, 3))
+
+        rows = index.tolist()
+
+        offset = 0
+        for i, frame in enumerate(frames):
+            self.assertEqual(rows[i], [offset, len(frame), len(original[i])])
+            self.assertEqual(result[i].tobytes(), frame)
+            offset += len(frame)
+
+    @unittest.skipUnless(
+        "multi_buffer_async" in zstd.backend_features,
+        "async only implemented in Rust backend",
+    )
+    def test_async(self):
+        cctx = zstd.ZstdCompressor(write_checksum=True)
+
+        original = [b"foo" * 12, b"bar" * 6, b"baz" * 32]
+        frames = [cctx.compress(c) for c in original]
+
+        async def compress():
+            result = await cctx.multi_compress_to_buffer_async(
+                original, threads=2
+            )
+            _, index = await cctx.multi_compress_to_buffer_async(
+                original, return_index=True
+            )
+
+            return result, index
+
+        result, index = asyncio.run(compress())
+
+        self.assertIsInstance(result, zstd.BufferWithSegmentsCollection)
+        self.assertEqual([b.tobytes() for b in result], frames)
+        self.assertEqual(index.shape, (3, 3))
+
+        async def empty():
+            await cctx.multi_compress_to_buffer_async([b"", b""])
+
+        with self.assertRaisesRegex(ValueError, "source elements are empty"):
+            asyncio.run(empty())
+
+        with self.assertRaisesRegex(RuntimeError, "no running event loop"):
+            cctx.multi_compress_to_buffer_async(original)
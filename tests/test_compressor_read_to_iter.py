@@ -138,3 +138,46 @@ class TestCompressor_read_to_iter(unittest.TestCase):
 
         with self.assertRaisesRegex(IOError, "read"):
             next(it)
+
+    @unittest.skipUnless(
+        "as_memoryview" in zstd.backend_features,
+        "as_memoryview only implemented in Rust backend",
+    )
+    def test_as_memoryview(self):
+        source = io.BytesIO()
+        for i in range(255):
+            source.write(bytes([i]) * 16384)
+
+        cctx = zstd.ZstdCompressor()
+        expected = b"".join(cctx.read_to_iter(source.getvalue(), write_size=512))
+
+        chunks = []
+        for chunk in cctx.read_to_iter(
+            source.getvalue(), write_size=512, as_memoryview=True
+        ):
+            self.assertIsInstance(chunk, memoryview)
+            chunks.append(chunk.tobytes())
+
+        self.assertGreater(len(chunks), 1)
+        self.assertEqual(b"".join(chunks), expected)
+
+    @unittest.skipUnless(
+        "callable_sources" in zstd.backend_features,
+        "callable sources only implemented in Rust backend",
+    )
+    def test_callable_source(self):
+        source = io.BytesIO(b"foobar" * 4096)
+        sizes = []
+
+        def provider(size):
+            sizes.append(size)
+            return source.read(size)
+
+        cctx = zstd.ZstdCompressor()
+        frame = b"".join(cctx.read_to_iter(provider, read_size=512))
+
+        self.assertEqual(set(sizes), {512})
+        self.assertEqual(
+            zstd.ZstdDecompressor().decompress(frame, max_output_size=2 ** 20),
+            source.getvalue(),
+        )
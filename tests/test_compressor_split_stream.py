@@ -0,0 +1,96 @@
+import io
+import random
+import unittest
+
+import zstandard as zstd
+
+
+def mixed_data():
+    # Alternate compressible and incompressible runs so the compression
+    # ratio varies across the input.
+    r = random.Random(42)
+    chunks = []
+    for i in range(20000):
+        if r.random() < 0.3:
+            chunks.append(bytes(r.getrandbits(8) for _ in range(50)))
+        else:
+            chunks.append(b"record %d " % i * 10)
+
+    return b"".join(chunks)
+
+
+@unittest.skipUnless(
+    "split_stream" in zstd.backend_features,
+    "split_stream() only implemented in Rust backend",
+)
+class TestCompressor_split_stream(unittest.TestCase):
+    def test_buffer(self):
+        source = mixed_data()
+        writers = [io.BytesIO() for _ in range(4)]
+
+        cctx = zstd.ZstdCompressor()
+        result = cctx.split_stream(source, writers)
+
+        self.assertEqual(len(result), 4)
+        self.assertEqual(sum(r[0] for r in result), len(source))
+
+        for (read, written), writer in zip(result, writers):
+            self.assertEqual(len(writer.getvalue()), written)
+
+        # Shards are within a block of each other.
+        sizes = [r[1] for r in result]
+        self.assertLess(max(sizes) - min(sizes), 131072)
+
+        # Each shard is an independently decompressible frame.
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(
+            b"".join(
+                dctx.decompressobj().decompress(w.getvalue()) for w in writers
+            ),
+            source,
+        )
+
+    def test_stream(self):
+        source = mixed_data()
+        writers = [io.BytesIO() for _ in range(3)]
+
+        cctx = zstd.ZstdCompressor()
+
+        with self.assertRaisesRegex(
+            ValueError, "size must be specified when the source size is unknown"
+        ):
+            cctx.split_stream(io.BytesIO(source), writers)
+
+        result = cctx.split_stream(
+            io.BytesIO(source), writers, size=len(source), read_size=1000000
+        )
+        self.assertEqual(len(result), 3)
+        self.assertEqual(sum(r[0] for r in result), len(source))
+
+    def test_short_input(self):
+        writers = [io.BytesIO() for _ in range(3)]
+
+        cctx = zstd.ZstdCompressor()
+        result = cctx.split_stream(b"foobar", writers)
+
+        # Writers without input get empty frames.
+        self.assertEqual([r[0] for r in result], [6, 0, 0])
+
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(
+            [dctx.decompressobj().decompress(w.getvalue()) for w in writers],
+            [b"foobar", b"", b""],
+        )
+
+    def test_bad_writers(self):
+        cctx = zstd.ZstdCompressor()
+
+        with self.assertRaisesRegex(
+            ValueError, "must specify at least one writer"
+        ):
+            cctx.split_stream(b"foo", [])
+
+        with self.assertRaisesRegex(
+            ValueError, "writers must have a write\\(\\) method"
+        ):
+            cctx.split_stream(b"foo", [io.BytesIO(), object()])
@@ -1,5 +1,8 @@
+import gc
 import io
+import os
 import unittest
+import warnings
 
 import zstandard as zstd
 
@@ -222,6 +225,7 @@ class TestCompressor_stream_reader(unittest.TestCase):
         self.assertEqual(b[:], foo[2:4])
         self.assertEqual(reader.readinto(b), 2)
         self.assertEqual(b[:], foo[4:6])
+        reader.close()
 
     def test_readinto1(self):
         cctx = zstd.ZstdCompressor()
@@ -361,6 +365,39 @@ class TestCompressor_stream_reader(unittest.TestCase):
 
         self.assertTrue(reader.closed)
 
+    @unittest.skipUnless(
+        "fd_streams" in zstd.backend_features,
+        "integer fds only supported in Rust backend",
+    )
+    def test_fd(self):
+        cctx = zstd.ZstdCompressor()
+
+        rfd, wfd = os.pipe()
+        with os.fdopen(wfd, "wb") as fh:
+            fh.write(b"foo" * 1024)
+
+        with cctx.stream_reader(rfd) as reader:
+            compressed = reader.read()
+
+        self.assertEqual(
+            zstd.ZstdDecompressor().decompress(
+                compressed, max_output_size=3072
+            ),
+            b"foo" * 1024,
+        )
+
+        with self.assertRaises(OSError):
+            os.fstat(rfd)
+
+        rfd, wfd = os.pipe()
+        os.close(wfd)
+
+        with cctx.stream_reader(rfd, closefd=False) as reader:
+            reader.read()
+
+        os.fstat(rfd)
+        os.close(rfd)
+
     def test_write_exception(self):
         b = CustomBytesIO()
         b.write_exception = IOError("write")
@@ -373,3 +410,32 @@ class TestCompressor_stream_reader(unittest.TestCase):
 
         with self.assertRaisesRegex(IOError, "write"):
             writer.flush()
+
+        b.write_exception = None
+        writer.close()
+
+    @unittest.skipUnless(
+        "unfinished_frame_warning" in zstd.backend_features,
+        "ResourceWarning only implemented in Rust backend",
+    )
+    def test_unfinished_frame_warning(self):
+        cctx = zstd.ZstdCompressor()
+
+        reader = cctx.stream_reader(b"foobar" * 1024)
+        reader.read(4)
+        with self.assertWarnsRegex(
+            ResourceWarning,
+            "ZstdCompressionReader destroyed before the end of the frame "
+            "\\(4 bytes",
+        ):
+            del reader
+            gc.collect()
+
+        # Explicitly closing signals intent to stop early.
+        reader = cctx.stream_reader(b"foobar" * 1024)
+        reader.read(4)
+        reader.close()
+        with warnings.catch_warnings():
+            warnings.simplefilter("error")
+            del reader
+            gc.collect()
@@ -1,9 +1,12 @@
+import array
+import gc
 import hashlib
 import io
 import os
 import tarfile
 import tempfile
 import unittest
+import warnings
 
 import zstandard as zstd
 
@@ -14,6 +17,11 @@ from .common import (
 
 
 class TestCompressor_stream_writer(unittest.TestCase):
+    def test_unknown_argument(self):
+        cctx = zstd.ZstdCompressor()
+        with self.assertRaises(TypeError):
+            cctx.stream_writer(io.BytesIO(), bogus=True)
+
     def test_io_api(self):
         buffer = io.BytesIO()
         cctx = zstd.ZstdCompressor()
@@ -193,6 +201,43 @@ class TestCompressor_stream_writer(unittest.TestCase):
         self.assertFalse(buffer.closed)
         self.assertEqual(buffer._flush_count, 0)
 
+    @unittest.skipUnless(
+        "fd_streams" in zstd.backend_features,
+        "integer fds only supported in Rust backend",
+    )
+    def test_fd(self):
+        cctx = zstd.ZstdCompressor()
+
+        rfd, wfd = os.pipe()
+        with cctx.stream_writer(wfd) as compressor:
+            compressor.write(b"foo" * 1024)
+
+        with self.assertRaises(OSError):
+            os.fstat(wfd)
+
+        with os.fdopen(rfd, "rb") as fh:
+            compressed = fh.read()
+
+        self.assertEqual(
+            zstd.ZstdDecompressor().decompress(
+                compressed, max_output_size=3072
+            ),
+            b"foo" * 1024,
+        )
+
+        rfd, wfd = os.pipe()
+        with cctx.stream_writer(wfd, closefd=False) as compressor:
+            compressor.write(b"foo")
+
+        os.close(wfd)
+        with os.fdopen(rfd, "rb") as fh:
+            self.assertEqual(
+                zstd.ZstdDecompressor().decompress(
+                    fh.read(), max_output_size=3
+                ),
+                b"foo",
+            )
+
     def test_empty(self):
         buffer = io.BytesIO()
         cctx = zstd.ZstdCompressor(level=1, write_content_size=False)
@@ -249,6 +294,7 @@ class TestCompressor_stream_writer(unittest.TestCase):
 
             compressor = cctx.stream_writer(buffer, write_return_read=False)
             self.assertEqual(compressor.write(source), 0)
+            compressor.close()
 
     def test_multiple_compress(self):
         buffer = io.BytesIO()
@@ -284,6 +330,7 @@ class TestCompressor_stream_writer(unittest.TestCase):
         self.assertEqual(compressor.write(b"foo"), 0)
         self.assertEqual(compressor.write(b"barbiz"), 0)
         self.assertEqual(compressor.write(b"x" * 8192), 0)
+        compressor.close()
 
     def test_dictionary(self):
         samples = []
@@ -491,6 +538,32 @@ class TestCompressor_stream_writer(unittest.TestCase):
         self.assertEqual(dest._write_count, 3)
         self.assertEqual(dest._flush_count, 2)
 
+    @unittest.skipUnless(
+        "flush_result" in zstd.backend_features,
+        "FlushResult only implemented in Rust backend",
+    )
+    def test_flush_result(self):
+        cctx = zstd.ZstdCompressor(level=3)
+        dest = CustomBytesIO()
+        with cctx.stream_writer(dest, closefd=False) as compressor:
+            compressor.write(b"foo")
+            result = compressor.flush()
+            self.assertEqual(result.bytes_written, 12)
+            self.assertFalse(result.frame_ended)
+            self.assertTrue(result.writer_flushed)
+            self.assertEqual(int(result), 12)
+            self.assertEqual(
+                repr(result),
+                "FlushResult(bytes_written=12, frame_ended=False, "
+                "writer_flushed=True)",
+            )
+
+            compressor.write(b"bar")
+            result = compressor.flush(zstd.FLUSH_FRAME)
+            self.assertEqual(result, 6)
+            self.assertTrue(result.frame_ended)
+            self.assertEqual(len(dest.getvalue()), 18)
+
     def test_flush_empty_block(self):
         cctx = zstd.ZstdCompressor(level=3, write_checksum=True)
         dest = CustomBytesIO()
@@ -591,3 +664,423 @@ class TestCompressor_stream_writer(unittest.TestCase):
             with tarfile.open(mode="r|", fileobj=reader) as tf:
                 for member in tf:
                     self.assertEqual(member.name, "test_compressor.py")
+
+    @unittest.skipUnless(
+        "write_vectored" in zstd.backend_features,
+        "write_vectored() only implemented in Rust backend",
+    )
+    def test_write_vectored(self):
+        buffers = [b"foo" * 1024, bytearray(b"bar" * 512), memoryview(b"baz")]
+
+        cctx = zstd.ZstdCompressor()
+        dest = NonClosingBytesIO()
+        with cctx.stream_writer(dest) as compressor:
+            self.assertEqual(
+                compressor.write_vectored(buffers), sum(map(len, buffers))
+            )
+
+        self.assertEqual(
+            zstd.ZstdDecompressor().decompress(
+                dest.getvalue(), max_output_size=2 ** 20
+            ),
+            b"".join(buffers),
+        )
+
+        with self.assertRaisesRegex(ValueError, "stream is closed"):
+            compressor.write_vectored([b"foo"])
+
+    def test_multidimensional_buffers(self):
+        raw = bytes(range(256)) * 64
+
+        sources = [
+            array.array("d", range(2048)),
+            memoryview(raw).cast("B", shape=[64, 256]),
+            memoryview(raw).cast("I", shape=[16, 256]),
+        ]
+
+        cctx = zstd.ZstdCompressor()
+        for source in sources:
+            dest = NonClosingBytesIO()
+            with cctx.stream_writer(dest, write_return_read=True) as compressor:
+                self.assertEqual(
+                    compressor.write(source), memoryview(source).nbytes
+                )
+
+            self.assertEqual(
+                zstd.ZstdDecompressor().decompress(
+                    dest.getvalue(), max_output_size=2 ** 20
+                ),
+                memoryview(source).tobytes(),
+            )
+
+    @unittest.skipUnless(
+        "unfinished_frame_warning" in zstd.backend_features,
+        "ResourceWarning only implemented in Rust backend",
+    )
+    def test_unfinished_frame_warning(self):
+        cctx = zstd.ZstdCompressor()
+
+        writer = cctx.stream_writer(io.BytesIO())
+        writer.write(b"foobar")
+        with self.assertWarnsRegex(
+            ResourceWarning,
+            "ZstdCompressionWriter destroyed with an unfinished frame "
+            "\\(6 bytes written",
+        ):
+            del writer
+            gc.collect()
+
+        # A finished frame doesn't warn.
+        writer = cctx.stream_writer(io.BytesIO())
+        writer.write(b"foobar")
+        writer.flush(zstd.FLUSH_FRAME)
+        with warnings.catch_warnings():
+            warnings.simplefilter("error")
+            del writer
+            gc.collect()
+
+    @unittest.skipUnless(
+        "deadline" in zstd.backend_features,
+        "deadline_ms only implemented in Rust backend",
+    )
+    def test_deadline(self):
+        data = b"".join(
+            b"foo%dbar%d" % (i, i * 7 % 1000) for i in range(400000)
+        )
+
+        cctx = zstd.ZstdCompressor(level=19)
+
+        with self.assertRaisesRegex(
+            ValueError, "cannot specify both size and deadline_ms"
+        ):
+            cctx.stream_writer(io.BytesIO(), size=10, deadline_ms=1)
+
+        dest = NonClosingBytesIO()
+        with cctx.stream_writer(dest, deadline_ms=1) as compressor:
+            compressor.write(data)
+            compressor.flush(zstd.FLUSH_FRAME)
+            compressor.write(b"foobar" * 100)
+
+        decoder = zstd.ZstdDecompressor().streaming_decoder()
+        output, events = decoder.feed(dest.getvalue())
+        self.assertEqual(output, data + b"foobar" * 100)
+        self.assertEqual(
+            [e[0] for e in events],
+            ["frame_complete", "frame_complete", "frame_complete"],
+        )
+
+        # The last frame is compressed at the original level again.
+        expected = NonClosingBytesIO()
+        cctx = zstd.ZstdCompressor(level=19)
+        with cctx.stream_writer(expected) as compressor:
+            compressor.write(b"foobar" * 100)
+
+        self.assertTrue(dest.getvalue().endswith(expected.getvalue()))
+
+    @unittest.skipUnless(
+        "frame_progression" in zstd.backend_features,
+        "frame_progression() only implemented in Rust backend",
+    )
+    def test_frame_progression(self):
+        cctx = zstd.ZstdCompressor()
+        dest = io.BytesIO()
+
+        with cctx.stream_writer(dest, closefd=False) as writer:
+            self.assertEqual(writer.frame_progression(), (0, 0, 0))
+            writer.write(b"foobar")
+            self.assertEqual(writer.frame_progression(), (6, 0, 0))
+
+            self.assertEqual(
+                writer.frame_progression(detailed=True),
+                {
+                    "ingested": 6,
+                    "consumed": 0,
+                    "produced": 0,
+                    "flushed": 0,
+                    "current_job_id": 0,
+                    "active_workers": 0,
+                },
+            )
+
+    @unittest.skipUnless(
+        "frame_progression" in zstd.backend_features,
+        "frame_progression() only implemented in Rust backend",
+    )
+    def test_frame_progression_multithreaded(self):
+        cctx = zstd.ZstdCompressor(threads=2)
+        dest = io.BytesIO()
+
+        source = b"".join(b"foo%dbar" % i for i in range(500000))
+
+        with cctx.stream_writer(dest, closefd=False) as writer:
+            writer.write(source)
+            writer.flush()
+
+            progression = writer.frame_progression(detailed=True)
+            self.assertEqual(progression["ingested"], len(source))
+            self.assertEqual(progression["consumed"], len(source))
+            self.assertEqual(progression["flushed"], progression["produced"])
+            self.assertGreaterEqual(progression["current_job_id"], 1)
+
+        self.assertEqual(
+            cctx.frame_progression(detailed=True)["ingested"], len(source)
+        )
+
+    @unittest.skipUnless(
+        "pad_to" in zstd.backend_features,
+        "pad_to only implemented in Rust backend",
+    )
+    def test_pad_to(self):
+        cctx = zstd.ZstdCompressor()
+        dest = io.BytesIO()
+
+        with cctx.stream_writer(dest, closefd=False, pad_to=1024) as writer:
+            writer.write(b"foo" * 1000)
+            writer.flush(zstd.FLUSH_FRAME)
+            self.assertEqual(dest.tell(), 1024)
+            self.assertEqual(writer.tell(), 1024)
+
+            # Data past one record is padded to the next.
+            writer.write(os.urandom(1500))
+            writer.flush(zstd.FLUSH_BLOCK)
+            self.assertNotEqual(dest.tell() % 1024, 0)
+            writer.flush(zstd.FLUSH_FRAME)
+            self.assertEqual(dest.tell(), 3072)
+
+            writer.write(b"bar" * 1000)
+
+        self.assertEqual(len(dest.getvalue()), 4096)
+
+        dctx = zstd.ZstdDecompressor()
+        reader = dctx.stream_reader(dest.getvalue(), read_across_frames=True)
+        self.assertEqual(len(reader.read()), 7500)
+
+        with self.assertRaisesRegex(ValueError, "pad_to must be positive"):
+            cctx.stream_writer(io.BytesIO(), pad_to=0)
+
+    @unittest.skipUnless(
+        "hasher" in zstd.backend_features,
+        "hasher only implemented in Rust backend",
+    )
+    def test_hasher(self):
+        cctx = zstd.ZstdCompressor()
+        dest = io.BytesIO()
+
+        writer = cctx.stream_writer(
+            dest, closefd=False, hasher=hashlib.sha256()
+        )
+        writer.write(b"foo" * 1024)
+        writer.write(memoryview(b"bar" * 1024))
+
+        self.assertIsNone(writer.digest)
+        self.assertIsNone(writer.flush().digest)
+
+        expected = hashlib.sha256(b"foo" * 1024 + b"bar" * 1024).digest()
+
+        result = writer.flush(zstd.FLUSH_FRAME)
+        self.assertEqual(result.digest, expected)
+        self.assertEqual(writer.digest, expected)
+
+        # The hasher sees input across frames.
+        writer.write(b"baz")
+        writer.close()
+        self.assertEqual(
+            writer.digest,
+            hashlib.sha256(b"foo" * 1024 + b"bar" * 1024 + b"baz").digest(),
+        )
+
+        with self.assertRaisesRegex(
+            ValueError, "hasher must have update\\(\\) and digest\\(\\) methods"
+        ):
+            cctx.stream_writer(io.BytesIO(), hasher=object())
+
+    @unittest.skipUnless(
+        "frame_dict" in zstd.backend_features,
+        "per-frame dictionaries only implemented in Rust backend",
+    )
+    def test_flush_frame_dict(self):
+        record_a = b"".join(b"record %d alpha\n" % i for i in range(2048))
+        record_b = b"".join(b"record %d bravo\n" % i for i in range(2048))
+        dict_a = zstd.ZstdCompressionDict(
+            record_a, dict_type=zstd.DICT_TYPE_RAWCONTENT
+        )
+
+        cctx = zstd.ZstdCompressor()
+        dest = io.BytesIO()
+        writer = cctx.stream_writer(dest, closefd=False)
+
+        # Overrides given before any input apply to the first frame.
+        result = writer.flush(zstd.FLUSH_FRAME, dict_data=dict_a)
+        self.assertEqual(result, 0)
+        self.assertEqual(dest.getvalue(), b"")
+
+        offsets = [0]
+        writer.write(record_a)
+        writer.flush(zstd.FLUSH_FRAME, prefix=record_b)
+        offsets.append(writer.tell())
+        writer.write(record_b)
+        writer.flush(zstd.FLUSH_FRAME)
+        offsets.append(writer.tell())
+        writer.write(record_a)
+        writer.close()
+        offsets.append(writer.tell())
+
+        data = dest.getvalue()
+        frames = [data[a:b] for a, b in zip(offsets, offsets[1:])]
+
+        # Frames referencing matching content are much smaller.
+        self.assertLess(len(frames[0]) * 4, len(frames[2]))
+        self.assertLess(len(frames[1]) * 4, len(frames[2]))
+
+        dctx = zstd.ZstdDecompressor(dict_data=dict_a)
+        self.assertEqual(
+            dctx.decompress(frames[0], max_output_size=len(record_a)), record_a
+        )
+
+        dict_b = zstd.ZstdCompressionDict(
+            record_b, dict_type=zstd.DICT_TYPE_RAWCONTENT
+        )
+        dctx = zstd.ZstdDecompressor(dict_data=dict_b)
+        self.assertEqual(
+            dctx.decompress(frames[1], max_output_size=len(record_b)), record_b
+        )
+
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(
+            dctx.decompress(frames[2], max_output_size=len(record_a)), record_a
+        )
+
+        # The compressor's own dictionary is unaffected.
+        self.assertEqual(dctx.decompress(cctx.compress(record_b)), record_b)
+
+        writer = cctx.stream_writer(io.BytesIO())
+        with self.assertRaisesRegex(
+            ValueError, "dict_data and prefix require flush_mode=FLUSH_FRAME"
+        ):
+            writer.flush(zstd.FLUSH_BLOCK, prefix=record_a)
+
+        with self.assertRaisesRegex(
+            ValueError, "cannot specify both dict_data and prefix"
+        ):
+            writer.flush(zstd.FLUSH_FRAME, dict_data=dict_a, prefix=record_a)
+
+    @unittest.skipUnless(
+        "frame_index" in zstd.backend_features,
+        "frame_index only implemented in Rust backend",
+    )
+    def test_frame_index(self):
+        cctx = zstd.ZstdCompressor()
+        dctx = zstd.ZstdDecompressor()
+        sources = [b"foo" * 1024, b"bar", b"baz" * 4096]
+
+        self.assertIsNone(cctx.stream_writer(io.BytesIO()).frame_index)
+
+        for pad_to in (None, 512):
+            dest = io.BytesIO()
+            writer = cctx.stream_writer(
+                dest, closefd=False, frame_index=True, pad_to=pad_to
+            )
+            self.assertEqual(writer.frame_index, [])
+
+            for source in sources:
+                writer.write(source)
+                writer.flush(zstd.FLUSH_FRAME)
+
+            # Block flushes don't end frames.
+            writer.write(b"qux")
+            writer.flush(zstd.FLUSH_BLOCK)
+            self.assertEqual(len(writer.frame_index), 3)
+
+            writer.close()
+
+            data = dest.getvalue()
+            index = writer.frame_index
+            self.assertEqual(len(index), 4)
+
+            for (offset, size, content_size), source in zip(
+                index, sources + [b"qux"]
+            ):
+                if pad_to:
+                    self.assertEqual(offset % pad_to, 0)
+
+                self.assertEqual(content_size, len(source))
+                self.assertEqual(
+                    dctx.decompress(
+                        data[offset : offset + size],
+                        max_output_size=len(source),
+                    ),
+                    source,
+                )
+
+    @unittest.skipUnless(
+        "patch_content_size" in zstd.backend_features,
+        "patch_content_size only implemented in Rust backend",
+    )
+    def test_patch_content_size(self):
+        cctx = zstd.ZstdCompressor(write_checksum=True)
+
+        dest = io.BytesIO()
+        dest.write(b"header")
+        writer = cctx.stream_writer(
+            dest, patch_content_size=True, closefd=False
+        )
+        offsets = [writer.tell()]
+
+        writer.write(b"foo" * 65536)
+        writer.flush(zstd.FLUSH_FRAME)
+        offsets.append(writer.tell())
+
+        writer.write(b"bar" * 16)
+        writer.flush(zstd.FLUSH_BLOCK)
+        writer.write(b"baz")
+        writer.close()
+        offsets.append(writer.tell())
+
+        data = dest.getvalue()
+        self.assertEqual(data[0:6], b"header")
+        self.assertEqual(len(data), offsets[-1] + 6)
+
+        frames = [data[a + 6 : b + 6] for a, b in zip(offsets, offsets[1:])]
+        self.assertEqual(
+            zstd.get_frame_parameters(frames[0]).content_size, 196608
+        )
+        self.assertEqual(zstd.get_frame_parameters(frames[1]).content_size, 51)
+
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(dctx.decompress(frames[0]), b"foo" * 65536)
+        self.assertEqual(dctx.decompress(frames[1]), b"bar" * 16 + b"baz")
+
+        # A pledged size is left alone.
+        dest = io.BytesIO()
+        with cctx.stream_writer(
+            dest, size=3, patch_content_size=True, closefd=False
+        ) as writer:
+            writer.write(b"foo")
+
+        self.assertEqual(dest.getvalue(), cctx.compress(b"foo"))
+
+    @unittest.skipUnless(
+        "patch_content_size" in zstd.backend_features,
+        "patch_content_size only implemented in Rust backend",
+    )
+    def test_patch_content_size_invalid(self):
+        cctx = zstd.ZstdCompressor()
+
+        rfd, wfd = os.pipe()
+        try:
+            with self.assertRaisesRegex(
+                ValueError, "patch_content_size requires a seekable writer"
+            ):
+                cctx.stream_writer(
+                    wfd, patch_content_size=True, closefd=False
+                )
+        finally:
+            os.close(rfd)
+            os.close(wfd)
+
+        cctx = zstd.ZstdCompressor(write_content_size=False)
+
+        with self.assertRaisesRegex(
+            ValueError, "patch_content_size requires write_content_size=True"
+        ):
+            cctx.stream_writer(io.BytesIO(), patch_content_size=True)
@@ -0,0 +1,71 @@
+import unittest
+
+import zstandard as zstd
+
+
+@unittest.skipUnless(
+    "streaming_encoder" in zstd.backend_features,
+    "streaming_encoder() only implemented in Rust backend",
+)
+class TestCompressor_streaming_encoder(unittest.TestCase):
+    def test_simple(self):
+        encoder = zstd.ZstdCompressor().streaming_encoder()
+        self.assertIsInstance(encoder, zstd.StreamingEncoder)
+        self.assertFalse(encoder.finished)
+
+        frame = encoder.encode(b"foobar") + encoder.finish()
+        self.assertTrue(encoder.finished)
+        self.assertEqual(
+            zstd.ZstdDecompressor().decompressobj().decompress(frame), b"foobar"
+        )
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "cannot call encode\\(\\) after finish\\(\\)"
+        ):
+            encoder.encode(b"foo")
+
+        with self.assertRaisesRegex(zstd.ZstdError, "encoder already finished"):
+            encoder.finish()
+
+    def test_flushed_output_decodable(self):
+        for threads in (0, 2):
+            encoder = zstd.ZstdCompressor(threads=threads).streaming_encoder()
+            decoder = zstd.ZstdDecompressor().streaming_decoder()
+
+            for i in range(10):
+                message = b"message %d " % i * 64
+                data, events = decoder.feed(encoder.encode(message))
+                self.assertEqual(data, message)
+                self.assertEqual(events, [])
+
+            data, events = decoder.feed(encoder.finish())
+            self.assertEqual(data, b"")
+            self.assertEqual(events, [("frame_complete", False)])
+
+    def test_no_flush(self):
+        encoder = zstd.ZstdCompressor().streaming_encoder()
+
+        chunks = [encoder.encode(b"foo", flush=False) for i in range(4)]
+        chunks.append(encoder.finish())
+
+        dobj = zstd.ZstdDecompressor().decompressobj()
+        self.assertEqual(dobj.decompress(b"".join(chunks)), b"foo" * 4)
+
+    def test_dictionary(self):
+        samples = []
+        for i in range(128):
+            samples.append(b"foo" * 64)
+            samples.append(b"bar" * 64)
+            samples.append(b"foobar" * 64)
+
+        d = zstd.train_dictionary(8192, samples)
+
+        encoder = zstd.ZstdCompressor(dict_data=d).streaming_encoder()
+        frame = encoder.encode(b"foobar" * 16) + encoder.finish()
+
+        dctx = zstd.ZstdDecompressor(dict_data=d)
+        self.assertEqual(dctx.decompressobj().decompress(frame), b"foobar" * 16)
+
+    def test_bad_write_size(self):
+        with self.assertRaisesRegex(ValueError, "write_size must be positive"):
+            zstd.ZstdCompressor().streaming_encoder(write_size=0)
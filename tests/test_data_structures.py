@@ -1,3 +1,5 @@
+import io
+import struct
 import unittest
 
 import zstandard as zstd
@@ -25,6 +27,29 @@ class TestCompressionParameters(unittest.TestCase):
             strategy=zstd.STRATEGY_BTULTRA2,
         )
 
+    @unittest.skipUnless(
+        "parameter_bounds" in zstd.backend_features,
+        "bounds validation only in Rust backend",
+    )
+    def test_out_of_bounds(self):
+        with self.assertRaisesRegex(
+            ValueError, "window_log must be between 10 and 31; got 40"
+        ):
+            zstd.ZstdCompressionParameters(window_log=40)
+
+        with self.assertRaisesRegex(
+            ValueError, "strategy must be between 1 and 9; got 10"
+        ):
+            zstd.ZstdCompressionParameters(strategy=10)
+
+        with self.assertRaisesRegex(
+            ValueError, "ldm_hash_rate_log must be between 0 and"
+        ):
+            zstd.ZstdCompressionParameters(enable_ldm=1, ldm_hash_rate_log=99)
+
+        with self.assertRaisesRegex(ValueError, "level must be less than 23"):
+            zstd.ZstdCompressionParameters(compression_level=100)
+
     def test_from_level(self):
         p = zstd.ZstdCompressionParameters.from_level(1)
         self.assertIsInstance(p, zstd.ZstdCompressionParameters)
@@ -34,6 +59,59 @@ class TestCompressionParameters(unittest.TestCase):
         p = zstd.ZstdCompressionParameters.from_level(-4)
         self.assertEqual(p.window_log, 19)
 
+    @unittest.skipUnless(
+        "negative_levels" in zstd.backend_features,
+        "MIN_COMPRESSION_LEVEL only implemented in Rust backend",
+    )
+    def test_from_negative_level(self):
+        p = zstd.ZstdCompressionParameters.from_level(-7)
+        self.assertEqual(p.strategy, zstd.STRATEGY_FAST)
+        self.assertEqual(p.target_length, 7)
+
+        cctx = zstd.ZstdCompressor(compression_params=p)
+        frame = cctx.compress(b"foobar" * 1024)
+        self.assertEqual(
+            zstd.ZstdDecompressor().decompress(frame), b"foobar" * 1024
+        )
+
+        with self.assertRaisesRegex(ValueError, "level must be at least"):
+            zstd.ZstdCompressionParameters.from_level(
+                zstd.MIN_COMPRESSION_LEVEL - 1
+            )
+
+    @unittest.skipUnless(
+        "negative_levels" in zstd.backend_features,
+        "MIN_COMPRESSION_LEVEL only implemented in Rust backend",
+    )
+    def test_level_validation(self):
+        p = zstd.ZstdCompressionParameters(
+            compression_level=zstd.MIN_COMPRESSION_LEVEL
+        )
+        self.assertEqual(p.compression_level, zstd.MIN_COMPRESSION_LEVEL)
+
+        # Every entry point rejects the same levels with the same error.
+        for level, message in (
+            (23, "level must be less than 23"),
+            (
+                zstd.MIN_COMPRESSION_LEVEL - 1,
+                "level must be at least %d" % zstd.MIN_COMPRESSION_LEVEL,
+            ),
+            (
+                -200000,
+                "level must be at least %d" % zstd.MIN_COMPRESSION_LEVEL,
+            ),
+        ):
+            with self.assertRaisesRegex(ValueError, message):
+                zstd.ZstdCompressionParameters(compression_level=level)
+            with self.assertRaisesRegex(ValueError, message):
+                zstd.ZstdCompressionParameters.from_level(level)
+            with self.assertRaisesRegex(ValueError, message):
+                zstd.ZstdCompressor(level=level)
+            with self.assertRaisesRegex(ValueError, message):
+                zstd.ZstdCompressionParameters.builder().compression_level(
+                    level
+                ).build()
+
     def test_members(self):
         p = zstd.ZstdCompressionParameters(
             window_log=10,
@@ -120,6 +198,484 @@ class TestCompressionParameters(unittest.TestCase):
         p = zstd.ZstdCompressionParameters(overlap_log=2)
         self.assertEqual(p.overlap_log, 2)
 
+    @unittest.skipUnless(
+        "ldm_auto" in zstd.backend_features,
+        "enable_ldm='auto' only implemented in Rust backend",
+    )
+    def test_enable_ldm_auto(self):
+        p = zstd.ZstdCompressionParameters(enable_ldm="auto")
+        self.assertEqual(p.enable_ldm, "auto")
+        self.assertEqual(p.window_log, 0)
+
+        p = zstd.ZstdCompressionParameters.from_level(19, enable_ldm="auto")
+        self.assertEqual(p.enable_ldm, "auto")
+        self.assertEqual(p.window_log, 0)
+
+        with self.assertRaisesRegex(
+            ValueError, 'cannot specify window_log with enable_ldm="auto"'
+        ):
+            zstd.ZstdCompressionParameters(enable_ldm="auto", window_log=20)
+
+    @unittest.skipUnless(
+        "ldm_disable" in zstd.backend_features,
+        "enable_ldm='disable' only implemented in Rust backend",
+    )
+    def test_enable_ldm_disable(self):
+        p = zstd.ZstdCompressionParameters(enable_ldm="disable")
+        self.assertEqual(p.enable_ldm, "disable")
+        self.assertEqual(p.as_dict()["enable_ldm"], "disable")
+        self.assertNotEqual(p, zstd.ZstdCompressionParameters())
+        self.assertEqual(p.replace(window_log=20).enable_ldm, "disable")
+
+        with self.assertRaisesRegex(
+            ValueError, 'cannot specify ldm_hash_log with enable_ldm="disable"'
+        ):
+            zstd.ZstdCompressionParameters(
+                enable_ldm="disable", ldm_hash_log=20
+            )
+
+    @unittest.skipUnless(
+        "parameter_bounds" in zstd.backend_features,
+        "LDM window validation only implemented in Rust backend",
+    )
+    def test_ldm_window_validation(self):
+        with self.assertRaisesRegex(
+            ValueError, r"ldm_hash_log \(22\) must not exceed window_log \(20\)"
+        ):
+            zstd.ZstdCompressionParameters(
+                enable_ldm=True, window_log=20, ldm_hash_log=22
+            )
+
+        with self.assertRaisesRegex(
+            ValueError,
+            r"ldm_hash_rate_log \(21\) must not exceed window_log \(20\)",
+        ):
+            zstd.ZstdCompressionParameters(
+                enable_ldm=True, window_log=20, ldm_hash_rate_log=21
+            )
+
+        # Without window_log, zstd uses a 2**27 window.
+        with self.assertRaisesRegex(
+            ValueError, r"ldm_hash_log \(28\) must not exceed window_log \(27\)"
+        ):
+            zstd.ZstdCompressionParameters(enable_ldm="auto", ldm_hash_log=28)
+
+        # Only checked when long distance matching is enabled.
+        p = zstd.ZstdCompressionParameters(window_log=20, ldm_hash_log=22)
+        self.assertEqual(p.ldm_hash_log, 22)
+
+    @unittest.skipUnless(
+        "deterministic_ref_prefix" in zstd.backend_features,
+        "deterministic_ref_prefix only implemented in Rust backend",
+    )
+    def test_deterministic_ref_prefix(self):
+        p = zstd.ZstdCompressionParameters()
+        self.assertEqual(p.deterministic_ref_prefix, 0)
+
+        p = zstd.ZstdCompressionParameters(deterministic_ref_prefix=True)
+        self.assertEqual(p.deterministic_ref_prefix, 1)
+
+        p = (
+            zstd.ZstdCompressionParameters.builder()
+            .deterministic_ref_prefix(1)
+            .build()
+        )
+        self.assertEqual(p.deterministic_ref_prefix, 1)
+
+        data = b"foobar" * 8192
+        cctx = zstd.ZstdCompressor(compression_params=p)
+        frame = cctx.compress(data)
+        self.assertEqual(cctx.compress(data), frame)
+        self.assertEqual(zstd.ZstdDecompressor().decompress(frame), data)
+
+    def test_compression_level_resolved(self):
+        p = zstd.ZstdCompressionParameters(compression_level=0)
+        self.assertEqual(p.compression_level, 3)
+        p = zstd.ZstdCompressionParameters(compression_level=22)
+        self.assertEqual(p.compression_level, 22)
+
+    @unittest.skipUnless(
+        "match_finder_parameters" in zstd.backend_features,
+        "match finder parameters only implemented in Rust backend",
+    )
+    def test_match_finder_parameters(self):
+        p = zstd.ZstdCompressionParameters()
+        self.assertEqual(p.enable_dedicated_dict_search, 0)
+        self.assertEqual(p.use_row_match_finder, zstd.ROW_MATCH_FINDER_AUTO)
+
+        p = zstd.ZstdCompressionParameters(
+            enable_dedicated_dict_search=1,
+            use_row_match_finder=zstd.ROW_MATCH_FINDER_ENABLED,
+        )
+        self.assertEqual(p.enable_dedicated_dict_search, 1)
+        self.assertEqual(p.use_row_match_finder, zstd.ROW_MATCH_FINDER_ENABLED)
+
+        p = (
+            zstd.ZstdCompressionParameters.builder()
+            .enable_dedicated_dict_search(1)
+            .use_row_match_finder(zstd.ROW_MATCH_FINDER_DISABLED)
+            .build()
+        )
+        self.assertEqual(p.enable_dedicated_dict_search, 1)
+        self.assertEqual(
+            p.use_row_match_finder, zstd.ROW_MATCH_FINDER_DISABLED
+        )
+
+        with self.assertRaisesRegex(
+            ValueError, "use_row_match_finder must be between 0 and 2"
+        ):
+            zstd.ZstdCompressionParameters.builder().use_row_match_finder(
+                3
+            ).build()
+
+    @unittest.skipUnless(
+        "target_cblock_size" in zstd.backend_features,
+        "target_cblock_size only implemented in Rust backend",
+    )
+    def test_target_cblock_size(self):
+        p = zstd.ZstdCompressionParameters()
+        self.assertEqual(p.target_cblock_size, 0)
+
+        p = zstd.ZstdCompressionParameters(target_cblock_size=2048)
+        self.assertEqual(p.target_cblock_size, 2048)
+
+        with self.assertRaisesRegex(
+            ValueError, "target_cblock_size must be between"
+        ):
+            zstd.ZstdCompressionParameters.builder().target_cblock_size(
+                1
+            ).build()
+
+        p = (
+            zstd.ZstdCompressionParameters.builder()
+            .target_cblock_size(1024)
+            .build()
+        )
+        self.assertEqual(p.target_cblock_size, 1024)
+
+        def block_sizes(frame):
+            offset = zstd.frame_header_size(frame)
+            sizes = []
+            while True:
+                header = int.from_bytes(frame[offset : offset + 3], "little")
+                # RLE blocks store a single byte.
+                size = 1 if (header >> 1) & 3 == 1 else header >> 3
+                sizes.append(size)
+                offset += 3 + size
+
+                if header & 1:
+                    return sizes
+
+        data = b"".join(b"%d foo bar baz\n" % i for i in range(20000))
+
+        frame = zstd.ZstdCompressor(compression_params=p).compress(data)
+        self.assertEqual(zstd.ZstdDecompressor().decompress(frame), data)
+
+        # The target is approximate.
+        sizes = block_sizes(frame)
+        default_sizes = block_sizes(zstd.compress(data))
+        self.assertGreater(len(sizes), len(default_sizes))
+        self.assertLess(max(sizes), 2048)
+
+    @unittest.skipUnless(
+        "stable_buffers" in zstd.backend_features,
+        "stable buffer parameters only implemented in Rust backend",
+    )
+    def test_stable_buffers(self):
+        p = zstd.ZstdCompressionParameters()
+        self.assertEqual(p.stable_in_buffer, 0)
+        self.assertEqual(p.stable_out_buffer, 0)
+
+        p = zstd.ZstdCompressionParameters(
+            stable_in_buffer=1, stable_out_buffer=1
+        )
+        self.assertEqual(p.stable_in_buffer, 1)
+        self.assertEqual(p.stable_out_buffer, 1)
+
+        p = (
+            zstd.ZstdCompressionParameters.builder()
+            .stable_in_buffer(1)
+            .stable_out_buffer(1)
+            .build()
+        )
+        self.assertEqual(p.stable_in_buffer, 1)
+        self.assertEqual(p.stable_out_buffer, 1)
+
+        with self.assertRaisesRegex(
+            ValueError, "stable_out_buffer must be between 0 and 1"
+        ):
+            zstd.ZstdCompressionParameters.builder().stable_out_buffer(
+                2
+            ).build()
+
+        data = b"foo" * 8192
+
+        # One-shot compression hands zstd the whole input and output.
+        cctx = zstd.ZstdCompressor(compression_params=p)
+        frame = cctx.compress(data)
+        self.assertEqual(zstd.ZstdDecompressor().decompress(frame), data)
+
+        buffer = bytearray(len(frame) + 1024)
+        self.assertEqual(cctx.compress_into(data, buffer), len(frame))
+        self.assertEqual(bytes(buffer[0 : len(frame)]), frame)
+
+        # Streaming APIs pass new buffers per call, so are rejected up front.
+        for f in (
+            cctx.compressobj,
+            cctx.chunker,
+            lambda: cctx.stream_writer(io.BytesIO()),
+            lambda: cctx.stream_reader(data),
+            lambda: cctx.read_to_iter(io.BytesIO(data)),
+            lambda: cctx.copy_stream(io.BytesIO(data), io.BytesIO()),
+            lambda: cctx.compress(data, deadline_ms=1000),
+        ):
+            with self.assertRaisesRegex(
+                ValueError,
+                "stable_in_buffer cannot be used with .*; use one-shot "
+                "compression such as compress\\(\\)",
+            ):
+                f()
+
+        p = zstd.ZstdCompressionParameters(stable_out_buffer=1)
+        cctx = zstd.ZstdCompressor(compression_params=p)
+        with self.assertRaisesRegex(
+            ValueError, "stable_out_buffer cannot be used with output_size_hint"
+        ):
+            cctx.compress(data, output_size_hint=16)
+
+        # Only stable input is compatible with a growing output.
+        p = zstd.ZstdCompressionParameters(stable_in_buffer=1)
+        cctx = zstd.ZstdCompressor(compression_params=p)
+        frame = cctx.compress(data, output_size_hint=16)
+        self.assertEqual(zstd.ZstdDecompressor().decompress(frame), data)
+
+    @unittest.skipUnless(
+        "parameters_as_dict" in zstd.backend_features,
+        "as_dict() only implemented in Rust backend",
+    )
+    def test_as_dict(self):
+        p = zstd.ZstdCompressionParameters(window_log=20, threads=2)
+        d = p.as_dict()
+
+        self.assertEqual(d["window_log"], 20)
+        self.assertEqual(d["threads"], 2)
+        self.assertEqual(d["hash_log"], 0)
+        self.assertEqual(d["write_content_size"], 1)
+        self.assertEqual(zstd.ZstdCompressionParameters(**d), p)
+
+        p = zstd.ZstdCompressionParameters(enable_ldm="auto")
+        self.assertEqual(p.as_dict()["enable_ldm"], "auto")
+
+    @unittest.skipUnless(
+        "parameters_replace" in zstd.backend_features,
+        "replace() only implemented in Rust backend",
+    )
+    def test_replace(self):
+        p = zstd.ZstdCompressionParameters.from_level(5, write_checksum=1)
+        q = p.replace(window_log=21, write_checksum=0)
+
+        self.assertEqual(q.window_log, 21)
+        self.assertEqual(q.write_checksum, 0)
+        self.assertEqual(q.hash_log, p.hash_log)
+        self.assertEqual(
+            p.window_log,
+            zstd.ZstdCompressionParameters.from_level(5).window_log,
+        )
+        self.assertEqual(p.replace(), p)
+
+        p = zstd.ZstdCompressionParameters(enable_ldm="auto")
+        self.assertEqual(p.replace(), p)
+        self.assertEqual(p.replace(enable_ldm=1).enable_ldm, 1)
+
+        with self.assertRaisesRegex(
+            ValueError, "window_log must be between 10 and 31; got 40"
+        ):
+            p.replace(enable_ldm=0, window_log=40)
+
+    @unittest.skipUnless(
+        "parameters_equality" in zstd.backend_features,
+        "equality only implemented in Rust backend",
+    )
+    def test_equality(self):
+        p = zstd.ZstdCompressionParameters(window_log=20)
+
+        self.assertEqual(p, zstd.ZstdCompressionParameters(window_log=20))
+        self.assertNotEqual(p, zstd.ZstdCompressionParameters(window_log=21))
+        self.assertNotEqual(p, p.as_dict())
+        self.assertEqual(
+            hash(p), hash(zstd.ZstdCompressionParameters(window_log=20))
+        )
+        self.assertEqual(len({p, p.replace(), p.replace(window_log=21)}), 2)
+
+        self.assertNotEqual(
+            zstd.ZstdCompressionParameters(enable_ldm="auto"),
+            zstd.ZstdCompressionParameters(),
+        )
+
+        with self.assertRaises(TypeError):
+            p < p
+
+    @unittest.skipUnless(
+        "repr" in zstd.backend_features,
+        "__repr__ only implemented in Rust backend",
+    )
+    def test_repr(self):
+        p = zstd.ZstdCompressionParameters(
+            window_log=20, strategy=zstd.STRATEGY_BTOPT, threads=2
+        )
+        self.assertEqual(
+            repr(p),
+            "ZstdCompressionParameters(threads=2, compression_level=3, "
+            "window_log=20, strategy=7, write_content_size=1)",
+        )
+
+        p = zstd.ZstdCompressionParameters(enable_ldm="auto")
+        self.assertEqual(
+            repr(p),
+            "ZstdCompressionParameters(compression_level=3, "
+            "write_content_size=1, enable_ldm='auto')",
+        )
+
+
+@unittest.skipUnless(
+    "parameters_from_frame" in zstd.backend_features,
+    "from_frame() only implemented in Rust backend",
+)
+class TestCompressionParametersFromFrame(unittest.TestCase):
+    def test_from_frame(self):
+        params = zstd.ZstdCompressionParameters(
+            window_log=20, write_checksum=True, write_content_size=False
+        )
+        cobj = zstd.ZstdCompressor(compression_params=params).compressobj()
+        frame = cobj.compress(b"foobar" * 1024) + cobj.flush()
+
+        p = zstd.ZstdCompressionParameters.from_frame(frame)
+        self.assertEqual(p.window_log, 20)
+        self.assertEqual(p.write_checksum, 1)
+        self.assertEqual(p.write_content_size, 0)
+        self.assertEqual(p.write_dict_id, 0)
+
+        p = zstd.ZstdCompressionParameters.from_frame(
+            frame, compression_level=5, window_log=18
+        )
+        self.assertEqual(p.compression_level, 5)
+        self.assertEqual(p.window_log, 18)
+
+    def test_single_segment(self):
+        # The window is the content size, rounded down to a power of 2.
+        frame = zstd.ZstdCompressor().compress(b"x" * 5000)
+        self.assertEqual(zstd.get_frame_parameters(frame).window_size, 5000)
+
+        p = zstd.ZstdCompressionParameters.from_frame(frame)
+        self.assertEqual(p.window_log, 12)
+        self.assertEqual(p.write_content_size, 1)
+
+        frame = zstd.ZstdCompressor().compress(b"x")
+        p = zstd.ZstdCompressionParameters.from_frame(frame)
+        self.assertEqual(p.window_log, 10)
+
+    def test_dict_id(self):
+        samples = [b"foo" * 64, b"bar" * 64, b"foobar" * 64] * 128
+        d = zstd.train_dictionary(8192, samples)
+        frame = zstd.ZstdCompressor(dict_data=d).compress(b"foobar")
+
+        p = zstd.ZstdCompressionParameters.from_frame(frame)
+        self.assertEqual(p.write_dict_id, 1)
+
+    def test_invalid(self):
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "cannot derive parameters from skippable frame"
+        ):
+            zstd.ZstdCompressionParameters.from_frame(
+                b"\x50\x2a\x4d\x18\x03\x00\x00\x00foo"
+            )
+
+        with self.assertRaisesRegex(zstd.ZstdError, "not enough data"):
+            zstd.ZstdCompressionParameters.from_frame(zstd.FRAME_HEADER)
+
+
+@unittest.skipUnless(
+    "parameters_builder" in zstd.backend_features,
+    "builder() only implemented in Rust backend",
+)
+class TestCompressionParametersBuilder(unittest.TestCase):
+    def test_build(self):
+        p = (
+            zstd.ZstdCompressionParameters.builder()
+            .compression_level(10)
+            .window_log(24)
+            .enable_ldm(True)
+            .ldm_hash_log(20)
+            .threads(2)
+            .job_size(1048576)
+            .build()
+        )
+
+        self.assertIsInstance(p, zstd.ZstdCompressionParameters)
+        self.assertEqual(p.compression_level, 10)
+        self.assertEqual(p.window_log, 24)
+        self.assertEqual(p.enable_ldm, 1)
+        self.assertEqual(p.ldm_hash_log, 20)
+        self.assertEqual(p.threads, 2)
+        self.assertEqual(p.job_size, 1048576)
+
+    def test_empty(self):
+        p = zstd.ZstdCompressionParameters.builder().build()
+        self.assertEqual(p.window_log, 0)
+
+    def test_violations(self):
+        builder = (
+            zstd.ZstdCompressionParameters.builder()
+            .window_log(40)
+            .ldm_hash_log(20)
+            .ldm_bucket_size_log(8)
+            .job_size(1048576)
+        )
+
+        with self.assertRaises(ValueError) as cm:
+            builder.build()
+
+        self.assertEqual(
+            str(cm.exception),
+            "invalid compression parameters: "
+            "window_log must be between 10 and 31; got 40; "
+            "ldm_hash_log requires enable_ldm; "
+            "ldm_bucket_size_log requires enable_ldm; "
+            "job_size requires threads",
+        )
+
+    def test_ldm_relationships(self):
+        builder = (
+            zstd.ZstdCompressionParameters.builder()
+            .window_log(20)
+            .enable_ldm(True)
+            .ldm_hash_log(22)
+            .ldm_bucket_size_log(8)
+            .ldm_hash_log(6)
+        )
+
+        with self.assertRaisesRegex(
+            ValueError,
+            r"ldm_bucket_size_log \(8\) must not exceed ldm_hash_log \(6\)",
+        ):
+            builder.build()
+
+        builder.ldm_hash_log(22).ldm_bucket_size_log(4)
+        with self.assertRaisesRegex(
+            ValueError,
+            r"ldm_hash_log \(22\) must not exceed window_log \(20\)",
+        ):
+            builder.build()
+
+    def test_enable_ldm_auto(self):
+        builder = zstd.ZstdCompressionParameters.builder().enable_ldm("auto")
+        self.assertEqual(builder.build().enable_ldm, "auto")
+
+        with self.assertRaisesRegex(
+            ValueError, 'window_log cannot be combined with enable_ldm="auto"'
+        ):
+            builder.window_log(20).build()
+
 
 class TestFrameParameters(unittest.TestCase):
     def test_invalid_type(self):
@@ -190,6 +746,18 @@ class TestFrameParameters(unittest.TestCase):
         self.assertEqual(params.dict_id, 15)
         self.assertTrue(params.has_checksum)
 
+    @unittest.skipUnless(
+        "repr" in zstd.backend_features,
+        "__repr__ only implemented in Rust backend",
+    )
+    def test_repr(self):
+        params = zstd.get_frame_parameters(zstd.FRAME_HEADER + b"\x00\x00")
+        self.assertEqual(
+            repr(params),
+            "FrameParameters(content_size=%d, window_size=1024, dict_id=0, "
+            "has_checksum=False)" % zstd.CONTENTSIZE_UNKNOWN,
+        )
+
     def test_input_types(self):
         v = zstd.FRAME_HEADER + b"\x00\x00"
 
@@ -208,3 +776,235 @@ class TestFrameParameters(unittest.TestCase):
             self.assertEqual(params.window_size, 1024)
             self.assertEqual(params.dict_id, 0)
             self.assertFalse(params.has_checksum)
+
+
+@unittest.skipUnless(
+    "validate_frame" in zstd.backend_features,
+    "validate_frame() only implemented in Rust backend",
+)
+class TestValidateFrame(unittest.TestCase):
+    def test_conforming(self):
+        frame = zstd.FRAME_HEADER + b"\x45\x40\x0f\x10\x00"
+
+        self.assertEqual(zstd.validate_frame(frame, {}), [])
+        self.assertEqual(
+            zstd.validate_frame(
+                frame,
+                {
+                    "max_window_size": 262144,
+                    "require_checksum": True,
+                    "allowed_dict_ids": [15],
+                    "max_content_size": 272,
+                },
+            ),
+            [],
+        )
+
+    def test_violations(self):
+        # 256 KiB window, dict 15, 272 byte content, no checksum.
+        frame = zstd.FRAME_HEADER + b"\x41\x40\x0f\x10\x00"
+
+        self.assertEqual(
+            zstd.validate_frame(
+                frame,
+                {
+                    "max_window_size": 1024,
+                    "require_checksum": True,
+                    "allowed_dict_ids": [1, 2],
+                    "max_content_size": 100,
+                },
+            ),
+            [
+                ("max_window_size", 1024, 262144),
+                ("require_checksum", True, False),
+                ("allowed_dict_ids", [1, 2], 15),
+                ("max_content_size", 100, 272),
+            ],
+        )
+
+    def test_unknown_content_size(self):
+        frame = zstd.FRAME_HEADER + b"\x00\x00"
+
+        self.assertEqual(
+            zstd.validate_frame(frame, {"max_content_size": 100}),
+            [("max_content_size", 100, None)],
+        )
+
+    def test_skippable(self):
+        frame = b"\x50\x2a\x4d\x18\x03\x00\x00\x00foo"
+
+        self.assertEqual(
+            zstd.validate_frame(frame, {"require_checksum": True}), []
+        )
+
+    def test_invalid(self):
+        with self.assertRaisesRegex(ValueError, "unknown frame policy rule"):
+            zstd.validate_frame(zstd.FRAME_HEADER + b"\x00\x00", {"foo": 1})
+
+        with self.assertRaisesRegex(zstd.ZstdError, "not enough data"):
+            zstd.validate_frame(zstd.FRAME_HEADER, {})
+
+
+@unittest.skipUnless(
+    "rewrite_frame_header" in zstd.backend_features,
+    "rewrite_frame_header() only implemented in Rust backend",
+)
+class TestRewriteFrameHeader(unittest.TestCase):
+    def test_strip_checksum_and_dict_id(self):
+        samples = [b"foo" * 64, b"bar" * 64, b"foobar" * 64] * 128
+        d = zstd.train_dictionary(8192, samples, dict_id=42)
+        cctx = zstd.ZstdCompressor(dict_data=d, write_checksum=True)
+        frame = cctx.compress(b"foobar" * 1024)
+
+        params = zstd.get_frame_parameters(frame)
+        self.assertEqual(params.dict_id, 42)
+        self.assertTrue(params.has_checksum)
+
+        rewritten = zstd.rewrite_frame_header(frame, dict_id=0, checksum=False)
+        self.assertEqual(len(rewritten), len(frame) - 5)
+
+        params = zstd.get_frame_parameters(rewritten)
+        self.assertEqual(params.dict_id, 0)
+        self.assertFalse(params.has_checksum)
+        self.assertEqual(params.content_size, 6144)
+
+        dctx = zstd.ZstdDecompressor(dict_data=d)
+        self.assertEqual(dctx.decompress(rewritten), b"foobar" * 1024)
+
+        # The dictionary ID can be put back.
+        restored = zstd.rewrite_frame_header(rewritten, dict_id=42)
+        self.assertEqual(zstd.get_frame_parameters(restored).dict_id, 42)
+        self.assertEqual(dctx.decompress(restored), b"foobar" * 1024)
+
+    def test_content_size(self):
+        source = b"foobar" * 8192
+        cobj = zstd.ZstdCompressor(write_content_size=False).compressobj()
+        frame = cobj.compress(source) + cobj.flush()
+        self.assertEqual(zstd.frame_content_size(frame), -1)
+
+        for size in (0, 200, 1000, 40000):
+            data = source[0:size]
+            cobj = zstd.ZstdCompressor(write_content_size=False).compressobj()
+            frame = cobj.compress(data) + cobj.flush()
+
+            rewritten = zstd.rewrite_frame_header(frame, content_size=size)
+            self.assertEqual(zstd.frame_content_size(rewritten), size)
+            self.assertEqual(
+                zstd.ZstdDecompressor().decompress(rewritten), data
+            )
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "exceeds frame decompression bound"
+        ):
+            zstd.rewrite_frame_header(frame, content_size=2 ** 40)
+
+        with self.assertRaisesRegex(
+            ValueError, "content_size must be non-negative or -1"
+        ):
+            zstd.rewrite_frame_header(frame, content_size=-2)
+
+    def test_remove_content_size(self):
+        for size in (0, 100, 5000, 1000000):
+            data = b"foobar" * size
+            frame = zstd.ZstdCompressor().compress(data)
+
+            rewritten = zstd.rewrite_frame_header(frame, content_size=-1)
+            self.assertEqual(zstd.frame_content_size(rewritten), -1)
+
+            dobj = zstd.ZstdDecompressor().decompressobj()
+            self.assertEqual(dobj.decompress(rewritten), data)
+
+    def test_content_size_mismatch(self):
+        frame = zstd.ZstdCompressor().compress(b"foobar")
+
+        self.assertEqual(
+            zstd.rewrite_frame_header(frame, content_size=6), frame
+        )
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "frame content size is 6; cannot set to 7"
+        ):
+            zstd.rewrite_frame_header(frame, content_size=7)
+
+    def test_add_checksum(self):
+        frame = zstd.ZstdCompressor().compress(b"foobar")
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "cannot add a checksum to a frame without one"
+        ):
+            zstd.rewrite_frame_header(frame, checksum=True)
+
+    def test_invalid(self):
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "cannot rewrite header of skippable frame"
+        ):
+            zstd.rewrite_frame_header(b"\x50\x2a\x4d\x18\x03\x00\x00\x00foo")
+
+        frame = zstd.ZstdCompressor().compress(b"foobar")
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "data contains 3 bytes after the frame"
+        ):
+            zstd.rewrite_frame_header(frame + b"foo")
+
+        with self.assertRaisesRegex(zstd.ZstdError, "error finding frame size"):
+            zstd.rewrite_frame_header(frame[:-1])
+
+
+@unittest.skipUnless(
+    "frame_predicates" in zstd.backend_features,
+    "frame predicates only implemented in Rust backend",
+)
+class TestFramePredicates(unittest.TestCase):
+    def test_zstd_frame(self):
+        frame = zstd.ZstdCompressor().compress(b"foo")
+
+        self.assertTrue(zstd.is_zstd_frame(frame))
+        self.assertTrue(zstd.is_zstd_frame(zstd.FRAME_HEADER))
+        self.assertTrue(zstd.is_zstd_frame(memoryview(frame)))
+        self.assertFalse(zstd.is_skippable_frame(frame))
+
+    def test_skippable_frame(self):
+        for variant in (0x50, 0x5F):
+            frame = bytes([variant]) + b"\x2a\x4d\x18\x03\x00\x00\x00foo"
+
+            self.assertTrue(zstd.is_skippable_frame(frame))
+            self.assertTrue(zstd.is_zstd_frame(frame))
+
+    def test_other(self):
+        for data in (b"", b"\x28\xb5", b"foobar", b"\x60\x2a\x4d\x18"):
+            self.assertFalse(zstd.is_zstd_frame(data))
+            self.assertFalse(zstd.is_skippable_frame(data))
+
+
+@unittest.skipUnless(
+    "skippable_frame_header" in zstd.backend_features,
+    "skippable_frame_header() only implemented in Rust backend",
+)
+class TestSkippableFrameHeader(unittest.TestCase):
+    def test_constants(self):
+        self.assertEqual(zstd.MAGIC_SKIPPABLE_START, 0x184D2A50)
+        self.assertEqual(zstd.MAGIC_SKIPPABLE_MASK, 0xFFFFFFF0)
+
+    def test_header(self):
+        header = zstd.skippable_frame_header(3)
+        self.assertEqual(header, b"\x50\x2a\x4d\x18\x03\x00\x00\x00")
+
+        self.assertTrue(zstd.is_skippable_frame(header + b"foo"))
+
+        header = zstd.skippable_frame_header(0, variant=15)
+        self.assertEqual(header, b"\x5f\x2a\x4d\x18\x00\x00\x00\x00")
+
+        magic = struct.unpack("<I", header[0:4])[0]
+        self.assertEqual(
+            magic & zstd.MAGIC_SKIPPABLE_MASK, zstd.MAGIC_SKIPPABLE_START
+        )
+
+    def test_invalid(self):
+        with self.assertRaisesRegex(ValueError, "variant must be less than 16"):
+            zstd.skippable_frame_header(0, variant=16)
+
+        with self.assertRaisesRegex(
+            ValueError, "skippable frame content must be less than 4 GiB"
+        ):
+            zstd.skippable_frame_header(2 ** 32)
@@ -1,3 +1,4 @@
+import io
 import unittest
 
 import zstandard as zstd
@@ -66,3 +67,94 @@ class TestDecompressor(unittest.TestCase):
         dctx = zstd.ZstdDecompressor()
 
         self.assertGreater(dctx.memory_size(), 100)
+
+    def test_unknown_argument(self):
+        with self.assertRaises(TypeError):
+            zstd.ZstdDecompressor(bogus=True)
+
+        dctx = zstd.ZstdDecompressor()
+        frame = zstd.ZstdCompressor().compress(b"foobar")
+
+        with self.assertRaises(TypeError):
+            dctx.copy_stream(io.BytesIO(frame), io.BytesIO(), bogus=True)
+        with self.assertRaises(TypeError):
+            dctx.read_to_iter(frame, bogus=True)
+        with self.assertRaises(TypeError):
+            dctx.stream_reader(frame, bogus=True)
+        with self.assertRaises(TypeError):
+            dctx.stream_writer(io.BytesIO(), bogus=True)
+
+    @unittest.skipUnless(
+        "config" in zstd.backend_features,
+        "to_config() only implemented in Rust backend",
+    )
+    def test_config_roundtrip(self):
+        dctx = zstd.ZstdDecompressor(
+            max_window_size=2 ** 20, format=zstd.FORMAT_ZSTD1_MAGICLESS
+        )
+        config = dctx.to_config()
+
+        dctx2 = zstd.ZstdDecompressor.from_config(config)
+        self.assertEqual(dctx2.to_config(), config)
+
+    @unittest.skipUnless(
+        "repr" in zstd.backend_features,
+        "__repr__ only implemented in Rust backend",
+    )
+    def test_repr(self):
+        self.assertEqual(
+            repr(zstd.ZstdDecompressor()),
+            "ZstdDecompressor(dict_id=None, max_window_size=0, format=0)",
+        )
+
+        dctx = zstd.ZstdDecompressor(
+            max_window_size=2 ** 20, format=zstd.FORMAT_ZSTD1_MAGICLESS
+        )
+        self.assertEqual(
+            repr(dctx),
+            "ZstdDecompressor(dict_id=None, max_window_size=1048576, format=1)",
+        )
+
+        with self.assertRaisesRegex(ValueError, "not for a decompressor"):
+            zstd.ZstdDecompressor.from_config(zstd.ZstdCompressor().to_config())
+
+    @unittest.skipUnless(
+        "window_usage" in zstd.backend_features,
+        "window_usage() only implemented in Rust backend",
+    )
+    def test_window_usage(self):
+        dctx = zstd.ZstdDecompressor()
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "window usage is not tracked"
+        ):
+            dctx.window_usage()
+
+        dctx = zstd.ZstdDecompressor(track_window_usage=True)
+        self.assertEqual(
+            dctx.window_usage(),
+            {"frames": 0, "max_window_size": 0, "window_size_limit": 2 ** 27},
+        )
+
+        small = zstd.ZstdCompressor(write_content_size=False).compress(b"foo")
+        params = zstd.ZstdCompressionParameters(window_log=20)
+        cctx = zstd.ZstdCompressor(compression_params=params)
+        cobj = cctx.compressobj()
+        large = cobj.compress(b"foobar" * 65536) + cobj.flush()
+
+        dctx.decompress(small, max_output_size=3)
+        dctx.decompressobj().decompress(large)
+        with dctx.stream_reader(large) as reader:
+            reader.read()
+
+        usage = dctx.window_usage(reset=True)
+        self.assertEqual(usage["frames"], 3)
+        self.assertEqual(usage["max_window_size"], 2 ** 20)
+        self.assertEqual(dctx.window_usage()["frames"], 0)
+
+        dctx = zstd.ZstdDecompressor(
+            max_window_size=2 ** 22, track_window_usage=True
+        )
+        self.assertEqual(dctx.window_usage()["window_size_limit"], 2 ** 22)
+
+        dctx = zstd.ZstdDecompressor.from_config(dctx.to_config())
+        self.assertEqual(dctx.window_usage()["frames"], 0)
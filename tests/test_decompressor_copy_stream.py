@@ -1,3 +1,4 @@
+import hashlib
 import io
 import struct
 import unittest
@@ -89,3 +90,132 @@ class TestDecompressor_copy_stream(unittest.TestCase):
 
         with self.assertRaisesRegex(IOError, "write"):
             cctx.copy_stream(source, dest)
+
+    @unittest.skipUnless(
+        "copy_stream_multiple_writers" in zstd.backend_features,
+        "multiple writers only implemented in Rust backend",
+    )
+    def test_multiple_writers(self):
+        source = io.BytesIO(zstd.ZstdCompressor().compress(b"foobar" * 8192))
+        dest1 = io.BytesIO()
+        dest2 = CustomBytesIO()
+
+        dctx = zstd.ZstdDecompressor()
+        r, w = dctx.copy_stream(source, [dest1, dest2], write_size=1024)
+
+        self.assertEqual(r, len(source.getvalue()))
+        self.assertEqual(w, len(b"foobar" * 8192))
+        self.assertEqual(dest1.getvalue(), b"foobar" * 8192)
+        self.assertEqual(dest2.getvalue(), b"foobar" * 8192)
+        self.assertEqual(dest2._write_count, 48)
+
+    @unittest.skipUnless(
+        "copy_stream_multiple_writers" in zstd.backend_features,
+        "multiple writers only implemented in Rust backend",
+    )
+    def test_multiple_writers_invalid(self):
+        source = io.BytesIO()
+        dctx = zstd.ZstdDecompressor()
+
+        with self.assertRaisesRegex(ValueError, "at least 1 writer"):
+            dctx.copy_stream(source, [])
+
+        with self.assertRaisesRegex(
+            ValueError, "writer 1 must have a write\\(\\) method"
+        ):
+            dctx.copy_stream(source, (io.BytesIO(), object()))
+
+    @unittest.skipUnless(
+        "hasher" in zstd.backend_features,
+        "hasher only implemented in Rust backend",
+    )
+    def test_hasher(self):
+        source = io.BytesIO(zstd.ZstdCompressor().compress(b"foobar" * 8192))
+        dest = io.BytesIO()
+
+        dctx = zstd.ZstdDecompressor()
+        r, w, digest = dctx.copy_stream(
+            source, dest, write_size=64, hasher=hashlib.sha256()
+        )
+
+        self.assertEqual(r, len(source.getvalue()))
+        self.assertEqual(w, len(b"foobar" * 8192))
+        self.assertEqual(digest, hashlib.sha256(b"foobar" * 8192).digest())
+
+        with self.assertRaisesRegex(
+            ValueError, "hasher must have update\\(\\) and digest\\(\\) methods"
+        ):
+            dctx.copy_stream(io.BytesIO(), io.BytesIO(), hasher=object())
+
+    @unittest.skipUnless(
+        "copy_stream_max_time_ms" in zstd.backend_features,
+        "max_time_ms only implemented in Rust backend",
+    )
+    def test_max_time_ms(self):
+        source = io.BytesIO(zstd.ZstdCompressor().compress(b"foobar" * 8192))
+        dest = io.BytesIO()
+
+        dctx = zstd.ZstdDecompressor()
+        r, w, finished = dctx.copy_stream(
+            source, dest, read_size=16, max_time_ms=0
+        )
+        self.assertEqual((r, finished), (16, False))
+
+        r, w, finished = dctx.copy_stream(
+            source, dest, read_size=16, max_time_ms=60000, resume=True
+        )
+        self.assertTrue(finished)
+        self.assertEqual(r, len(source.getvalue()) - 16)
+        self.assertEqual(dest.getvalue(), b"foobar" * 8192)
+
+        # Resuming with a zero time slice makes progress on every call.
+        source.seek(0)
+        dest = io.BytesIO()
+        result = dctx.copy_stream(source, dest, read_size=16, max_time_ms=0)
+        calls = 1
+        while not result.finished:
+            self.assertGreater(result.bytes_read, 0)
+            result = dctx.copy_stream(
+                source, dest, read_size=16, max_time_ms=0, resume=True
+            )
+            calls += 1
+
+        self.assertGreater(calls, 1)
+        self.assertEqual(dest.getvalue(), b"foobar" * 8192)
+
+    @unittest.skipUnless(
+        "copy_stream_result" in zstd.backend_features,
+        "CopyStreamResult only implemented in Rust backend",
+    )
+    def test_result(self):
+        frames = zstd.ZstdCompressor(write_checksum=True).compress(b"foo")
+        frames += zstd.ZstdCompressor(write_checksum=True).compress(b"bar")
+        source = io.BytesIO(frames)
+        dest = io.BytesIO()
+
+        dctx = zstd.ZstdDecompressor()
+        result = dctx.copy_stream(source, dest)
+
+        self.assertEqual(result, (len(frames), 6))
+        self.assertEqual(len(result), 2)
+        self.assertEqual(result[0], len(frames))
+        self.assertEqual(result[-1], 6)
+        self.assertEqual(result.bytes_read, len(frames))
+        self.assertEqual(result.bytes_written, 6)
+        self.assertEqual(result.frames, 2)
+        self.assertTrue(result.checksum)
+        self.assertTrue(result.finished)
+        self.assertIsNone(result.digest)
+        self.assertGreaterEqual(result.duration, 0.0)
+
+        frames += zstd.ZstdCompressor(write_checksum=False).compress(b"baz")
+        result = dctx.copy_stream(io.BytesIO(frames), io.BytesIO())
+        self.assertEqual(result.frames, 3)
+        self.assertFalse(result.checksum)
+
+        result = dctx.copy_stream(
+            io.BytesIO(frames), io.BytesIO(), hasher=hashlib.sha256()
+        )
+        digest = hashlib.sha256(b"foobarbaz").digest()
+        self.assertEqual(result, (len(frames), 9, digest))
+        self.assertEqual(result.digest, digest)
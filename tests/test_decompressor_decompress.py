@@ -1,3 +1,5 @@
+import struct
+import threading
 import unittest
 
 import zstandard as zstd
@@ -20,6 +22,13 @@ class TestDecompressor_decompress(unittest.TestCase):
         ):
             dctx.decompress(b"foobar")
 
+    def test_unknown_argument(self):
+        dctx = zstd.ZstdDecompressor()
+        frame = zstd.ZstdCompressor().compress(b"foobar")
+
+        with self.assertRaises(TypeError):
+            dctx.decompress(frame, bogus=True)
+
     def test_input_types(self):
         cctx = zstd.ZstdCompressor(level=1)
         compressed = cctx.compress(b"foo")
@@ -92,6 +101,158 @@ class TestDecompressor_decompress(unittest.TestCase):
         )
         self.assertEqual(decompressed, source)
 
+    @unittest.skipUnless(
+        "bound_fallback" in zstd.backend_features,
+        "bound_fallback only implemented in Rust backend",
+    )
+    def test_bound_fallback(self):
+        cctx = zstd.ZstdCompressor(write_content_size=False)
+        source = b"foobar" * 65536
+        compressed = cctx.compress(source)
+
+        dctx = zstd.ZstdDecompressor()
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "could not determine content size in frame header"
+        ):
+            dctx.decompress(compressed)
+
+        self.assertEqual(
+            dctx.decompress(compressed, bound_fallback=True), source
+        )
+
+        # Data after the frame doesn't contribute to the bound.
+        self.assertEqual(
+            dctx.decompress(compressed + b"junk", bound_fallback=True), source
+        )
+
+        # An explicit max_output_size takes precedence.
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "decompression error: did not decompress full frame"
+        ):
+            dctx.decompress(
+                compressed, max_output_size=len(source) - 1, bound_fallback=True
+            )
+
+        # Streamed frames work too.
+        cobj = cctx.compressobj()
+        compressed = cobj.compress(source) + cobj.flush()
+        self.assertEqual(
+            dctx.decompress(compressed, bound_fallback=True), source
+        )
+
+        # The bound is only known for complete frames.
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "could not determine content size in frame header"
+        ):
+            dctx.decompress(compressed[:-4], bound_fallback=True)
+
+    @unittest.skipUnless(
+        "streaming_fallback" in zstd.backend_features,
+        "streaming_fallback only implemented in Rust backend",
+    )
+    def test_streaming_fallback(self):
+        cctx = zstd.ZstdCompressor(write_content_size=False)
+        dctx = zstd.ZstdDecompressor()
+
+        # Highly compressible input needs the buffer to grow many times.
+        for source in (b"", b"foo", b"\x00" * 10000000, b"foobar" * 65536):
+            compressed = cctx.compress(source)
+            self.assertEqual(
+                dctx.decompress(compressed, streaming_fallback=True), source
+            )
+
+        compressed = cctx.compress(b"foobar" * 65536)
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "decompression error: did not decompress full frame"
+        ):
+            dctx.decompress(compressed[:-10], streaming_fallback=True)
+
+    @unittest.skipUnless(
+        "allow_extra_data" in zstd.backend_features,
+        "allow_extra_data only implemented in Rust backend",
+    )
+    def test_allow_extra_data(self):
+        cctx = zstd.ZstdCompressor()
+        dctx = zstd.ZstdDecompressor()
+
+        frame = cctx.compress(b"foo")
+        data = frame + cctx.compress(b"bar")
+
+        # Data after the first frame is ignored by default.
+        self.assertEqual(dctx.decompress(data), b"foo")
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError,
+            "compressed input contains %d bytes of unused data at offset %d, "
+            "which is disallowed" % (len(data) - len(frame), len(frame)),
+        ):
+            dctx.decompress(data, allow_extra_data=False)
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "contains 3 bytes of unused data"
+        ):
+            dctx.decompress(frame + b"foo", allow_extra_data=False)
+
+        empty = cctx.compress(b"")
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "contains 3 bytes of unused data"
+        ):
+            dctx.decompress(empty + b"foo", allow_extra_data=False)
+
+        self.assertEqual(dctx.decompress(frame, allow_extra_data=False), b"foo")
+
+    @unittest.skipUnless(
+        "read_across_frames" in zstd.backend_features,
+        "read_across_frames only implemented in Rust backend",
+    )
+    def test_read_across_frames(self):
+        cctx = zstd.ZstdCompressor()
+        no_size = zstd.ZstdCompressor(write_content_size=False)
+        dctx = zstd.ZstdDecompressor()
+
+        data = b"".join(
+            [
+                cctx.compress(b"foo" * 1024),
+                cctx.compress(b""),
+                zstd.skippable_frame_header(4) + b"meta",
+                cctx.compress(b"bar"),
+            ]
+        )
+        self.assertEqual(
+            dctx.decompress(data, read_across_frames=True),
+            b"foo" * 1024 + b"bar",
+        )
+
+        # Frames without a content size need a fallback.
+        data = cctx.compress(b"foo") + no_size.compress(b"\x00" * 1000000)
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "could not determine content size in frame header"
+        ):
+            dctx.decompress(data, read_across_frames=True)
+
+        self.assertEqual(
+            dctx.decompress(
+                data, read_across_frames=True, streaming_fallback=True
+            ),
+            b"foo" + b"\x00" * 1000000,
+        )
+        self.assertEqual(
+            dctx.decompress(data, 1000003, read_across_frames=True),
+            b"foo" + b"\x00" * 1000000,
+        )
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "did not decompress full frame"
+        ):
+            dctx.decompress(data, 1000002, read_across_frames=True)
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "did not decompress full frame"
+        ):
+            dctx.decompress(
+                data[:-4], read_across_frames=True, streaming_fallback=True
+            )
+
     def test_stupidly_large_output_buffer(self):
         cctx = zstd.ZstdCompressor(write_content_size=False)
         compressed = cctx.compress(b"foobar" * 256)
@@ -124,6 +285,48 @@ class TestDecompressor_decompress(unittest.TestCase):
 
         self.assertEqual(decompressed, orig)
 
+    @unittest.skipUnless(
+        "ignore_dict_id" in zstd.backend_features,
+        "ignore_dict_id only implemented in Rust backend",
+    )
+    def test_ignore_dict_id(self):
+        samples = []
+        for i in range(128):
+            samples.append(b"foo" * 64)
+            samples.append(b"foobar" * 64)
+            samples.append(b"qwert" * 64)
+
+        d = zstd.train_dictionary(8192, samples, dict_id=42)
+
+        # Same content under a different dictionary ID.
+        data = d.as_bytes()
+        other = zstd.ZstdCompressionDict(
+            data[0:4] + struct.pack("<I", 43) + data[8:]
+        )
+        self.assertEqual(other.dict_id(), 43)
+
+        orig = b"foobar" * 16384
+        compressed = zstd.ZstdCompressor(level=1, dict_data=d).compress(orig)
+        stripped = zstd.ZstdCompressor(
+            level=1, dict_data=d, write_dict_id=False
+        ).compress(orig)
+
+        with self.assertRaisesRegex(zstd.ZstdError, "Dictionary mismatch"):
+            zstd.ZstdDecompressor(dict_data=other).decompress(compressed)
+
+        dctx = zstd.ZstdDecompressor(dict_data=other, ignore_dict_id=True)
+        self.assertEqual(dctx.decompress(compressed), orig)
+        self.assertEqual(dctx.decompress(stripped), orig)
+        self.assertEqual(dctx.decompressobj().decompress(compressed), orig)
+
+        with dctx.stream_reader(compressed) as reader:
+            self.assertEqual(reader.read(), orig)
+
+        with self.assertRaisesRegex(
+            ValueError, "ignore_dict_id requires dict_data"
+        ):
+            zstd.ZstdDecompressor(ignore_dict_id=True)
+
     def test_dictionary_multiple(self):
         samples = []
         for i in range(128):
@@ -148,6 +351,106 @@ class TestDecompressor_decompress(unittest.TestCase):
             decompressed = dctx.decompress(compressed[i])
             self.assertEqual(decompressed, sources[i])
 
+    @unittest.skipUnless(
+        "dict_data_list" in zstd.backend_features,
+        "dict_data lists only implemented in Rust backend",
+    )
+    def test_dictionary_list(self):
+        samples = []
+        for i in range(128):
+            samples.append(b"foo" * 64)
+            samples.append(b"bar" * 64)
+            samples.append(b"foobar" * 64)
+            samples.append(b"qwert" * 64)
+            samples.append(b"yuiop" * 64)
+
+        d1 = zstd.train_dictionary(8192, samples, dict_id=1)
+        d2 = zstd.train_dictionary(8192, samples, dict_id=2)
+
+        orig = (b"foobar" * 4096, b"qwert" * 4096, b"yuiop" * 4096)
+        compressed = [
+            zstd.ZstdCompressor(level=1, dict_data=d1).compress(orig[0]),
+            zstd.ZstdCompressor(level=1, dict_data=d2).compress(orig[1]),
+            zstd.ZstdCompressor(level=1).compress(orig[2]),
+        ]
+
+        dctx = zstd.ZstdDecompressor(dict_data=[d1, d2])
+        for source, frame in zip(orig, compressed):
+            self.assertEqual(dctx.decompress(frame), source)
+            self.assertEqual(b"".join(dctx.read_to_iter(frame)), source)
+
+            with dctx.stream_reader(frame) as reader:
+                self.assertEqual(reader.read(), source)
+
+        result = dctx.multi_decompress_to_buffer(compressed, threads=2)
+        self.assertEqual([b.tobytes() for b in result], list(orig))
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "no dictionary matches the frame's dictionary ID"
+        ):
+            zstd.ZstdDecompressor(dict_data=(d1,)).decompress(compressed[1])
+
+        with self.assertRaisesRegex(
+            ValueError, "multiple dictionaries have dictionary ID 1"
+        ):
+            zstd.ZstdDecompressor(dict_data=[d1, d2, d1])
+
+        with self.assertRaisesRegex(
+            ValueError, "dictionaries in a list must have a dictionary ID"
+        ):
+            zstd.ZstdDecompressor(
+                dict_data=[d1, zstd.ZstdCompressionDict(b"foobar" * 64)]
+            )
+
+        with self.assertRaisesRegex(ValueError, "dict_data must not be empty"):
+            zstd.ZstdDecompressor(dict_data=[])
+
+        with self.assertRaisesRegex(
+            TypeError, "dict_data items must be ZstdCompressionDict instances"
+        ):
+            zstd.ZstdDecompressor(dict_data=[d1, b"foo"])
+
+        with self.assertRaisesRegex(
+            ValueError, "ignore_dict_id requires a single dictionary"
+        ):
+            zstd.ZstdDecompressor(dict_data=[d1, d2], ignore_dict_id=True)
+
+        with self.assertRaisesRegex(
+            ValueError, "cannot serialize a decompressor with multiple dictionaries"
+        ):
+            dctx.to_config()
+
+    def test_dictionary_shared_threads(self):
+        samples = []
+        for i in range(128):
+            samples.append(b"foo" * 64)
+            samples.append(b"bar" * 64)
+            samples.append(b"foobar" * 64)
+
+        d = zstd.train_dictionary(8192, samples)
+
+        orig = b"foobar" * 16384
+        compressed = zstd.ZstdCompressor(level=1, dict_data=d).compress(orig)
+        results = [None] * 8
+
+        # The prepared dictionary is created by whichever thread gets there
+        # first.
+        def decompress(i):
+            results[i] = [
+                zstd.ZstdDecompressor(dict_data=d).decompress(compressed)
+                for _ in range(16)
+            ]
+
+        threads = [
+            threading.Thread(target=decompress, args=(i,)) for i in range(8)
+        ]
+        for t in threads:
+            t.start()
+        for t in threads:
+            t.join()
+
+        self.assertEqual(results, [[orig] * 16] * 8)
+
     def test_max_window_size(self):
         with open(__file__, "rb") as fh:
             source = fh.read()
@@ -164,3 +467,193 @@ class TestDecompressor_decompress(unittest.TestCase):
             "decompression error: Frame requires too much memory",
         ):
             dctx.decompress(frame, max_output_size=len(source))
+
+    @unittest.skipUnless(
+        "max_window_log" in zstd.backend_features,
+        "max_window_log only implemented in Rust backend",
+    )
+    def test_max_window_log(self):
+        params = zstd.ZstdCompressionParameters(window_log=20)
+        cctx = zstd.ZstdCompressor(compression_params=params)
+        cobj = cctx.compressobj()
+        source = b"foobar" * 65536
+        frame = cobj.compress(source) + cobj.flush()
+
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(dctx.max_window_log, 0)
+
+        dctx.max_window_log = 19
+        self.assertEqual(dctx.max_window_log, 19)
+        self.assertIn("max_window_size=524288", repr(dctx))
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError,
+            "decompression error: Frame requires too much memory",
+        ):
+            dctx.decompress(frame, max_output_size=len(source))
+
+        dctx.max_window_log = 20
+        self.assertEqual(
+            dctx.decompress(frame, max_output_size=len(source)), source
+        )
+
+        dctx.max_window_log = 0
+        self.assertIn("max_window_size=0", repr(dctx))
+        self.assertEqual(
+            dctx.decompress(frame, max_output_size=len(source)), source
+        )
+
+        # Sizes that aren't a power of 2 are rounded down.
+        dctx = zstd.ZstdDecompressor(max_window_size=2 ** 20 + 1)
+        self.assertEqual(dctx.max_window_log, 20)
+
+        with self.assertRaisesRegex(
+            ValueError,
+            "max_window_log must be between %d and " % zstd.WINDOWLOG_MIN,
+        ):
+            dctx.max_window_log = zstd.WINDOWLOG_MIN - 1
+
+        self.assertEqual(dctx.max_window_log, 20)
+
+    @unittest.skipUnless(
+        "ignore_checksum" in zstd.backend_features,
+        "ignore_checksum only implemented in Rust backend",
+    )
+    def test_ignore_checksum(self):
+        source = b"foobar" * 1024
+        frame = zstd.ZstdCompressor(write_checksum=True).compress(source)
+        corrupt = frame[:-1] + bytes([frame[-1] ^ 0xFF])
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "Restored data doesn't match checksum"
+        ):
+            zstd.ZstdDecompressor().decompress(corrupt)
+
+        dctx = zstd.ZstdDecompressor(ignore_checksum=True)
+        self.assertEqual(dctx.decompress(corrupt), source)
+
+        with dctx.stream_reader(corrupt) as reader:
+            self.assertEqual(reader.read(), source)
+
+        dctx = zstd.ZstdDecompressor.from_config(dctx.to_config())
+        self.assertEqual(dctx.decompress(corrupt), source)
+
+        with self.assertRaisesRegex(
+            ValueError,
+            "cannot specify both require_checksum and ignore_checksum",
+        ):
+            zstd.ZstdDecompressor(require_checksum=True, ignore_checksum=True)
+
+    @unittest.skipUnless(
+        "require_checksum" in zstd.backend_features,
+        "require_checksum only implemented in Rust backend",
+    )
+    def test_require_checksum(self):
+        no_checksum = zstd.ZstdCompressor(write_checksum=False).compress(b"foo")
+        checksum = zstd.ZstdCompressor(write_checksum=True).compress(b"foo")
+
+        dctx = zstd.ZstdDecompressor(require_checksum=True)
+        self.assertEqual(dctx.decompress(checksum), b"foo")
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "frame does not have a content checksum"
+        ):
+            dctx.decompress(no_checksum)
+
+        # Skippable frames don't need a checksum.
+        skippable = b"\x50\x2a\x4d\x18\x03\x00\x00\x00bar"
+        with dctx.stream_reader(
+            skippable + checksum, read_across_frames=True
+        ) as reader:
+            self.assertEqual(reader.read(), b"foo")
+
+    @unittest.skipUnless(
+        "content_size_checks" in zstd.backend_features,
+        "allocation cap only implemented in Rust backend",
+    )
+    def test_lying_content_size(self):
+        # Header declares 1 TiB of content but the frame holds a 3 byte block.
+        frame = (
+            zstd.FRAME_HEADER
+            + b"\xc0\x00"
+            + struct.pack("<Q", 2 ** 40)
+            + b"\x19\x00\x00foo"
+        )
+        self.assertEqual(zstd.get_frame_parameters(frame).content_size, 2 ** 40)
+
+        dctx = zstd.ZstdDecompressor()
+        with self.assertRaisesRegex(zstd.ZstdError, "decompression error"):
+            dctx.decompress(frame)
+
+    @unittest.skipUnless(
+        "content_size_checks" in zstd.backend_features,
+        "decompressBound check only implemented in Rust backend",
+    )
+    def test_truncated_frame(self):
+        frame = zstd.ZstdCompressor().compress(b"foobar" * 1024)
+
+        dctx = zstd.ZstdDecompressor()
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "input is not a valid or complete zstd frame"
+        ):
+            dctx.decompress(frame[:-3])
+
+    def test_trailing_data(self):
+        source = b"foobar" * 2000
+        frame = zstd.ZstdCompressor().compress(source)
+
+        dctx = zstd.ZstdDecompressor()
+        for extra in (b"junk", b"\x00" * 7, frame[:10]):
+            self.assertEqual(dctx.decompress(frame + extra), source)
+
+    @unittest.skipUnless(
+        "content_size_checks" in zstd.backend_features,
+        "allocation cap only implemented in Rust backend",
+    )
+    def test_output_larger_than_preallocation(self):
+        source = bytes(range(256)) * (96 * 1024)
+        frame = zstd.ZstdCompressor(level=1).compress(source)
+
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(dctx.decompress(frame), source)
+
+    @unittest.skipUnless(
+        "patch_from" in zstd.backend_features,
+        "decompress_patch() only implemented in Rust backend",
+    )
+    def test_decompress_patch(self):
+        old = b"".join(b"line %d\n" % i for i in range(100000))
+        new = old.replace(b"line 50000\n", b"changed\n")
+        patch = zstd.ZstdCompressor().compress_patch(old, new)
+
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(dctx.decompress_patch(old, patch), new)
+        self.assertEqual(
+            dctx.decompress_patch(memoryview(old), bytearray(patch)), new
+        )
+
+        # old_data is only referenced by the patch.
+        with self.assertRaisesRegex(zstd.ZstdError, "decompression error"):
+            dctx.decompress(patch)
+        frame = zstd.ZstdCompressor().compress(new)
+        self.assertEqual(dctx.decompress(frame), new)
+
+        # Dictionaries aren't used.
+        d = zstd.ZstdCompressionDict(
+            b"foobar" * 100, dict_type=zstd.DICT_TYPE_RAWCONTENT
+        )
+        dctx = zstd.ZstdDecompressor(dict_data=d)
+        self.assertEqual(dctx.decompress_patch(old, patch), new)
+
+        # Output larger than the up-front allocation.
+        big_old = old * 24
+        big_new = old * 12 + new * 12
+        self.assertGreater(len(big_new), 16 * 1024 * 1024)
+        big_patch = zstd.ZstdCompressor().compress_patch(big_old, big_new)
+        self.assertEqual(dctx.decompress_patch(big_old, big_patch), big_new)
+
+        cctx = zstd.ZstdCompressor(write_content_size=False)
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "could not determine content size in frame header"
+        ):
+            dctx.decompress_patch(old, cctx.compress_patch(old, new))
@@ -0,0 +1,106 @@
+import array
+import threading
+import unittest
+
+import zstandard as zstd
+
+
+@unittest.skipUnless(
+    "decompress_into" in zstd.backend_features,
+    "decompress_into() only implemented in Rust backend",
+)
+class TestDecompressor_decompress_into(unittest.TestCase):
+    def test_simple(self):
+        source = array.array("d", range(4096))
+        frame = zstd.ZstdCompressor().compress(source.tobytes())
+
+        dest = array.array("d", bytes(len(source) * 8))
+        count = zstd.ZstdDecompressor().decompress_into(frame, dest)
+        self.assertEqual(count, 4096)
+        self.assertEqual(dest, source)
+
+    def test_bytearray(self):
+        frame = zstd.ZstdCompressor().compress(b"foobar")
+
+        dest = bytearray(10)
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(dctx.decompress_into(frame, dest), 6)
+        self.assertEqual(dest, b"foobar\x00\x00\x00\x00")
+
+    def test_multidimensional(self):
+        frame = zstd.ZstdCompressor().compress(bytes(range(256)) * 4)
+
+        dest = memoryview(bytearray(1024)).cast("I", shape=[4, 64])
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(dctx.decompress_into(frame, dest), 256)
+        self.assertEqual(dest.tobytes(), bytes(range(256)) * 4)
+
+    def test_readonly(self):
+        frame = zstd.ZstdCompressor().compress(b"foobar")
+        dctx = zstd.ZstdDecompressor()
+
+        # The buffer protocol refuses writable views of these.
+        for dest in (b"\x00" * 6, memoryview(bytearray(6)).toreadonly()):
+            with self.assertRaises(BufferError):
+                dctx.decompress_into(frame, dest)
+
+    def test_too_small(self):
+        frame = zstd.ZstdCompressor().compress(b"foobar")
+
+        with self.assertRaisesRegex(
+            ValueError,
+            "destination buffer too small: frame content is 6 bytes; "
+            "buffer is 5 bytes",
+        ):
+            zstd.ZstdDecompressor().decompress_into(frame, bytearray(5))
+
+    def test_item_size_mismatch(self):
+        frame = zstd.ZstdCompressor().compress(b"foobar")
+
+        with self.assertRaisesRegex(
+            ValueError,
+            "frame content size 6 is not a multiple of the destination item "
+            "size 4",
+        ):
+            zstd.ZstdDecompressor().decompress_into(
+                frame, array.array("I", [0] * 4)
+            )
+
+    def test_unknown_content_size(self):
+        cctx = zstd.ZstdCompressor(write_content_size=False)
+        frame = cctx.compress(b"foobar")
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "could not determine content size in frame header"
+        ):
+            zstd.ZstdDecompressor().decompress_into(frame, bytearray(6))
+
+    def test_truncated(self):
+        frame = zstd.ZstdCompressor().compress(b"foobar" * 1024)
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "did not decompress full frame"
+        ):
+            zstd.ZstdDecompressor().decompress_into(frame[:-4], bytearray(6144))
+
+    def test_threads(self):
+        source = [bytes([i]) * 65536 + b"foobar" * i for i in range(8)]
+        frames = [zstd.ZstdCompressor().compress(s) for s in source]
+        dests = [bytearray(len(s)) for s in source]
+        counts = [None] * len(source)
+
+        def decompress(i):
+            dctx = zstd.ZstdDecompressor()
+            counts[i] = dctx.decompress_into(frames[i], dests[i])
+
+        threads = [
+            threading.Thread(target=decompress, args=(i,))
+            for i in range(len(source))
+        ]
+        for t in threads:
+            t.start()
+        for t in threads:
+            t.join()
+
+        self.assertEqual(counts, [len(s) for s in source])
+        self.assertEqual(dests, source)
@@ -1,3 +1,4 @@
+import threading
 import unittest
 
 import zstandard as zstd
@@ -64,3 +65,96 @@ class TestDecompressor_decompressobj(unittest.TestCase):
         for i in range(128):
             dobj = dctx.decompressobj(write_size=i + 1)
             self.assertEqual(dobj.decompress(data), source)
+
+    @unittest.skipUnless(
+        "decompressobj_reset" in zstd.backend_features,
+        "reset() only implemented in Rust backend",
+    )
+    def test_reset(self):
+        cctx = zstd.ZstdCompressor()
+        dctx = zstd.ZstdDecompressor()
+        dobj = dctx.decompressobj()
+
+        # Reset between complete frames.
+        for i in range(3):
+            data = b"message %d" % i
+            self.assertEqual(dobj.decompress(cctx.compress(data)), data)
+            dobj.reset()
+
+        # Reset discards a partially decompressed frame.
+        frame = cctx.compress(b"foobar" * 1024)
+        dobj.decompress(frame[0:10])
+        dobj.reset()
+        self.assertEqual(dobj.decompress(frame), b"foobar" * 1024)
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "cannot use a decompressobj multiple times"
+        ):
+            dobj.decompress(frame)
+
+    @unittest.skipUnless(
+        "unused_data" in zstd.backend_features,
+        "unused_data only implemented in Rust backend",
+    )
+    def test_unused_data(self):
+        frame = zstd.ZstdCompressor().compress(b"foobar")
+        dctx = zstd.ZstdDecompressor()
+
+        dobj = dctx.decompressobj()
+        self.assertEqual(dobj.unused_data, b"")
+        self.assertEqual(dobj.decompress(frame[0:4]), b"")
+        self.assertEqual(dobj.decompress(frame[4:] + b"extra"), b"foobar")
+        self.assertEqual(dobj.unused_data, b"extra")
+
+        dobj.reset()
+        self.assertEqual(dobj.unused_data, b"")
+
+    @unittest.skipUnless(
+        "tolerate_padding" in zstd.backend_features,
+        "tolerate_padding only implemented in Rust backend",
+    )
+    def test_tolerate_padding(self):
+        frame = zstd.ZstdCompressor().compress(b"foobar")
+        dctx = zstd.ZstdDecompressor()
+
+        dobj = dctx.decompressobj()
+        self.assertEqual(dobj.decompress(frame + b"\x00" * 3), b"foobar")
+        self.assertEqual(dobj.unused_data, b"\x00" * 3)
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "cannot use a decompressobj multiple times"
+        ):
+            dobj.decompress(b"\x00" * 4)
+
+        dobj = dctx.decompressobj(tolerate_padding=True)
+        self.assertEqual(dobj.decompress(frame + b"\x00" * 3), b"foobar")
+        self.assertEqual(dobj.decompress(b"\x00" * 4), b"")
+        self.assertEqual(dobj.decompress(b""), b"")
+        self.assertEqual(dobj.unused_data, b"\x00" * 7)
+
+        # Only NUL bytes are padding.
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "cannot use a decompressobj multiple times"
+        ):
+            dobj.decompress(b"\x00\x01")
+
+        self.assertEqual(dobj.unused_data, b"\x00" * 7)
+
+    def test_threads(self):
+        source = b"".join(b"line %d\n" % i for i in range(65536))
+        frame = zstd.ZstdCompressor().compress(source)
+        results = [None] * 4
+
+        def decompress(i):
+            dobj = zstd.ZstdDecompressor().decompressobj()
+            results[i] = dobj.decompress(frame)
+
+        threads = [
+            threading.Thread(target=decompress, args=(i,)) for i in range(4)
+        ]
+        for t in threads:
+            t.start()
+        for t in threads:
+            t.join()
+
+        self.assertEqual(results, [source] * 4)
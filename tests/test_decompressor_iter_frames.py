@@ -0,0 +1,67 @@
+import unittest
+
+import zstandard as zstd
+
+
+@unittest.skipUnless(
+    "iter_frames" in zstd.backend_features,
+    "iter_frames() only implemented in Rust backend",
+)
+class TestDecompressor_iter_frames(unittest.TestCase):
+    def test_empty_input(self):
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(list(dctx.iter_frames(b"")), [])
+
+    def test_multiple_frames(self):
+        cctx = zstd.ZstdCompressor()
+        no_size = zstd.ZstdCompressor(write_content_size=False)
+
+        sources = [b"foo" * 1024, b"", b"bar", bytes(range(256)) * 4096]
+        data = b"".join(
+            [
+                cctx.compress(sources[0]),
+                cctx.compress(sources[1]),
+                zstd.skippable_frame_header(4) + b"meta",
+                no_size.compress(sources[2]),
+                no_size.compress(sources[3]),
+            ]
+        )
+
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(list(dctx.iter_frames(data)), sources)
+        self.assertEqual(list(dctx.iter_frames(memoryview(data))), sources)
+
+    def test_dictionary(self):
+        samples = []
+        for i in range(128):
+            samples.append(b"foo" * 64)
+            samples.append(b"foobar" * 64)
+
+        d = zstd.train_dictionary(8192, samples)
+        cctx = zstd.ZstdCompressor(dict_data=d)
+
+        data = cctx.compress(b"foo" * 64) + cctx.compress(b"foobar" * 64)
+
+        dctx = zstd.ZstdDecompressor(dict_data=d)
+        self.assertEqual(
+            list(dctx.iter_frames(data)), [b"foo" * 64, b"foobar" * 64]
+        )
+
+    def test_truncated(self):
+        frame = zstd.ZstdCompressor().compress(b"foobar")
+
+        it = zstd.ZstdDecompressor().iter_frames(frame + frame[:-1])
+        self.assertEqual(next(it), b"foobar")
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "error finding frame at offset %d" % len(frame)
+        ):
+            next(it)
+
+    def test_magicless(self):
+        dctx = zstd.ZstdDecompressor(format=zstd.FORMAT_ZSTD1_MAGICLESS)
+
+        with self.assertRaisesRegex(
+            ValueError, "iter_frames\\(\\) does not support magicless frames"
+        ):
+            dctx.iter_frames(b"")
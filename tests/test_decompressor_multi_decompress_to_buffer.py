@@ -1,3 +1,4 @@
+import asyncio
 import struct
 import unittest
 
@@ -229,3 +230,168 @@ class TestDecompressor_multi_decompress_to_buffer(unittest.TestCase):
             "Destination buffer is too small)",
         ):
             dctx.multi_decompress_to_buffer(frames, threads=2)
+
+    @unittest.skipUnless(
+        "on_error" in zstd.backend_features,
+        "on_error only implemented in Rust backend",
+    )
+    def test_item_failure_skip(self):
+        cctx = zstd.ZstdCompressor()
+        frames = [
+            cctx.compress(b"x" * 128),
+            cctx.compress(b"y" * 128),
+            cctx.compress(b"z" * 128),
+        ]
+
+        frames[1] = frames[1][0:15] + b"extra" + frames[1][15:]
+
+        dctx = zstd.ZstdDecompressor()
+
+        for threads in (1, 2):
+            result, errors = dctx.multi_decompress_to_buffer(
+                frames, threads=threads, on_error="skip"
+            )
+            self.assertIsInstance(result, zstd.BufferWithSegmentsCollection)
+            self.assertEqual(
+                [b.tobytes() for b in result], [b"x" * 128, b"", b"z" * 128]
+            )
+            self.assertEqual(errors, [(1, "Corrupted block detected")])
+
+        result, errors = dctx.multi_decompress_to_buffer(
+            [frames[1]], on_error="skip"
+        )
+        self.assertEqual(len(result), 1)
+        self.assertEqual(result.size(), 0)
+        self.assertEqual(len(errors), 1)
+
+        with self.assertRaisesRegex(
+            ValueError, 'on_error must be "raise" or "skip"; got ignore'
+        ):
+            dctx.multi_decompress_to_buffer(frames, on_error="ignore")
+
+        with self.assertRaisesRegex(
+            ValueError, 'on_error="skip" cannot be used with out'
+        ):
+            dctx.multi_decompress_to_buffer(
+                frames,
+                out=bytearray(384),
+                offsets=struct.pack("=QQQ", 0, 128, 256),
+                on_error="skip",
+            )
+
+        async def decompress(frames):
+            return await dctx.multi_decompress_to_buffer_async(
+                frames, threads=2, on_error="skip"
+            )
+
+        result, errors = asyncio.run(decompress(frames))
+        self.assertEqual(
+            [b.tobytes() for b in result], [b"x" * 128, b"", b"z" * 128]
+        )
+        self.assertEqual(errors, [(1, "Corrupted block detected")])
+
+    @unittest.skipUnless(
+        "multi_buffer_async" in zstd.backend_features,
+        "async only implemented in Rust backend",
+    )
+    def test_async(self):
+        cctx = zstd.ZstdCompressor()
+
+        original = [b"foo" * 1024, b"bar" * 512, b"baz" * 2048]
+        frames = cctx.multi_compress_to_buffer(original)
+
+        dctx = zstd.ZstdDecompressor()
+
+        async def decompress(frames):
+            return await dctx.multi_decompress_to_buffer_async(
+                frames, threads=2
+            )
+
+        result = asyncio.run(decompress(frames))
+        self.assertIsInstance(result, zstd.BufferWithSegmentsCollection)
+        self.assertEqual([b.tobytes() for b in result], original)
+
+        frames = [cctx.compress(b"x" * 128), cctx.compress(b"y" * 128)]
+        frames[1] = frames[1][0:15] + b"extra" + frames[1][15:]
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "error decompressing item 1"
+        ):
+            asyncio.run(decompress(frames))
+
+    @unittest.skipUnless(
+        "multi_decompress_out" in zstd.backend_features,
+        "out= only implemented in Rust backend",
+    )
+    def test_out_buffer(self):
+        cctx = zstd.ZstdCompressor()
+
+        original = [b"foo" * 1024, b"bar" * 512, b"baz" * 2048]
+        frames = [cctx.compress(d) for d in original]
+
+        # Regions in a different order than frames, with a gap.
+        offsets = struct.pack("=QQQ", 9216, 0, 3072)
+        out = bytearray(16384)
+
+        for threads in (0, 2):
+            dctx = zstd.ZstdDecompressor()
+            written = dctx.multi_decompress_to_buffer(
+                frames, threads=threads, out=out, offsets=offsets
+            )
+
+            self.assertEqual(written, sum(map(len, original)))
+            self.assertEqual(out[9216:12288], original[0])
+            self.assertEqual(out[0:1536], original[1])
+            self.assertEqual(out[3072:9216], original[2])
+            self.assertEqual(out[1536:3072], b"\x00" * 1536)
+
+    @unittest.skipUnless(
+        "multi_decompress_out" in zstd.backend_features,
+        "out= only implemented in Rust backend",
+    )
+    def test_out_buffer_invalid(self):
+        cctx = zstd.ZstdCompressor()
+        frames = [cctx.compress(b"foo" * 4), cctx.compress(b"bar" * 4)]
+
+        dctx = zstd.ZstdDecompressor()
+
+        with self.assertRaisesRegex(
+            ValueError, "offsets must be specified with out"
+        ):
+            dctx.multi_decompress_to_buffer(frames, out=bytearray(24))
+
+        with self.assertRaisesRegex(ValueError, "out buffer is not writable"):
+            dctx.multi_decompress_to_buffer(
+                frames, out=b"\x00" * 24, offsets=struct.pack("=QQ", 0, 12)
+            )
+
+        with self.assertRaisesRegex(
+            ValueError, "offsets size mismatch; expected 2, got 1"
+        ):
+            dctx.multi_decompress_to_buffer(
+                frames, out=bytearray(24), offsets=struct.pack("=Q", 0)
+            )
+
+        with self.assertRaisesRegex(
+            ValueError,
+            "item 1 of 12 bytes at offset 16 exceeds out buffer of 24 bytes",
+        ):
+            dctx.multi_decompress_to_buffer(
+                frames, out=bytearray(24), offsets=struct.pack("=QQ", 0, 16)
+            )
+
+        with self.assertRaisesRegex(
+            ValueError, "out regions for items 0 and 1 overlap"
+        ):
+            dctx.multi_decompress_to_buffer(
+                frames, out=bytearray(24), offsets=struct.pack("=QQ", 0, 8)
+            )
+
+        frames = [cctx.compress(b"x" * 128), cctx.compress(b"y" * 128)]
+        frames[1] = frames[1][0:15] + b"extra" + frames[1][15:]
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "error decompressing item 1"
+        ):
+            dctx.multi_decompress_to_buffer(
+                frames, out=bytearray(256), offsets=struct.pack("=QQ", 0, 128)
+            )
@@ -0,0 +1,65 @@
+import io
+import unittest
+
+import zstandard as zstd
+
+
+@unittest.skipUnless(
+    "read_records" in zstd.backend_features,
+    "read_records() only implemented in Rust backend",
+)
+class TestDecompressor_read_records(unittest.TestCase):
+    def test_invalid_record_size(self):
+        dctx = zstd.ZstdDecompressor()
+
+        with self.assertRaisesRegex(ValueError, "record_size must be positive"):
+            dctx.read_records(b"", 0)
+
+    def test_type_validation(self):
+        dctx = zstd.ZstdDecompressor()
+
+        with self.assertRaisesRegex(ValueError, "must pass an object with"):
+            dctx.read_records(True, 8)
+
+    def test_empty_input(self):
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(list(dctx.read_records(b"", 8)), [])
+
+    def test_exact_records(self):
+        source = b"".join(
+            i.to_bytes(4, "little") * 4 for i in range(100000)
+        )
+        frame = zstd.ZstdCompressor().compress(source)
+
+        dctx = zstd.ZstdDecompressor()
+        records = list(dctx.read_records(io.BytesIO(frame), 16, read_size=7))
+
+        self.assertEqual(len(records), 100000)
+        for i, record in enumerate(records):
+            self.assertEqual(record, i.to_bytes(4, "little") * 4)
+
+    def test_short_final_record(self):
+        frame = zstd.ZstdCompressor().compress(b"foobarbaz")
+
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(
+            list(dctx.read_records(frame, 4)), [b"foob", b"arba", b"z"]
+        )
+
+    def test_large_record(self):
+        source = b"foo" * 1048576
+        frame = zstd.ZstdCompressor().compress(source)
+
+        dctx = zstd.ZstdDecompressor()
+        records = list(dctx.read_records(frame, 2097152))
+        self.assertEqual([len(r) for r in records], [2097152, 1048576])
+        self.assertEqual(b"".join(records), source)
+
+    def test_iterable_input(self):
+        frame = zstd.ZstdCompressor().compress(b"a" * 10)
+        chunks = [frame[i : i + 1] for i in range(len(frame))]
+
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(
+            list(dctx.read_records(chunks, 5)), [b"aaaaa", b"aaaaa"]
+        )
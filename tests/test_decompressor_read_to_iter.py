@@ -90,6 +90,65 @@ class TestDecompressor_read_to_iter(unittest.TestCase):
         output = b"".join(dctx.read_to_iter(b"hdr" + compressed, skip_bytes=3))
         self.assertEqual(output, b"foobar")
 
+    @unittest.skipUnless(
+        "auto_write_size" in zstd.backend_features,
+        "write_size auto-tuning only implemented in Rust backend",
+    )
+    def test_auto_write_size(self):
+        source = b"".join(b"foo%dbar" % i for i in range(600000))
+        dctx = zstd.ZstdDecompressor()
+
+        # Output is emitted in window sized chunks.
+        params = zstd.ZstdCompressionParameters(
+            window_log=22, write_content_size=False
+        )
+        frame = zstd.ZstdCompressor(compression_params=params).compress(source)
+        self.assertEqual(zstd.get_frame_parameters(frame).window_size, 2 ** 22)
+
+        chunks = list(dctx.read_to_iter(frame))
+        self.assertEqual(b"".join(chunks), source)
+        self.assertEqual(len(chunks[0]), 2 ** 22)
+        self.assertEqual(len(chunks), 2)
+
+        # Small frames use the default.
+        frame = zstd.ZstdCompressor().compress(b"foobar")
+        self.assertEqual(list(dctx.read_to_iter(frame)), [b"foobar"])
+
+        # An explicit write_size is honored.
+        chunks = list(dctx.read_to_iter(frame, write_size=2))
+        self.assertEqual(chunks, [b"fo", b"ob", b"ar"])
+
+    @unittest.skipUnless(
+        "auto_write_size" in zstd.backend_features,
+        "write_size auto-tuning only implemented in Rust backend",
+    )
+    def test_auto_write_size_clamped(self):
+        dctx = zstd.ZstdDecompressor()
+
+        # Chunks are capped at 8 MiB.
+        source = b"".join(b"foo%dbar" % i for i in range(1500000))
+        params = zstd.ZstdCompressionParameters(
+            window_log=24, write_content_size=False
+        )
+        frame = zstd.ZstdCompressor(compression_params=params).compress(source)
+        self.assertEqual(zstd.get_frame_parameters(frame).window_size, 2 ** 24)
+
+        chunks = list(dctx.read_to_iter(frame))
+        self.assertEqual(b"".join(chunks), source)
+        self.assertEqual(len(chunks[0]), 2 ** 23)
+        self.assertEqual(len(chunks), 3)
+
+        # A window sized to the content is rounded down to a power of 2.
+        source = source[0 : 3 * 2 ** 19]
+        frame = zstd.ZstdCompressor().compress(source)
+        self.assertEqual(
+            zstd.get_frame_parameters(frame).window_size, len(source)
+        )
+
+        chunks = list(dctx.read_to_iter(frame))
+        self.assertEqual(b"".join(chunks), source)
+        self.assertEqual([len(chunk) for chunk in chunks], [2 ** 20, 2 ** 19])
+
     def test_large_output(self):
         source = io.BytesIO()
         source.write(b"f" * zstd.DECOMPRESSION_RECOMMENDED_OUTPUT_SIZE)
@@ -232,3 +291,125 @@ class TestDecompressor_read_to_iter(unittest.TestCase):
         dctx = zstd.ZstdDecompressor(format=zstd.FORMAT_ZSTD1_MAGICLESS)
         res = b"".join(dctx.read_to_iter(frame))
         self.assertEqual(res, b"foobar")
+
+    @unittest.skipUnless(
+        "as_memoryview" in zstd.backend_features,
+        "as_memoryview only implemented in Rust backend",
+    )
+    def test_as_memoryview(self):
+        source = b"foobar" * 16384
+        frame = zstd.ZstdCompressor().compress(source)
+
+        dctx = zstd.ZstdDecompressor()
+
+        chunks = []
+        for chunk in dctx.read_to_iter(
+            frame, write_size=1024, as_memoryview=True
+        ):
+            self.assertIsInstance(chunk, memoryview)
+            self.assertLessEqual(len(chunk), 1024)
+            chunks.append(chunk.tobytes())
+
+        self.assertEqual(b"".join(chunks), source)
+
+    @unittest.skipUnless(
+        "callable_sources" in zstd.backend_features,
+        "callable sources only implemented in Rust backend",
+    )
+    def test_callable_source(self):
+        source = b"foobar" * 4096
+        frame = io.BytesIO(zstd.ZstdCompressor().compress(source))
+
+        dctx = zstd.ZstdDecompressor()
+        chunks = list(dctx.read_to_iter(frame.read, read_size=16))
+        self.assertEqual(b"".join(chunks), source)
+
+        frame.seek(0)
+        with dctx.stream_reader(frame.read, read_size=16) as reader:
+            self.assertEqual(reader.read(), source)
+
+    @unittest.skipUnless(
+        "iterable_sources" in zstd.backend_features,
+        "iterable sources only implemented in Rust backend",
+    )
+    def test_iterable_source(self):
+        source = b"foobar" * 4096
+        frame = zstd.ZstdCompressor().compress(source)
+
+        def parts():
+            for i in range(0, len(frame), 7):
+                yield frame[i : i + 7]
+
+        dctx = zstd.ZstdDecompressor()
+        self.assertEqual(b"".join(dctx.read_to_iter(parts())), source)
+        self.assertEqual(b"".join(dctx.read_to_iter(list(parts()))), source)
+
+        with dctx.stream_reader(parts()) as reader:
+            self.assertEqual(reader.read(), source)
+
+        with self.assertRaises(TypeError):
+            list(dctx.read_to_iter([frame[0:4], None]))
+
+        # Empty parts don't end the stream.
+        parts = iter([frame[0:5], b"", frame[5:10], b"", b"", frame[10:]])
+        self.assertEqual(b"".join(dctx.read_to_iter(parts)), source)
+
+        cctx = zstd.ZstdCompressor()
+        parts = iter([source[0:5], b"", source[5:]])
+        frame = b"".join(cctx.read_to_iter(parts))
+        self.assertEqual(dctx.decompressobj().decompress(frame), source)
+
+    @unittest.skipUnless(
+        "on_error" in zstd.backend_features,
+        "on_error only implemented in Rust backend",
+    )
+    def test_on_error_skip(self):
+        cctx = zstd.ZstdCompressor()
+        first = cctx.compress(b"a" * 1000)
+        damaged = cctx.compress(b"b" * 1000)
+        damaged = damaged[0:15] + b"extra" + damaged[15:]
+        last = cctx.compress(b"c" * 1000)
+        data = first + damaged + last
+
+        dctx = zstd.ZstdDecompressor()
+
+        with self.assertRaisesRegex(zstd.ZstdError, "Corrupted block"):
+            b"".join(dctx.read_to_iter(damaged + last))
+
+        # Magic numbers split across input chunks are found.
+        for read_size in (1, 3, 7, 8192):
+            it = dctx.read_to_iter(data, read_size=read_size, on_error="skip")
+            self.assertEqual(b"".join(it), b"a" * 1000 + b"c" * 1000)
+            self.assertEqual(
+                it.errors, [(len(first), "Corrupted block detected")]
+            )
+
+        it = dctx.read_to_iter(
+            io.BytesIO(b"garbage" + first), read_size=4, on_error="skip"
+        )
+        self.assertEqual(b"".join(it), b"a" * 1000)
+        self.assertEqual(len(it.errors), 1)
+        self.assertEqual(it.errors[0][0], 0)
+
+        it = dctx.read_to_iter(first + last, on_error="skip")
+        self.assertEqual(b"".join(it), b"a" * 1000 + b"c" * 1000)
+        self.assertEqual(it.errors, [])
+
+    @unittest.skipUnless(
+        "on_error" in zstd.backend_features,
+        "on_error only implemented in Rust backend",
+    )
+    def test_on_error_invalid(self):
+        dctx = zstd.ZstdDecompressor()
+
+        with self.assertRaisesRegex(
+            ValueError, 'on_error must be "raise" or "skip"; got ignore'
+        ):
+            dctx.read_to_iter(b"", on_error="ignore")
+
+        dctx = zstd.ZstdDecompressor(format=zstd.FORMAT_ZSTD1_MAGICLESS)
+
+        with self.assertRaisesRegex(
+            ValueError, "cannot be used with FORMAT_ZSTD1_MAGICLESS"
+        ):
+            dctx.read_to_iter(b"", on_error="skip")
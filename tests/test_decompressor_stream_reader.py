@@ -259,6 +259,32 @@ class TestDecompressor_stream_reader(unittest.TestCase):
 
         self.assertTrue(reader.closed)
 
+    @unittest.skipUnless(
+        "fd_streams" in zstd.backend_features,
+        "integer fds only supported in Rust backend",
+    )
+    def test_fd(self):
+        dctx = zstd.ZstdDecompressor()
+
+        rfd, wfd = os.pipe()
+        with os.fdopen(wfd, "wb") as fh:
+            fh.write(zstd.ZstdCompressor().compress(b"foo" * 1024))
+
+        with dctx.stream_reader(rfd) as reader:
+            self.assertEqual(reader.read(), b"foo" * 1024)
+
+        with self.assertRaises(OSError):
+            os.fstat(rfd)
+
+        rfd, wfd = os.pipe()
+        os.close(wfd)
+
+        with dctx.stream_reader(rfd, closefd=False) as reader:
+            self.assertEqual(reader.read(), b"")
+
+        os.fstat(rfd)
+        os.close(rfd)
+
     def test_read_after_exit(self):
         cctx = zstd.ZstdCompressor()
         frame = cctx.compress(b"foo" * 60)
@@ -590,3 +616,80 @@ class TestDecompressor_stream_reader(unittest.TestCase):
 
         self.assertEqual(len(lines), 1024)
         self.assertEqual(b"".join(lines), source)
+
+    @unittest.skipUnless(
+        "require_checksum" in zstd.backend_features,
+        "require_checksum only implemented in Rust backend",
+    )
+    def test_require_checksum(self):
+        cctx = zstd.ZstdCompressor(write_checksum=True)
+        frames = cctx.compress(b"foo") + zstd.ZstdCompressor().compress(b"bar")
+
+        dctx = zstd.ZstdDecompressor(require_checksum=True)
+
+        with dctx.stream_reader(frames, read_across_frames=True) as reader:
+            with self.assertRaisesRegex(
+                zstd.ZstdError, "frame does not have a content checksum"
+            ):
+                reader.read()
+
+        # Header bytes arrive one at a time.
+        with dctx.stream_reader(
+            io.BytesIO(frames), read_size=1, read_across_frames=True
+        ) as reader:
+            with self.assertRaisesRegex(
+                zstd.ZstdError, "frame does not have a content checksum"
+            ):
+                reader.read()
+
+        # The reader can relax the decompressor's policy.
+        with dctx.stream_reader(
+            frames, read_across_frames=True, require_checksum=False
+        ) as reader:
+            self.assertEqual(reader.read(), b"foobar")
+
+        dctx = zstd.ZstdDecompressor()
+        with dctx.stream_reader(
+            frames, read_across_frames=True, require_checksum=True
+        ) as reader:
+            with self.assertRaisesRegex(
+                zstd.ZstdError, "frame does not have a content checksum"
+            ):
+                reader.read()
+
+    @unittest.skipUnless(
+        "max_decompress_per_call" in zstd.backend_features,
+        "max_decompress_per_call only implemented in Rust backend",
+    )
+    def test_max_decompress_per_call(self):
+        source = b"".join(b"foo%d" % i for i in range(65536))
+        frame = zstd.ZstdCompressor().compress(source)
+
+        dctx = zstd.ZstdDecompressor()
+
+        chunks = []
+        with dctx.stream_reader(frame, max_decompress_per_call=1000) as reader:
+            while True:
+                chunk = reader.read(65536)
+                if not chunk:
+                    break
+
+                self.assertLessEqual(len(chunk), 1000)
+                chunks.append(chunk)
+
+        self.assertEqual(b"".join(chunks), source)
+
+        with dctx.stream_reader(frame, max_decompress_per_call=1000) as reader:
+            b = bytearray(65536)
+            self.assertEqual(reader.readinto(b), 1000)
+            self.assertEqual(reader.readinto1(b), 1000)
+            self.assertEqual(len(reader.read1(65536)), 1000)
+            self.assertEqual(reader.tell(), 3000)
+
+            # read() without a size isn't limited.
+            self.assertEqual(reader.read(), source[3000:])
+
+        with self.assertRaisesRegex(
+            ValueError, "max_decompress_per_call must be positive"
+        ):
+            dctx.stream_reader(frame, max_decompress_per_call=0)
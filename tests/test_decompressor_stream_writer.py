@@ -199,6 +199,32 @@ class TestDecompressor_stream_writer(unittest.TestCase):
         self.assertFalse(buffer.closed)
         self.assertEqual(buffer._flush_count, 0)
 
+    @unittest.skipUnless(
+        "fd_streams" in zstd.backend_features,
+        "integer fds only supported in Rust backend",
+    )
+    def test_fd(self):
+        dctx = zstd.ZstdDecompressor()
+        frame = zstd.ZstdCompressor().compress(b"foo" * 1024)
+
+        rfd, wfd = os.pipe()
+        with dctx.stream_writer(wfd) as decompressor:
+            decompressor.write(frame)
+
+        with self.assertRaises(OSError):
+            os.fstat(wfd)
+
+        with os.fdopen(rfd, "rb") as fh:
+            self.assertEqual(fh.read(), b"foo" * 1024)
+
+        rfd, wfd = os.pipe()
+        with dctx.stream_writer(wfd, closefd=False) as decompressor:
+            decompressor.write(frame)
+
+        os.close(wfd)
+        with os.fdopen(rfd, "rb") as fh:
+            self.assertEqual(fh.read(), b"foo" * 1024)
+
     def test_flush(self):
         buffer = CustomBytesIO()
         dctx = zstd.ZstdDecompressor()
@@ -363,3 +389,102 @@ class TestDecompressor_stream_writer(unittest.TestCase):
 
         with self.assertRaisesRegex(IOError, "write"):
             writer.write(frame)
+
+    @unittest.skipUnless(
+        "direct_fd" in zstd.backend_features,
+        "direct_fd only implemented in Rust backend",
+    )
+    def test_direct_fd(self):
+        source = b"".join(b"foo%dbar" % i for i in range(100000))
+        frame = zstd.ZstdCompressor().compress(source)
+
+        with tempfile.TemporaryFile("w+b") as tf:
+            # Buffered data is flushed before decompressed data is written.
+            tf.write(b"header")
+
+            dctx = zstd.ZstdDecompressor()
+            with dctx.stream_writer(
+                tf, write_size=8192, direct_fd=True, closefd=False
+            ) as writer:
+                for i in range(0, len(frame), 4096):
+                    writer.write(frame[i : i + 4096])
+
+            tf.seek(0)
+            self.assertEqual(tf.read(), b"header" + source)
+
+    @unittest.skipUnless(
+        "direct_fd" in zstd.backend_features,
+        "direct_fd only implemented in Rust backend",
+    )
+    def test_direct_fd_no_fileno(self):
+        dctx = zstd.ZstdDecompressor()
+
+        with self.assertRaisesRegex(
+            ValueError, "direct_fd requires a writer with a file descriptor"
+        ):
+            dctx.stream_writer(io.BytesIO(), direct_fd=True)
+
+    @unittest.skipUnless(
+        "decompression_writer_overrides" in zstd.backend_features,
+        "per-writer settings only implemented in Rust backend",
+    )
+    def test_format_override(self):
+        params = zstd.ZstdCompressionParameters.from_level(
+            1, format=zstd.FORMAT_ZSTD1_MAGICLESS
+        )
+        magicless = zstd.ZstdCompressor(compression_params=params).compress(
+            b"foobar"
+        )
+        frame = zstd.ZstdCompressor().compress(b"foobar")
+
+        dctx = zstd.ZstdDecompressor()
+
+        dest = io.BytesIO()
+        with dctx.stream_writer(
+            dest, format=zstd.FORMAT_ZSTD1_MAGICLESS, closefd=False
+        ) as writer:
+            writer.write(magicless)
+        self.assertEqual(dest.getvalue(), b"foobar")
+
+        # The override doesn't leak into later operations.
+        self.assertEqual(dctx.decompress(frame), b"foobar")
+
+        dest = io.BytesIO()
+        with dctx.stream_writer(dest, closefd=False) as writer:
+            writer.write(frame)
+        self.assertEqual(dest.getvalue(), b"foobar")
+
+        with self.assertRaisesRegex(ValueError, "invalid format value"):
+            dctx.stream_writer(io.BytesIO(), format=42)
+
+    @unittest.skipUnless(
+        "decompression_writer_overrides" in zstd.backend_features,
+        "per-writer settings only implemented in Rust backend",
+    )
+    def test_max_window_size_override(self):
+        source = b"".join(b"foo%dbar" % i for i in range(10000))
+        frame = zstd.ZstdCompressor(write_content_size=False).compress(source)
+
+        dctx = zstd.ZstdDecompressor()
+
+        writer = dctx.stream_writer(
+            io.BytesIO(), max_window_size=2 ** zstd.WINDOWLOG_MIN
+        )
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "Frame requires too much memory"
+        ):
+            writer.write(frame)
+
+        # The limit doesn't leak into later operations.
+        self.assertEqual(
+            dctx.decompress(frame, max_output_size=len(source)), source
+        )
+
+        # And a decompressor's own limit can be relaxed per writer.
+        dctx = zstd.ZstdDecompressor(max_window_size=2 ** zstd.WINDOWLOG_MIN)
+        dest = io.BytesIO()
+        with dctx.stream_writer(
+            dest, max_window_size=2 ** 20, closefd=False
+        ) as writer:
+            writer.write(frame)
+        self.assertEqual(dest.getvalue(), source)
@@ -0,0 +1,116 @@
+import unittest
+
+import zstandard as zstd
+
+
+@unittest.skipUnless(
+    "streaming_decoder" in zstd.backend_features,
+    "streaming_decoder() only implemented in Rust backend",
+)
+class TestDecompressor_streaming_decoder(unittest.TestCase):
+    def test_simple(self):
+        frame = zstd.ZstdCompressor(write_checksum=True).compress(b"foobar")
+
+        decoder = zstd.ZstdDecompressor().streaming_decoder()
+        self.assertIsInstance(decoder, zstd.StreamingDecoder)
+
+        data, events = decoder.feed(frame)
+        self.assertEqual(data, b"foobar")
+        self.assertEqual(events, [("frame_complete", True)])
+        self.assertEqual(decoder.frames_completed, 1)
+        self.assertFalse(decoder.in_frame)
+        decoder.finish()
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "cannot call feed\\(\\) after finish\\(\\)"
+        ):
+            decoder.feed(frame)
+
+    def test_byte_at_a_time(self):
+        source = b"foobar" * 1024
+        cctx = zstd.ZstdCompressor()
+        stream = cctx.compress(source) + cctx.compress(source)
+
+        decoder = zstd.ZstdDecompressor().streaming_decoder(write_size=64)
+
+        chunks = []
+        all_events = []
+        for i in range(len(stream)):
+            data, events = decoder.feed(stream[i : i + 1])
+            chunks.append(data)
+            all_events.extend(events)
+
+        self.assertEqual(b"".join(chunks), source * 2)
+        self.assertEqual(
+            all_events, [("frame_complete", False), ("frame_complete", False)]
+        )
+        decoder.finish()
+
+    def test_skippable_frame(self):
+        frame = zstd.ZstdCompressor().compress(b"foo")
+        skippable = b"\x50\x2a\x4d\x18\x03\x00\x00\x00bar"
+
+        decoder = zstd.ZstdDecompressor().streaming_decoder()
+        data, events = decoder.feed(skippable + frame)
+
+        self.assertEqual(data, b"foo")
+        self.assertEqual(
+            events, [("skippable_frame", None), ("frame_complete", False)]
+        )
+        self.assertEqual(decoder.frames_completed, 1)
+
+    def test_single_frame(self):
+        frame = zstd.ZstdCompressor().compress(b"foo")
+
+        decoder = zstd.ZstdDecompressor().streaming_decoder(
+            multiple_frames=False
+        )
+        data, events = decoder.feed(frame + b"trailing")
+
+        self.assertEqual(data, b"foo")
+        self.assertEqual(
+            events, [("frame_complete", False), ("unused_data", 8)]
+        )
+        self.assertTrue(decoder.eof)
+        self.assertEqual(decoder.unused_data, b"trailing")
+
+        self.assertEqual(decoder.feed(b"more"), (b"", [("unused_data", 4)]))
+        self.assertEqual(decoder.unused_data, b"trailingmore")
+
+    def test_truncated(self):
+        frame = zstd.ZstdCompressor().compress(b"foobar" * 1024)
+
+        decoder = zstd.ZstdDecompressor().streaming_decoder()
+        decoder.feed(frame[0:-4])
+        self.assertTrue(decoder.in_frame)
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "input ended in the middle of a frame"
+        ):
+            decoder.finish()
+
+    def test_independent_contexts(self):
+        cctx = zstd.ZstdCompressor()
+        a = cctx.compress(b"foo" * 1024)
+        b = cctx.compress(b"bar" * 1024)
+
+        dctx = zstd.ZstdDecompressor()
+        decoder_a = dctx.streaming_decoder()
+        decoder_b = dctx.streaming_decoder()
+
+        out_a, _ = decoder_a.feed(a[0:10])
+        out_b, _ = decoder_b.feed(b[0:10])
+        out_a += decoder_a.feed(a[10:])[0]
+        out_b += decoder_b.feed(b[10:])[0]
+
+        self.assertEqual(out_a, b"foo" * 1024)
+        self.assertEqual(out_b, b"bar" * 1024)
+
+    def test_dictionary(self):
+        samples = [b"foo" * 64, b"bar" * 64, b"foobar" * 64] * 32
+        d = zstd.train_dictionary(8192, samples)
+
+        frame = zstd.ZstdCompressor(dict_data=d).compress(b"foobar" * 64)
+
+        decoder = zstd.ZstdDecompressor(dict_data=d).streaming_decoder()
+        self.assertEqual(decoder.feed(frame)[0], b"foobar" * 64)
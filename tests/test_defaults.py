@@ -0,0 +1,83 @@
+import json
+import os
+import subprocess
+import sys
+import unittest
+
+import zstandard as zstd
+
+
+def config_params(cctx):
+    return json.loads(cctx.to_config())["params"]
+
+
+@unittest.skipUnless(
+    "defaults" in zstd.backend_features,
+    "set_defaults() only implemented in Rust backend",
+)
+class TestDefaults(unittest.TestCase):
+    def setUp(self):
+        self.orig = zstd.get_defaults()
+
+    def tearDown(self):
+        zstd.set_defaults(**self.orig)
+
+    def test_set_defaults(self):
+        zstd.set_defaults(level=3, threads=0)
+
+        self.assertEqual(
+            zstd.set_defaults(level=9), {"level": 9, "threads": 0}
+        )
+        self.assertEqual(
+            zstd.set_defaults(threads=2), {"level": 9, "threads": 2}
+        )
+        self.assertEqual(zstd.get_defaults(), {"level": 9, "threads": 2})
+
+        params = config_params(zstd.ZstdCompressor())
+        self.assertEqual(params["compression_level"], 9)
+        self.assertEqual(params["threads"], 2)
+
+        # Explicit arguments take precedence.
+        params = config_params(zstd.ZstdCompressor(level=1, threads=0))
+        self.assertEqual(params["compression_level"], 1)
+        self.assertEqual(params["threads"], 0)
+
+        # Default threads don't conflict with compression_params.
+        cctx = zstd.ZstdCompressor(
+            compression_params=zstd.ZstdCompressionParameters(
+                compression_level=5
+            )
+        )
+        params = config_params(cctx)
+        self.assertEqual(params["compression_level"], 5)
+        self.assertEqual(params["threads"], 0)
+
+    def test_invalid_level(self):
+        with self.assertRaisesRegex(ValueError, "level must be less than 23"):
+            zstd.set_defaults(level=23)
+
+        self.assertEqual(zstd.get_defaults(), self.orig)
+
+    def test_environment(self):
+        env = dict(os.environ)
+        env["ZSTANDARD_DEFAULT_LEVEL"] = "7"
+        env["ZSTANDARD_THREADS"] = "2"
+
+        code = (
+            "import zstandard; "
+            "print(sorted(zstandard.get_defaults().items()))"
+        )
+        output = subprocess.check_output([sys.executable, "-c", code], env=env)
+        self.assertEqual(output.strip(), b"[('level', 7), ('threads', 2)]")
+
+        env["ZSTANDARD_DEFAULT_LEVEL"] = "fast"
+        res = subprocess.run(
+            [sys.executable, "-c", code],
+            env=env,
+            stdout=subprocess.PIPE,
+            stderr=subprocess.PIPE,
+        )
+        self.assertNotEqual(res.returncode, 0)
+        self.assertIn(
+            b"ZSTANDARD_DEFAULT_LEVEL must be an integer", res.stderr
+        )
@@ -0,0 +1,119 @@
+import io
+import unittest
+
+import zstandard as zstd
+
+
+def make_dict(dict_id):
+    samples = []
+    for i in range(128):
+        samples.append(b"foo" * 64)
+        samples.append(b"bar" * 64)
+        samples.append(b"foobar" * 64)
+        samples.append(b"qwert" * 64)
+        samples.append(b"yuiop" * 64)
+
+    return zstd.train_dictionary(8192, samples, dict_id=dict_id)
+
+
+@unittest.skipUnless(
+    "dict_registry" in zstd.backend_features,
+    "dict_registry only implemented in Rust backend",
+)
+class TestDictRegistry(unittest.TestCase):
+    def setUp(self):
+        zstd.dict_registry.clear()
+
+    def tearDown(self):
+        zstd.dict_registry.clear()
+
+    def test_register(self):
+        d = make_dict(42)
+
+        self.assertEqual(len(zstd.dict_registry), 0)
+        self.assertEqual(zstd.dict_registry.register(d), 42)
+        self.assertEqual(len(zstd.dict_registry), 1)
+        self.assertIn(42, zstd.dict_registry)
+        self.assertNotIn(43, zstd.dict_registry)
+        self.assertIs(zstd.dict_registry.get(42), d)
+        self.assertIsNone(zstd.dict_registry.get(43))
+
+        zstd.dict_registry.register(make_dict(7))
+        self.assertEqual(zstd.dict_registry.dict_ids(), [7, 42])
+
+        self.assertTrue(zstd.dict_registry.unregister(42))
+        self.assertFalse(zstd.dict_registry.unregister(42))
+        self.assertEqual(zstd.dict_registry.dict_ids(), [7])
+
+    def test_register_no_dict_id(self):
+        d = zstd.ZstdCompressionDict(b"foobar" * 64)
+
+        with self.assertRaisesRegex(
+            ValueError, "dictionary does not have a dictionary ID"
+        ):
+            zstd.dict_registry.register(d)
+
+    def test_dict_data_conflict(self):
+        with self.assertRaisesRegex(
+            ValueError, "cannot specify both dict_data and use_dict_registry"
+        ):
+            zstd.ZstdDecompressor(
+                dict_data=make_dict(1), use_dict_registry=True
+            )
+
+    def test_decompress(self):
+        d1 = make_dict(1)
+        d2 = make_dict(2)
+        zstd.dict_registry.register(d1)
+        zstd.dict_registry.register(d2)
+
+        orig = b"foobar" * 1024
+        frame1 = zstd.ZstdCompressor(dict_data=d1).compress(orig)
+        frame2 = zstd.ZstdCompressor(dict_data=d2).compress(orig)
+        plain = zstd.ZstdCompressor().compress(orig)
+
+        dctx = zstd.ZstdDecompressor(use_dict_registry=True)
+        self.assertEqual(dctx.decompress(frame1), orig)
+        self.assertEqual(dctx.decompress(frame2), orig)
+        self.assertEqual(dctx.decompress(plain), orig)
+
+        dobj = dctx.decompressobj()
+        self.assertEqual(dobj.decompress(frame2), orig)
+
+    def test_stream_reader_multiple_frames(self):
+        d1 = make_dict(1)
+        d2 = make_dict(2)
+        zstd.dict_registry.register(d1)
+        zstd.dict_registry.register(d2)
+
+        frames = b"".join(
+            [
+                zstd.ZstdCompressor(dict_data=d1).compress(b"foo" * 512),
+                zstd.ZstdCompressor().compress(b"bar" * 512),
+                zstd.ZstdCompressor(dict_data=d2).compress(b"qwert" * 512),
+            ]
+        )
+
+        dctx = zstd.ZstdDecompressor(use_dict_registry=True)
+        with dctx.stream_reader(
+            io.BytesIO(frames), read_size=7, read_across_frames=True
+        ) as reader:
+            self.assertEqual(
+                reader.read(), b"foo" * 512 + b"bar" * 512 + b"qwert" * 512
+            )
+
+    def test_unregistered_dict_id(self):
+        d = make_dict(9)
+        frame = zstd.ZstdCompressor(dict_data=d).compress(b"foobar" * 64)
+
+        dctx = zstd.ZstdDecompressor(use_dict_registry=True)
+        with self.assertRaisesRegex(
+            zstd.ZstdError,
+            "no registered dictionary matches the frame's dictionary ID",
+        ):
+            dctx.decompress(frame)
+
+    def test_config(self):
+        config = zstd.ZstdDecompressor(use_dict_registry=True).to_config()
+        dctx = zstd.ZstdDecompressor.from_config(config)
+        self.assertEqual(dctx.to_config(), config)
@@ -26,7 +26,30 @@ class TestModuleAttributes(unittest.TestCase):
             },
         }[zstd.backend]
 
-        self.assertEqual(zstd.backend_features, expected)
+        if zstd.backend == "rust":
+            # Also lists the APIs and arguments only the Rust backend has.
+            self.assertLess(expected, zstd.backend_features)
+            self.assertIn("output_size_hint", zstd.backend_features)
+        else:
+            self.assertEqual(zstd.backend_features, expected)
+
+    def test_require(self):
+        for feature in zstd.backend_features:
+            zstd.require(feature)
+
+        # Attributes aren't features, as their arguments may differ.
+        with self.assertRaisesRegex(
+            zstd.ZstdNotSupportedError,
+            "ZstdCompressor.compress is not supported by the %s backend"
+            % zstd.backend,
+        ):
+            zstd.require("ZstdCompressor.compress")
+
+        with self.assertRaises(NotImplementedError):
+            zstd.require("missing")
+
+        with self.assertRaises(zstd.ZstdError):
+            zstd.require("")
 
     def test_constants(self):
         self.assertEqual(zstd.MAX_COMPRESSION_LEVEL, 22)
@@ -0,0 +1,249 @@
+import os
+import pathlib
+import tempfile
+import unittest
+
+import zstandard as zstd
+
+
+def write_file(path, data):
+    with open(path, "wb") as fh:
+        fh.write(data)
+
+
+def read_file(path):
+    with open(path, "rb") as fh:
+        return fh.read()
+
+
+@unittest.skipUnless(
+    "paths" in zstd.backend_features,
+    "compress_paths() only implemented in Rust backend",
+)
+class TestCompressPaths(unittest.TestCase):
+    def test_empty(self):
+        with tempfile.TemporaryDirectory() as td:
+            dest = os.path.join(td, "out")
+            self.assertEqual(zstd.compress_paths([], dest), [])
+            self.assertFalse(os.path.exists(dest))
+
+    def test_basic(self):
+        with tempfile.TemporaryDirectory() as td:
+            sources = {
+                "empty": b"",
+                "foo": b"foo" * 1024,
+                "random": os.urandom(1048576),
+            }
+
+            paths = []
+            for name, data in sources.items():
+                path = os.path.join(td, name)
+                write_file(path, data)
+                paths.append(path)
+
+            dest = os.path.join(td, "out")
+            results = zstd.compress_paths(paths, dest, level=1, threads=2)
+
+            self.assertEqual(len(results), 3)
+            dctx = zstd.ZstdDecompressor()
+
+            for path, result in zip(paths, results):
+                data = sources[os.path.basename(path)]
+
+                self.assertIsNone(result.error)
+                self.assertEqual(result.source, path)
+                self.assertEqual(
+                    result.dest,
+                    os.path.join(dest, os.path.basename(path) + ".zst"),
+                )
+                self.assertEqual(result.bytes_read, len(data))
+
+                frame = read_file(result.dest)
+                self.assertEqual(result.bytes_written, len(frame))
+                self.assertEqual(zstd.frame_content_size(frame), len(data))
+                self.assertEqual(dctx.decompress(frame), data)
+
+    def test_pathlike(self):
+        with tempfile.TemporaryDirectory() as td:
+            source = pathlib.Path(td) / "source"
+            write_file(source, b"foobar")
+
+            results = zstd.compress_paths([source], pathlib.Path(td))
+            self.assertIsNone(results[0].error)
+            self.assertEqual(results[0].dest, str(source) + ".zst")
+
+    def test_errors(self):
+        with tempfile.TemporaryDirectory() as td:
+            good = os.path.join(td, "good")
+            write_file(good, b"foobar")
+
+            results = zstd.compress_paths(
+                [os.path.join(td, "missing"), good], td, threads=-1
+            )
+
+            self.assertRegex(results[0].error, "error opening .*missing")
+            self.assertEqual(results[0].bytes_read, 0)
+            self.assertFalse(os.path.exists(os.path.join(td, "missing.zst")))
+
+            self.assertIsNone(results[1].error)
+            self.assertTrue(os.path.exists(good + ".zst"))
+
+    def test_invalid_arguments(self):
+        with tempfile.TemporaryDirectory() as td:
+            with self.assertRaisesRegex(ValueError, "level must be less than"):
+                zstd.compress_paths([], td, level=23)
+
+            with self.assertRaisesRegex(
+                ValueError, "multiple paths would be written to"
+            ):
+                zstd.compress_paths(
+                    [os.path.join(td, "a", "x"), os.path.join(td, "b", "x")],
+                    td,
+                )
+
+            with self.assertRaises(TypeError):
+                zstd.compress_paths([42], td)
+
+
+@unittest.skipUnless(
+    "paths" in zstd.backend_features,
+    "decompress_paths() only implemented in Rust backend",
+)
+class TestDecompressPaths(unittest.TestCase):
+    def test_empty(self):
+        with tempfile.TemporaryDirectory() as td:
+            dest = os.path.join(td, "out")
+            self.assertEqual(zstd.decompress_paths([], dest), [])
+            self.assertFalse(os.path.exists(dest))
+
+    def test_roundtrip(self):
+        with tempfile.TemporaryDirectory() as td:
+            sources = {
+                "empty": b"",
+                "foo": b"foo" * 1024,
+                "random": os.urandom(1048576),
+            }
+
+            paths = []
+            for name, data in sources.items():
+                path = os.path.join(td, name)
+                write_file(path, data)
+                paths.append(path)
+
+            compressed = [
+                r.dest for r in zstd.compress_paths(paths, td, threads=2)
+            ]
+
+            dest = os.path.join(td, "out")
+            results = zstd.decompress_paths(compressed, dest, threads=2)
+
+            self.assertEqual(len(results), 3)
+            for path, result in zip(compressed, results):
+                name = os.path.basename(path)[: -len(".zst")]
+
+                self.assertIsNone(result.error)
+                self.assertEqual(result.source, path)
+                self.assertEqual(result.dest, os.path.join(dest, name))
+                self.assertEqual(result.bytes_read, os.path.getsize(path))
+                self.assertEqual(result.bytes_written, len(sources[name]))
+                self.assertEqual(read_file(result.dest), sources[name])
+
+    def test_multiple_frames(self):
+        with tempfile.TemporaryDirectory() as td:
+            cctx = zstd.ZstdCompressor()
+            source = os.path.join(td, "data.zst")
+            write_file(source, cctx.compress(b"foo") + cctx.compress(b"bar"))
+
+            dest = os.path.join(td, "out")
+            results = zstd.decompress_paths([source], dest)
+            self.assertIsNone(results[0].error)
+            self.assertEqual(read_file(os.path.join(dest, "data")), b"foobar")
+
+    def test_errors(self):
+        with tempfile.TemporaryDirectory() as td:
+            frame = zstd.ZstdCompressor().compress(b"foobar" * 1024)
+
+            truncated = os.path.join(td, "truncated.zst")
+            write_file(truncated, frame[:-4])
+            invalid = os.path.join(td, "invalid.zst")
+            write_file(invalid, b"foobar")
+            good = os.path.join(td, "good.zst")
+            write_file(good, frame)
+
+            dest = os.path.join(td, "out")
+            results = zstd.decompress_paths(
+                [truncated, invalid, good], dest, threads=-1
+            )
+
+            self.assertEqual(
+                results[0].error, "input ends with an incomplete frame"
+            )
+            self.assertFalse(os.path.exists(results[0].dest))
+            self.assertRegex(results[1].error, "zstd decompress error: ")
+            self.assertFalse(os.path.exists(results[1].dest))
+            self.assertIsNone(results[2].error)
+            self.assertEqual(read_file(results[2].dest), b"foobar" * 1024)
+
+    def test_safety_limits(self):
+        with tempfile.TemporaryDirectory() as td:
+            source = os.path.join(td, "zeros.zst")
+            write_file(source, zstd.ZstdCompressor().compress(bytes(1048576)))
+            dest = os.path.join(td, "out")
+
+            results = zstd.decompress_paths(
+                [source], dest, safety_limits={"max_output_size": 1000}
+            )
+            self.assertEqual(
+                results[0].error,
+                "decompressed size exceeds safety limit of 1000 bytes",
+            )
+            self.assertFalse(os.path.exists(results[0].dest))
+
+            size = os.path.getsize(source)
+            results = zstd.decompress_paths(
+                [source], dest, safety_limits={"max_ratio": 10}
+            )
+            self.assertEqual(
+                results[0].error,
+                "decompressed size exceeds safety limit of %d bytes"
+                % (size * 10),
+            )
+
+            results = zstd.decompress_paths(
+                [source],
+                dest,
+                safety_limits={"max_output_size": 1048576, "max_ratio": 1e6},
+            )
+            self.assertIsNone(results[0].error)
+
+            frame = zstd.ZstdCompressor(level=19).compress(
+                os.urandom(1048576) * 2
+            )
+            large_window = os.path.join(td, "window.zst")
+            write_file(large_window, frame)
+            results = zstd.decompress_paths(
+                [large_window], dest, safety_limits={"max_window_size": 1024}
+            )
+            self.assertRegex(results[0].error, "zstd decompress error: ")
+
+    def test_invalid_arguments(self):
+        with tempfile.TemporaryDirectory() as td:
+            with self.assertRaisesRegex(
+                ValueError, "path does not have a .zst extension: "
+            ):
+                zstd.decompress_paths([os.path.join(td, "foo")], td)
+
+            with self.assertRaisesRegex(
+                ValueError, "path does not have a .zst extension: "
+            ):
+                zstd.decompress_paths([os.path.join(td, ".zst")], td)
+
+            with self.assertRaisesRegex(
+                ValueError, "unknown safety limit: max_size"
+            ):
+                zstd.decompress_paths([], td, safety_limits={"max_size": 1})
+
+            with self.assertRaisesRegex(
+                ValueError, "max_ratio must be positive"
+            ):
+                zstd.decompress_paths([], td, safety_limits={"max_ratio": 0})
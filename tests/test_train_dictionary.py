@@ -1,9 +1,12 @@
+import mmap
 import struct
+import tempfile
 import unittest
 
 import zstandard as zstd
 
 from .common import (
+    captured_stderr,
     generate_samples,
     get_optimal_dict_size_heuristically,
     random_input_data,
@@ -22,6 +25,9 @@ class TestTrainDictionary(unittest.TestCase):
         with self.assertRaises(ValueError):
             zstd.train_dictionary(8192, [u"foo"])
 
+        with self.assertRaises(TypeError):
+            zstd.train_dictionary(8192, [b"foo"], bogus=True)
+
     def test_no_params(self):
         d = zstd.train_dictionary(8192, random_input_data())
         self.assertIsInstance(d.dict_id(), int)
@@ -32,6 +38,166 @@ class TestTrainDictionary(unittest.TestCase):
         data = d.as_bytes()
         self.assertEqual(data[0:8], expected)
 
+    @unittest.skipUnless(
+        "train_buffer_samples" in zstd.backend_features,
+        "buffer samples only implemented in Rust backend",
+    )
+    def test_buffer_with_segments_collection(self):
+        samples = generate_samples()
+        half = len(samples) // 2
+
+        def make_buffer(data, fh=None):
+            offsets = []
+            offset = 0
+            for sample in data:
+                offsets.extend([offset, len(sample)])
+                offset += len(sample)
+
+            raw = b"".join(data)
+
+            if fh:
+                fh.write(raw)
+                fh.flush()
+                raw = mmap.mmap(fh.fileno(), 0, access=mmap.ACCESS_READ)
+
+            return zstd.BufferWithSegments(
+                raw, struct.pack("=%dQ" % len(offsets), *offsets)
+            )
+
+        with tempfile.TemporaryFile() as fh:
+            collection = zstd.BufferWithSegmentsCollection(
+                make_buffer(samples[0:half]), make_buffer(samples[half:], fh)
+            )
+
+            d = zstd.train_dictionary(
+                get_optimal_dict_size_heuristically(samples),
+                collection,
+                k=64,
+                d=8,
+            )
+
+            # Only the chosen samples are copied out of the buffers.
+            sampled = zstd.train_dictionary(
+                get_optimal_dict_size_heuristically(samples),
+                collection,
+                k=64,
+                d=8,
+                sample_count=256,
+            )
+
+            # A quarter of the corpus is chosen and copied.
+            bounded = zstd.train_dictionary(
+                get_optimal_dict_size_heuristically(samples),
+                collection,
+                k=64,
+                d=8,
+                max_samples_size=len(b"".join(samples)) // 4,
+            )
+
+        reference = zstd.train_dictionary(
+            get_optimal_dict_size_heuristically(samples), samples, k=64, d=8
+        )
+
+        self.assertEqual(d.as_bytes(), reference.as_bytes())
+        self.assertEqual(
+            sampled.as_bytes(),
+            zstd.train_dictionary(
+                get_optimal_dict_size_heuristically(samples),
+                samples,
+                k=64,
+                d=8,
+                sample_count=256,
+            ).as_bytes(),
+        )
+        self.assertNotEqual(bounded.as_bytes(), d.as_bytes())
+        self.assertEqual(
+            bounded.as_bytes(),
+            zstd.train_dictionary(
+                get_optimal_dict_size_heuristically(samples),
+                samples,
+                k=64,
+                d=8,
+                max_samples_size=len(b"".join(samples)) // 4,
+            ).as_bytes(),
+        )
+
+    @unittest.skipUnless(
+        "train_sample_sizes" in zstd.backend_features,
+        "sample_sizes only implemented in Rust backend",
+    )
+    def test_sample_sizes(self):
+        samples = generate_samples()
+        dict_size = get_optimal_dict_size_heuristically(samples)
+        sizes = [len(sample) for sample in samples]
+        data = b"".join(samples)
+
+        reference = zstd.train_dictionary(dict_size, samples, k=64, d=8)
+
+        for source in (data, bytearray(data), memoryview(data)):
+            d = zstd.train_dictionary(
+                dict_size, source, k=64, d=8, sample_sizes=sizes
+            )
+            self.assertEqual(d.as_bytes(), reference.as_bytes())
+
+        self.assertEqual(
+            zstd.train_dictionary(
+                dict_size,
+                data,
+                k=64,
+                d=8,
+                sample_sizes=sizes,
+                sample_count=256,
+            ).as_bytes(),
+            zstd.train_dictionary(
+                dict_size, samples, k=64, d=8, sample_count=256
+            ).as_bytes(),
+        )
+
+        with self.assertRaisesRegex(
+            ValueError,
+            r"sample_sizes must add up to the size of samples \(%d bytes\)"
+            % len(data),
+        ):
+            zstd.train_dictionary(dict_size, data, sample_sizes=sizes[:-1])
+
+        with self.assertRaises(TypeError):
+            zstd.train_dictionary(dict_size, samples, sample_sizes=sizes)
+
+    @unittest.skipUnless(
+        "train_shrink_dict" in zstd.backend_features,
+        "shrink_dict only implemented in Rust backend",
+    )
+    def test_shrink_dict(self):
+        samples = generate_samples()
+        dict_size = get_optimal_dict_size_heuristically(samples)
+
+        for algorithm in ("fastcover", "cover"):
+            full = zstd.train_dictionary(
+                dict_size, samples, k=64, d=8, algorithm=algorithm
+            )
+            shrunk = zstd.train_dictionary(
+                dict_size,
+                samples,
+                k=64,
+                d=8,
+                algorithm=algorithm,
+                shrink_dict=True,
+                shrink_dict_max_regression=5,
+            )
+            self.assertLessEqual(len(shrunk.as_bytes()), len(full.as_bytes()))
+
+            cctx = zstd.ZstdCompressor(dict_data=shrunk)
+            dctx = zstd.ZstdDecompressor(dict_data=shrunk)
+            for sample in samples:
+                self.assertEqual(dctx.decompress(cctx.compress(sample)), sample)
+
+        with self.assertRaisesRegex(
+            ValueError, "shrink_dict_max_regression requires shrink_dict"
+        ):
+            zstd.train_dictionary(
+                dict_size, samples, shrink_dict_max_regression=5
+            )
+
     def test_basic(self):
         d = zstd.train_dictionary(8192, generate_samples(), k=500, d=8)
         self.assertIsInstance(d.dict_id(), int)
@@ -55,19 +221,132 @@ class TestTrainDictionary(unittest.TestCase):
 
     def test_optimize(self):
         samples = generate_samples()
-        d = zstd.train_dictionary(
-            get_optimal_dict_size_heuristically(samples),
-            samples,
-            threads=-1,
-            steps=1,
-            d=6,
-            notifications=2,
-        )
+        with captured_stderr():
+            d = zstd.train_dictionary(
+                get_optimal_dict_size_heuristically(samples),
+                samples,
+                threads=-1,
+                steps=1,
+                d=6,
+                notifications=2,
+            )
 
         # This varies by platform.
         self.assertIn(d.k, (50, 2000))
         self.assertEqual(d.d, 6)
 
+    @unittest.skipUnless(
+        "train_algorithm" in zstd.backend_features,
+        "algorithm only implemented in Rust backend",
+    )
+    def test_algorithm(self):
+        samples = generate_samples()
+        dict_size = get_optimal_dict_size_heuristically(samples)
+
+        fastcover = zstd.train_dictionary(dict_size, samples, k=64, d=8)
+        self.assertEqual(
+            zstd.train_dictionary(
+                dict_size, samples, k=64, d=8, algorithm="fastcover"
+            ).as_bytes(),
+            fastcover.as_bytes(),
+        )
+
+        cover = zstd.train_dictionary(
+            dict_size, samples, k=64, d=8, algorithm="cover"
+        )
+        self.assertEqual(cover.as_bytes()[0:4], b"\x37\xa4\x30\xec")
+        self.assertEqual((cover.k, cover.d), (64, 8))
+        self.assertNotEqual(cover.as_bytes(), fastcover.as_bytes())
+
+        source = b"".join(samples[0:64])
+        frame = zstd.ZstdCompressor(dict_data=cover).compress(source)
+        self.assertEqual(
+            zstd.ZstdDecompressor(dict_data=cover).decompress(frame), source
+        )
+
+        # The optimizer picks parameters when they aren't given.
+        cover = zstd.train_dictionary(
+            dict_size, samples, d=6, steps=1, algorithm="cover"
+        )
+        self.assertGreater(cover.k, 0)
+        self.assertEqual(cover.d, 6)
+
+        with self.assertRaisesRegex(
+            ValueError, 'algorithm must be "cover" or "fastcover"; got legacy'
+        ):
+            zstd.train_dictionary(dict_size, samples, algorithm="legacy")
+
+        with self.assertRaisesRegex(
+            ValueError, 'f and accel require algorithm="fastcover"'
+        ):
+            zstd.train_dictionary(
+                dict_size, samples, k=64, d=8, f=20, algorithm="cover"
+            )
+
+    @unittest.skipUnless(
+        "train_algorithm" in zstd.backend_features,
+        "algorithm only implemented in Rust backend",
+    )
+    def test_notifications(self):
+        samples = generate_samples()
+        dict_size = get_optimal_dict_size_heuristically(samples)
+
+        for algorithm in ("fastcover", "cover"):
+            with captured_stderr() as output:
+                zstd.train_dictionary(
+                    dict_size, samples, d=6, steps=1, algorithm=algorithm
+                )
+            self.assertEqual(output, b"")
+
+            with captured_stderr() as output:
+                zstd.train_dictionary(
+                    dict_size,
+                    samples,
+                    d=6,
+                    steps=1,
+                    notifications=2,
+                    algorithm=algorithm,
+                )
+            self.assertIn(b"Trying 2 different sets of parameters", output)
+
+    @unittest.skipUnless(
+        "train_sample_count" in zstd.backend_features,
+        "sample_count only implemented in Rust backend",
+    )
+    def test_sample_count(self):
+        samples = generate_samples()
+
+        def train(samples, seed=0):
+            return zstd.train_dictionary(
+                8192, samples, k=64, d=8, sample_count=256, seed=seed
+            ).as_bytes()
+
+        # Sampling is reproducible for a seed, whatever the input type.
+        d = train(iter(samples))
+        self.assertEqual(train(s for s in samples), d)
+        self.assertEqual(train(samples), d)
+        self.assertNotEqual(train(iter(samples), seed=1), d)
+
+        # Asking for more samples than exist uses them all.
+        self.assertEqual(
+            zstd.train_dictionary(
+                8192, iter(samples), k=64, d=8, sample_count=100000
+            ).as_bytes(),
+            zstd.train_dictionary(8192, samples, k=64, d=8).as_bytes(),
+        )
+
+        with self.assertRaisesRegex(ValueError, "samples must be bytes"):
+            zstd.train_dictionary(8192, iter([u"foo"]), sample_count=1)
+
+        with self.assertRaisesRegex(
+            ValueError, "sample_count must be positive"
+        ):
+            zstd.train_dictionary(8192, samples, sample_count=0)
+
+        # Iterators are only accepted when sampling.
+        with self.assertRaises(TypeError):
+            zstd.train_dictionary(8192, iter(samples))
+
 
 class TestCompressionDict(unittest.TestCase):
     def test_bad_mode(self):
@@ -105,3 +384,169 @@ class TestCompressionDict(unittest.TestCase):
             zstd.ZstdError, "unable to precompute dictionary"
         ):
             d.precompute_compress(level=1)
+
+    @unittest.skipUnless(
+        "dict_load_method" in zstd.backend_features,
+        "load_method only implemented in Rust backend",
+    )
+    def test_load_method(self):
+        samples = generate_samples()
+        trained = zstd.train_dictionary(
+            get_optimal_dict_size_heuristically(samples), samples, k=64, d=8
+        )
+        self.assertEqual(trained.info()["load_method"], "byref")
+
+        source = b"foobar" * 1024
+        for method, name in (
+            (zstd.DICT_LOAD_BY_REF, "byref"),
+            (zstd.DICT_LOAD_BY_COPY, "bycopy"),
+        ):
+            d = zstd.ZstdCompressionDict(trained.as_bytes(), load_method=method)
+            self.assertEqual(d.info()["load_method"], name)
+
+            frame = zstd.ZstdCompressor(dict_data=d).compress(source)
+            dctx = zstd.ZstdDecompressor(dict_data=d)
+            self.assertEqual(dctx.decompress(frame), source)
+
+            d.precompute_compress(level=1)
+            frame = zstd.ZstdCompressor(dict_data=d).compress(source)
+            self.assertEqual(dctx.decompress(frame), source)
+
+        with self.assertRaisesRegex(
+            ValueError, "invalid dictionary load method: 42"
+        ):
+            zstd.ZstdCompressionDict(b"foo", load_method=42)
+
+    @unittest.skipUnless(
+        "dict_precompute_in_use" in zstd.backend_features,
+        "replacing a dictionary in use only supported by Rust backend",
+    )
+    def test_precompute_compress_while_used(self):
+        samples = generate_samples()
+        d = zstd.train_dictionary(
+            get_optimal_dict_size_heuristically(samples), samples, k=64, d=8
+        )
+        d.precompute_compress(level=1)
+        cctx = zstd.ZstdCompressor(dict_data=d)
+
+        # Compressors keep using the dictionary they were set up with.
+        d.precompute_compress(level=19)
+        source = b"foobar" * 1024
+        frame = cctx.compress(source)
+        self.assertEqual(
+            zstd.ZstdDecompressor(dict_data=d).decompress(frame), source
+        )
+
+    @unittest.skipUnless(
+        "dict_info" in zstd.backend_features,
+        "info() only implemented in Rust backend",
+    )
+    def test_info(self):
+        samples = generate_samples()
+        d = zstd.train_dictionary(
+            get_optimal_dict_size_heuristically(samples), samples, k=64, d=8
+        )
+
+        info = d.info()
+        self.assertEqual(info["size"], len(d.as_bytes()))
+        self.assertEqual(info["dict_type"], "fulldict")
+        self.assertTrue(info["has_header"])
+        self.assertEqual(info["dict_id"], d.dict_id())
+        self.assertTrue(info["entropy_tables_valid"])
+        self.assertGreater(info["header_size"], 8)
+        self.assertEqual(
+            info["header_size"] + info["content_size"], info["size"]
+        )
+        self.assertEqual((info["k"], info["d"]), (64, 8))
+
+        # Corrupt the entropy tables following the magic and dict ID.
+        data = d.as_bytes()
+        corrupt = zstd.ZstdCompressionDict(data[0:8] + b"\xff" * 64 + data[72:])
+        info = corrupt.info()
+        self.assertTrue(info["has_header"])
+        self.assertEqual(info["dict_id"], d.dict_id())
+        self.assertFalse(info["entropy_tables_valid"])
+        self.assertIsNone(info["header_size"])
+        self.assertIsNone(info["content_size"])
+
+        raw = zstd.ZstdCompressionDict(
+            b"dictcontent" * 64, dict_type=zstd.DICT_TYPE_RAWCONTENT
+        )
+        info = raw.info()
+        self.assertEqual(info["dict_type"], "rawcontent")
+        self.assertFalse(info["has_header"])
+        self.assertEqual(info["dict_id"], 0)
+        self.assertIsNone(info["entropy_tables_valid"])
+        self.assertIsNone(info["header_size"])
+        self.assertEqual(info["content_size"], len(b"dictcontent" * 64))
+        self.assertEqual((info["k"], info["d"]), (0, 0))
+
+
+@unittest.skipUnless(
+    "dict_shared_memory" in zstd.backend_features,
+    "shared memory only implemented in Rust backend",
+)
+class TestCompressionDictSharedMemory(unittest.TestCase):
+    def setUp(self):
+        from multiprocessing import shared_memory
+
+        self.shared_memory = shared_memory
+
+    def test_roundtrip(self):
+        samples = generate_samples()
+        d = zstd.train_dictionary(
+            get_optimal_dict_size_heuristically(samples), samples, k=64, d=8
+        )
+
+        shm = d.share()
+        try:
+            shared = zstd.ZstdCompressionDict.from_shared_memory(shm.name)
+            self.assertEqual(shared.as_bytes(), d.as_bytes())
+            self.assertEqual(shared.dict_id(), d.dict_id())
+
+            cctx = zstd.ZstdCompressor(dict_data=shared)
+            frame = cctx.compress(b"foobar" * 64)
+
+            dctx = zstd.ZstdDecompressor(dict_data=d)
+            self.assertEqual(dctx.decompress(frame), b"foobar" * 64)
+
+            del cctx, shared
+        finally:
+            shm.close()
+            shm.unlink()
+
+    def test_dict_type(self):
+        d = zstd.ZstdCompressionDict(
+            b"dictcontent" * 64, dict_type=zstd.DICT_TYPE_RAWCONTENT
+        )
+
+        shm = d.share()
+        try:
+            with self.assertRaisesRegex(
+                ValueError, "invalid dictionary load mode"
+            ):
+                zstd.ZstdCompressionDict.from_shared_memory(
+                    shm.name, dict_type=42
+                )
+
+            shared = zstd.ZstdCompressionDict.from_shared_memory(
+                shm.name, dict_type=zstd.DICT_TYPE_RAWCONTENT
+            )
+            shared.precompute_compress(level=1)
+            del shared
+        finally:
+            shm.close()
+            shm.unlink()
+
+    def test_invalid_segment(self):
+        shm = self.shared_memory.SharedMemory(create=True, size=16)
+        try:
+            shm.buf[0:8] = struct.pack("<Q", 2 ** 32)
+
+            with self.assertRaisesRegex(
+                zstd.ZstdError, "does not contain dictionary data"
+            ):
+                zstd.ZstdCompressionDict.from_shared_memory(shm.name)
+        finally:
+            shm.close()
+            shm.unlink()
@@ -1,3 +1,4 @@
+import io
 import unittest
 
 import zstandard as zstd
@@ -19,3 +20,64 @@ class TestDecompress(unittest.TestCase):
         source = b"foobar" * 8192
         frame = zstd.compress(source)
         self.assertEqual(zstd.decompress(frame), source)
+
+
+@unittest.skipUnless(
+    "recompress" in zstd.backend_features,
+    "recompress() only implemented in Rust backend",
+)
+class TestRecompress(unittest.TestCase):
+    def test_simple(self):
+        source = b"".join(b"foo%dbar" % i for i in range(100000))
+        frame = zstd.ZstdCompressor(level=1).compress(source)
+
+        dest = io.BytesIO()
+        result = zstd.recompress(
+            io.BytesIO(frame), dest, level=9, read_size=4096, write_size=1024
+        )
+        self.assertEqual(result, (len(frame), len(dest.getvalue())))
+
+        recompressed = dest.getvalue()
+        self.assertEqual(
+            zstd.ZstdDecompressor().decompressobj().decompress(recompressed),
+            source,
+        )
+
+    def test_multiple_frames(self):
+        cctx = zstd.ZstdCompressor(level=1)
+        frames = cctx.compress(b"foo" * 1000) + cctx.compress(b"bar" * 1000)
+
+        dest = io.BytesIO()
+        zstd.recompress(io.BytesIO(frames), dest, threads=2)
+
+        recompressed = dest.getvalue()
+        self.assertEqual(
+            zstd.ZstdDecompressor().decompressobj().decompress(recompressed),
+            b"foo" * 1000 + b"bar" * 1000,
+        )
+        self.assertEqual(
+            zstd.get_frame_parameters(recompressed).content_size,
+            zstd.CONTENTSIZE_UNKNOWN,
+        )
+
+    def test_empty(self):
+        dest = io.BytesIO()
+        self.assertEqual(zstd.recompress(io.BytesIO(), dest), (0, 9))
+        self.assertEqual(zstd.decompress(dest.getvalue()), b"")
+
+    def test_errors(self):
+        frame = zstd.ZstdCompressor().compress(b"foobar" * 1000)
+
+        with self.assertRaisesRegex(
+            zstd.ZstdError, "input ends with an incomplete frame"
+        ):
+            zstd.recompress(io.BytesIO(frame[:-4]), io.BytesIO())
+
+        with self.assertRaisesRegex(zstd.ZstdError, "zstd decompress error"):
+            zstd.recompress(io.BytesIO(b"foobar" * 10), io.BytesIO())
+
+        with self.assertRaisesRegex(ValueError, "must have a read"):
+            zstd.recompress(None, io.BytesIO())
+
+        with self.assertRaisesRegex(ValueError, "level must be less than"):
+            zstd.recompress(io.BytesIO(frame), io.BytesIO(), level=50)
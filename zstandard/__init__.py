@@ -208,3 +208,28 @@ def decompress(data: ByteString, max_output_size: int = 0) -> bytes:
     dctx = ZstdDecompressor()
 
     return dctx.decompress(data, max_output_size=max_output_size)
+
+
+class ZstdNotSupportedError(ZstdError, NotImplementedError):
+    """Raised when the active backend lacks a requested feature."""
+
+
+def require(feature: str) -> None:
+    """Ensure the active backend provides a feature.
+
+    Deployments that may load any of the C, CFFI or Rust backends can call
+    this at startup to fail fast rather than hitting an ``AttributeError`` or
+    ``TypeError`` at first use.
+
+    :param feature:
+       ``str`` naming the feature. This is a member of ``backend_features``
+       (e.g. ``multi_compress_to_buffer`` or ``output_size_hint``). Features
+       cover optional arguments as well as APIs, so a backend may lack a
+       feature even if the method it applies to exists.
+    :raises ZstdNotSupportedError:
+       If the active backend doesn't provide the feature.
+    """
+    if feature not in backend_features:
+        raise ZstdNotSupportedError(
+            "%s is not supported by the %s backend" % (feature, backend)
+        )
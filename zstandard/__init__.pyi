@@ -7,13 +7,17 @@
 import os
 
 from typing import (
+    Any,
+    Awaitable,
     BinaryIO,
     ByteString,
+    Dict,
     Generator,
     IO,
     Iterable,
     List,
     Optional,
+    Sequence,
     Set,
     Tuple,
     Union,
@@ -28,6 +32,7 @@ COMPRESSOBJ_FLUSH_BLOCK: int
 CONTENTSIZE_UNKNOWN: int
 CONTENTSIZE_ERROR: int
 
+MIN_COMPRESSION_LEVEL: int
 MAX_COMPRESSION_LEVEL: int
 
 COMPRESSION_RECOMMENDED_INPUT_SIZE: int
@@ -72,19 +77,28 @@ STRATEGY_BTULTRA2: int
 DICT_TYPE_AUTO: int
 DICT_TYPE_RAWCONTENT: int
 DICT_TYPE_FULLDICT: int
+DICT_LOAD_BY_REF: int
+DICT_LOAD_BY_COPY: int
 
 FORMAT_ZSTD1: int
 FORMAT_ZSTD1_MAGICLESS: int
+ROW_MATCH_FINDER_AUTO: int
+ROW_MATCH_FINDER_DISABLED: int
+ROW_MATCH_FINDER_ENABLED: int
 
 ZSTD_VERSION: Tuple[int, int, int]
 FRAME_HEADER: bytes
 MAGIC_NUMBER: int
+MAGIC_SKIPPABLE_START: int
+MAGIC_SKIPPABLE_MASK: int
 
 backend: str
 backend_features: Set[str]
 __version__: str
 
 class ZstdError(Exception): ...
+class ZstdDestinationTooSmallError(ZstdError): ...
+class ZstdNotSupportedError(ZstdError, NotImplementedError): ...
 
 class BufferSegment(object):
     offset: int
@@ -103,6 +117,14 @@ class BufferWithSegments(object):
     def segments(self): ...
     def tobytes(self) -> bytes: ...
 
+class BufferWithSegmentsBuilder(object):
+    size: int
+    def __init__(self, capacity: int = ...): ...
+    def __len__(self) -> int: ...
+    def append(self, data: ByteString) -> None: ...
+    def extend(self, iterable: Iterable[ByteString]) -> None: ...
+    def finish(self) -> BufferWithSegments: ...
+
 class BufferWithSegmentsCollection(object):
     def __init__(self, *args): ...
     def __len__(self) -> int: ...
@@ -131,7 +153,13 @@ class ZstdCompressionParameters(object):
         job_size: int = ...,
         overlap_log: int = ...,
         force_max_window: int = ...,
-        enable_ldm: int = ...,
+        deterministic_ref_prefix: int = ...,
+        target_cblock_size: int = ...,
+        enable_dedicated_dict_search: int = ...,
+        use_row_match_finder: int = ...,
+        stable_in_buffer: int = ...,
+        stable_out_buffer: int = ...,
+        enable_ldm: Union[int, str] = ...,
         ldm_hash_log: int = ...,
         ldm_min_match: int = ...,
         ldm_bucket_size_log: int = ...,
@@ -169,7 +197,19 @@ class ZstdCompressionParameters(object):
     @property
     def force_max_window(self) -> int: ...
     @property
-    def enable_ldm(self) -> int: ...
+    def deterministic_ref_prefix(self) -> int: ...
+    @property
+    def target_cblock_size(self) -> int: ...
+    @property
+    def enable_dedicated_dict_search(self) -> int: ...
+    @property
+    def use_row_match_finder(self) -> int: ...
+    @property
+    def stable_in_buffer(self) -> int: ...
+    @property
+    def stable_out_buffer(self) -> int: ...
+    @property
+    def enable_ldm(self) -> Union[int, str]: ...
     @property
     def ldm_hash_log(self) -> int: ...
     @property
@@ -181,6 +221,8 @@ class ZstdCompressionParameters(object):
     @property
     def threads(self) -> int: ...
     def estimated_compression_context_size(self) -> int: ...
+    def as_dict(self) -> Dict[str, Union[int, str]]: ...
+    def replace(self, **overrides) -> "ZstdCompressionParameters": ...
 
 class CompressionParameters(ZstdCompressionParameters): ...
 
@@ -193,6 +235,7 @@ class ZstdCompressionDict(object):
         dict_type: int = ...,
         k: int = ...,
         d: int = ...,
+        load_method: int = ...,
     ): ...
     def __len__(self) -> int: ...
     def dict_id(self) -> int: ...
@@ -204,13 +247,21 @@ class ZstdCompressionDict(object):
     ): ...
 
 class ZstdCompressionObj(object):
+    @property
+    def bytes_ingested(self) -> int: ...
+    @property
+    def bytes_produced(self) -> int: ...
     def compress(self, data: ByteString) -> bytes: ...
     def flush(self, flush_mode: int = ...) -> bytes: ...
+    def write_empty_frame(self) -> bytes: ...
 
 class ZstdCompressionChunker(object):
     def compress(self, data: ByteString): ...
-    def flush(self): ...
+    def flush(self, end_frame: bool = ...): ...
     def finish(self): ...
+    def write_empty_frame(self): ...
+    @property
+    def frame_index(self) -> Optional[List[Tuple[int, int, int]]]: ...
 
 class ZstdCompressionReader(BinaryIO):
     def __enter__(self) -> "ZstdCompressionReader": ...
@@ -258,25 +309,49 @@ class ZstdCompressionWriter(BinaryIO):
     def readall(self) -> bytes: ...
     def readinto(self, b): ...
     def write(self, data: ByteString) -> int: ...
-    def flush(self, flush_mode: int = ...) -> int: ...
+    def flush(
+        self,
+        flush_mode: int = ...,
+        *,
+        dict_data: Optional[ZstdCompressionDict] = ...,
+        prefix: Optional[ByteString] = ...,
+    ) -> int: ...
     def tell(self) -> int: ...
+    @property
+    def frame_index(self) -> Optional[List[Tuple[int, int, int]]]: ...
 
 class ZstdCompressor(object):
     def __init__(
         self,
-        level: int = ...,
+        level: Optional[int] = ...,
         dict_data: ZstdCompressionDict = ...,
         compression_params: ZstdCompressionParameters = ...,
+        *,
         write_checksum: bool = ...,
         write_content_size: bool = ...,
         write_dict_id: bool = ...,
-        threads: int = ...,
+        threads: Optional[int] = ...,
+        max_native_memory: Optional[int] = ...,
+        format: int = ...,
     ): ...
+    @property
+    def effective_level(self) -> int: ...
+    def ldm_parameters(
+        self, source_size: int = ...
+    ) -> Optional[Dict[str, int]]: ...
     def memory_size(self) -> int: ...
     def compress(self, data: ByteString) -> bytes: ...
+    def compress_into(self, source: ByteString, dest: ByteString) -> int: ...
+    def compress_patch(
+        self, old_data: ByteString, new_data: ByteString
+    ) -> bytes: ...
     def compressobj(self, size: int = ...) -> ZstdCompressionObj: ...
     def chunker(
-        self, size: int = ..., chunk_size: int = ...
+        self,
+        size: int = ...,
+        chunk_size: int = ...,
+        as_memoryview: bool = ...,
+        frame_index: bool = ...,
     ) -> ZstdCompressionChunker: ...
     def copy_stream(
         self,
@@ -286,9 +361,17 @@ class ZstdCompressor(object):
         read_size: int = ...,
         write_size: int = ...,
     ) -> Tuple[int, int]: ...
-    def stream_reader(
+    def split_stream(
         self,
         source: Union[IO[bytes], ByteString],
+        writers: list[IO[bytes]],
+        size: int = ...,
+        read_size: int = ...,
+        write_size: int = ...,
+    ) -> list[Tuple[int, int]]: ...
+    def stream_reader(
+        self,
+        source: Union[IO[bytes], ByteString, int],
         size: int = ...,
         read_size: int = ...,
         *,
@@ -296,12 +379,14 @@ class ZstdCompressor(object):
     ) -> ZstdCompressionReader: ...
     def stream_writer(
         self,
-        writer: IO[bytes],
+        writer: Union[IO[bytes], int],
         size: int = ...,
         write_size: int = ...,
         write_return_read: bool = ...,
         *,
         closefd: bool = ...,
+        patch_content_size: bool = ...,
+        frame_index: bool = ...,
     ) -> ZstdCompressionWriter: ...
     def read_to_iter(
         self,
@@ -318,8 +403,18 @@ class ZstdCompressor(object):
         ],
         threads: int = ...,
     ) -> BufferWithSegmentsCollection: ...
+    def multi_compress_to_buffer_async(
+        self,
+        data: Union[
+            BufferWithSegments, BufferWithSegmentsCollection, List[ByteString],
+        ],
+        threads: int = ...,
+        return_index: bool = ...,
+    ) -> Awaitable[Any]: ...
 
 class ZstdDecompressionObj(object):
+    @property
+    def unused_data(self) -> bytes: ...
     def decompress(self, data: ByteString) -> bytes: ...
     def flush(self, length: int = ...) -> bytes: ...
 
@@ -376,33 +471,59 @@ class ZstdDecompressionWriter(BinaryIO):
 class ZstdDecompressor(object):
     def __init__(
         self,
-        dict_data: ZstdCompressionDict = ...,
+        dict_data: Union[
+            ZstdCompressionDict, Sequence[ZstdCompressionDict]
+        ] = ...,
         max_window_size: int = ...,
         format: int = ...,
+        *,
+        ignore_checksum: bool = ...,
     ): ...
+    max_window_log: int
     def memory_size(self) -> int: ...
     def decompress(
-        self, data: ByteString, max_output_size: int = ...
+        self,
+        data: ByteString,
+        max_output_size: int = ...,
+        *,
+        bound_fallback: bool = ...,
+        streaming_fallback: bool = ...,
+        read_across_frames: bool = ...,
+        allow_extra_data: bool = ...,
     ) -> bytes: ...
     def stream_reader(
         self,
-        source: Union[IO[bytes], ByteString],
+        source: Union[IO[bytes], ByteString, int],
         read_size: int = ...,
         read_across_frames: bool = ...,
         *,
         closefd=False,
+        max_decompress_per_call: Optional[int] = ...,
     ) -> ZstdDecompressionReader: ...
-    def decompressobj(self, write_size: int = ...) -> ZstdDecompressionObj: ...
+    def iter_frames(self, data: ByteString) -> Generator[bytes, None, None]: ...
+    def decompressobj(
+        self, write_size: int = ..., *, tolerate_padding: bool = ...
+    ) -> ZstdDecompressionObj: ...
+    def window_usage(self, reset: bool = ...) -> Dict[str, int]: ...
     def read_to_iter(
         self,
         reader: Union[IO[bytes], ByteString],
         read_size: int = ...,
         write_size: int = ...,
         skip_bytes: int = ...,
+        *,
+        as_memoryview: bool = ...,
+        on_error: str = ...,
+    ) -> Generator[bytes, None, None]: ...
+    def read_records(
+        self,
+        reader: Union[IO[bytes], ByteString],
+        record_size: int,
+        read_size: int = ...,
     ) -> Generator[bytes, None, None]: ...
     def stream_writer(
         self,
-        writer: IO[bytes],
+        writer: Union[IO[bytes], int],
         write_size: int = ...,
         write_return_read: bool = ...,
         *,
@@ -418,6 +539,9 @@ class ZstdDecompressor(object):
     def decompress_content_dict_chain(
         self, frames: list[ByteString]
     ) -> bytes: ...
+    def decompress_patch(
+        self, old_data: ByteString, patch: ByteString
+    ) -> bytes: ...
     def multi_decompress_to_buffer(
         self,
         frames: Union[
@@ -425,7 +549,36 @@ class ZstdDecompressor(object):
         ],
         decompressed_sizes: ByteString = ...,
         threads: int = ...,
-    ) -> BufferWithSegmentsCollection: ...
+        *,
+        out: ByteString = ...,
+        offsets: ByteString = ...,
+        on_error: str = ...,
+    ) -> Union[
+        BufferWithSegmentsCollection,
+        int,
+        Tuple[BufferWithSegmentsCollection, List[Tuple[int, str]]],
+    ]: ...
+    def multi_decompress_to_buffer_async(
+        self,
+        frames: Union[
+            BufferWithSegments, BufferWithSegmentsCollection, List[ByteString],
+        ],
+        decompressed_sizes: ByteString = ...,
+        threads: int = ...,
+        on_error: str = ...,
+    ) -> Awaitable[
+        Union[
+            BufferWithSegmentsCollection,
+            Tuple[BufferWithSegmentsCollection, List[Tuple[int, str]]],
+        ]
+    ]: ...
+
+class ZstdPathResult(object):
+    source: str
+    dest: str
+    bytes_read: int
+    bytes_written: int
+    error: Optional[str]
 
 class FrameParameters(object):
     content_size: int
@@ -434,12 +587,14 @@ class FrameParameters(object):
     has_checksum: bool
 
 def estimate_decompression_context_size() -> int: ...
+def require(feature: str) -> None: ...
 def frame_content_size(data: ByteString) -> int: ...
 def frame_header_size(data: ByteString) -> int: ...
 def get_frame_parameters(data: ByteString) -> FrameParameters: ...
 def train_dictionary(
     dict_size: int,
     samples: list[ByteString],
+    *,
     k: int = ...,
     d: int = ...,
     f: int = ...,
@@ -450,7 +605,33 @@ def train_dictionary(
     level: int = ...,
     steps: int = ...,
     threads: int = ...,
+    sample_count: Optional[int] = ...,
+    seed: int = ...,
+    algorithm: str = ...,
+    sample_sizes: Optional[list[int]] = ...,
+    shrink_dict: bool = ...,
+    shrink_dict_max_regression: Optional[int] = ...,
+    max_samples_size: int = ...,
 ) -> ZstdCompressionDict: ...
+def skippable_frame_header(size: int, variant: int = ...) -> bytes: ...
+def compress_paths(
+    paths: Iterable[Union[str, os.PathLike]],
+    dest_dir: Union[str, os.PathLike],
+    level: Optional[int] = ...,
+    threads: int = ...,
+) -> List[ZstdPathResult]: ...
+def decompress_paths(
+    paths: Iterable[Union[str, os.PathLike]],
+    dest_dir: Union[str, os.PathLike],
+    threads: int = ...,
+    safety_limits: Optional[Dict[str, Union[int, float]]] = ...,
+) -> List[ZstdPathResult]: ...
+def get_defaults() -> Dict[str, int]: ...
+def set_defaults(
+    level: Optional[int] = ..., threads: Optional[int] = ...
+) -> Dict[str, int]: ...
+def get_c_compat_errors() -> bool: ...
+def set_c_compat_errors(enabled: bool) -> bool: ...
 def open(
     filename: Union[bytes, str, os.PathLike, BinaryIO],
     mode: str = ...,